@@ -0,0 +1,244 @@
+//! Authentication of reports against per-probe keys.
+//!
+//! A key file holds one probe per line: the probe id, whitespace, and
+//! the probe's 16-byte key as 32 hex digits. Blank lines and lines
+//! starting with `#` are ignored.
+//!
+//! ```text
+//! # probe id  key
+//! 1           000102030405060708090a0b0c0d0e0f
+//! ```
+
+use std::{collections::HashMap, fs, path::Path};
+
+use err_derive::Error;
+
+use modality_probe::{
+    wire::{siphash, ReportWireError, WireReport},
+    ProbeId,
+};
+
+#[derive(Debug, Error)]
+pub enum KeyFileError {
+    #[error(display = "Couldn't read the key file: {}", _0)]
+    Io(#[error(source)] std::io::Error),
+
+    #[error(display = "Key file line {}: {}", line, message)]
+    InvalidLine { line: usize, message: &'static str },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Error)]
+pub enum AuthenticationError {
+    #[error(display = "The report isn't authenticated")]
+    Unauthenticated,
+
+    #[error(display = "No key for probe {}", _0)]
+    UnknownProbe(u32),
+
+    #[error(display = "The report's MAC doesn't match probe {}'s key", _0)]
+    MacMismatch(u32),
+
+    #[error(display = "Report wire error")]
+    ReportWireError(#[error(source)] ReportWireError),
+}
+
+/// The keys each probe authenticates its reports with
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ReportKeys {
+    keys: HashMap<ProbeId, siphash::Key>,
+}
+
+impl ReportKeys {
+    /// Read keys from a key file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, KeyFileError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parse keys in the key file format
+    pub fn parse(contents: &str) -> Result<Self, KeyFileError> {
+        let mut keys = ReportKeys::default();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message| KeyFileError::InvalidLine {
+                line: i + 1,
+                message,
+            };
+            let mut words = line.split_whitespace();
+            let (probe_id, key) = match (words.next(), words.next(), words.next()) {
+                (Some(p), Some(k), None) => (p, k),
+                _ => return Err(invalid("expected a probe id and a key")),
+            };
+            let probe_id = probe_id
+                .parse()
+                .ok()
+                .and_then(ProbeId::new)
+                .ok_or_else(|| invalid("invalid probe id"))?;
+            let key = parse_key(key).ok_or_else(|| invalid("expected a key of 32 hex digits"))?;
+            if keys.insert(probe_id, key).is_some() {
+                return Err(invalid("duplicate probe id"));
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Set the key for a probe, returning its previous key, if any
+    pub fn insert(&mut self, probe_id: ProbeId, key: siphash::Key) -> Option<siphash::Key> {
+        self.keys.insert(probe_id, key)
+    }
+
+    /// Return the key for a probe
+    pub fn get(&self, probe_id: ProbeId) -> Option<&siphash::Key> {
+        self.keys.get(&probe_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Check that a report is authenticated with its probe's key.
+    /// Reports from probes without a key are rejected too.
+    pub fn authenticate<T: AsRef<[u8]>>(
+        &self,
+        report: &WireReport<T>,
+    ) -> Result<(), AuthenticationError> {
+        let probe_id = report.probe_id()?;
+        if !report.is_authenticated() {
+            return Err(AuthenticationError::Unauthenticated);
+        }
+        let key = self
+            .get(probe_id)
+            .ok_or_else(|| AuthenticationError::UnknownProbe(probe_id.get_raw()))?;
+        report
+            .check_mac(key)
+            .map_err(|_| AuthenticationError::MacMismatch(probe_id.get_raw()))
+    }
+
+    /// Check that the report in `bytes` is authenticated with its
+    /// probe's key
+    pub fn authenticate_bytes(&self, bytes: &[u8]) -> Result<(), AuthenticationError> {
+        self.authenticate(&WireReport::new(bytes)?)
+    }
+}
+
+fn parse_key(hex: &str) -> Option<siphash::Key> {
+    if hex.len() != 2 * siphash::KEY_LEN || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; siphash::KEY_LEN];
+    for (b, digits) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *b = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Report;
+    use modality_probe::{
+        EventId, LogicalClock, NanosecondResolution, ProbeEpoch, ProbeTicks, WallClockId,
+    };
+
+    fn report_bytes(raw_probe_id: u32, key: Option<&siphash::Key>) -> Vec<u8> {
+        let probe_id = ProbeId::new(raw_probe_id).unwrap();
+        let clock = LogicalClock {
+            id: probe_id,
+            epoch: ProbeEpoch(0),
+            ticks: ProbeTicks(1),
+        };
+        let report = Report {
+            probe_id,
+            probe_clock: clock,
+            seq_num: 0.into(),
+            persistent_epoch_counting: false,
+            time_resolution: NanosecondResolution::UNSPECIFIED,
+            wall_clock_id: WallClockId::default(),
+            frontier_clocks: vec![clock],
+            event_log: vec![crate::EventLogEntry::Event(EventId::new(1).unwrap())],
+        };
+        let mut bytes = vec![0u8; 128];
+        let len = match key {
+            Some(key) => report.write_into_authenticated_le_bytes(&mut bytes, key),
+            None => report.write_into_le_bytes(&mut bytes),
+        }
+        .unwrap();
+        bytes.truncate(len);
+        bytes
+    }
+
+    #[test]
+    fn key_file() {
+        let keys = ReportKeys::parse(
+            "# probe id  key\n\
+             1 000102030405060708090a0b0c0d0e0f\n\
+             \n\
+             \t200   FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF  \n",
+        )
+        .unwrap();
+        assert_eq!(keys.len(), 2);
+        let mut expected = [0u8; siphash::KEY_LEN];
+        for (i, b) in expected.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        assert_eq!(keys.get(ProbeId::new(1).unwrap()), Some(&expected));
+        assert_eq!(
+            keys.get(ProbeId::new(200).unwrap()),
+            Some(&[0xFF; siphash::KEY_LEN])
+        );
+        assert_eq!(keys.get(ProbeId::new(2).unwrap()), None);
+    }
+
+    #[test]
+    fn invalid_key_files() {
+        let line = |contents| match ReportKeys::parse(contents) {
+            Err(KeyFileError::InvalidLine { line, .. }) => line,
+            r => panic!("Expected an invalid line, got {:?}", r),
+        };
+        assert_eq!(line("1"), 1);
+        assert_eq!(line("\n0 000102030405060708090a0b0c0d0e0f"), 2);
+        assert_eq!(line("1 000102030405060708090a0b0c0d0e"), 1);
+        assert_eq!(line("1 000102030405060708090a0b0c0d0e0g"), 1);
+        assert_eq!(line("1 000102030405060708090a0b0c0d0e0f extra"), 1);
+        assert_eq!(
+            line("1 000102030405060708090a0b0c0d0e0f\n1 000102030405060708090a0b0c0d0e0f"),
+            2
+        );
+    }
+
+    #[test]
+    fn authentication() {
+        let key = [5; siphash::KEY_LEN];
+        let mut keys = ReportKeys::default();
+        keys.insert(ProbeId::new(1).unwrap(), key);
+
+        assert_eq!(
+            keys.authenticate_bytes(&report_bytes(1, Some(&key))),
+            Ok(())
+        );
+        assert_eq!(
+            keys.authenticate_bytes(&report_bytes(1, None)),
+            Err(AuthenticationError::Unauthenticated)
+        );
+        assert_eq!(
+            keys.authenticate_bytes(&report_bytes(1, Some(&[6; siphash::KEY_LEN]))),
+            Err(AuthenticationError::MacMismatch(1))
+        );
+        assert_eq!(
+            keys.authenticate_bytes(&report_bytes(2, Some(&key))),
+            Err(AuthenticationError::UnknownProbe(2))
+        );
+        assert_eq!(
+            keys.authenticate_bytes(&[0; 64]),
+            Err(AuthenticationError::ReportWireError(
+                ReportWireError::InvalidFingerprint
+            ))
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Reassembly of reports sent as fragments (see
+//! `modality_probe::wire::fragment`).
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    hash::Hash,
+};
+
+use modality_probe::wire::WireReportFragment;
+
+use crate::{Report, SerializationError};
+
+/// Rebuilds reports from their fragments.
+///
+/// Fragments are grouped by `source`, which tells apart the probes
+/// sharing a link, e.g. a CAN id or a peer address. When a fragment
+/// of a new report arrives before the previous report from the same
+/// source was complete, the previous report is dropped.
+#[derive(Debug)]
+pub struct ReportReassembler<K> {
+    partial_reports: HashMap<K, PartialReport>,
+    dropped_reports: u64,
+}
+
+#[derive(Debug)]
+struct PartialReport {
+    report_tag: u8,
+    fragments: BTreeMap<u16, Vec<u8>>,
+    last_index: Option<u16>,
+}
+
+impl PartialReport {
+    fn new(report_tag: u8) -> Self {
+        PartialReport {
+            report_tag,
+            fragments: BTreeMap::new(),
+            last_index: None,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        match self.last_index {
+            Some(last) => self.fragments.len() == usize::from(last) + 1,
+            None => false,
+        }
+    }
+}
+
+impl<K: Eq + Hash> ReportReassembler<K> {
+    /// A reassembler with no fragments pending
+    pub fn new() -> Self {
+        ReportReassembler {
+            partial_reports: HashMap::new(),
+            dropped_reports: 0,
+        }
+    }
+
+    /// Add a fragment received from `source`, returning the report it
+    /// completes, if any.
+    pub fn push(
+        &mut self,
+        source: K,
+        fragment: &[u8],
+    ) -> Result<Option<Report>, SerializationError> {
+        let fragment = WireReportFragment::new(fragment)
+            .map_err(|_| SerializationError::MissingFragmentHeader)?;
+        let report_tag = fragment.report_tag();
+        let index = fragment.index();
+
+        let partial = self
+            .partial_reports
+            .entry(source)
+            .or_insert_with(|| PartialReport::new(report_tag));
+        // A new tag, or a repeated index, means the partial report's
+        // missing fragments aren't coming
+        if partial.report_tag != report_tag || partial.fragments.contains_key(&index) {
+            if !partial.fragments.is_empty() {
+                self.dropped_reports += 1;
+            }
+            *partial = PartialReport::new(report_tag);
+        }
+        partial.fragments.insert(index, fragment.payload().to_vec());
+        if fragment.is_last() {
+            partial.last_index = Some(index);
+        }
+        if !partial.is_complete() {
+            return Ok(None);
+        }
+
+        let bytes: Vec<u8> = partial.fragments.values().flatten().copied().collect();
+        *partial = PartialReport::new(report_tag);
+        Report::try_from(&bytes[..]).map(Some)
+    }
+
+    /// The number of reports dropped because some of their fragments
+    /// never arrived
+    pub fn dropped_reports(&self) -> u64 {
+        self.dropped_reports
+    }
+}
+
+impl<K: Eq + Hash> Default for ReportReassembler<K> {
+    fn default() -> Self {
+        ReportReassembler::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use modality_probe::*;
+    use pretty_assertions::assert_eq;
+    use std::mem::MaybeUninit;
+
+    const FRAGMENT_LEN: usize = 8;
+
+    fn fragmented_report(probe: &mut ModalityProbe, events: u32) -> (Report, Vec<Vec<u8>>) {
+        for id in 1..=events {
+            probe.record_event(EventId::new(id).unwrap());
+        }
+        let mut scratch = vec![0u8; 512];
+        let mut fragment = [0u8; FRAGMENT_LEN];
+        let mut fragments = Vec::new();
+        let count = probe
+            .report_fragmented(&mut scratch, &mut fragment, |f| fragments.push(f.to_vec()))
+            .unwrap();
+        assert_eq!(count, fragments.len());
+        assert!(fragments.iter().all(|f| f.len() <= FRAGMENT_LEN));
+
+        let wire_report = modality_probe::wire::WireReport::new(&scratch[..]).unwrap();
+        let report_len = wire_report.report_len();
+        (Report::try_from(&scratch[..report_len]).unwrap(), fragments)
+    }
+
+    #[test]
+    fn reassembles_fragmented_reports() {
+        let mut storage = vec![MaybeUninit::new(0u8); 1024];
+        let mut probe = ModalityProbe::new_with_storage(
+            &mut storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let mut reassembler = ReportReassembler::new();
+
+        let (report, fragments) = fragmented_report(&mut probe, 10);
+        assert!(fragments.len() > 1);
+        let (last, rest) = fragments.split_last().unwrap();
+        for f in rest {
+            assert_eq!(reassembler.push(1, f).unwrap(), None);
+        }
+        assert_eq!(reassembler.push(1, last).unwrap(), Some(report));
+        assert_eq!(reassembler.dropped_reports(), 0);
+    }
+
+    #[test]
+    fn drops_only_reports_missing_fragments() {
+        let mut storage = vec![MaybeUninit::new(0u8); 1024];
+        let mut probe = ModalityProbe::new_with_storage(
+            &mut storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let mut reassembler = ReportReassembler::new();
+
+        let (_, lossy_fragments) = fragmented_report(&mut probe, 5);
+        let (report, fragments) = fragmented_report(&mut probe, 3);
+
+        // Lose the first report's second fragment
+        for f in lossy_fragments
+            .iter()
+            .take(1)
+            .chain(lossy_fragments.iter().skip(2))
+        {
+            assert_eq!(reassembler.push(1, f).unwrap(), None);
+        }
+        let mut reassembled = None;
+        for f in fragments.iter() {
+            reassembled = reassembler.push(1, f).unwrap();
+        }
+        assert_eq!(reassembled, Some(report));
+        assert_eq!(reassembler.dropped_reports(), 1);
+    }
+}
@@ -11,10 +11,12 @@ use modality_probe::{
     time::{
         NanosecondResolution, Nanoseconds, NanosecondsHighBits, NanosecondsLowBits, WallClockId,
     },
-    wire::{le_bytes, ReportWireError, WireReport},
+    wire::{compression, le_bytes, siphash, ReportWireError, WireReport},
     EventId, LogicalClock, ProbeEpoch, ProbeId, ProbeTicks,
 };
 
+pub mod auth;
+pub mod fragment;
 pub mod json;
 
 assert_eq_size!(LogEntry, u32);
@@ -86,6 +88,9 @@ pub enum SerializationError {
 
     #[error(display = "Invalid time {:?}", _0)]
     InvalidTime((NanosecondsLowBits, NanosecondsHighBits)),
+
+    #[error(display = "Report fragment is missing its header")]
+    MissingFragmentHeader,
 }
 
 #[derive(Debug, PartialEq)]
@@ -472,6 +477,19 @@ impl TryFrom<&[u8]> for Report {
     type Error = SerializationError;
     fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
         let report = WireReport::new(buf)?;
+        match report.version() {
+            // Versions 0 and 1 differ only in their headers and
+            // trailers (an explicit version, flags, a MAC, a
+            // checksum), which `WireReport` takes care of; their
+            // payloads are the same once a compressed log is decoded
+            0 | 1 => Report::from_payload_v0(&report),
+            v => Err(ReportWireError::UnsupportedVersion(v).into()),
+        }
+    }
+}
+
+impl Report {
+    fn from_payload_v0(report: &WireReport<&[u8]>) -> Result<Self, SerializationError> {
         let (epoch, ticks) = modality_probe::unpack_clock_word(report.clock());
         let id = report.probe_id()?;
         let mut owned_report = Report {
@@ -510,9 +528,21 @@ impl TryFrom<&[u8]> for Report {
             }
         }
 
+        let decompressed_log;
+        let log_bytes = if report.is_compressed() {
+            let mut bytes = Vec::new();
+            compression::decode(&payload[clocks_len..], |word| {
+                bytes.extend_from_slice(&word.to_le_bytes())
+            })?;
+            decompressed_log = bytes;
+            &decompressed_log[..]
+        } else {
+            &payload[clocks_len..]
+        };
+
         let mut paired_wall_clock_time = None;
         let mut interpret_next_as = Next::DontKnow;
-        for u32_bytes in payload[clocks_len..].chunks_exact(mem::size_of::<LogEntry>()) {
+        for u32_bytes in log_bytes.chunks_exact(mem::size_of::<LogEntry>()) {
             let raw = le_bytes::read_u32(u32_bytes);
             match interpret_next_as {
                 Next::DontKnow => {
@@ -712,15 +742,44 @@ impl Report {
     }
 
     pub fn write_into_le_bytes(&self, bytes: &mut [u8]) -> Result<usize, SerializationError> {
+        self.write_into(bytes, None)
+    }
+
+    /// Write the report, authenticated with a MAC under `key`
+    pub fn write_into_authenticated_le_bytes(
+        &self,
+        bytes: &mut [u8],
+        key: &siphash::Key,
+    ) -> Result<usize, SerializationError> {
+        self.write_into(bytes, Some(key))
+    }
+
+    fn write_into(
+        &self,
+        bytes: &mut [u8],
+        key: Option<&siphash::Key>,
+    ) -> Result<usize, SerializationError> {
         if self.frontier_clocks.len() > std::u16::MAX as usize {
             return Err(SerializationError::TooManyFrontierClocks(
                 self.frontier_clocks.len(),
             ));
         }
 
+        // Whatever is in the buffer already says nothing about where
+        // the fields go, so make sure the version and flags bytes fit,
+        // write them and the fingerprint, and only then check the rest
+        // of the header fits
+        if bytes.len() < WireReport::<&[u8]>::header_len() {
+            return Err(ReportWireError::MissingHeader.into());
+        }
         let mut wire = WireReport::new_unchecked(bytes);
+        wire.set_versioned_fingerprint();
+        wire.set_version(WireReport::<&[u8]>::VERSION);
+        wire.set_flags(match key {
+            Some(_) => WireReport::<&[u8]>::AUTHENTICATED_FLAG,
+            None => 0,
+        });
         wire.check_len()?;
-        wire.set_fingerprint();
         wire.set_probe_id(self.probe_id);
         wire.set_clock(modality_probe::pack_clock_word(
             self.probe_clock.epoch,
@@ -834,10 +893,11 @@ impl Report {
             }
         }
 
-        Ok(WireReport::<&[u8]>::buffer_len(
-            self.frontier_clocks.len(),
-            num_u32_entries as _,
-        ))
+        if let Some(key) = key {
+            wire.set_mac(key);
+        }
+        wire.set_checksum();
+        Ok(wire.report_len())
     }
 }
 
@@ -1241,6 +1301,117 @@ pub(crate) mod test {
         assert_eq!(o_report, i_report);
     }
 
+    #[test]
+    fn short_buffers_are_rejected() {
+        let report = Report {
+            probe_id: ProbeId::new(1).unwrap(),
+            probe_clock: LogicalClock {
+                id: ProbeId::new(1).unwrap(),
+                epoch: ProbeEpoch(0),
+                ticks: ProbeTicks(0),
+            },
+            seq_num: 0.into(),
+            persistent_epoch_counting: false,
+            time_resolution: NanosecondResolution::UNSPECIFIED,
+            wall_clock_id: WallClockId::local_only(),
+            frontier_clocks: vec![],
+            event_log: vec![],
+        };
+        // Long enough for a version 0 header, but not for the version
+        // and flags bytes too
+        for len in [0, 4, 33, 34].iter() {
+            let mut bytes = vec![0; *len];
+            match report.write_into_le_bytes(&mut bytes) {
+                Err(SerializationError::ReportWireError(ReportWireError::MissingHeader)) => (),
+                r => panic!("Expected a missing header, got {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn checksums_are_verified() {
+        let mut storage = vec![MaybeUninit::new(0u8); 1024];
+        let mut probe = ModalityProbe::new_with_storage(
+            &mut storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        probe.record_event_with_payload(EventId::new(1).unwrap(), 7);
+        let mut report_dest = vec![0; 512];
+        let n_bytes = probe.report(&mut report_dest).unwrap().unwrap().get();
+        let report = Report::try_from(&report_dest[..n_bytes]).unwrap();
+
+        // Flip a payload bit
+        let mut corrupt = report_dest[..n_bytes].to_vec();
+        corrupt[n_bytes - 5] ^= 0x2;
+        match Report::try_from(&corrupt[..]) {
+            Err(SerializationError::ReportWireError(ReportWireError::ChecksumMismatch)) => (),
+            r => panic!("Expected a checksum mismatch, got {:?}", r),
+        }
+
+        // Reports without a checksum (or version and flags bytes) are
+        // still accepted
+        let mut unchecksummed = report_dest[..4].to_vec();
+        unchecksummed.extend_from_slice(&report_dest[6..n_bytes - 4]);
+        WireReport::new_unchecked(&mut unchecksummed[..]).set_fingerprint();
+        assert_eq!(Report::try_from(&unchecksummed[..]).unwrap(), report);
+    }
+
+    #[test]
+    fn compressed_reports_decode_like_raw_ones() {
+        let mut reports = Vec::new();
+        for compressed in [false, true].iter() {
+            let mut storage = vec![MaybeUninit::new(0u8); 1024];
+            let mut probe = ModalityProbe::new_with_storage(
+                &mut storage,
+                ProbeId::new(1).unwrap(),
+                NanosecondResolution::UNSPECIFIED,
+                WallClockId::local_only(),
+                RestartCounterProvider::NoRestartTracking,
+            )
+            .unwrap();
+            probe.set_compressed_reports(*compressed);
+            for i in 0..10 {
+                let time = Nanoseconds::new(1_000 * (10 - i)).unwrap();
+                probe.record_event_with_payload_with_time(EventId::new(1).unwrap(), i as u32, time);
+                probe.record_event(EventId::new(2).unwrap());
+                probe.record_event(EventId::new(2).unwrap());
+            }
+            let mut report_dest = vec![0; 512];
+            let n_bytes = probe.report(&mut report_dest).unwrap().unwrap().get();
+            assert_eq!(
+                WireReport::new(&report_dest[..n_bytes])
+                    .unwrap()
+                    .is_compressed(),
+                *compressed
+            );
+            reports.push(Report::try_from(&report_dest[..n_bytes]).unwrap());
+        }
+        assert_eq!(reports[0], reports[1]);
+
+        // A compressed log that ends mid-token
+        let mut corrupt = vec![0; 512];
+        let len = {
+            let mut wire = WireReport::new_unchecked(&mut corrupt[..]);
+            wire.set_versioned_fingerprint();
+            wire.set_version(WireReport::<&[u8]>::VERSION);
+            wire.set_flags(WireReport::<&[u8]>::COMPRESSED_FLAG);
+            wire.set_probe_id(ProbeId::new(1).unwrap());
+            wire.set_n_clocks(0);
+            wire.set_n_log_entries(1);
+            wire.payload_mut()[0] = 0x80;
+            wire.set_checksum();
+            wire.report_len()
+        };
+        match Report::try_from(&corrupt[..len]) {
+            Err(SerializationError::ReportWireError(ReportWireError::InvalidCompressedLog)) => (),
+            r => panic!("Expected an invalid compressed log, got {:?}", r),
+        }
+    }
+
     proptest! {
         #[test]
         fn round_trip_serialization(
@@ -0,0 +1,137 @@
+//! Decoding of reports and snapshots in every wire format version,
+//! against a corpus of golden binaries produced by each version.
+//!
+//! Every golden report was produced by the same scenario on the probe
+//! side: probe 1 (1000ns time resolution, wall clock id 3) records
+//! event 1, event 2 with payload 0xDEADBEEF, merges a snapshot from
+//! probe 2, records event 3 at 100ns and the time 200ns, then reports.
+//! `snapshot_v0.bin` is the snapshot probe 2 sent.
+//! `report_v1_authenticated.bin` was produced with the report key
+//! 0x00, 0x01, ..., 0x0F. `report_v1_compressed.bin` was produced
+//! with compressed reports turned on.
+//!
+//! When the report or snapshot format changes, add a golden file
+//! for the new version produced by the same scenario, rather than
+//! replacing the existing ones.
+
+use modality_probe::{
+    wire::{siphash, CausalSnapshotWireError, ReportWireError, WireCausalSnapshot, WireReport},
+    CausalSnapshot, EventId, LogicalClock, Nanoseconds, ProbeEpoch, ProbeId, ProbeTicks,
+};
+use modality_probe_collector_common::{EventLogEntry, Report, SerializationError};
+use std::convert::TryFrom;
+
+const REPORT_V0: &[u8] = include_bytes!("golden/report_v0.bin");
+const REPORT_V1: &[u8] = include_bytes!("golden/report_v1.bin");
+const REPORT_V1_AUTHENTICATED: &[u8] = include_bytes!("golden/report_v1_authenticated.bin");
+const REPORT_V1_COMPRESSED: &[u8] = include_bytes!("golden/report_v1_compressed.bin");
+const SNAPSHOT_V0: &[u8] = include_bytes!("golden/snapshot_v0.bin");
+
+fn clock(id: u32, ticks: u16) -> LogicalClock {
+    LogicalClock {
+        id: ProbeId::new(id).unwrap(),
+        epoch: ProbeEpoch(0),
+        ticks: ProbeTicks(ticks),
+    }
+}
+
+fn golden_key() -> siphash::Key {
+    let mut key = [0; siphash::KEY_LEN];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    key
+}
+
+fn expected_report() -> Report {
+    Report {
+        probe_id: ProbeId::new(1).unwrap(),
+        probe_clock: clock(1, 1),
+        seq_num: 0.into(),
+        persistent_epoch_counting: false,
+        time_resolution: 1000.into(),
+        wall_clock_id: 3.into(),
+        frontier_clocks: vec![clock(1, 0)],
+        event_log: vec![
+            EventLogEntry::TraceClock(clock(1, 0)),
+            EventLogEntry::Event(EventId::EVENT_PROBE_INITIALIZED),
+            EventLogEntry::Event(EventId::new(1).unwrap()),
+            EventLogEntry::EventWithPayload(EventId::new(2).unwrap(), 0xDEAD_BEEF),
+            EventLogEntry::TraceClock(clock(1, 1)),
+            EventLogEntry::TraceClock(clock(2, 0)),
+            EventLogEntry::EventWithTime(Nanoseconds::new(100).unwrap(), EventId::new(3).unwrap()),
+            EventLogEntry::WallClockTime(Nanoseconds::new(200).unwrap()),
+        ],
+    }
+}
+
+#[test]
+fn golden_reports_decode() {
+    let goldens = [
+        (0, REPORT_V0),
+        (1, REPORT_V1),
+        (1, REPORT_V1_AUTHENTICATED),
+        (1, REPORT_V1_COMPRESSED),
+    ];
+    for (version, bytes) in goldens.iter() {
+        let wire = WireReport::new(*bytes).unwrap();
+        assert_eq!(wire.version(), *version);
+        assert_eq!(wire.is_checksummed(), *version >= 1);
+        assert_eq!(wire.is_authenticated(), *bytes == REPORT_V1_AUTHENTICATED);
+        assert_eq!(wire.is_compressed(), *bytes == REPORT_V1_COMPRESSED);
+        assert_eq!(wire.report_len(), bytes.len());
+        assert_eq!(Report::try_from(*bytes).unwrap(), expected_report());
+    }
+}
+
+#[test]
+fn current_version_matches_newest_golden_report() {
+    let mut bytes = vec![0u8; 512];
+    let len = expected_report().write_into_le_bytes(&mut bytes).unwrap();
+    assert_eq!(&bytes[..len], REPORT_V1);
+    let len = expected_report()
+        .write_into_authenticated_le_bytes(&mut bytes, &golden_key())
+        .unwrap();
+    assert_eq!(&bytes[..len], REPORT_V1_AUTHENTICATED);
+}
+
+#[test]
+fn golden_authenticated_report_verifies() {
+    let wire = WireReport::new(REPORT_V1_AUTHENTICATED).unwrap();
+    assert_eq!(wire.check_mac(&golden_key()), Ok(()));
+    assert_eq!(
+        wire.check_mac(&[0; siphash::KEY_LEN]),
+        Err(ReportWireError::MacMismatch)
+    );
+}
+
+#[test]
+fn newer_report_versions_are_rejected() {
+    let mut bytes = REPORT_V1.to_vec();
+    let unsupported = WireReport::<&[u8]>::VERSION + 1;
+    WireReport::new_unchecked(&mut bytes[..]).set_version(unsupported);
+    match Report::try_from(&bytes[..]) {
+        Err(SerializationError::ReportWireError(ReportWireError::UnsupportedVersion(v))) => {
+            assert_eq!(v, unsupported)
+        }
+        r => panic!("Expected an unsupported version, got {:?}", r),
+    }
+}
+
+#[test]
+fn golden_snapshots_decode() {
+    let wire = WireCausalSnapshot::new(SNAPSHOT_V0).unwrap();
+    assert_eq!(wire.version(), 0);
+    let snapshot = CausalSnapshot::try_from(SNAPSHOT_V0).unwrap();
+    assert_eq!(snapshot.clock, clock(2, 0));
+    assert_eq!(snapshot.interaction_id(), None);
+    assert_eq!(&snapshot.to_le_bytes()[..], SNAPSHOT_V0);
+
+    let mut bytes = SNAPSHOT_V0.to_vec();
+    let unsupported = WireCausalSnapshot::<&[u8]>::VERSION + 1;
+    WireCausalSnapshot::new_unchecked(&mut bytes[..]).set_version(unsupported);
+    assert_eq!(
+        CausalSnapshot::try_from(&bytes[..]),
+        Err(CausalSnapshotWireError::UnsupportedVersion(unsupported))
+    );
+}
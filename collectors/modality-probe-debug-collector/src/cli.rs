@@ -8,6 +8,7 @@ use structopt::StructOpt;
 
 use goblin::elf::Elf;
 
+use modality_probe::{EventId, EventMask, EVENT_MASK_BITS};
 use modality_probe_debug_collector::{Config, ProbeAddr, TargetConfig, Word};
 
 #[derive(Debug, Error)]
@@ -24,6 +25,14 @@ pub enum CliError {
     InvalidDuration(String),
     #[error(display = "Symbol not found in given ELF file: \"{}\"", _0)]
     SymbolNotFound(String),
+    #[error(display = "Not a valid event id: \"{}\"", _0)]
+    InvalidEventId(String),
+    #[error(
+        display = "Event id {} can't be disabled; only ids up to {} can",
+        _0,
+        _1
+    )]
+    UnmaskableEventId(u32, u32),
 }
 
 #[derive(Debug, Default, StructOpt)]
@@ -75,6 +84,13 @@ pub struct Opts {
     #[structopt(short = "r", long = "reset")]
     init_timeout: Option<String>,
 
+    /// Comma-separated ids of events the probes should stop recording.
+    /// Replaces any events the probes had disabled themselves. Only
+    /// ids from 1 to 256 can be disabled, and only on probes built
+    /// with the `event-filtering` feature.
+    #[structopt(long = "disable-events")]
+    disabled_events: Option<String>,
+
     /// Symbols and/or raw addresses of probes or probe pointers.
     /// Raw addresses should be in hex format, prefixed with '0x' or '0X'
     /// Probe pointer addresses and symbols should be prefixed with `*`.
@@ -142,6 +158,28 @@ pub(crate) fn config_from_options(options: Opts) -> Result<Config, CliError> {
         None
     };
 
+    let disabled_events = if let Some(ids) = options.disabled_events.as_ref() {
+        let mut mask = EventMask::EMPTY;
+        for id in ids.split(',') {
+            let event_id = id
+                .trim()
+                .parse()
+                .ok()
+                .and_then(EventId::new)
+                .ok_or_else(|| CliError::InvalidEventId(id.to_string()))?;
+            if !EventMask::can_contain(event_id) {
+                return Err(CliError::UnmaskableEventId(
+                    event_id.get_raw(),
+                    EVENT_MASK_BITS,
+                ));
+            }
+            mask = mask.with_event(event_id);
+        }
+        Some(mask)
+    } else {
+        None
+    };
+
     let target = if let Some(probe_rs_target) = options.chip_type {
         TargetConfig::ProbeRsTarget(probe_rs_target)
     } else if let Some(gdb_addr) = options.gdb_addr {
@@ -158,6 +196,7 @@ pub(crate) fn config_from_options(options: Opts) -> Result<Config, CliError> {
         interval,
         output_path: options.output_path,
         probe_addrs,
+        disabled_events,
     })
 }
 
@@ -291,11 +330,63 @@ mod tests {
                 target: TargetConfig::ProbeRsTarget("stm32".to_string()),
                 interval: Duration::from_millis(1000),
                 output_path: "./out".into(),
+                disabled_events: None,
                 probe_addrs: vec![ProbeAddr::Addr(Word::U32(0x100))]
             }
         )
     }
 
+    /// Disable some events on the probes
+    #[test]
+    fn disable_events() {
+        let config = config_from_options(
+            options_from_str(
+                "modality-probe-debug-collector \
+                --attach stm32 \
+                --interval 1s \
+                --output ./out \
+                --disable-events 1,2,256 \
+                0x100",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let expected = EventMask::EMPTY
+            .with_event(EventId::new(1).unwrap())
+            .with_event(EventId::new(2).unwrap())
+            .with_event(EventId::new(256).unwrap());
+        assert_eq!(config.disabled_events, Some(expected));
+
+        assert!(config_from_options(
+            options_from_str(
+                "modality-probe-debug-collector \
+                --attach stm32 \
+                --interval 1s \
+                --output ./out \
+                --disable-events 0 \
+                0x100",
+            )
+            .unwrap()
+        )
+        .is_err());
+
+        // Ids above 256 would alias other events' bits
+        assert!(matches!(
+            config_from_options(
+                options_from_str(
+                    "modality-probe-debug-collector \
+                    --attach stm32 \
+                    --interval 1s \
+                    --output ./out \
+                    --disable-events 1,257 \
+                    0x100",
+                )
+                .unwrap()
+            ),
+            Err(CliError::UnmaskableEventId(257, 256))
+        ));
+    }
+
     /// Specify gdb server instead of attach target
     #[test]
     fn specify_gdb_server() {
@@ -318,6 +409,7 @@ mod tests {
                 target: TargetConfig::GdbAddr(SocketAddrV4::from_str("127.0.0.1:3000").unwrap()),
                 interval: Duration::from_millis(1000),
                 output_path: "./out".into(),
+                disabled_events: None,
                 probe_addrs: vec![ProbeAddr::Addr(Word::U32(0x100))]
             }
         )
@@ -428,6 +520,7 @@ mod tests {
                 target: TargetConfig::ProbeRsTarget("stm32".to_string()),
                 interval: Duration::from_millis(1000),
                 output_path: "./out".into(),
+                disabled_events: None,
                 probe_addrs: vec![
                     ProbeAddr::Addr(Word::U32(0x20000000)),
                     ProbeAddr::Addr(Word::U32(0x20000004)),
@@ -463,6 +556,7 @@ mod tests {
                 target: TargetConfig::ProbeRsTarget("stm32".to_string()),
                 interval: Duration::from_millis(1000),
                 output_path: "./out".into(),
+                disabled_events: None,
                 probe_addrs: vec![
                     ProbeAddr::Addr(Word::U32(0x1)),
                     ProbeAddr::Addr(Word::U32(0x10)),
@@ -517,6 +611,7 @@ mod tests {
                 target: TargetConfig::ProbeRsTarget("stm32".to_string()),
                 interval: Duration::from_millis(1000),
                 output_path: "./out".into(),
+                disabled_events: None,
                 probe_addrs: vec![ProbeAddr::Addr(Word::U64(0x1))]
             }
         )
@@ -547,6 +642,7 @@ mod tests {
                 target: TargetConfig::ProbeRsTarget("stm32".to_string()),
                 interval: Duration::from_millis(1000),
                 output_path: "./out".into(),
+                disabled_events: None,
                 probe_addrs: vec![
                     ProbeAddr::PtrAddr(Word::U32(0x1)),
                     ProbeAddr::PtrAddr(Word::U32(0x10)),
@@ -583,6 +679,7 @@ mod tests {
                 target: TargetConfig::ProbeRsTarget("stm32".to_string()),
                 interval: Duration::from_millis(1000),
                 output_path: "./out".into(),
+                disabled_events: None,
                 probe_addrs: vec![
                     ProbeAddr::PtrAddr(Word::U64(0x1)),
                     ProbeAddr::Addr(Word::U64(0x10)),
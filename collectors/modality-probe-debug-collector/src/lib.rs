@@ -20,7 +20,7 @@ use fenced_ring_buffer::async_reader::{FencedReader, Snapper};
 use fenced_ring_buffer::WholeEntry;
 use modality_probe::field_offsets::*;
 use modality_probe::{
-    log::LogEntry, EventId, LogicalClock, ModalityProbe, NanosecondResolution, OrdClock,
+    log::LogEntry, EventId, EventMask, LogicalClock, ModalityProbe, NanosecondResolution, OrdClock,
     ProbeEpoch, ProbeId, ProbeTicks, WallClockId,
 };
 use modality_probe_collector_common::{
@@ -91,6 +91,8 @@ pub struct Config {
     pub output_path: PathBuf,
     pub init_timeout: Option<Duration>,
     pub probe_addrs: Vec<ProbeAddr>,
+    /// Events the probes should stop recording, if any
+    pub disabled_events: Option<EventMask>,
 }
 
 /// Target device, either directly through probe-rs or by proxy through a gdb server
@@ -268,6 +270,27 @@ impl PriorityWriter {
     }
 }
 
+/// Used to write to probe's "disabled_events" field, which only
+/// probes built with the `event-filtering` feature have. Each bit of
+/// the mask is one event id from 1 to `EVENT_MASK_BITS`; larger ids
+/// can't be masked, see `EventMask::can_contain`.
+struct EventMaskWriter {
+    /// Memory accessor used to write to device memory
+    mem_accessor: Rc<RefCell<dyn Target>>,
+    /// Address of the disabled events field
+    mask_field_addr: Word,
+}
+
+impl EventMaskWriter {
+    fn write(&mut self, mask: &EventMask) -> Result<(), TargetError> {
+        let mut mem = self.mem_accessor.borrow_mut();
+        for (i, word) in mask.words().iter().enumerate() {
+            mem.write_32(self.mask_field_addr + (i * size_of::<u32>()) as u64, *word)?;
+        }
+        Ok(())
+    }
+}
+
 /// Log collector for a single probe
 pub struct Collector {
     /// Sequence number of next report
@@ -282,6 +305,8 @@ pub struct Collector {
     clocks: Vec<LogicalClock>,
     /// Used to write to the probe's "overwrite_priority" field
     priority_writer: PriorityWriter,
+    /// Used to write to the probe's "disabled_events" field
+    event_mask_writer: EventMaskWriter,
     /// Time resolution extracted from the probe
     time_resolution: NanosecondResolution,
     /// Wall clock id extracted from the probe
@@ -328,8 +353,10 @@ impl Collector {
         let overwrite_seqn_high_addr = hist_addr + overwrite_seqn_high_offset();
         let overwrite_seqn_low_addr = hist_addr + overwrite_seqn_low_offset();
         let priority_field_addr = hist_addr + overwrite_priority_offset();
+        let mask_field_addr = hist_addr + disabled_events_offset(hist_addr.size());
 
         let priority_mem_accessor = mem_accessor.clone();
+        let mask_mem_accessor = mem_accessor.clone();
         let mut clocks = Vec::new();
         // Merge self clock set to 0
         Self::merge_clock(
@@ -360,6 +387,10 @@ impl Collector {
                 mem_accessor: priority_mem_accessor,
                 priority_field_addr,
             },
+            event_mask_writer: EventMaskWriter {
+                mem_accessor: mask_mem_accessor,
+                mask_field_addr,
+            },
             time_resolution: time_res.into(),
             wall_clock_id: wall_clock_id.into(),
             persistent_epoch_counting: persistent_epoch_counting != 0,
@@ -511,6 +542,12 @@ impl Collector {
     pub fn set_overwrite_priority(&mut self, level: u32) -> Result<(), TargetError> {
         self.priority_writer.write(level)
     }
+
+    /// Write to "disabled events" field in probe, replacing the set
+    /// of events the probe does not record
+    pub fn set_disabled_events(&mut self, mask: &EventMask) -> Result<(), TargetError> {
+        self.event_mask_writer.write(mask)
+    }
 }
 
 /// Open memory accessor based on config
@@ -539,7 +576,11 @@ fn initialize_collectors(
 ) -> Result<Vec<Collector>, Error> {
     let mut collectors = Vec::new();
     for probe_addr in c.probe_addrs.iter() {
-        collectors.push(Collector::initialize(probe_addr, mem_accessor.clone())?);
+        let mut collector = Collector::initialize(probe_addr, mem_accessor.clone())?;
+        if let Some(mask) = c.disabled_events.as_ref() {
+            collector.set_disabled_events(mask)?;
+        }
+        collectors.push(collector);
     }
     Ok(collectors)
 }
@@ -1025,6 +1066,10 @@ pub mod tests {
 
         collector.set_overwrite_priority(1).unwrap();
         assert_eq!(probe.get_overwrite_priority_level(), 1);
+
+        let mask = EventMask::EMPTY.with_event(ev(1)).with_event(ev(256));
+        collector.set_disabled_events(&mask).unwrap();
+        assert_eq!(probe.disabled_events(), mask);
     }
 
     struct HashMapMemAccessor(HashMap<Word, u32>);
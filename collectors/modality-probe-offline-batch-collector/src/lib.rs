@@ -9,9 +9,13 @@ use std::path::PathBuf;
 use buf_redux::BufReader;
 use chrono::Utc;
 use log::{debug, warn};
-use modality_probe::{wire::WireReport, ProbeId};
+use modality_probe::{
+    wire::{ReportWireError, WireReport},
+    ProbeId,
+};
 use modality_probe_collector_common::{
-    self as common, json, Report, ReportLogEntry, SequenceNumber, SessionId,
+    self as common, auth::ReportKeys, json, Report, ReportLogEntry, SequenceNumber,
+    SerializationError, SessionId,
 };
 use structopt::StructOpt;
 
@@ -32,6 +36,11 @@ pub struct Opts {
     /// The output file location, defaults to the current directory
     #[structopt(short = "o", long, parse(from_os_str))]
     pub output_file: Option<PathBuf>,
+
+    /// Only accept reports authenticated with the keys in this file,
+    /// one `<probe id> <32 hex digit key>` per line
+    #[structopt(short = "k", long, parse(from_os_str))]
+    pub key_file: Option<PathBuf>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -39,6 +48,12 @@ pub struct ReportMetrics {
     pub bytes_accumulated: u64,
     pub bytes_discarded: u64,
     pub reports_discarded: u64,
+    /// Reports whose checksum didn't match, also counted in
+    /// `reports_discarded`
+    pub reports_corrupt: u64,
+    /// Reports which weren't authenticated with their probe's key,
+    /// also counted in `reports_discarded`
+    pub reports_unauthenticated: u64,
     pub probe_report_metrics: HashMap<ProbeId, ProbeReportMetrics>,
 }
 
@@ -72,10 +87,10 @@ impl ProbeReportMetrics {
 #[derive(Debug)]
 pub struct OfflineBatchCollector<'a, I: Read, O: Write + 'a> {
     fingerprint_len: usize,
-    header_len: usize,
     log_entries_buffer: Vec<ReportLogEntry>,
     metrics: ReportMetrics,
     session_id: SessionId,
+    report_keys: Option<ReportKeys>,
     eof_reached: bool,
     reader: BufReader<I>,
     log_output_writer: &'a mut O,
@@ -86,16 +101,22 @@ impl<'a, I: Read, O: Write> OfflineBatchCollector<'a, I, O> {
         let fingerprint_len = mem::size_of_val(&WireReport::<&[u8]>::FINGERPRINT);
         OfflineBatchCollector {
             fingerprint_len,
-            header_len: WireReport::<&[u8]>::header_len(),
             log_entries_buffer: Vec::with_capacity(4096),
             metrics: ReportMetrics::default(),
             session_id,
+            report_keys: None,
             eof_reached: false,
             reader: BufReader::with_capacity_ringbuf(8192, reader),
             log_output_writer,
         }
     }
 
+    /// Only accept reports authenticated with their probe's key
+    pub fn with_report_keys(mut self, report_keys: ReportKeys) -> Self {
+        self.report_keys = Some(report_keys);
+        self
+    }
+
     /// Run the collection loop, consuming until EOF or an error is encountered
     pub fn run(mut self) -> io::Result<ReportMetrics> {
         // Keep consuming until EOF or an error is encountered
@@ -190,13 +211,29 @@ impl<'a, I: Read, O: Write> OfflineBatchCollector<'a, I, O> {
                     let slice = &bytes[fingerprint_offset..];
                     let r = WireReport::new_unchecked(&slice[..]);
                     if r.check_len().is_ok() && r.check_payload_len().is_ok() {
-                        let payload_len = r.payload_len();
-                        let report_size = self.header_len + payload_len;
+                        let report_size = r.report_len();
                         bytes_consumed += report_size;
                         debug!("Found report, size {} bytes", report_size);
                         let report_bytes = &r.into_inner()[..report_size];
                         self.log_entries_buffer.clear();
+                        let authenticated = match &self.report_keys {
+                            Some(keys) => keys.authenticate_bytes(report_bytes),
+                            None => Ok(()),
+                        };
                         match Report::try_from(report_bytes) {
+                            Ok(_) if authenticated.is_err() => {
+                                self.metrics.reports_discarded =
+                                    self.metrics.reports_discarded.saturating_add(1);
+                                self.metrics.reports_unauthenticated =
+                                    self.metrics.reports_unauthenticated.saturating_add(1);
+                                self.metrics.bytes_discarded = self
+                                    .metrics
+                                    .bytes_discarded
+                                    .saturating_add(report_size as _);
+                                if let Err(e) = authenticated {
+                                    warn!("{}, throwing away {} bytes", e, report_size);
+                                }
+                            }
                             Ok(log_report) => {
                                 let metrics = self
                                     .metrics
@@ -213,6 +250,21 @@ impl<'a, I: Read, O: Write> OfflineBatchCollector<'a, I, O> {
                                     warn!("Encountered a malformed report, discarding: {}", e);
                                 }
                             }
+                            Err(SerializationError::ReportWireError(
+                                ReportWireError::ChecksumMismatch,
+                            )) => {
+                                // The header may be what's corrupt, so don't
+                                // trust its length; resume the search for a
+                                // fingerprint just past this one
+                                bytes_consumed -= report_size - 1;
+                                self.metrics.reports_discarded =
+                                    self.metrics.reports_discarded.saturating_add(1);
+                                self.metrics.reports_corrupt =
+                                    self.metrics.reports_corrupt.saturating_add(1);
+                                self.metrics.bytes_discarded =
+                                    self.metrics.bytes_discarded.saturating_add(1);
+                                warn!("Corrupt report, resynchronizing");
+                            }
                             Err(e) => {
                                 self.metrics.reports_discarded =
                                     self.metrics.reports_discarded.saturating_add(1);
@@ -4,7 +4,7 @@ use std::io::{self, Read};
 use std::{env, fs};
 
 use log::info;
-use modality_probe_collector_common::SessionId;
+use modality_probe_collector_common::{auth::ReportKeys, SessionId};
 use structopt::StructOpt;
 
 use modality_probe_offline_batch_collector::{OfflineBatchCollector, Opts};
@@ -35,7 +35,17 @@ fn main() -> io::Result<()> {
         }
     };
 
-    let collector = OfflineBatchCollector::new(session_id, reader, &mut log_output_writer);
+    let mut collector = OfflineBatchCollector::new(session_id, reader, &mut log_output_writer);
+    if let Some(key_file) = opts.key_file {
+        let keys = ReportKeys::from_file(&key_file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        info!(
+            "Using {} report keys from {}",
+            keys.len(),
+            key_file.display()
+        );
+        collector = collector.with_report_keys(keys);
+    }
 
     let metrics = collector.run()?;
 
@@ -47,11 +57,13 @@ fn main() -> io::Result<()> {
         .sum();
 
     info!(
-        "Collected {} reports from {} probes in {}, {} reports were discarded",
+        "Collected {} reports from {} probes in {}, {} reports were discarded ({} corrupt, {} unauthenticated)",
         num_reports,
         num_probes,
         output_file.display(),
         metrics.reports_discarded,
+        metrics.reports_corrupt,
+        metrics.reports_unauthenticated,
     );
 
     info!(
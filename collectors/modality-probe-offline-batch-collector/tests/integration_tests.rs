@@ -1,7 +1,7 @@
 #![deny(warnings)]
 
 use modality_probe::*;
-use modality_probe_collector_common::SessionId;
+use modality_probe_collector_common::{auth::ReportKeys, SessionId};
 use modality_probe_offline_batch_collector::{OfflineBatchCollector, ProbeReportMetrics};
 use proptest::prelude::*;
 use std::convert::TryInto;
@@ -341,4 +341,171 @@ fn discarded_reports_are_detected() {
     assert_eq!(metrics.bytes_accumulated, bytes_written as u64);
     assert_eq!(metrics.bytes_discarded, corrupted_bytes as u64);
     assert_eq!(metrics.reports_discarded, 1);
+    assert_eq!(metrics.reports_corrupt, 1);
+}
+
+#[test]
+fn collection_resumes_after_corrupt_headers() {
+    init_logging();
+
+    let probe_id = 1.try_into().unwrap();
+    let mut storage = vec![MaybeUninit::new(0_u8); STORAGE_SIZE];
+    let probe = ModalityProbe::initialize_at(
+        &mut storage,
+        probe_id,
+        NanosecondResolution::UNSPECIFIED,
+        WallClockId::local_only(),
+        RestartCounterProvider::NoRestartTracking,
+    )
+    .unwrap();
+
+    let root_dir = tempfile::tempdir().unwrap();
+    let root_path = root_dir.path().to_owned();
+    let reports_in_path = root_path.join("reports.bin");
+    let logs_out_path = root_path.join("reports.jsonl");
+
+    let (bytes_written, corrupted_bytes) = {
+        let mut report_file = File::create(&reports_in_path).unwrap();
+        let mut bytes_written = 0;
+
+        // First report is ok
+        fill_probe_log(probe);
+        bytes_written += write_report(probe, &mut report_file);
+
+        // Second has a bit flipped in its length, so taking it at its
+        // word would swallow the start of the third
+        fill_probe_log(probe);
+        let mut buffer = vec![0_u8; 2 * STORAGE_SIZE];
+        let corrupted_bytes = probe.report(&mut buffer[..]).unwrap().unwrap().get();
+        {
+            let mut r = wire::WireReport::new(&mut buffer[..corrupted_bytes]).unwrap();
+            let n_log_entries = r.n_log_entries();
+            r.set_n_log_entries(n_log_entries ^ 0x1);
+        }
+        report_file.write_all(&buffer[..corrupted_bytes]).unwrap();
+        bytes_written += corrupted_bytes;
+
+        // Third is in the original format, without version and flags
+        // bytes or a checksum
+        fill_probe_log(probe);
+        let len = probe.report(&mut buffer[..]).unwrap().unwrap().get();
+        let mut original = buffer[..4].to_vec();
+        original.extend_from_slice(&buffer[6..len - 4]);
+        wire::WireReport::new_unchecked(&mut original[..]).set_fingerprint();
+        report_file.write_all(&original).unwrap();
+        bytes_written += original.len();
+
+        report_file.sync_all().unwrap();
+
+        (bytes_written, corrupted_bytes)
+    };
+
+    let metrics = {
+        let mut reader = File::open(&reports_in_path).unwrap();
+        let mut logs_out_file = File::create(&logs_out_path).unwrap();
+
+        let session_id = SessionId(0);
+        let collector = OfflineBatchCollector::new(session_id, &mut reader, &mut logs_out_file);
+        let metrics = collector.run().unwrap();
+
+        logs_out_file.sync_all().unwrap();
+
+        metrics
+    };
+
+    assert_eq!(
+        metrics.probe_report_metrics.get(&probe_id).unwrap().clone(),
+        ProbeReportMetrics {
+            num_reports: 2,
+            missed_seq_nums: 1,
+            last_seq_num: 2.into(),
+        }
+    );
+
+    assert_eq!(metrics.bytes_accumulated, bytes_written as u64);
+    assert_eq!(metrics.bytes_discarded, corrupted_bytes as u64);
+    assert_eq!(metrics.reports_discarded, 1);
+    assert_eq!(metrics.reports_corrupt, 1);
+}
+
+#[test]
+fn unauthenticated_reports_are_rejected() {
+    init_logging();
+
+    let key = [1; wire::siphash::KEY_LEN];
+    let mut keys = ReportKeys::default();
+    let mut probes = Vec::new();
+    let mut storages = vec![vec![MaybeUninit::new(0_u8); STORAGE_SIZE]; 4];
+    for (i, storage) in storages.iter_mut().enumerate() {
+        let probe_id: ProbeId = (i as u32 + 1).try_into().unwrap();
+        let probe = ModalityProbe::initialize_at(
+            storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        probes.push((probe_id, probe));
+    }
+    // Probe 1 is authenticated, probe 2 isn't, probe 3 uses the wrong
+    // key and probe 4 has no key on the collector's side
+    probes[0].1.set_report_key(Some(key));
+    keys.insert(probes[0].0, key);
+    keys.insert(probes[1].0, key);
+    probes[2]
+        .1
+        .set_report_key(Some([2; wire::siphash::KEY_LEN]));
+    keys.insert(probes[2].0, key);
+    probes[3].1.set_report_key(Some(key));
+
+    let root_dir = tempfile::tempdir().unwrap();
+    let root_path = root_dir.path().to_owned();
+    let reports_in_path = root_path.join("reports.bin");
+    let logs_out_path = root_path.join("reports.jsonl");
+
+    let (bytes_written, rejected_bytes) = {
+        let mut report_file = File::create(&reports_in_path).unwrap();
+        let mut bytes_written = 0;
+        let mut rejected_bytes = 0;
+        for (i, (_, probe)) in probes.iter_mut().enumerate() {
+            fill_probe_log(probe);
+            let n = write_report(probe, &mut report_file);
+            bytes_written += n;
+            if i != 0 {
+                rejected_bytes += n;
+            }
+        }
+        report_file.sync_all().unwrap();
+        (bytes_written, rejected_bytes)
+    };
+
+    let metrics = {
+        let mut reader = File::open(&reports_in_path).unwrap();
+        let mut logs_out_file = File::create(&logs_out_path).unwrap();
+
+        let session_id = SessionId(0);
+        let collector = OfflineBatchCollector::new(session_id, &mut reader, &mut logs_out_file)
+            .with_report_keys(keys);
+        let metrics = collector.run().unwrap();
+
+        logs_out_file.sync_all().unwrap();
+
+        metrics
+    };
+
+    assert_eq!(metrics.probe_report_metrics.len(), 1);
+    assert_eq!(
+        metrics
+            .probe_report_metrics
+            .get(&probes[0].0)
+            .unwrap()
+            .num_reports,
+        1
+    );
+    assert_eq!(metrics.bytes_accumulated, bytes_written as u64);
+    assert_eq!(metrics.bytes_discarded, rejected_bytes as u64);
+    assert_eq!(metrics.reports_discarded, 3);
+    assert_eq!(metrics.reports_unauthenticated, 3);
+    assert_eq!(metrics.reports_corrupt, 0);
 }
@@ -1,13 +1,24 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::{
-    io::{Error as IoError, Write},
-    net::{SocketAddr, UdpSocket},
+    cmp,
+    io::{Error as IoError, ErrorKind, Write},
+    net::{IpAddr, SocketAddr, UdpSocket},
     path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use chrono::Utc;
 
-use modality_probe_collector_common::{self as common, json, Report, ReportLogEntry, SessionId};
+use modality_probe::{
+    wire::{siphash, ControlCommand, WireControlMessage},
+    LogicalClock, ProbeId,
+};
+
+use modality_probe_collector_common::{
+    self as common, auth::ReportKeys, json, Report, ReportLogEntry, SequenceNumber, SessionId,
+};
 
 mod opts;
 
@@ -18,6 +29,14 @@ pub struct Config {
     pub addr: SocketAddr,
     pub session_id: SessionId,
     pub output_file: PathBuf,
+    /// Acknowledge each report received, for probes in acknowledged
+    /// reporting mode
+    pub acknowledge_reports: bool,
+    /// Only accept reports authenticated with the keys in this file
+    pub key_file: Option<PathBuf>,
+    /// Forward control messages received from these addresses to
+    /// their probes; forwarding is off when this is empty
+    pub control_sources: Vec<IpAddr>,
 }
 
 pub struct ShutdownSignalSender {
@@ -54,10 +73,69 @@ impl ShutdownSignalSender {
     }
 }
 
+/// The counter for the next authenticated control message. It's the
+/// wall clock time in nanoseconds, so it keeps increasing across
+/// collector restarts, but never repeats within this process.
+fn next_control_counter() -> u64 {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let next = |last: u64| cmp::max(now, last + 1);
+    let last = LAST
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+            Some(next(last))
+        })
+        .unwrap_or_else(|last| last);
+    next(last)
+}
+
+/// Send a control command to a probe at `probe_addr` from `socket`,
+/// tagged with a counter and MAC under `key` if the probe has a
+/// report key
+pub fn send_control_command(
+    socket: &UdpSocket,
+    probe_addr: SocketAddr,
+    probe_id: ProbeId,
+    key: Option<&siphash::Key>,
+    command: &ControlCommand,
+) -> Result<(), IoError> {
+    let mut buf = [0u8; 64];
+    let len = match key {
+        Some(key) => {
+            command.write_authenticated_message(probe_id, key, next_control_counter(), &mut buf)
+        }
+        None => command.write_message(probe_id, &mut buf),
+    }
+    .expect("Control message buffer too small");
+    socket.send_to(&buf[..len], probe_addr)?;
+    Ok(())
+}
+
+/// Send a control command to a probe by way of the collector at
+/// `collector_addr`, which forwards it to the address it last
+/// received a report from that probe at
+pub fn send_control_command_via_collector(
+    collector_addr: SocketAddr,
+    probe_id: ProbeId,
+    key: Option<&siphash::Key>,
+    command: &ControlCommand,
+) -> Result<(), IoError> {
+    let socket = UdpSocket::bind(OS_PICK_ADDR_HINT)?;
+    send_control_command(&socket, collector_addr, probe_id, key, command)
+}
+
 pub fn start_receiving(
     config: Config,
     shutdown_signal_receiver: ShutdownSignalReceiver,
 ) -> Result<(), IoError> {
+    let report_keys = match config.key_file {
+        Some(path) => Some(
+            ReportKeys::from_file(path)
+                .map_err(|e| IoError::new(ErrorKind::InvalidData, e.to_string()))?,
+        ),
+        None => None,
+    };
     let mut file = std::fs::OpenOptions::new()
         .append(true)
         .create(true)
@@ -65,6 +143,9 @@ pub fn start_receiving(
     start_receiving_at_addr(
         config.addr,
         config.session_id,
+        config.acknowledge_reports,
+        report_keys.as_ref(),
+        &config.control_sources,
         &mut file,
         shutdown_signal_receiver,
     )
@@ -73,12 +154,18 @@ pub fn start_receiving(
 pub fn start_receiving_at_addr<W: Write>(
     addr: SocketAddr,
     session_id: SessionId,
+    acknowledge_reports: bool,
+    report_keys: Option<&ReportKeys>,
+    control_sources: &[IpAddr],
     log_output_writer: &mut W,
     shutdown_signal_receiver: ShutdownSignalReceiver,
 ) -> Result<(), IoError> {
     start_receiving_from_socket(
         UdpSocket::bind(addr)?,
         session_id,
+        acknowledge_reports,
+        report_keys,
+        control_sources,
         log_output_writer,
         shutdown_signal_receiver,
     );
@@ -88,12 +175,23 @@ pub fn start_receiving_at_addr<W: Write>(
 pub fn start_receiving_from_socket<W: Write>(
     socket: UdpSocket,
     session_id: SessionId,
+    acknowledge_reports: bool,
+    report_keys: Option<&ReportKeys>,
+    control_sources: &[IpAddr],
     log_output_writer: &mut W,
     shutdown_signal_receiver: ShutdownSignalReceiver,
 ) {
     let addr = socket.local_addr().map(|a| a.to_string());
     let mut buf = vec![0u8; 1024 * 1024];
     let mut log_entries_buffer: Vec<ReportLogEntry> = Vec::with_capacity(4096);
+    // Where each probe's reports last came from, for forwarding control messages
+    let mut probe_addrs: HashMap<ProbeId, SocketAddr> = HashMap::new();
+    // The sequence number and clock of the last report seen from each
+    // probe, to drop retransmissions. The clock tells a probe that
+    // rebooted and started its sequence numbers over from one that
+    // sent the same report again.
+    let mut last_reports: HashMap<ProbeId, (SequenceNumber, LogicalClock)> = HashMap::new();
+    let mut reports_unauthenticated: u64 = 0;
     loop {
         if shutdown_signal_receiver.try_recv().is_ok() {
             return;
@@ -103,7 +201,7 @@ pub fn start_receiving_from_socket<W: Write>(
         for b in buf[..8].iter_mut() {
             *b = 0;
         }
-        let (bytes_read, _src) = match socket.recv_from(&mut buf) {
+        let (bytes_read, src) = match socket.recv_from(&mut buf) {
             Ok(r) => r,
             Err(e) => {
                 match addr.as_ref() {
@@ -118,6 +216,17 @@ pub fn start_receiving_from_socket<W: Write>(
             // during a shutdown
             continue;
         }
+        if let Ok(msg) = WireControlMessage::new(&buf[..bytes_read]) {
+            if control_sources.contains(&src.ip()) {
+                forward_control_message(&socket, &probe_addrs, msg);
+            } else {
+                eprintln!(
+                    "Dropped a control message from {}, which isn't an allowed control source",
+                    src
+                );
+            }
+            continue;
+        }
         let receive_time = Utc::now();
 
         // N.B. If we were feeling bottlenecked, hand off the read bytes to another thread
@@ -129,20 +238,8 @@ pub fn start_receiving_from_socket<W: Write>(
         // log format settles down some before doing this.
         log_entries_buffer.clear();
 
-        match Report::try_from(&buf[..bytes_read]) {
-            Ok(log_report) => {
-                if let Err(e) = common::add_log_report_to_entries(
-                    &log_report,
-                    session_id,
-                    receive_time,
-                    &mut log_entries_buffer,
-                ) {
-                    eprintln!(
-                        "Encountered a malformed report, not adding it to the trace: {}",
-                        e
-                    )
-                }
-            }
+        let log_report = match Report::try_from(&buf[..bytes_read]) {
+            Ok(log_report) => log_report,
             Err(_) => {
                 eprintln!(
                     "Error parsing a message as a report, throwing away {} bytes",
@@ -151,12 +248,84 @@ pub fn start_receiving_from_socket<W: Write>(
 
                 continue;
             }
+        };
+        if let Some(keys) = report_keys {
+            if let Err(e) = keys.authenticate_bytes(&buf[..bytes_read]) {
+                reports_unauthenticated += 1;
+                eprintln!(
+                    "Rejected a report from {} ({} rejected so far): {}",
+                    src, reports_unauthenticated, e
+                );
+                continue;
+            }
+        }
+        probe_addrs.insert(log_report.probe_id, src);
+
+        // A retransmission of a report already written out only needs
+        // acknowledging again. Only probes in acknowledged reporting
+        // mode retransmit, so don't second guess anyone else's reports.
+        let report_id = (log_report.seq_num, log_report.probe_clock);
+        let is_retransmission = acknowledge_reports
+            && last_reports.insert(log_report.probe_id, report_id) == Some(report_id);
+        if !is_retransmission {
+            if let Err(e) = common::add_log_report_to_entries(
+                &log_report,
+                session_id,
+                receive_time,
+                &mut log_entries_buffer,
+            ) {
+                eprintln!(
+                    "Encountered a malformed report, not adding it to the trace: {}",
+                    e
+                )
+            }
+            if let Err(e) = json::write_log_entries(log_output_writer, &log_entries_buffer) {
+                eprintln!("Error writing log entries: {}", e);
+                // Leave the report unacknowledged so the probe sends it again
+                last_reports.remove(&log_report.probe_id);
+                continue;
+            }
+            let _ = log_output_writer.flush();
         }
 
-        if let Err(e) = json::write_log_entries(log_output_writer, &log_entries_buffer) {
-            eprintln!("Error writing log entries: {}", e);
+        if acknowledge_reports {
+            let ack = ControlCommand::AcknowledgeReport(log_report.seq_num.0);
+            let key = report_keys.and_then(|keys| keys.get(log_report.probe_id));
+            if let Err(e) = send_control_command(&socket, src, log_report.probe_id, key, &ack) {
+                eprintln!("Error acknowledging a report: {}", e);
+            }
+        }
+    }
+}
+
+/// Forward a control message to the address its probe last reported from
+fn forward_control_message(
+    socket: &UdpSocket,
+    probe_addrs: &HashMap<ProbeId, SocketAddr>,
+    msg: WireControlMessage<&[u8]>,
+) {
+    let probe_id = match msg.probe_id() {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Error parsing a control message, not forwarding it: {}", e);
+            return;
+        }
+    };
+    match probe_addrs.get(&probe_id) {
+        Some(addr) => {
+            if let Err(e) = socket.send_to(msg.as_ref(), addr) {
+                eprintln!(
+                    "Error forwarding a control message to probe {} at {}: {}",
+                    probe_id.get_raw(),
+                    addr,
+                    e
+                );
+            }
         }
-        let _ = log_output_writer.flush();
+        None => eprintln!(
+            "No reports seen from probe {} yet, not forwarding a control message to it",
+            probe_id.get_raw()
+        ),
     }
 }
 
@@ -374,6 +543,9 @@ mod tests {
             addr: server_addr,
             session_id,
             output_file: output_file_path.clone(),
+            acknowledge_reports: false,
+            key_file: None,
+            control_sources: Vec::new(),
         };
         let h = std::thread::spawn(move || {
             let mut file = std::fs::OpenOptions::new()
@@ -385,7 +557,15 @@ mod tests {
             server_state_sender
                 .send(ServerState::Started)
                 .expect("Could not send status update");
-            start_receiving_from_socket(socket, config.session_id, &mut file, shutdown_receiver);
+            start_receiving_from_socket(
+                socket,
+                config.session_id,
+                config.acknowledge_reports,
+                None,
+                &[],
+                &mut file,
+                shutdown_receiver,
+            );
             let _ = server_state_sender.send(ServerState::Shutdown);
         });
         thread::yield_now();
@@ -442,8 +622,365 @@ mod tests {
         h.join().expect("Couldn't join server handler thread");
     }
 
+    #[test]
+    fn rejects_unauthenticated_reports() {
+        let addrs = find_usable_addrs(2);
+        let server_addr = addrs[0];
+        let (shutdown_sender, shutdown_receiver) = ShutdownSignalSender::new(server_addr);
+        let session_id = gen_session_id().into();
+        let f = tempfile::NamedTempFile::new().expect("Could not make temp file");
+        let output_file_path = PathBuf::from(f.path());
+        let key = [4; wire::siphash::KEY_LEN];
+        let mut keys = ReportKeys::default();
+        keys.insert(ProbeId::new(31).unwrap(), key);
+        keys.insert(ProbeId::new(32).unwrap(), key);
+        let socket = UdpSocket::bind(server_addr).expect("Could not bind to server socket");
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&output_file_path)
+            .expect("Could not open file for writing");
+        let h = std::thread::spawn(move || {
+            start_receiving_from_socket(
+                socket,
+                session_id,
+                false,
+                Some(&keys),
+                &[],
+                &mut file,
+                shutdown_receiver,
+            );
+        });
+
+        let client = UdpSocket::bind(addrs[1]).expect("Could not bind to socket for sending");
+        let mut bytes = [0u8; 1024];
+        // Unauthenticated, under the wrong key, and from a probe
+        // without a key
+        let len = dummy_report(31).write_into_le_bytes(&mut bytes).unwrap();
+        client.send_to(&bytes[..len], server_addr).unwrap();
+        let len = dummy_report(32)
+            .write_into_authenticated_le_bytes(&mut bytes, &[5; wire::siphash::KEY_LEN])
+            .unwrap();
+        client.send_to(&bytes[..len], server_addr).unwrap();
+        let len = dummy_report(33)
+            .write_into_authenticated_le_bytes(&mut bytes, &key)
+            .unwrap();
+        client.send_to(&bytes[..len], server_addr).unwrap();
+        // Authenticated
+        let log_report = dummy_report(31);
+        let len = log_report
+            .write_into_authenticated_le_bytes(&mut bytes, &key)
+            .unwrap();
+        client.send_to(&bytes[..len], server_addr).unwrap();
+        thread::sleep(std::time::Duration::from_millis(200));
+        shutdown_sender.shutdown();
+        h.join().expect("Couldn't join server handler thread");
+
+        let mut file_reader =
+            std::fs::File::open(&output_file_path).expect("Could not open output file for reading");
+        let found_log_entries = json::read_log_entries(&mut file_reader)
+            .expect("Could not read output file as json log entries");
+        let expected_entries: usize = log_report.frontier_clocks.len() + log_report.event_log.len();
+        assert_eq!(expected_entries, found_log_entries.len());
+        for e in found_log_entries.iter() {
+            assert_eq!(log_report.probe_id, e.probe_id);
+        }
+    }
+
+    #[test]
+    fn forwards_control_messages_to_last_seen_probe_addr() {
+        let addrs = find_usable_addrs(2);
+        let server_addr = addrs[0];
+        let probe_addr = addrs[1];
+        let (shutdown_sender, shutdown_receiver) = ShutdownSignalSender::new(server_addr);
+        let socket = UdpSocket::bind(server_addr).expect("Could not bind to server socket");
+        let h = std::thread::spawn(move || {
+            start_receiving_from_socket(
+                socket,
+                gen_session_id().into(),
+                false,
+                None,
+                &[Ipv4Addr::LOCALHOST.into()],
+                &mut std::io::sink(),
+                shutdown_receiver,
+            );
+        });
+
+        let probe_socket = UdpSocket::bind(probe_addr).expect("Could not bind probe socket");
+        probe_socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let log_report = dummy_report(31);
+        let mut report_bytes = [0u8; 1024];
+        let report_len = log_report
+            .write_into_le_bytes(&mut report_bytes)
+            .expect("Could not write log report");
+        probe_socket
+            .send_to(&report_bytes[..report_len], server_addr)
+            .expect("Could not send report");
+        thread::sleep(std::time::Duration::from_millis(200));
+
+        let command = ControlCommand::SetOverwritePriority(7);
+        send_control_command_via_collector(server_addr, log_report.probe_id, None, &command)
+            .expect("Could not send control command");
+
+        let mut buf = [0u8; 64];
+        let (len, src) = probe_socket
+            .recv_from(&mut buf)
+            .expect("Did not receive the forwarded control message");
+        assert_eq!(src, server_addr);
+        let msg = WireControlMessage::new(&buf[..len]).expect("Invalid control message");
+        assert_eq!(msg.probe_id(), Ok(log_report.probe_id));
+        assert_eq!(msg.command(), Ok(command));
+
+        shutdown_sender.shutdown();
+        h.join().expect("Couldn't join server handler thread");
+    }
+
+    #[test]
+    fn drops_control_messages_from_unallowed_sources() {
+        let addrs = find_usable_addrs(2);
+        let server_addr = addrs[0];
+        let probe_addr = addrs[1];
+        let allowed_ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+        let (shutdown_sender, shutdown_receiver) = ShutdownSignalSender::new(server_addr);
+        let socket = UdpSocket::bind(server_addr).expect("Could not bind to server socket");
+        let h = std::thread::spawn(move || {
+            start_receiving_from_socket(
+                socket,
+                gen_session_id().into(),
+                false,
+                None,
+                &[allowed_ip],
+                &mut std::io::sink(),
+                shutdown_receiver,
+            );
+        });
+
+        let probe_socket = UdpSocket::bind(probe_addr).expect("Could not bind probe socket");
+        probe_socket
+            .set_read_timeout(Some(std::time::Duration::from_millis(500)))
+            .unwrap();
+        let log_report = dummy_report(31);
+        let mut report_bytes = [0u8; 1024];
+        let report_len = log_report
+            .write_into_le_bytes(&mut report_bytes)
+            .expect("Could not write log report");
+        probe_socket
+            .send_to(&report_bytes[..report_len], server_addr)
+            .expect("Could not send report");
+        thread::sleep(std::time::Duration::from_millis(200));
+
+        // 127.0.0.1 isn't on the allow list
+        let unallowed_socket =
+            UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).expect("Could not bind control socket");
+        let dropped = ControlCommand::SetOverwritePriority(7);
+        send_control_command(
+            &unallowed_socket,
+            server_addr,
+            log_report.probe_id,
+            None,
+            &dropped,
+        )
+        .expect("Could not send control command");
+        let mut buf = [0u8; 64];
+        assert!(probe_socket.recv_from(&mut buf).is_err());
+
+        let allowed_socket =
+            UdpSocket::bind((allowed_ip, 0)).expect("Could not bind control socket");
+        let forwarded = ControlCommand::ResetMissedLogEntryCount;
+        send_control_command(
+            &allowed_socket,
+            server_addr,
+            log_report.probe_id,
+            None,
+            &forwarded,
+        )
+        .expect("Could not send control command");
+        let (len, _) = probe_socket
+            .recv_from(&mut buf)
+            .expect("Did not receive the forwarded control message");
+        let msg = WireControlMessage::new(&buf[..len]).expect("Invalid control message");
+        assert_eq!(msg.command(), Ok(forwarded));
+
+        shutdown_sender.shutdown();
+        h.join().expect("Couldn't join server handler thread");
+    }
+
+    #[test]
+    fn acknowledges_reports_and_drops_retransmissions() {
+        let addrs = find_usable_addrs(2);
+        let server_addr = addrs[0];
+        let probe_addr = addrs[1];
+        let (shutdown_sender, shutdown_receiver) = ShutdownSignalSender::new(server_addr);
+        let f = tempfile::NamedTempFile::new().expect("Could not make temp file");
+        let output_file_path = PathBuf::from(f.path());
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&output_file_path)
+            .expect("Could not open file for writing");
+        let socket = UdpSocket::bind(server_addr).expect("Could not bind to server socket");
+        let h = std::thread::spawn(move || {
+            start_receiving_from_socket(
+                socket,
+                gen_session_id().into(),
+                true,
+                None,
+                &[],
+                &mut file,
+                shutdown_receiver,
+            );
+        });
+
+        let probe_socket = UdpSocket::bind(probe_addr).expect("Could not bind probe socket");
+        probe_socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let log_report = dummy_report(31);
+        let mut report_bytes = [0u8; 1024];
+        let report_len = log_report
+            .write_into_le_bytes(&mut report_bytes)
+            .expect("Could not write log report");
+        for _ in 0..2 {
+            probe_socket
+                .send_to(&report_bytes[..report_len], server_addr)
+                .expect("Could not send report");
+            let mut buf = [0u8; 64];
+            let (len, _) = probe_socket
+                .recv_from(&mut buf)
+                .expect("Did not receive an acknowledgement");
+            let msg = WireControlMessage::new(&buf[..len]).expect("Invalid control message");
+            assert_eq!(msg.probe_id(), Ok(log_report.probe_id));
+            assert_eq!(
+                msg.command(),
+                Ok(ControlCommand::AcknowledgeReport(log_report.seq_num.0))
+            );
+        }
+
+        shutdown_sender.shutdown();
+        h.join().expect("Couldn't join server handler thread");
+
+        let mut file_reader =
+            std::fs::File::open(&output_file_path).expect("Could not open output file for reading");
+        let found_log_entries = json::read_log_entries(&mut file_reader)
+            .expect("Could not read output file as json log entries");
+        let expected_entries: usize = log_report.frontier_clocks.len() + log_report.event_log.len();
+        assert_eq!(expected_entries, found_log_entries.len());
+    }
+
+    #[test]
+    fn tags_acknowledgements_for_probes_with_a_key() {
+        let addrs = find_usable_addrs(2);
+        let server_addr = addrs[0];
+        let probe_addr = addrs[1];
+        let (shutdown_sender, shutdown_receiver) = ShutdownSignalSender::new(server_addr);
+        let key = [4; wire::siphash::KEY_LEN];
+        let mut keys = ReportKeys::default();
+        keys.insert(ProbeId::new(31).unwrap(), key);
+        let socket = UdpSocket::bind(server_addr).expect("Could not bind to server socket");
+        let h = std::thread::spawn(move || {
+            start_receiving_from_socket(
+                socket,
+                gen_session_id().into(),
+                true,
+                Some(&keys),
+                &[],
+                &mut std::io::sink(),
+                shutdown_receiver,
+            );
+        });
+
+        let probe_socket = UdpSocket::bind(probe_addr).expect("Could not bind probe socket");
+        probe_socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let log_report = dummy_report(31);
+        let mut report_bytes = [0u8; 1024];
+        let report_len = log_report
+            .write_into_authenticated_le_bytes(&mut report_bytes, &key)
+            .expect("Could not write log report");
+        probe_socket
+            .send_to(&report_bytes[..report_len], server_addr)
+            .expect("Could not send report");
+        let mut buf = [0u8; 64];
+        let (len, _) = probe_socket
+            .recv_from(&mut buf)
+            .expect("Did not receive an acknowledgement");
+        let msg = WireControlMessage::new(&buf[..len]).expect("Invalid control message");
+        assert_eq!(
+            msg.command(),
+            Ok(ControlCommand::AcknowledgeReport(log_report.seq_num.0))
+        );
+        assert_eq!(msg.check_mac(&key), Ok(()));
+
+        shutdown_sender.shutdown();
+        h.join().expect("Couldn't join server handler thread");
+    }
+
+    #[test]
+    fn keeps_reports_from_a_rebooted_probe() {
+        let addrs = find_usable_addrs(2);
+        let server_addr = addrs[0];
+        let probe_addr = addrs[1];
+        let (shutdown_sender, shutdown_receiver) = ShutdownSignalSender::new(server_addr);
+        let f = tempfile::NamedTempFile::new().expect("Could not make temp file");
+        let output_file_path = PathBuf::from(f.path());
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&output_file_path)
+            .expect("Could not open file for writing");
+        let socket = UdpSocket::bind(server_addr).expect("Could not bind to server socket");
+        let h = std::thread::spawn(move || {
+            start_receiving_from_socket(
+                socket,
+                gen_session_id().into(),
+                true,
+                None,
+                &[],
+                &mut file,
+                shutdown_receiver,
+            );
+        });
+
+        let probe_socket = UdpSocket::bind(probe_addr).expect("Could not bind probe socket");
+        probe_socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        // After a reboot the probe starts its sequence numbers over,
+        // but in a new epoch
+        let first_boot = dummy_report(31);
+        let mut second_boot = dummy_report(31);
+        second_boot.probe_clock.epoch = ProbeEpoch(2);
+        for log_report in [&first_boot, &second_boot].iter() {
+            assert_eq!(log_report.seq_num, first_boot.seq_num);
+            let mut report_bytes = [0u8; 1024];
+            let report_len = log_report
+                .write_into_le_bytes(&mut report_bytes)
+                .expect("Could not write log report");
+            probe_socket
+                .send_to(&report_bytes[..report_len], server_addr)
+                .expect("Could not send report");
+            let mut buf = [0u8; 64];
+            probe_socket
+                .recv_from(&mut buf)
+                .expect("Did not receive an acknowledgement");
+        }
+
+        shutdown_sender.shutdown();
+        h.join().expect("Couldn't join server handler thread");
+
+        let mut file_reader =
+            std::fs::File::open(&output_file_path).expect("Could not open output file for reading");
+        let found_log_entries = json::read_log_entries(&mut file_reader)
+            .expect("Could not read output file as json log entries");
+        let entries_per_report = first_boot.frontier_clocks.len() + first_boot.event_log.len();
+        assert_eq!(2 * entries_per_report, found_log_entries.len());
+    }
+
     const SNAPSHOT_BYTES_SIZE: usize = 12;
-    const PROBE_STORAGE_BYTES_SIZE: usize = 512;
+    const PROBE_STORAGE_BYTES_SIZE: usize = 1024;
     const LOG_REPORT_BYTES_SIZE: usize = 512;
 
     #[test]
@@ -459,6 +996,9 @@ mod tests {
             addr: server_addr,
             session_id,
             output_file: output_file_path.clone(),
+            acknowledge_reports: false,
+            key_file: None,
+            control_sources: Vec::new(),
         };
         let h = thread::spawn(move || {
             let mut file = std::fs::OpenOptions::new()
@@ -470,7 +1010,15 @@ mod tests {
             server_state_sender
                 .send(ServerState::Started)
                 .expect("Could not send status update");
-            start_receiving_from_socket(socket, config.session_id, &mut file, shutdown_receiver);
+            start_receiving_from_socket(
+                socket,
+                config.session_id,
+                config.acknowledge_reports,
+                None,
+                &[],
+                &mut file,
+                shutdown_receiver,
+            );
             let _ = server_state_sender.send(ServerState::Shutdown);
         });
         thread::yield_now();
@@ -599,6 +1147,9 @@ mod tests {
             addr: server_addr,
             session_id,
             output_file: output_file_path.clone(),
+            acknowledge_reports: false,
+            key_file: None,
+            control_sources: Vec::new(),
         };
         let h = thread::spawn(move || {
             let mut file = std::fs::OpenOptions::new()
@@ -610,7 +1161,15 @@ mod tests {
             server_state_sender
                 .send(ServerState::Started)
                 .expect("Could not send status update");
-            start_receiving_from_socket(socket, config.session_id, &mut file, shutdown_receiver);
+            start_receiving_from_socket(
+                socket,
+                config.session_id,
+                config.acknowledge_reports,
+                None,
+                &[],
+                &mut file,
+                shutdown_receiver,
+            );
             let _ = server_state_sender.send(ServerState::Shutdown);
         });
         thread::yield_now();
@@ -729,6 +1288,9 @@ mod tests {
             addr: server_addr,
             session_id,
             output_file: output_file_path.clone(),
+            acknowledge_reports: false,
+            key_file: None,
+            control_sources: Vec::new(),
         };
         let h = thread::spawn(move || {
             let mut file = std::fs::OpenOptions::new()
@@ -740,7 +1302,15 @@ mod tests {
             server_state_sender
                 .send(ServerState::Started)
                 .expect("Could not send status update");
-            start_receiving_from_socket(socket, config.session_id, &mut file, shutdown_receiver);
+            start_receiving_from_socket(
+                socket,
+                config.session_id,
+                config.acknowledge_reports,
+                None,
+                &[],
+                &mut file,
+                shutdown_receiver,
+            );
             let _ = server_state_sender.send(ServerState::Shutdown);
         });
         thread::yield_now();
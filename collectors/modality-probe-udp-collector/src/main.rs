@@ -1,3 +1,4 @@
+use modality_probe_collector_common::auth::ReportKeys;
 #[cfg(feature = "cli")]
 use structopt::StructOpt;
 
@@ -7,11 +8,58 @@ fn main() {
     #[cfg(feature = "cli")]
     let opts = modality_probe_udp_collector::Opts::from_args();
 
+    if let Some(modality_probe_udp_collector::Command::Control(control)) = &opts.command {
+        let collector_addr = control.collector_addr(opts.port);
+        let (probe_id, command) = match control.probe_command() {
+            Ok(pc) => pc,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+        let key = match &opts.key_file {
+            Some(key_file) => match ReportKeys::from_file(key_file) {
+                Ok(keys) => match keys.get(probe_id) {
+                    Some(key) => Some(*key),
+                    None => {
+                        eprintln!("No key for probe {} in the key file", probe_id.get_raw());
+                        std::process::exit(1);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            },
+            None => None,
+        };
+        modality_probe_udp_collector::send_control_command_via_collector(
+            collector_addr,
+            probe_id,
+            key.as_ref(),
+            &command,
+        )
+        .expect("Could not send the control command");
+        return;
+    }
+
     let config: modality_probe_udp_collector::Config = opts.into();
     println!("Using the configuration:");
     println!("    addr:\t\t{}", config.addr);
     println!("    session id:\t\t{}", config.session_id.0);
     println!("    output file:\t{}", config.output_file.display());
+    println!("    ack reports:\t{}", config.acknowledge_reports);
+    if let Some(key_file) = &config.key_file {
+        println!("    key file:\t\t{}", key_file.display());
+    }
+    if !config.control_sources.is_empty() {
+        let sources: Vec<String> = config
+            .control_sources
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect();
+        println!("    control sources:\t{}", sources.join(", "));
+    }
     let (shutdown_sender, shutdown_receiver) =
         modality_probe_udp_collector::ShutdownSignalSender::new(config.addr);
     ctrlc::set_handler(move || {
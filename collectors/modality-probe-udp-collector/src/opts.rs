@@ -1,5 +1,6 @@
 use crate::Config;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use modality_probe::{wire::ControlCommand, EventId, EventMask, ProbeId, EVENT_MASK_BITS};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
 use std::path::PathBuf;
 #[cfg(feature = "cli")]
 use structopt::StructOpt;
@@ -30,11 +31,130 @@ pub struct Opts {
         structopt(short = "o", long = "output-file", parse(from_os_str))
     )]
     pub output_file: Option<PathBuf>,
+
+    /// Acknowledge each report received, for probes using acknowledged reporting.
+    #[cfg_attr(feature = "cli", structopt(long = "ack-reports"))]
+    pub ack_reports: bool,
+
+    /// Only accept reports authenticated with the keys in this file,
+    /// one `<probe id> <32 hex digit key>` per line. Control messages
+    /// sent with the `control` subcommand are tagged with them too.
+    #[cfg_attr(
+        feature = "cli",
+        structopt(short = "k", long = "key-file", parse(from_os_str))
+    )]
+    pub key_file: Option<PathBuf>,
+
+    /// Forward control messages sent to this collector on to their probes.
+    #[cfg_attr(feature = "cli", structopt(long = "forward-control"))]
+    pub forward_control: bool,
+
+    /// With --forward-control, only forward control messages sent from
+    /// these addresses instead of from loopback.
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "control-source", number_of_values = 1)
+    )]
+    pub control_sources: Vec<IpAddr>,
+
+    #[cfg_attr(feature = "cli", structopt(subcommand))]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(StructOpt))]
+pub enum Command {
+    /// Send a control command to a probe by way of a running collector.
+    Control(ControlOpts),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(StructOpt))]
+pub struct ControlOpts {
+    /// The address of the running collector, defaults to localhost on the collector's port.
+    #[cfg_attr(feature = "cli", structopt(short = "c", long))]
+    pub collector: Option<SocketAddr>,
+
+    /// The id of the probe to send the command to.
+    #[cfg_attr(feature = "cli", structopt(long = "probe-id"))]
+    pub probe_id: u32,
+
+    #[cfg_attr(feature = "cli", structopt(subcommand))]
+    pub command: ControlCommandOpts,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "cli", derive(StructOpt))]
+pub enum ControlCommandOpts {
+    /// Ask the probe to send a report as soon as possible.
+    RequestReport,
+    /// Set the probe's overwrite priority.
+    SetOverwritePriority {
+        /// The new overwrite priority level.
+        level: u32,
+    },
+    /// Replace the probe's set of disabled events.
+    SetDisabledEvents {
+        /// The ids of the events to disable, from 1 to 256; none
+        /// re-enables every event.
+        event_ids: Vec<u32>,
+    },
+    /// Reset the probe's count of missed log entries.
+    ResetMissedCount,
+}
+
+impl ControlOpts {
+    /// The collector address to send the command to, defaulting to
+    /// localhost on the given collector port
+    pub fn collector_addr(&self, port: Option<u16>) -> SocketAddr {
+        self.collector.unwrap_or_else(|| {
+            SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::LOCALHOST,
+                port.unwrap_or(DEFAULT_PORT),
+            ))
+        })
+    }
+
+    /// The target probe id and the command to send it
+    pub fn probe_command(&self) -> Result<(ProbeId, ControlCommand), String> {
+        let probe_id = ProbeId::new(self.probe_id)
+            .ok_or_else(|| format!("Invalid probe id {}", self.probe_id))?;
+        let command = match &self.command {
+            ControlCommandOpts::RequestReport => ControlCommand::RequestReport,
+            ControlCommandOpts::SetOverwritePriority { level } => {
+                ControlCommand::SetOverwritePriority(*level)
+            }
+            ControlCommandOpts::SetDisabledEvents { event_ids } => {
+                let mut mask = EventMask::EMPTY;
+                for raw in event_ids {
+                    let id =
+                        EventId::new(*raw).ok_or_else(|| format!("Invalid event id {}", raw))?;
+                    if !EventMask::can_contain(id) {
+                        return Err(format!(
+                            "Event id {} can't be disabled; only ids up to {} can",
+                            raw, EVENT_MASK_BITS
+                        ));
+                    }
+                    mask = mask.with_event(id);
+                }
+                ControlCommand::SetDisabledEvents(mask)
+            }
+            ControlCommandOpts::ResetMissedCount => ControlCommand::ResetMissedLogEntryCount,
+        };
+        Ok((probe_id, command))
+    }
 }
 
 impl From<Opts> for Config {
     fn from(o: Opts) -> Self {
         let session_id = o.session_id.unwrap_or(0);
+        let control_sources = if !o.forward_control {
+            Vec::new()
+        } else if o.control_sources.is_empty() {
+            vec![Ipv4Addr::LOCALHOST.into(), Ipv6Addr::LOCALHOST.into()]
+        } else {
+            o.control_sources
+        };
         Config {
             addr: SocketAddr::V4(SocketAddrV4::new(
                 Ipv4Addr::new(0, 0, 0, 0),
@@ -46,6 +166,9 @@ impl From<Opts> for Config {
                     .expect("Could not retrieve current directory")
                     .join(format!("session_{}_log_entries.jsonl", session_id))
             }),
+            acknowledge_reports: o.ack_reports,
+            key_file: o.key_file,
+            control_sources,
         }
     }
 }
@@ -5,8 +5,10 @@ use core::cmp::max;
 use core::fmt;
 use core::mem::size_of;
 use core::mem::MaybeUninit;
+use core::ptr;
 use core::sync::atomic::fence;
 use core::sync::atomic::Ordering;
+use memoffset::offset_of;
 
 /// Minimum allowed capacity of backing storage
 pub const MIN_STORAGE_CAP: usize = 4;
@@ -30,6 +32,18 @@ fn round_to_power_2(length: usize) -> usize {
     1 << exp
 }
 
+/// The sequence numbers locating a buffer's entries in its storage, as
+/// read from memory which held the buffer before a reset
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RetainedSeqNums {
+    /// Sequence number of the next entry to be written
+    pub write_seqn: SeqNum,
+    /// Sequence number of the next entry to be overwritten
+    pub overwrite_seqn: SeqNum,
+    /// Sequence number of next entry to be read from buffer
+    pub read_seqn: SeqNum,
+}
+
 #[derive(Debug)]
 #[repr(C)]
 /// Struct used to write to buffer
@@ -109,6 +123,60 @@ where
         )
     }
 
+    /// Read the sequence numbers of the buffer at `prior`, e.g. one left
+    /// in RAM which is retained across resets. Nothing else about the
+    /// buffer is read, so its storage reference may be stale.
+    ///
+    /// # Safety
+    ///
+    /// `prior` must be properly aligned and valid for reads of a
+    /// `FencedRingBuffer`.
+    #[inline]
+    pub unsafe fn read_retained_seqns(prior: *const Self) -> RetainedSeqNums {
+        // Read through byte offsets, since the buffer as a whole may not
+        // be valid, and volatilely, since the compiler can't know what a
+        // previous run left there
+        let prior = prior as *const u8;
+        let read_seqn_at = |offset: usize| ptr::read_volatile(prior.add(offset) as *const SeqNum);
+        RetainedSeqNums {
+            write_seqn: read_seqn_at(offset_of!(Self, write_seqn)),
+            overwrite_seqn: read_seqn_at(offset_of!(Self, overwrite_seqn)),
+            read_seqn: read_seqn_at(offset_of!(Self, read_seqn)),
+        }
+    }
+
+    /// Pick up where a previous buffer over the same backing storage left
+    /// off, keeping the entries it wrote. Returns false, leaving this
+    /// buffer as it is, if the sequence numbers couldn't have belonged to a
+    /// buffer over this storage, e.g. because one was being updated when
+    /// the previous buffer went away.
+    ///
+    /// # Safety
+    ///
+    /// This buffer's storage and indexing must be the same as the previous
+    /// buffer's, and the entries it wrote must still be in place.
+    pub unsafe fn restore_retained_seqns(&mut self, seqns: RetainedSeqNums) -> bool {
+        let RetainedSeqNums {
+            write_seqn,
+            overwrite_seqn,
+            read_seqn,
+        } = seqns;
+        let is_updating = |s: SeqNum| s.high & SeqNum::UPDATING_HIGH_MASK != 0;
+        if is_updating(write_seqn)
+            || is_updating(overwrite_seqn)
+            || is_updating(read_seqn)
+            || overwrite_seqn > write_seqn
+            || read_seqn > write_seqn
+            || u64::from(write_seqn - overwrite_seqn) > self.capacity() as u64
+        {
+            return false;
+        }
+        self.write_seqn = write_seqn;
+        self.overwrite_seqn = overwrite_seqn;
+        self.read_seqn = read_seqn;
+        true
+    }
+
     /// Get value of backing storage corresponding at index corresponding to
     /// given sequence number
     #[inline]
@@ -226,6 +294,22 @@ where
         tail
     }
 
+    /// Sequence number of the entry at tail, or of the oldest entry present
+    /// in the buffer if tail has already been overwritten
+    pub fn tail_seqn(&self) -> SeqNum {
+        max(self.read_seqn, self.overwrite_seqn)
+    }
+
+    /// Move the tail to the given sequence number, e.g. one previously
+    /// returned by `tail_seqn`, so the entries from there on are read again.
+    /// Entries which have been overwritten since are skipped, as with any
+    /// overwritten tail.
+    /// Note: seqn should not point at a double-entry suffix
+    pub fn set_tail_seqn(&mut self, seqn: SeqNum) {
+        debug_assert!(seqn <= self.write_seqn);
+        self.read_seqn = seqn;
+    }
+
     /// Create iterator over the entries currently present in the buffer without
     /// changing the tail
     #[inline]
@@ -421,6 +505,54 @@ mod tests {
         assert_eq!(e.is_double(), true);
     }
 
+    #[test]
+    fn retained_buffers_keep_their_entries() {
+        let mut storage = vec![MaybeUninit::new(0u8); 8 * size_of::<OrderedEntry>()];
+        let seqns = {
+            let mut buf =
+                FencedRingBuffer::<OrderedEntry>::new_from_uninit_bytes(&mut storage[..], false)
+                    .unwrap();
+            for i in 0..10 {
+                buf.push(OrderedEntry::from_index(i));
+            }
+            assert_eq!(
+                buf.pop(),
+                Some(WholeEntry::Single(OrderedEntry::from_index(2)))
+            );
+            unsafe { FencedRingBuffer::read_retained_seqns(&buf) }
+        };
+        assert_eq!(u64::from(seqns.write_seqn), 10);
+        assert_eq!(u64::from(seqns.overwrite_seqn), 2);
+        assert_eq!(u64::from(seqns.read_seqn), 3);
+
+        let mut buf =
+            FencedRingBuffer::<OrderedEntry>::new_from_uninit_bytes(&mut storage[..], false)
+                .unwrap();
+        assert!(unsafe { buf.restore_retained_seqns(seqns) });
+        let entries: Vec<_> = buf.drain().collect();
+        assert_eq!(
+            entries,
+            (3..10)
+                .map(|i| WholeEntry::Single(OrderedEntry::from_index(i)))
+                .collect::<Vec<_>>()
+        );
+
+        // Sequence numbers which couldn't belong to a buffer this size
+        let too_far_apart = RetainedSeqNums {
+            overwrite_seqn: SeqNum::new(0, 1),
+            ..seqns
+        };
+        let mut updating = seqns;
+        updating.write_seqn.high |= 0x8000_0000;
+        let mut buf =
+            FencedRingBuffer::<OrderedEntry>::new_from_uninit_bytes(&mut storage[..], false)
+                .unwrap();
+        for invalid in [too_far_apart, updating].iter() {
+            assert!(!unsafe { buf.restore_retained_seqns(*invalid) });
+            assert!(buf.is_empty());
+        }
+    }
+
     /// Test backing storage size rounding and minimum size enforcement
     #[test]
     fn test_init_sizes() {
@@ -585,6 +717,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rewinding_tail() {
+        const STORAGE_CAP: usize = 4;
+        let mut storage = [MaybeUninit::uninit(); STORAGE_CAP];
+        let mut buf = FencedRingBuffer::new(&mut storage[..], false).unwrap();
+
+        for i in 0..3 {
+            buf.push(OrderedEntry::from_index(i));
+        }
+        let start = buf.tail_seqn();
+        buf.pop();
+        buf.pop();
+        // Rewinding makes popped entries readable again
+        buf.set_tail_seqn(start);
+        assert_eq!(buf.len(), 3);
+        assert_eq!(
+            buf.pop(),
+            Some(WholeEntry::Single(OrderedEntry::from_index(0)))
+        );
+
+        // Rewinding past overwritten entries skips them
+        for i in 3..6 {
+            buf.push(OrderedEntry::from_index(i));
+        }
+        buf.set_tail_seqn(start);
+        assert_eq!(buf.num_missed(), 2);
+        assert_eq!(buf.tail_seqn(), 2.into());
+        assert_eq!(
+            buf.pop(),
+            Some(WholeEntry::Single(OrderedEntry::from_index(2)))
+        );
+    }
+
     #[test]
     fn test_double_entries() {
         const STORAGE_CAP: usize = 4;
@@ -199,7 +199,7 @@ where
 pub mod async_reader;
 
 pub mod buffer;
-pub use buffer::FencedRingBuffer;
+pub use buffer::{FencedRingBuffer, RetainedSeqNums};
 
 #[cfg(all(feature = "std", test))]
 mod test_support;
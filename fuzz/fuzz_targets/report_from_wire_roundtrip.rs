@@ -10,7 +10,14 @@ fuzz_target!(|data: &[u8]| {
             {
                 let mut other_report = WireReport::new_unchecked(&mut other_buffer);
 
-                other_report.set_fingerprint();
+                match r.version() {
+                    0 => other_report.set_fingerprint(),
+                    v => {
+                        other_report.set_versioned_fingerprint();
+                        other_report.set_version(v);
+                        other_report.set_flags(r.flags());
+                    }
+                }
                 other_report.set_probe_id(probe_id);
                 other_report.set_clock(r.clock());
                 other_report.set_seq_num(r.seq_num());
@@ -25,11 +32,19 @@ fuzz_target!(|data: &[u8]| {
                 let source_payload_slice = &r.payload()[..r.payload_len()];
                 let dest_payload_slice = &mut other_report.payload_mut()[..r.payload_len()];
                 dest_payload_slice.copy_from_slice(source_payload_slice);
+                // The MAC can't be recomputed without the key, so copy it
+                if let Some(mac) = r.mac() {
+                    let mac_slice =
+                        &mut other_report.payload_mut()[r.payload_len()..r.payload_len() + 8];
+                    mac_slice.copy_from_slice(&mac.to_le_bytes());
+                }
+                if r.is_checksummed() {
+                    other_report.set_checksum();
+                }
             }
 
             // compare the relevant portion of the buffers
-            // (magic number from report::field::PAYLOAD.start)
-            let report_data_size = WireReport::<&[u8]>::header_len() + r.payload_len();
+            let report_data_size = r.report_len();
             assert_eq!(&data[..report_data_size], &other_buffer[..report_data_size]);
         }
     }
@@ -1,11 +1,14 @@
 #![no_main]
 use libfuzzer_sys::fuzz_target;
-use modality_probe::wire::report::WireReport;
+use modality_probe::wire::{compression, report::WireReport};
 
 fuzz_target!(|data: &[u8]| {
     match WireReport::new(data) {
         Ok(r) => {
-            assert_eq!(r.fingerprint(), WireReport::<&[u8]>::FINGERPRINT);
+            assert!(
+                r.fingerprint() == WireReport::<&[u8]>::FINGERPRINT
+                    || r.fingerprint() == WireReport::<&[u8]>::VERSIONED_FINGERPRINT
+            );
             // hit all of the getters to make sure the report is usable without
             // panicing
             let _ = r.probe_id();
@@ -18,6 +21,16 @@ fuzz_target!(|data: &[u8]| {
             let _ = r.n_log_entries();
             let _ = r.payload_len();
             let _ = r.payload();
+            let _ = r.version();
+            let _ = r.flags();
+            let _ = r.mac();
+            let _ = r.checksum();
+            if r.is_compressed() {
+                let clocks_len = r.n_clocks() as usize * 8;
+                if let Some(log) = r.payload().get(clocks_len..r.payload_len()) {
+                    let _ = compression::decode(log, |_| ());
+                }
+            }
         }
         Err(_) => (),
     };
@@ -18,6 +18,34 @@ extern "C" {
 #define MODALITY_PROBE_MACROS_ENABLED 1
 #endif
 
+/*
+ * The optional features the library was built with, which are all on
+ * by default like the modality-probe-capi cargo features. Define one
+ * as 0 when using a library built without that feature, which leaves
+ * out the declarations of its functions.
+ */
+#ifndef MODALITY_PROBE_FEATURE_EVENT_FILTERING
+#define MODALITY_PROBE_FEATURE_EVENT_FILTERING 1
+#endif
+#ifndef MODALITY_PROBE_FEATURE_ACKNOWLEDGED_REPORTING
+#define MODALITY_PROBE_FEATURE_ACKNOWLEDGED_REPORTING 1
+#endif
+#ifndef MODALITY_PROBE_FEATURE_CLOCK_EVICTION
+#define MODALITY_PROBE_FEATURE_CLOCK_EVICTION 1
+#endif
+#ifndef MODALITY_PROBE_FEATURE_REPORT_AUTHENTICATION
+#define MODALITY_PROBE_FEATURE_REPORT_AUTHENTICATION 1
+#endif
+#ifndef MODALITY_PROBE_FEATURE_REPORT_COMPRESSION
+#define MODALITY_PROBE_FEATURE_REPORT_COMPRESSION 1
+#endif
+#ifndef MODALITY_PROBE_FEATURE_LOG_WATERMARK
+#define MODALITY_PROBE_FEATURE_LOG_WATERMARK 1
+#endif
+#ifndef MODALITY_PROBE_FEATURE_PROBE_STATS
+#define MODALITY_PROBE_FEATURE_PROBE_STATS 1
+#endif
+
 #define MODALITY_PROBE_NULL_INITIALIZER (NULL)
 
 /*
@@ -30,6 +58,27 @@ extern "C" {
  */
 #define MODALITY_PROBE_TIME_RESOLUTION_UNSPECIFIED (0)
 
+/*
+ * The number of 32-bit words in an event mask.
+ *
+ * Event id `n` maps to bit `(n - 1) % 32` of word `(n - 1) / 32`, so
+ * only ids 1 through 256 can be disabled; larger ids are always recorded.
+ */
+#define MODALITY_PROBE_EVENT_MASK_WORDS (8)
+
+/*
+ * Clock eviction policies, see modality_probe_set_clock_eviction_policy.
+ */
+#define MODALITY_PROBE_CLOCK_EVICTION_KEEP_EXISTING (0)
+#define MODALITY_PROBE_CLOCK_EVICTION_LEAST_RECENTLY_MERGED (1)
+#define MODALITY_PROBE_CLOCK_EVICTION_REPORTED (2)
+
+/*
+ * The size, in bytes, of a report authentication key, see
+ * modality_probe_set_report_key.
+ */
+#define MODALITY_PROBE_REPORT_KEY_SIZE (16)
+
 /*
  * Modality probe is the type of a probe instance. Expected to be single-threaded.
  */
@@ -74,15 +123,68 @@ typedef struct modality_probe_causal_snapshot {
      */
     modality_probe_logical_clock clock;
     /*
-     * Reserved field.
+     * Reserved field. Holds the low half of the little endian
+     * interaction id, if the snapshot carries one.
      */
     uint8_t reserved_0[2];
     /*
-     * Reserved field.
+     * Reserved field. Holds the high half of the little endian
+     * interaction id, if the snapshot carries one.
      */
     uint8_t reserved_1[2];
 } modality_probe_causal_snapshot;
 
+/*
+ * Counters a probe keeps about its own operation, e.g. to tune a
+ * target's log and report buffer sizes. Each counter saturates
+ * rather than wrapping.
+ */
+typedef struct modality_probe_stats {
+    /*
+     * The number of events recorded, including internal events and
+     * time-only events
+     */
+    uint32_t events_recorded;
+
+    /*
+     * The number of log entries overwritten before they could be
+     * reported
+     */
+    uint32_t entries_overwritten;
+
+    /*
+     * The number of reports produced, including retransmissions
+     */
+    uint32_t reports_produced;
+
+    /*
+     * The total size of the reports produced, in bytes
+     */
+    uint32_t bytes_reported;
+
+    /*
+     * The number of causal snapshots produced
+     */
+    uint32_t snapshots_produced;
+
+    /*
+     * The number of causal snapshots from other probes merged in
+     */
+    uint32_t snapshots_merged;
+
+    /*
+     * The number of times the probe's logical clock overflowed into
+     * a new epoch
+     */
+    uint32_t clock_overflows;
+
+    /*
+     * The number of times a neighbor clock couldn't be tracked
+     * because the clock table was full
+     */
+    uint32_t frontier_clock_overflows;
+} modality_probe_stats;
+
 /*
  * Function type for retrieving the next persistent sequence number.
  *
@@ -109,6 +211,46 @@ typedef size_t (*modality_probe_next_sequence_id_fn)(
         void *user_state,
         uint16_t *out_sequence_id);
 
+/*
+ * Function type notified when a probe's log fills up to its
+ * watermark, set with modality_probe_set_log_watermark.
+ *
+ * It's called from within whichever probe function wrote the entry
+ * that crossed the watermark, so it shouldn't call back into the
+ * probe; set a flag or wake a task to report instead.
+ */
+typedef void (*modality_probe_log_watermark_fn)(
+        uint32_t probe_id,
+        size_t fill_len,
+        size_t fill_capacity,
+        void *user_state);
+
+/*
+ * Flash memory for modality_probe_flash_next_sequence_id to keep
+ * restart counters in.
+ *
+ * Offsets are in bytes from the start of the region set aside for the
+ * counters, and are always a multiple of 8. Erased flash must read as
+ * 0xFF. Each function returns 0 on success.
+ */
+typedef struct modality_probe_flash {
+    /*
+     * The size, in bytes, of an erasable sector, a multiple of 8
+     */
+    size_t sector_len;
+    /*
+     * The number of sectors in the region, at least two
+     */
+    size_t sector_count;
+    size_t (*read)(void *state, size_t offset, uint8_t *dest, size_t len);
+    size_t (*write)(void *state, size_t offset, const uint8_t *src, size_t len);
+    size_t (*erase)(void *state, size_t sector_index);
+    /*
+     * User's state provided to the functions above
+     */
+    void *state;
+} modality_probe_flash;
+
 typedef enum {
     /*
      * Everything is okay
@@ -162,6 +304,21 @@ typedef enum {
      * A wall clock time outside of the allowed range was provided.
      */
     MODALITY_PROBE_ERROR_INVALID_WALL_CLOCK_TIME = 10,
+    /*
+     * A compact snapshot with a truncated epoch was merged before any
+     * epoch was known for its probe.
+     * Detected during merging.
+     */
+    MODALITY_PROBE_ERROR_AMBIGUOUS_EPOCH = 11,
+    /*
+     * The provided value does not name a clock eviction policy.
+     */
+    MODALITY_PROBE_ERROR_INVALID_CLOCK_EVICTION_POLICY = 12,
+    /*
+     * The provided saved probe state was truncated, corrupted, or
+     * written by a newer version of the probe.
+     */
+    MODALITY_PROBE_ERROR_INVALID_SAVED_STATE = 13,
 } modality_probe_error;
 
 /*
@@ -443,6 +600,84 @@ typedef enum {
             event, \
             time_ns) : MODALITY_PROBE_ERROR_OK)
 
+/*
+ * Modality probe span begin recording macro.
+ *
+ * Used to expose span information to the CLI tooling. The event's
+ * name must end in `_BEGIN`, and the span is closed by recording an
+ * event with the same name ending in `_END` and the same span id.
+ * Spans recorded by the same probe may nest.
+ *
+ * Expands to call `modality_probe_record_event_with_payload_u32(probe, event, span_id)`.
+ *
+ * The trailing variadic macro arguments accept (in any order):
+ * - Tags: MODALITY_TAGS(<tag>[,<tag>])
+ * - A string for the event description
+ *
+ */
+#define MODALITY_PROBE_SPAN_BEGIN(probe, event, span_id, ...) \
+    ((MODALITY_PROBE_MACROS_ENABLED) ? modality_probe_record_event_with_payload_u32(\
+            probe, \
+            event, \
+            (span_id)) : MODALITY_PROBE_ERROR_OK)
+
+/*
+ * Modality probe span begin with time recording macro.
+ *
+ * Used to expose span information to the CLI tooling.
+ *
+ * Expands to call `modality_probe_record_event_with_payload_u32_with_time(probe, event, span_id, time_ns)`.
+ *
+ * The trailing variadic macro arguments accept (in any order):
+ * - Tags: MODALITY_TAGS(<tag>[,<tag>])
+ * - A string for the event description
+ *
+ */
+#define MODALITY_PROBE_SPAN_BEGIN_W_TIME(probe, event, span_id, time_ns, ...) \
+    ((MODALITY_PROBE_MACROS_ENABLED) ? modality_probe_record_event_with_payload_u32_with_time(\
+            probe, \
+            event, \
+            (span_id), \
+            time_ns) : MODALITY_PROBE_ERROR_OK)
+
+/*
+ * Modality probe span end recording macro.
+ *
+ * Used to expose span information to the CLI tooling. The event's
+ * name must end in `_END`.
+ *
+ * Expands to call `modality_probe_record_event_with_payload_u32(probe, event, span_id)`.
+ *
+ * The trailing variadic macro arguments accept (in any order):
+ * - Tags: MODALITY_TAGS(<tag>[,<tag>])
+ * - A string for the event description
+ *
+ */
+#define MODALITY_PROBE_SPAN_END(probe, event, span_id, ...) \
+    ((MODALITY_PROBE_MACROS_ENABLED) ? modality_probe_record_event_with_payload_u32(\
+            probe, \
+            event, \
+            (span_id)) : MODALITY_PROBE_ERROR_OK)
+
+/*
+ * Modality probe span end with time recording macro.
+ *
+ * Used to expose span information to the CLI tooling.
+ *
+ * Expands to call `modality_probe_record_event_with_payload_u32_with_time(probe, event, span_id, time_ns)`.
+ *
+ * The trailing variadic macro arguments accept (in any order):
+ * - Tags: MODALITY_TAGS(<tag>[,<tag>])
+ * - A string for the event description
+ *
+ */
+#define MODALITY_PROBE_SPAN_END_W_TIME(probe, event, span_id, time_ns, ...) \
+    ((MODALITY_PROBE_MACROS_ENABLED) ? modality_probe_record_event_with_payload_u32_with_time(\
+            probe, \
+            event, \
+            (span_id), \
+            time_ns) : MODALITY_PROBE_ERROR_OK)
+
 /*
  * Create a Modality probe instance. probe_id must be non-zero.
  *
@@ -464,6 +699,47 @@ size_t modality_probe_initialize(
         void *next_sequence_id_user_state,
         modality_probe **out);
 
+/*
+ * Create a Modality probe instance in memory which keeps its contents
+ * across resets, like a .noinit section, taking the same arguments as
+ * modality_probe_initialize.
+ *
+ * If destination holds a probe with the same probe_id, left there
+ * before a reset, and its log is consistent, the log's unreported
+ * entries are kept and reported first, under the epoch they were
+ * recorded in. The probe records a MODALITY_PRIOR_LOG_RETAINED event
+ * whose payload is the number of entries kept.
+ *
+ * destination must be the same region, at the same address, on every
+ * initialization. Call modality_probe_initialize instead after a power
+ * cycle, when the memory's contents can't be trusted.
+ */
+size_t modality_probe_initialize_retained(
+        uint8_t *destination,
+        size_t destination_size_bytes,
+        uint32_t probe_id,
+        uint32_t time_resolution_ns,
+        uint16_t wall_clock_id,
+        modality_probe_next_sequence_id_fn next_sequence_id_fn,
+        void *next_sequence_id_user_state,
+        modality_probe **out);
+
+/*
+ * A modality_probe_next_sequence_id_fn which keeps restart counters
+ * for any number of probes in flash memory. Pass it to
+ * modality_probe_initialize along with a pointer to a
+ * modality_probe_flash as the user state.
+ *
+ * Counters are appended to the flash as 8 byte records, so a restart
+ * costs one write rather than an erase. When a sector fills up, the
+ * latest counter for each probe is carried over into the next one,
+ * spreading erases evenly across the sectors.
+ */
+size_t modality_probe_flash_next_sequence_id(
+        uint32_t probe_id,
+        void *user_state,
+        uint16_t *out_sequence_id);
+
 /*
  * Record time.
  */
@@ -772,6 +1048,34 @@ size_t modality_probe_merge_snapshot_bytes_with_time(
         size_t history_source_bytes,
         uint64_t time_ns);
 
+/*
+ * Produce a compact opaque blob of this Modality probe's causal
+ * history, 5 to 9 bytes long, for use by another Modality probe
+ * elsewhere in the system. If truncate_epoch is true, only the low
+ * byte of the epoch is sent, which the receiver reconstructs from
+ * the last epoch it saw from this probe.
+ *
+ * Populates the number of bytes written in out_written_bytes.
+ */
+size_t modality_probe_produce_compact_snapshot_bytes(
+        modality_probe *probe,
+        bool truncate_epoch,
+        uint8_t *history_destination,
+        size_t history_destination_bytes,
+        size_t *out_written_bytes);
+
+/*
+ * Consume a compact opaque causal history blob provided
+ * by some other Modality probe.
+ *
+ * Returns MODALITY_PROBE_ERROR_AMBIGUOUS_EPOCH if the blob's epoch
+ * is truncated and no epoch is known for its probe yet.
+ */
+size_t modality_probe_merge_compact_snapshot_bytes(
+        modality_probe *probe,
+        const uint8_t *history_source,
+        size_t history_source_bytes);
+
 /*
  * Capture the Modality probe instance's moment in causal time
  * for correlation with external systems.
@@ -783,6 +1087,213 @@ size_t modality_probe_merge_snapshot_bytes_with_time(
 modality_probe_instant modality_probe_now(
         modality_probe *probe);
 
+#if MODALITY_PROBE_FEATURE_EVENT_FILTERING
+/*
+ * Stop recording the events in the given mask of
+ * MODALITY_PROBE_EVENT_MASK_WORDS words, in addition to any events
+ * that are already disabled. Internal events are always recorded.
+ */
+size_t modality_probe_disable_events(
+        modality_probe *probe,
+        const uint32_t *mask);
+
+/*
+ * Resume recording the events in the given mask of
+ * MODALITY_PROBE_EVENT_MASK_WORDS words.
+ */
+size_t modality_probe_enable_events(
+        modality_probe *probe,
+        const uint32_t *mask);
+
+/*
+ * Replace the set of events which are not recorded with the given mask
+ * of MODALITY_PROBE_EVENT_MASK_WORDS words.
+ */
+size_t modality_probe_set_disabled_events(
+        modality_probe *probe,
+        const uint32_t *mask);
+
+/*
+ * Write the set of events which are currently not recorded to the
+ * MODALITY_PROBE_EVENT_MASK_WORDS words at mask_out.
+ */
+size_t modality_probe_get_disabled_events(
+        modality_probe *probe,
+        uint32_t *mask_out);
+#endif
+
+#if MODALITY_PROBE_FEATURE_ACKNOWLEDGED_REPORTING
+/*
+ * Turn acknowledged reporting on or off.
+ *
+ * With it on, the log entries in a report are kept until the
+ * report's sequence number is acknowledged. Until then,
+ * modality_probe_report and modality_probe_retransmit_unacked write
+ * the same report again. Turning it off gives up on any report
+ * awaiting acknowledgement.
+ */
+size_t modality_probe_set_acknowledged_reporting(
+        modality_probe *probe,
+        bool enabled);
+#endif
+
+#if MODALITY_PROBE_FEATURE_CLOCK_EVICTION
+/*
+ * Choose what happens when a snapshot from a new neighbor arrives
+ * and the probe's clock table is full.
+ *
+ * MODALITY_PROBE_CLOCK_EVICTION_KEEP_EXISTING (the default) drops the
+ * new neighbor's clock.
+ * MODALITY_PROBE_CLOCK_EVICTION_LEAST_RECENTLY_MERGED evicts the
+ * neighbor merged with least recently.
+ * MODALITY_PROBE_CLOCK_EVICTION_REPORTED only evicts neighbors whose
+ * clocks have already gone out in a report.
+ *
+ * Each eviction is recorded as a MODALITY_CLOCK_EVICTED event naming
+ * the dropped probe.
+ */
+size_t modality_probe_set_clock_eviction_policy(
+        modality_probe *probe,
+        uint8_t policy);
+#endif
+
+#if MODALITY_PROBE_FEATURE_REPORT_AUTHENTICATION
+/*
+ * Authenticate reports with a SipHash-2-4 MAC under the
+ * MODALITY_PROBE_REPORT_KEY_SIZE byte key at `key`, so collectors
+ * holding the same key can reject reports that didn't come from this
+ * probe. Pass a NULL `key` to go back to unauthenticated reports.
+ *
+ * Call it right after initialization, before the first report.
+ * The key is copied into the probe's storage.
+ */
+size_t modality_probe_set_report_key(
+        modality_probe *probe,
+        const uint8_t *key);
+#endif
+
+#if MODALITY_PROBE_FEATURE_REPORT_COMPRESSION
+/*
+ * Turn report compression on or off.
+ *
+ * With it on, the log entries in a report are written in a compact
+ * encoding: event ids and payloads as varints, wall clock times as
+ * differences from the previous one, and runs of a repeated entry
+ * as a count. More entries fit into each report; collectors decode
+ * them transparently.
+ */
+size_t modality_probe_set_compressed_reports(
+        modality_probe *probe,
+        bool enabled);
+#endif
+
+#if MODALITY_PROBE_FEATURE_ACKNOWLEDGED_REPORTING
+/*
+ * Mark the report with the given sequence number as received,
+ * letting go of its log entries.
+ */
+size_t modality_probe_acknowledge_report(
+        modality_probe *probe,
+        uint64_t seq_num);
+
+/*
+ * Write the report awaiting acknowledgement, if any, to a supplied
+ * destination again.
+ *
+ * Populates the number of bytes written in out_written_bytes, which
+ * is 0 if no report is awaiting acknowledgement.
+ */
+size_t modality_probe_retransmit_unacked(
+        modality_probe *probe,
+        uint8_t *log_report_destination,
+        size_t log_report_destination_bytes,
+        size_t *out_written_bytes);
+#endif
+
+#if MODALITY_PROBE_FEATURE_LOG_WATERMARK
+/*
+ * Call log_watermark_fn once each time the probe's log fills up to
+ * percent of its capacity, e.g. to schedule a report before entries
+ * start getting overwritten. It fires again once the log has dropped
+ * below the watermark and crossed it again.
+ *
+ * A percent of 0, or a NULL log_watermark_fn, stops watching the log.
+ */
+size_t modality_probe_set_log_watermark(
+        modality_probe *probe,
+        uint8_t percent,
+        modality_probe_log_watermark_fn log_watermark_fn,
+        void *log_watermark_user_state);
+#endif
+
+/*
+ * Get the number of log entries which haven't been reported yet, and
+ * the number of entries the log can hold.
+ */
+size_t modality_probe_log_fill_level(
+        modality_probe *probe,
+        size_t *out_len,
+        size_t *out_capacity);
+
+#if MODALITY_PROBE_FEATURE_PROBE_STATS
+/*
+ * Get the counters the probe keeps about its own operation since it
+ * was initialized.
+ */
+size_t modality_probe_get_stats(
+        modality_probe *probe,
+        modality_probe_stats *out_stats);
+
+/*
+ * Include the probe's stats in each report, as MODALITY_STATS_*
+ * internal events recorded just before the report is written.
+ * Reports which would otherwise be empty aren't produced just for
+ * the stats.
+ */
+size_t modality_probe_set_stats_in_reports(
+        modality_probe *probe,
+        bool enabled);
+#endif
+
+/*
+ * Save the probe's state to a supplied destination, e.g. retention
+ * memory, before entering a sleep which powers down the probe's
+ * memory.
+ *
+ * The state holds the probe's clocks, report sequence number and
+ * missed log entry count. If include_log is true, it also holds the
+ * log entries which haven't been reported; otherwise those are
+ * counted as missed.
+ *
+ * Populates the number of bytes written in out_written_bytes.
+ */
+size_t modality_probe_save_state(
+        modality_probe *probe,
+        uint8_t *state_destination,
+        size_t state_destination_size_bytes,
+        bool include_log,
+        size_t *out_written_bytes);
+
+/*
+ * Restore a probe from a state saved with modality_probe_save_state,
+ * in place of modality_probe_initialize on wakeup.
+ *
+ * The restored probe carries on in the saved probe's epoch, so
+ * causality continues across the sleep. The probe id comes from the
+ * saved state. Settings such as disabled events, the clock eviction
+ * policy and the report key are not saved, and must be applied again.
+ */
+size_t modality_probe_restore_state(
+        const uint8_t *state,
+        size_t state_size_bytes,
+        uint8_t *destination,
+        size_t destination_size_bytes,
+        uint32_t time_resolution_ns,
+        uint16_t wall_clock_id,
+        modality_probe_next_sequence_id_fn next_sequence_id_fn,
+        void *next_sequence_id_user_state,
+        modality_probe **out);
+
 #ifdef __cplusplus
 } // extern "C"
 #endif
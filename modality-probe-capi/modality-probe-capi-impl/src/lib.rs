@@ -1,10 +1,11 @@
 #![no_std]
 
 use core::mem::MaybeUninit;
-use modality_probe::*;
 pub use modality_probe::{
-    next_sequence_id_fn, CausalSnapshot, ModalityProbe, ModalityProbeInstant,
+    log_watermark_fn, next_sequence_id_fn, CausalSnapshot, ModalityProbe, ModalityProbeInstant,
+    ProbeStats,
 };
+use modality_probe::{wire::EpochEncoding, *};
 
 pub type ModalityProbeError = usize;
 /// Everything went fine
@@ -37,6 +38,15 @@ pub const MODALITY_PROBE_ERROR_INVALID_EXTERNAL_HISTORY_SEMANTICS: ModalityProbe
 pub const MODALITY_PROBE_ERROR_RESTART_PERSISTENCE_SEQUENCE_ID_UNAVAILABLE: ModalityProbeError = 9;
 /// A wall clock time outside of the allowed range was provided.
 pub const MODALITY_PROBE_ERROR_INVALID_WALL_CLOCK_TIME: ModalityProbeError = 10;
+/// A compact snapshot with a truncated epoch was merged before any
+/// epoch was known for its probe.
+/// Detected during merging.
+pub const MODALITY_PROBE_ERROR_AMBIGUOUS_EPOCH: ModalityProbeError = 11;
+/// The provided value does not name a clock eviction policy.
+pub const MODALITY_PROBE_ERROR_INVALID_CLOCK_EVICTION_POLICY: ModalityProbeError = 12;
+/// The provided saved probe state was truncated, corrupted, or
+/// written by a newer version of the probe.
+pub const MODALITY_PROBE_ERROR_INVALID_SAVED_STATE: ModalityProbeError = 13;
 
 /// # Safety
 ///
@@ -62,6 +72,62 @@ pub unsafe fn modality_probe_initialize(
     next_sequence_id: Option<next_sequence_id_fn>,
     next_sequence_id_user_state: *mut core::ffi::c_void,
     out: *mut *mut ModalityProbe<'static>,
+) -> ModalityProbeError {
+    initialize_with_log_storage(
+        destination,
+        destination_size_bytes,
+        probe_id,
+        time_resolution_ns,
+        wall_clock_id,
+        next_sequence_id,
+        next_sequence_id_user_state,
+        out,
+        &mut VolatileRam,
+    )
+}
+
+/// # Safety
+///
+/// As for `modality_probe_initialize`. Additionally, `destination`
+/// must be memory which the startup code leaves alone, and must be
+/// the same region, at the same address, that any previous probe was
+/// initialized in.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_initialize_retained(
+    destination: *mut MaybeUninit<u8>,
+    destination_size_bytes: usize,
+    probe_id: u32,
+    time_resolution_ns: u32,
+    wall_clock_id: u16,
+    next_sequence_id: Option<next_sequence_id_fn>,
+    next_sequence_id_user_state: *mut core::ffi::c_void,
+    out: *mut *mut ModalityProbe<'static>,
+) -> ModalityProbeError {
+    initialize_with_log_storage(
+        destination,
+        destination_size_bytes,
+        probe_id,
+        time_resolution_ns,
+        wall_clock_id,
+        next_sequence_id,
+        next_sequence_id_user_state,
+        out,
+        &mut RetainedRam,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn initialize_with_log_storage<S: LogStorage>(
+    destination: *mut MaybeUninit<u8>,
+    destination_size_bytes: usize,
+    probe_id: u32,
+    time_resolution_ns: u32,
+    wall_clock_id: u16,
+    next_sequence_id: Option<next_sequence_id_fn>,
+    next_sequence_id_user_state: *mut core::ffi::c_void,
+    out: *mut *mut ModalityProbe<'static>,
+    log_storage: &mut S,
 ) -> ModalityProbeError {
     if destination.is_null() {
         return MODALITY_PROBE_ERROR_NULL_POINTER;
@@ -80,12 +146,13 @@ pub unsafe fn modality_probe_initialize(
     } else {
         RestartCounterProvider::NoRestartTracking
     };
-    match ModalityProbe::try_initialize_at(
+    match ModalityProbe::try_initialize_at_with_log_storage(
         core::slice::from_raw_parts_mut(destination, destination_size_bytes),
         probe_id,
         time_resolution_ns.into(),
         wall_clock_id.into(),
         restart_counter_provider,
+        log_storage,
     ) {
         Ok(t) => {
             *out = t;
@@ -394,6 +461,7 @@ fn merge_error_to_modality_probe_error(merge_error: MergeError) -> ModalityProbe
         MergeError::ExternalHistorySemantics => {
             MODALITY_PROBE_ERROR_INVALID_EXTERNAL_HISTORY_SEMANTICS
         }
+        MergeError::AmbiguousEpoch => MODALITY_PROBE_ERROR_AMBIGUOUS_EPOCH,
     }
 }
 
@@ -513,6 +581,73 @@ pub unsafe fn modality_probe_merge_snapshot_bytes_with_time(
     }
 }
 
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_produce_compact_snapshot_bytes(
+    probe: *mut ModalityProbe<'static>,
+    truncate_epoch: bool,
+    history_destination: *mut u8,
+    history_destination_bytes: usize,
+    out_written_bytes: *mut usize,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if history_destination.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    if out_written_bytes.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    let epoch_encoding = if truncate_epoch {
+        EpochEncoding::Truncated
+    } else {
+        EpochEncoding::Whole
+    };
+    match probe.produce_compact_snapshot_bytes(
+        epoch_encoding,
+        core::slice::from_raw_parts_mut(history_destination, history_destination_bytes),
+    ) {
+        Ok(written_bytes) => {
+            *out_written_bytes = written_bytes;
+            MODALITY_PROBE_ERROR_OK
+        }
+        Err(e) => produce_error_to_modality_probe_error(e),
+    }
+}
+
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_merge_compact_snapshot_bytes(
+    probe: *mut ModalityProbe<'static>,
+    history_source: *const u8,
+    history_source_bytes: usize,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if history_source.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    match probe.merge_compact_snapshot_bytes(core::slice::from_raw_parts(
+        history_source,
+        history_source_bytes,
+    )) {
+        Ok(_) => MODALITY_PROBE_ERROR_OK,
+        Err(e) => merge_error_to_modality_probe_error(e),
+    }
+}
+
 /// Capture the ModalityProbe instance's moment in causal time
 /// for correlation with external systems.
 ///
@@ -546,93 +681,583 @@ pub unsafe fn modality_probe_now(probe: *mut ModalityProbe<'static>) -> Modality
     probe.now()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use core::mem::MaybeUninit;
+/// Read an event mask from `EVENT_MASK_WORDS` words at `mask`
+#[cfg(feature = "event-filtering")]
+unsafe fn read_event_mask(mask: *const u32) -> EventMask {
+    let mut words = [0; EVENT_MASK_WORDS];
+    words.copy_from_slice(core::slice::from_raw_parts(mask, EVENT_MASK_WORDS));
+    EventMask::from_words(words)
+}
 
-    fn stack_snapshot(probe: *mut ModalityProbe<'static>) -> CausalSnapshot {
-        let mut snap = MaybeUninit::uninit();
-        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
-            modality_probe_produce_snapshot(probe, snap.as_mut_ptr())
-        });
-        unsafe { snap.assume_init() }
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion. `mask` must point to `EVENT_MASK_WORDS` words.
+#[cfg(feature = "event-filtering")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_disable_events(
+    probe: *mut ModalityProbe<'static>,
+    mask: *const u32,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if mask.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
     }
+    probe.disable_events(&read_event_mask(mask));
+    MODALITY_PROBE_ERROR_OK
+}
 
-    #[test]
-    fn end_to_end_use_of_modality_probe_capi_works() {
-        let mut backend = [0u8; 2099];
-
-        let probe_id = 2;
-        let mut storage = [MaybeUninit::new(0u8); 1024];
-        let storage_slice = &mut storage;
-        let mut probe = MaybeUninit::uninit();
-        let result = unsafe {
-            modality_probe_initialize(
-                storage_slice.as_mut_ptr(),
-                storage_slice.len(),
-                probe_id,
-                0,
-                0,
-                None,
-                core::ptr::null_mut(),
-                probe.as_mut_ptr(),
-            )
-        };
-        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
-        let probe = unsafe { probe.assume_init() };
-        let snap_empty = stack_snapshot(probe);
-        assert_eq!(snap_empty.clock.id.get_raw(), probe_id);
-        let result = unsafe { modality_probe_record_event(probe, 100) };
-        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
-        let snap_a = stack_snapshot(probe);
-        assert!(snap_empty < snap_a);
-        assert!(!(snap_a < snap_empty));
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion. `mask` must point to `EVENT_MASK_WORDS` words.
+#[cfg(feature = "event-filtering")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_enable_events(
+    probe: *mut ModalityProbe<'static>,
+    mask: *const u32,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if mask.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    probe.enable_events(&read_event_mask(mask));
+    MODALITY_PROBE_ERROR_OK
+}
 
-        assert!(&backend.iter().all(|b| *b == 0));
-        let mut bytes_written: usize = 0;
-        let result = unsafe {
-            modality_probe_report(
-                probe,
-                backend.as_mut_ptr(),
-                backend.len(),
-                &mut bytes_written as *mut usize,
-            )
-        };
-        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
-        assert!(bytes_written > 0);
-        assert!(!&backend.iter().all(|b| *b == 0));
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion. `mask` must point to `EVENT_MASK_WORDS` words.
+#[cfg(feature = "event-filtering")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_set_disabled_events(
+    probe: *mut ModalityProbe<'static>,
+    mask: *const u32,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if mask.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    probe.set_disabled_events(read_event_mask(mask));
+    MODALITY_PROBE_ERROR_OK
+}
 
-        let result = unsafe { modality_probe_record_event(probe, 100) };
-        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
-        let result = unsafe { modality_probe_record_event_with_time(probe, 100, 1) };
-        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
-        let mut bytes_written: usize = 0;
-        let result = unsafe {
-            modality_probe_report(
-                probe,
-                (&mut backend[..6]).as_mut_ptr(),
-                6,
-                &mut bytes_written as *mut usize,
-            )
-        };
-        assert_eq!(MODALITY_PROBE_ERROR_INSUFFICIENT_DESTINATION_BYTES, result);
-        assert_eq!(bytes_written, 0);
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion. `mask_out` must point to `EVENT_MASK_WORDS` writable words.
+#[cfg(feature = "event-filtering")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_get_disabled_events(
+    probe: *mut ModalityProbe<'static>,
+    mask_out: *mut u32,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if mask_out.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    core::slice::from_raw_parts_mut(mask_out, EVENT_MASK_WORDS)
+        .copy_from_slice(probe.disabled_events().words());
+    MODALITY_PROBE_ERROR_OK
+}
 
-        let snap_b = stack_snapshot(probe);
-        // We expect the local clock to have progressed thanks to recording an event
-        // internally when we successfully transmitted the state to the backend.
-        assert!(snap_a < snap_b);
-        assert!(!(snap_b < snap_a));
-        let snap_b_neighborhood = stack_snapshot(probe);
-        assert!(snap_b < snap_b_neighborhood);
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "acknowledged-reporting")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_set_acknowledged_reporting(
+    probe: *mut ModalityProbe<'static>,
+    enabled: bool,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    probe.set_acknowledged_reporting(enabled);
+    MODALITY_PROBE_ERROR_OK
+}
 
-        // Share that snapshot with another component in the system, pretend it lives on
-        // some other thread.
-        let remote_probe_id = probe_id + 1;
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "clock-eviction")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_set_clock_eviction_policy(
+    probe: *mut ModalityProbe<'static>,
+    policy: u8,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    let policy = match policy {
+        0 => ClockEvictionPolicy::KeepExisting,
+        1 => ClockEvictionPolicy::LeastRecentlyMerged,
+        2 => ClockEvictionPolicy::Reported,
+        _ => return MODALITY_PROBE_ERROR_INVALID_CLOCK_EVICTION_POLICY,
+    };
+    probe.set_clock_eviction_policy(policy);
+    MODALITY_PROBE_ERROR_OK
+}
 
-        let mut remote_storage = [MaybeUninit::new(0u8); 1024];
-        let remote_storage_slice = &mut remote_storage;
+/// Authenticate reports with a MAC under the key at `key`, or stop
+/// authenticating them if `key` is null.
+///
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+///
+/// `key` must be null or point to `wire::siphash::KEY_LEN` readable bytes.
+#[cfg(feature = "report-authentication")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_set_report_key(
+    probe: *mut ModalityProbe<'static>,
+    key: *const u8,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    let key = if key.is_null() {
+        None
+    } else {
+        Some(core::ptr::read(key as *const wire::siphash::Key))
+    };
+    probe.set_report_key(key);
+    MODALITY_PROBE_ERROR_OK
+}
+
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "report-compression")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_set_compressed_reports(
+    probe: *mut ModalityProbe<'static>,
+    enabled: bool,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    probe.set_compressed_reports(enabled);
+    MODALITY_PROBE_ERROR_OK
+}
+
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "acknowledged-reporting")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_acknowledge_report(
+    probe: *mut ModalityProbe<'static>,
+    seq_num: u64,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    probe.acknowledge_report(seq_num);
+    MODALITY_PROBE_ERROR_OK
+}
+
+/// Write the report awaiting acknowledgement, if any, to the
+/// supplied byte destination again.
+///
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "acknowledged-reporting")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_retransmit_unacked(
+    probe: *mut ModalityProbe<'static>,
+    log_report_destination: *mut u8,
+    log_report_destination_size_bytes: usize,
+    out_written_bytes: *mut usize,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if log_report_destination.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    if out_written_bytes.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    match probe.retransmit_unacked(core::slice::from_raw_parts_mut(
+        log_report_destination,
+        log_report_destination_size_bytes,
+    )) {
+        Ok(b) => {
+            *out_written_bytes = b.map(|nonzero| nonzero.get()).unwrap_or(0);
+            MODALITY_PROBE_ERROR_OK
+        }
+        Err(e) => report_error_to_modality_probe_error(e),
+    }
+}
+
+/// Call `log_watermark` once each time the probe's log fills up to
+/// `percent` of its capacity. A `percent` of 0, or a null
+/// `log_watermark`, stops watching the log.
+///
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "log-watermark")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_set_log_watermark(
+    probe: *mut ModalityProbe<'static>,
+    percent: u8,
+    log_watermark: Option<log_watermark_fn>,
+    log_watermark_user_state: *mut core::ffi::c_void,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    let callback = if let Some(iface) = log_watermark {
+        LogWatermarkCallback::C(CLogWatermarkHandler {
+            iface,
+            state: log_watermark_user_state,
+        })
+    } else {
+        LogWatermarkCallback::None
+    };
+    probe.set_log_watermark(percent, callback);
+    MODALITY_PROBE_ERROR_OK
+}
+
+/// Get the number of log entries which haven't been reported yet, and
+/// the number the log can hold.
+///
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_log_fill_level(
+    probe: *mut ModalityProbe<'static>,
+    out_len: *mut usize,
+    out_capacity: *mut usize,
+) -> ModalityProbeError {
+    let probe = match probe.as_ref() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if out_len.is_null() || out_capacity.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    let level = probe.log_fill_level();
+    *out_len = level.len;
+    *out_capacity = level.capacity;
+    MODALITY_PROBE_ERROR_OK
+}
+
+/// Get the counters the probe keeps about its own operation.
+///
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "probe-stats")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_get_stats(
+    probe: *mut ModalityProbe<'static>,
+    out_stats: *mut ProbeStats,
+) -> ModalityProbeError {
+    let probe = match probe.as_ref() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if out_stats.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    *out_stats = probe.stats();
+    MODALITY_PROBE_ERROR_OK
+}
+
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg(feature = "probe-stats")]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_set_stats_in_reports(
+    probe: *mut ModalityProbe<'static>,
+    enabled: bool,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    probe.set_stats_in_reports(enabled);
+    MODALITY_PROBE_ERROR_OK
+}
+
+/// Save the probe's state to the supplied byte destination, so it can
+/// be restored with `modality_probe_restore_state` after its memory
+/// is powered down. If `include_log` is false, the log entries which
+/// haven't been reported are left out.
+///
+/// # Safety
+///
+/// The ModalityProbe instance pointer must be non-null and point
+/// to an initialized instance operating in a single-threaded
+/// fashion.
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_save_state(
+    probe: *mut ModalityProbe<'static>,
+    state_destination: *mut u8,
+    state_destination_size_bytes: usize,
+    include_log: bool,
+    out_written_bytes: *mut usize,
+) -> ModalityProbeError {
+    let probe = match probe.as_mut() {
+        Some(t) => t,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    if state_destination.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    if out_written_bytes.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    match probe.save_state(
+        core::slice::from_raw_parts_mut(state_destination, state_destination_size_bytes),
+        include_log,
+    ) {
+        Ok(b) => {
+            *out_written_bytes = b;
+            MODALITY_PROBE_ERROR_OK
+        }
+        Err(SaveStateError::InsufficientDestinationSize) => {
+            MODALITY_PROBE_ERROR_INSUFFICIENT_DESTINATION_BYTES
+        }
+    }
+}
+
+/// Restore a probe from a state saved with `modality_probe_save_state`,
+/// continuing its epoch instead of starting a new one.
+///
+/// # Safety
+///
+/// The same as for `modality_probe_initialize`. Additionally,
+/// `state` must point to `state_size_bytes` readable bytes.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_restore_state(
+    state: *const u8,
+    state_size_bytes: usize,
+    destination: *mut MaybeUninit<u8>,
+    destination_size_bytes: usize,
+    time_resolution_ns: u32,
+    wall_clock_id: u16,
+    next_sequence_id: Option<next_sequence_id_fn>,
+    next_sequence_id_user_state: *mut core::ffi::c_void,
+    out: *mut *mut ModalityProbe<'static>,
+) -> ModalityProbeError {
+    if state.is_null() || destination.is_null() || out.is_null() {
+        return MODALITY_PROBE_ERROR_NULL_POINTER;
+    }
+    if destination_size_bytes < core::mem::size_of::<ModalityProbe<'static>>() {
+        return MODALITY_PROBE_ERROR_INSUFFICIENT_DESTINATION_BYTES;
+    }
+    let restart_counter_provider = if let Some(iface) = next_sequence_id {
+        RestartCounterProvider::C(CRestartCounterProvider {
+            iface,
+            state: next_sequence_id_user_state,
+        })
+    } else {
+        RestartCounterProvider::NoRestartTracking
+    };
+    match ModalityProbe::restore_state(
+        core::slice::from_raw_parts(state, state_size_bytes),
+        core::slice::from_raw_parts_mut(destination, destination_size_bytes),
+        time_resolution_ns.into(),
+        wall_clock_id.into(),
+        restart_counter_provider,
+    ) {
+        Ok(t) => {
+            *out = t;
+            MODALITY_PROBE_ERROR_OK
+        }
+        Err(RestoreStateError::InvalidState) => MODALITY_PROBE_ERROR_INVALID_SAVED_STATE,
+        Err(RestoreStateError::ExceededAvailableClocks) => {
+            MODALITY_PROBE_ERROR_EXCEEDED_AVAILABLE_CLOCKS
+        }
+        Err(RestoreStateError::StorageSetupError(StorageSetupError::NullDestination)) => {
+            MODALITY_PROBE_ERROR_NULL_POINTER
+        }
+        Err(RestoreStateError::StorageSetupError(StorageSetupError::UnderMinimumAllowedSize)) => {
+            MODALITY_PROBE_ERROR_INSUFFICIENT_DESTINATION_BYTES
+        }
+        Err(RestoreStateError::StorageSetupError(
+            StorageSetupError::ExceededMaximumAddressableSize,
+        )) => MODALITY_PROBE_ERROR_EXCEEDED_MAXIMUM_ADDRESSABLE_SIZE,
+    }
+}
+
+/// A `next_sequence_id_fn` which keeps restart counters in flash
+/// memory, wear-levelled across its sectors. Pass a pointer to a
+/// `CFlash` describing the flash as the `state`.
+///
+/// # Safety
+///
+/// `state` must be non-null and point to a `CFlash` whose functions
+/// access the flash region set aside for restart counters.
+/// `out_sequence_id` must be non-null.
+#[cfg_attr(feature = "no_mangle", no_mangle)]
+pub unsafe fn modality_probe_flash_next_sequence_id(
+    probe_id: u32,
+    state: *mut core::ffi::c_void,
+    out_sequence_id: *mut u16,
+) -> ModalityProbeError {
+    let flash = match (state as *mut CFlash).as_mut() {
+        Some(f) => f,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    let out_sequence_id = match out_sequence_id.as_mut() {
+        Some(o) => o,
+        None => return MODALITY_PROBE_ERROR_NULL_POINTER,
+    };
+    let probe_id = match ProbeId::new(probe_id) {
+        Some(id) => id,
+        None => return MODALITY_PROBE_ERROR_INVALID_PROBE_ID,
+    };
+    match FlashRestartCounter::new(flash).next_sequence_id(probe_id) {
+        Ok(id) => {
+            *out_sequence_id = id;
+            MODALITY_PROBE_ERROR_OK
+        }
+        Err(RestartSequenceIdUnavailable) => {
+            MODALITY_PROBE_ERROR_RESTART_PERSISTENCE_SEQUENCE_ID_UNAVAILABLE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::MaybeUninit;
+
+    fn stack_snapshot(probe: *mut ModalityProbe<'static>) -> CausalSnapshot {
+        let mut snap = MaybeUninit::uninit();
+        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+            modality_probe_produce_snapshot(probe, snap.as_mut_ptr())
+        });
+        unsafe { snap.assume_init() }
+    }
+
+    #[test]
+    fn end_to_end_use_of_modality_probe_capi_works() {
+        let mut backend = [0u8; 2099];
+
+        let probe_id = 2;
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let storage_slice = &mut storage;
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage_slice.as_mut_ptr(),
+                storage_slice.len(),
+                probe_id,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+        let snap_empty = stack_snapshot(probe);
+        assert_eq!(snap_empty.clock.id.get_raw(), probe_id);
+        let result = unsafe { modality_probe_record_event(probe, 100) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let snap_a = stack_snapshot(probe);
+        assert!(snap_empty < snap_a);
+        assert!(!(snap_a < snap_empty));
+
+        assert!(&backend.iter().all(|b| *b == 0));
+        let mut bytes_written: usize = 0;
+        let result = unsafe {
+            modality_probe_report(
+                probe,
+                backend.as_mut_ptr(),
+                backend.len(),
+                &mut bytes_written as *mut usize,
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert!(bytes_written > 0);
+        assert!(!&backend.iter().all(|b| *b == 0));
+
+        let result = unsafe { modality_probe_record_event(probe, 100) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let result = unsafe { modality_probe_record_event_with_time(probe, 100, 1) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let mut bytes_written: usize = 0;
+        let result = unsafe {
+            modality_probe_report(
+                probe,
+                (&mut backend[..6]).as_mut_ptr(),
+                6,
+                &mut bytes_written as *mut usize,
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_INSUFFICIENT_DESTINATION_BYTES, result);
+        assert_eq!(bytes_written, 0);
+
+        let snap_b = stack_snapshot(probe);
+        // We expect the local clock to have progressed thanks to recording an event
+        // internally when we successfully transmitted the state to the backend.
+        assert!(snap_a < snap_b);
+        assert!(!(snap_b < snap_a));
+        let snap_b_neighborhood = stack_snapshot(probe);
+        assert!(snap_b < snap_b_neighborhood);
+
+        // Share that snapshot with another component in the system, pretend it lives on
+        // some other thread.
+        let remote_probe_id = probe_id + 1;
+
+        let mut remote_storage = [MaybeUninit::new(0u8); 1024];
+        let remote_storage_slice = &mut remote_storage;
         let mut remote_probe = MaybeUninit::uninit();
         let result = unsafe {
             modality_probe_initialize(
@@ -673,4 +1298,504 @@ mod tests {
         assert!(snap_b < snap_c);
         assert!(!(snap_c < snap_b));
     }
+
+    #[cfg(feature = "event-filtering")]
+    #[test]
+    fn event_masks() {
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+
+        let mut mask = [0u32; EVENT_MASK_WORDS];
+        mask[0] = 0b11;
+        let result = unsafe { modality_probe_disable_events(probe, mask.as_ptr()) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        mask[0] = 0b01;
+        let result = unsafe { modality_probe_enable_events(probe, mask.as_ptr()) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+
+        let mut disabled = [0u32; EVENT_MASK_WORDS];
+        let result = unsafe { modality_probe_get_disabled_events(probe, disabled.as_mut_ptr()) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert_eq!(disabled[0], 0b10);
+
+        let result = unsafe { modality_probe_set_disabled_events(probe, [0; 8].as_ptr()) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let result = unsafe { modality_probe_get_disabled_events(probe, disabled.as_mut_ptr()) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert_eq!(disabled, [0; EVENT_MASK_WORDS]);
+
+        let result = unsafe { modality_probe_disable_events(probe, core::ptr::null()) };
+        assert_eq!(MODALITY_PROBE_ERROR_NULL_POINTER, result);
+    }
+    #[test]
+    fn compact_snapshots() {
+        let mut storage_a = [MaybeUninit::new(0u8); 1024];
+        let mut storage_b = [MaybeUninit::new(0u8); 1024];
+        let mut probe_a = MaybeUninit::uninit();
+        let mut probe_b = MaybeUninit::uninit();
+        for (storage, probe_id, probe) in [
+            (&mut storage_a, 1, &mut probe_a),
+            (&mut storage_b, 2, &mut probe_b),
+        ]
+        .iter_mut()
+        {
+            let result = unsafe {
+                modality_probe_initialize(
+                    storage.as_mut_ptr(),
+                    storage.len(),
+                    *probe_id,
+                    0,
+                    0,
+                    None,
+                    core::ptr::null_mut(),
+                    probe.as_mut_ptr(),
+                )
+            };
+            assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        }
+        let probe_a = unsafe { probe_a.assume_init() };
+        let probe_b = unsafe { probe_b.assume_init() };
+
+        let mut snapshot = [0u8; 8];
+        let mut snapshot_len = 0;
+        let result = unsafe {
+            modality_probe_produce_compact_snapshot_bytes(
+                probe_a,
+                true,
+                snapshot.as_mut_ptr(),
+                snapshot.len(),
+                &mut snapshot_len,
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert_eq!(snapshot_len, 5);
+        let result = unsafe {
+            modality_probe_merge_compact_snapshot_bytes(probe_b, snapshot.as_ptr(), snapshot_len)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_AMBIGUOUS_EPOCH, result);
+
+        let result = unsafe {
+            modality_probe_produce_compact_snapshot_bytes(
+                probe_a,
+                false,
+                snapshot.as_mut_ptr(),
+                snapshot.len(),
+                &mut snapshot_len,
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert_eq!(snapshot_len, 6);
+        let result = unsafe {
+            modality_probe_merge_compact_snapshot_bytes(probe_b, snapshot.as_ptr(), snapshot_len)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+    }
+
+    #[cfg(feature = "clock-eviction")]
+    #[test]
+    fn clock_eviction_policy() {
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+
+        let result = unsafe { modality_probe_set_clock_eviction_policy(probe, 1) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let result = unsafe { modality_probe_set_clock_eviction_policy(probe, 3) };
+        assert_eq!(MODALITY_PROBE_ERROR_INVALID_CLOCK_EVICTION_POLICY, result);
+        let result = unsafe { modality_probe_set_clock_eviction_policy(core::ptr::null_mut(), 0) };
+        assert_eq!(MODALITY_PROBE_ERROR_NULL_POINTER, result);
+    }
+
+    #[cfg(feature = "report-authentication")]
+    #[test]
+    fn report_key() {
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+
+        let key = [3u8; wire::siphash::KEY_LEN];
+        let result = unsafe { modality_probe_set_report_key(probe, key.as_ptr()) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let mut report = [0u8; 256];
+        let mut report_size = 0;
+        let result = unsafe {
+            modality_probe_report(probe, report.as_mut_ptr(), report.len(), &mut report_size)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let wire = wire::WireReport::new(&report[..report_size]).unwrap();
+        assert_eq!(wire.check_mac(&key), Ok(()));
+
+        let result = unsafe { modality_probe_set_report_key(probe, core::ptr::null()) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let result = unsafe { modality_probe_set_report_key(core::ptr::null_mut(), key.as_ptr()) };
+        assert_eq!(MODALITY_PROBE_ERROR_NULL_POINTER, result);
+    }
+
+    #[cfg(feature = "report-compression")]
+    #[test]
+    fn compressed_reports() {
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+
+        let result = unsafe { modality_probe_set_compressed_reports(probe, true) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let mut report = [0u8; 256];
+        let mut report_size = 0;
+        let result = unsafe {
+            modality_probe_report(probe, report.as_mut_ptr(), report.len(), &mut report_size)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let wire = wire::WireReport::new(&report[..report_size]).unwrap();
+        assert!(wire.is_compressed());
+
+        let result = unsafe { modality_probe_set_compressed_reports(core::ptr::null_mut(), true) };
+        assert_eq!(MODALITY_PROBE_ERROR_NULL_POINTER, result);
+    }
+
+    #[cfg(feature = "acknowledged-reporting")]
+    #[test]
+    fn acknowledged_reporting() {
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+
+        let result = unsafe { modality_probe_set_acknowledged_reporting(probe, true) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+
+        let mut report = [0u8; 256];
+        let mut report_len = 0;
+        let result = unsafe {
+            modality_probe_report(probe, report.as_mut_ptr(), report.len(), &mut report_len)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert_ne!(report_len, 0);
+
+        let mut again = [0u8; 256];
+        let mut again_len = 0;
+        let result = unsafe {
+            modality_probe_retransmit_unacked(
+                probe,
+                again.as_mut_ptr(),
+                again.len(),
+                &mut again_len,
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert_eq!(&report[..report_len], &again[..again_len]);
+
+        let result = unsafe { modality_probe_acknowledge_report(probe, 0) };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let result = unsafe {
+            modality_probe_retransmit_unacked(
+                probe,
+                again.as_mut_ptr(),
+                again.len(),
+                &mut again_len,
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        assert_eq!(again_len, 0);
+    }
+
+    #[test]
+    fn flash_restart_counter() {
+        extern "C" fn read(
+            state: *mut core::ffi::c_void,
+            offset: usize,
+            dest: *mut u8,
+            len: usize,
+        ) -> usize {
+            let bytes = unsafe { &*(state as *const [u8; 64]) };
+            let dest = unsafe { core::slice::from_raw_parts_mut(dest, len) };
+            dest.copy_from_slice(&bytes[offset..offset + len]);
+            0
+        }
+        extern "C" fn write(
+            state: *mut core::ffi::c_void,
+            offset: usize,
+            src: *const u8,
+            len: usize,
+        ) -> usize {
+            let bytes = unsafe { &mut *(state as *mut [u8; 64]) };
+            let src = unsafe { core::slice::from_raw_parts(src, len) };
+            for (d, s) in bytes[offset..offset + len].iter_mut().zip(src) {
+                *d &= *s;
+            }
+            0
+        }
+        extern "C" fn erase(state: *mut core::ffi::c_void, index: usize) -> usize {
+            let bytes = unsafe { &mut *(state as *mut [u8; 64]) };
+            for b in bytes[index * 32..(index + 1) * 32].iter_mut() {
+                *b = 0xFF;
+            }
+            0
+        }
+
+        let mut bytes = [0u8; 64];
+        let mut flash = CFlash {
+            sector_len: 32,
+            sector_count: 2,
+            read,
+            write,
+            erase,
+            state: &mut bytes as *mut [u8; 64] as *mut core::ffi::c_void,
+        };
+        let state = &mut flash as *mut CFlash as *mut core::ffi::c_void;
+        for expected in 0..10 {
+            let mut id = 0xFFFF;
+            let result = unsafe { modality_probe_flash_next_sequence_id(1, state, &mut id) };
+            assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+            assert_eq!(id, expected);
+        }
+        let mut id = 0;
+        let result = unsafe { modality_probe_flash_next_sequence_id(0, state, &mut id) };
+        assert_eq!(MODALITY_PROBE_ERROR_INVALID_PROBE_ID, result);
+        let result =
+            unsafe { modality_probe_flash_next_sequence_id(1, core::ptr::null_mut(), &mut id) };
+        assert_eq!(MODALITY_PROBE_ERROR_NULL_POINTER, result);
+    }
+
+    #[test]
+    fn save_and_restore_state() {
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+            modality_probe_record_event(probe, 100)
+        });
+        let before = unsafe { modality_probe_now(probe) };
+
+        let mut state = [0u8; 256];
+        let mut state_len = 0;
+        let result = unsafe {
+            modality_probe_save_state(probe, state.as_mut_ptr(), 8, true, &mut state_len)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_INSUFFICIENT_DESTINATION_BYTES, result);
+        let result = unsafe {
+            modality_probe_save_state(probe, state.as_mut_ptr(), state.len(), true, &mut state_len)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+
+        let mut restored_storage = [MaybeUninit::new(0u8); 1024];
+        let mut restored = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_restore_state(
+                state.as_ptr(),
+                state_len,
+                restored_storage.as_mut_ptr(),
+                restored_storage.len(),
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                restored.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let restored = unsafe { restored.assume_init() };
+        assert_eq!(unsafe { modality_probe_now(restored) }, before);
+
+        state[0] ^= 1;
+        let mut restored = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_restore_state(
+                state.as_ptr(),
+                state_len,
+                restored_storage.as_mut_ptr(),
+                restored_storage.len(),
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                restored.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_INVALID_SAVED_STATE, result);
+    }
+
+    #[cfg(feature = "log-watermark")]
+    #[test]
+    fn log_watermark() {
+        extern "C" fn on_watermark(
+            probe_id: u32,
+            fill_len: usize,
+            fill_capacity: usize,
+            state: *mut core::ffi::c_void,
+        ) {
+            assert_eq!(probe_id, 1);
+            assert!(fill_len * 4 >= fill_capacity * 3);
+            unsafe { *(state as *mut usize) += 1 };
+        }
+
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+        let mut reached = 0usize;
+        let result = unsafe {
+            modality_probe_set_log_watermark(
+                probe,
+                75,
+                Some(on_watermark),
+                &mut reached as *mut usize as *mut core::ffi::c_void,
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+
+        let (mut len, mut capacity) = (0, 0);
+        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+            modality_probe_log_fill_level(probe, &mut len, &mut capacity)
+        });
+        assert!(len > 0);
+        for _ in 0..capacity {
+            assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+                modality_probe_record_event(probe, 100)
+            });
+        }
+        assert_eq!(reached, 1);
+        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+            modality_probe_log_fill_level(probe, &mut len, &mut capacity)
+        });
+        assert_eq!(len, capacity);
+    }
+
+    #[cfg(feature = "probe-stats")]
+    #[test]
+    fn stats() {
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let mut probe = MaybeUninit::uninit();
+        let result = unsafe {
+            modality_probe_initialize(
+                storage.as_mut_ptr(),
+                storage.len(),
+                1,
+                0,
+                0,
+                None,
+                core::ptr::null_mut(),
+                probe.as_mut_ptr(),
+            )
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+        let probe = unsafe { probe.assume_init() };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+            modality_probe_set_stats_in_reports(probe, true)
+        });
+        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+            modality_probe_record_event(probe, 100)
+        });
+        let mut report = [0u8; 256];
+        let mut report_size = 0;
+        let result = unsafe {
+            modality_probe_report(probe, report.as_mut_ptr(), report.len(), &mut report_size)
+        };
+        assert_eq!(MODALITY_PROBE_ERROR_OK, result);
+
+        let mut stats = ProbeStats::default();
+        assert_eq!(MODALITY_PROBE_ERROR_OK, unsafe {
+            modality_probe_get_stats(probe, &mut stats)
+        });
+        assert_eq!(stats.reports_produced, 1);
+        assert_eq!(stats.bytes_reported, report_size as u32);
+        assert!(stats.events_recorded >= 2);
+
+        assert_eq!(MODALITY_PROBE_ERROR_NULL_POINTER, unsafe {
+            modality_probe_get_stats(probe, core::ptr::null_mut())
+        });
+        assert_eq!(MODALITY_PROBE_ERROR_NULL_POINTER, unsafe {
+            modality_probe_set_stats_in_reports(core::ptr::null_mut(), true)
+        });
+    }
 }
@@ -3,7 +3,8 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 use core::mem::MaybeUninit;
 pub use modality_probe_capi_impl::{
-    next_sequence_id_fn, CausalSnapshot, ModalityProbe, ModalityProbeError, ModalityProbeInstant,
+    log_watermark_fn, next_sequence_id_fn, CausalSnapshot, ModalityProbe, ModalityProbeError,
+    ModalityProbeInstant, ProbeStats,
 };
 
 #[no_mangle]
@@ -31,6 +32,46 @@ pub extern "C" fn modality_probe_initialize(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn modality_probe_initialize_retained(
+    destination: *mut MaybeUninit<u8>,
+    destination_size_bytes: usize,
+    probe_id: u32,
+    time_resolution_ns: u32,
+    wall_clock_id: u16,
+    next_sequence_id: Option<next_sequence_id_fn>,
+    next_sequence_id_user_state: *mut core::ffi::c_void,
+    out: *mut *mut ModalityProbe<'static>,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_initialize_retained(
+            destination,
+            destination_size_bytes,
+            probe_id,
+            time_resolution_ns,
+            wall_clock_id,
+            next_sequence_id,
+            next_sequence_id_user_state,
+            out,
+        )
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn modality_probe_flash_next_sequence_id(
+    probe_id: u32,
+    state: *mut core::ffi::c_void,
+    out_sequence_id: *mut u16,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_flash_next_sequence_id(
+            probe_id,
+            state,
+            out_sequence_id,
+        )
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn modality_probe_record_time(
     probe: *mut ModalityProbe<'static>,
@@ -461,11 +502,235 @@ pub extern "C" fn modality_probe_merge_snapshot_bytes_with_time(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn modality_probe_produce_compact_snapshot_bytes(
+    probe: *mut ModalityProbe<'static>,
+    truncate_epoch: bool,
+    history_destination: *mut u8,
+    history_destination_bytes: usize,
+    out_written_bytes: *mut usize,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_produce_compact_snapshot_bytes(
+            probe,
+            truncate_epoch,
+            history_destination,
+            history_destination_bytes,
+            out_written_bytes,
+        )
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn modality_probe_merge_compact_snapshot_bytes(
+    probe: *mut ModalityProbe<'static>,
+    history_source: *const u8,
+    history_source_bytes: usize,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_merge_compact_snapshot_bytes(
+            probe,
+            history_source,
+            history_source_bytes,
+        )
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn modality_probe_now(probe: *mut ModalityProbe<'static>) -> ModalityProbeInstant {
     unsafe { modality_probe_capi_impl::modality_probe_now(probe) }
 }
 
+#[cfg(feature = "event-filtering")]
+#[no_mangle]
+pub extern "C" fn modality_probe_disable_events(
+    probe: *mut ModalityProbe<'static>,
+    mask: *const u32,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_disable_events(probe, mask) }
+}
+
+#[cfg(feature = "event-filtering")]
+#[no_mangle]
+pub extern "C" fn modality_probe_enable_events(
+    probe: *mut ModalityProbe<'static>,
+    mask: *const u32,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_enable_events(probe, mask) }
+}
+
+#[cfg(feature = "event-filtering")]
+#[no_mangle]
+pub extern "C" fn modality_probe_set_disabled_events(
+    probe: *mut ModalityProbe<'static>,
+    mask: *const u32,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_set_disabled_events(probe, mask) }
+}
+
+#[cfg(feature = "event-filtering")]
+#[no_mangle]
+pub extern "C" fn modality_probe_get_disabled_events(
+    probe: *mut ModalityProbe<'static>,
+    mask_out: *mut u32,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_get_disabled_events(probe, mask_out) }
+}
+
+#[cfg(feature = "acknowledged-reporting")]
+#[no_mangle]
+pub extern "C" fn modality_probe_set_acknowledged_reporting(
+    probe: *mut ModalityProbe<'static>,
+    enabled: bool,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_set_acknowledged_reporting(probe, enabled) }
+}
+
+#[cfg(feature = "clock-eviction")]
+#[no_mangle]
+pub extern "C" fn modality_probe_set_clock_eviction_policy(
+    probe: *mut ModalityProbe<'static>,
+    policy: u8,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_set_clock_eviction_policy(probe, policy) }
+}
+
+#[cfg(feature = "report-authentication")]
+#[no_mangle]
+pub extern "C" fn modality_probe_set_report_key(
+    probe: *mut ModalityProbe<'static>,
+    key: *const u8,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_set_report_key(probe, key) }
+}
+
+#[cfg(feature = "report-compression")]
+#[no_mangle]
+pub extern "C" fn modality_probe_set_compressed_reports(
+    probe: *mut ModalityProbe<'static>,
+    enabled: bool,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_set_compressed_reports(probe, enabled) }
+}
+
+#[cfg(feature = "acknowledged-reporting")]
+#[no_mangle]
+pub extern "C" fn modality_probe_acknowledge_report(
+    probe: *mut ModalityProbe<'static>,
+    seq_num: u64,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_acknowledge_report(probe, seq_num) }
+}
+
+#[cfg(feature = "acknowledged-reporting")]
+#[no_mangle]
+pub extern "C" fn modality_probe_retransmit_unacked(
+    probe: *mut ModalityProbe<'static>,
+    log_report_destination: *mut u8,
+    log_report_destination_size_bytes: usize,
+    out_written_bytes: *mut usize,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_retransmit_unacked(
+            probe,
+            log_report_destination,
+            log_report_destination_size_bytes,
+            out_written_bytes,
+        )
+    }
+}
+
+#[cfg(feature = "log-watermark")]
+#[no_mangle]
+pub extern "C" fn modality_probe_set_log_watermark(
+    probe: *mut ModalityProbe<'static>,
+    percent: u8,
+    log_watermark: Option<log_watermark_fn>,
+    log_watermark_user_state: *mut core::ffi::c_void,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_set_log_watermark(
+            probe,
+            percent,
+            log_watermark,
+            log_watermark_user_state,
+        )
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn modality_probe_log_fill_level(
+    probe: *mut ModalityProbe<'static>,
+    out_len: *mut usize,
+    out_capacity: *mut usize,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_log_fill_level(probe, out_len, out_capacity) }
+}
+
+#[cfg(feature = "probe-stats")]
+#[no_mangle]
+pub extern "C" fn modality_probe_get_stats(
+    probe: *mut ModalityProbe<'static>,
+    out_stats: *mut ProbeStats,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_get_stats(probe, out_stats) }
+}
+
+#[cfg(feature = "probe-stats")]
+#[no_mangle]
+pub extern "C" fn modality_probe_set_stats_in_reports(
+    probe: *mut ModalityProbe<'static>,
+    enabled: bool,
+) -> ModalityProbeError {
+    unsafe { modality_probe_capi_impl::modality_probe_set_stats_in_reports(probe, enabled) }
+}
+
+#[no_mangle]
+pub extern "C" fn modality_probe_save_state(
+    probe: *mut ModalityProbe<'static>,
+    state_destination: *mut u8,
+    state_destination_size_bytes: usize,
+    include_log: bool,
+    out_written_bytes: *mut usize,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_save_state(
+            probe,
+            state_destination,
+            state_destination_size_bytes,
+            include_log,
+            out_written_bytes,
+        )
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn modality_probe_restore_state(
+    state: *const u8,
+    state_size_bytes: usize,
+    destination: *mut MaybeUninit<u8>,
+    destination_size_bytes: usize,
+    time_resolution_ns: u32,
+    wall_clock_id: u16,
+    next_sequence_id: Option<next_sequence_id_fn>,
+    next_sequence_id_user_state: *mut core::ffi::c_void,
+    out: *mut *mut ModalityProbe<'static>,
+) -> ModalityProbeError {
+    unsafe {
+        modality_probe_capi_impl::modality_probe_restore_state(
+            state,
+            state_size_bytes,
+            destination,
+            destination_size_bytes,
+            time_resolution_ns,
+            wall_clock_id,
+            next_sequence_id,
+            next_sequence_id_user_state,
+            out,
+        )
+    }
+}
+
 #[cfg(not(test))]
 #[panic_handler]
 pub fn modality_probe_default_panic_abort(_info: &core::panic::PanicInfo) -> ! {
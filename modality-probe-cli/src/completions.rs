@@ -8,11 +8,16 @@ mod error;
 mod events;
 mod header_gen;
 mod lang;
+mod latency;
 mod log;
 mod manifest_gen;
 mod meta;
 mod opts;
 mod probes;
+mod report_html;
+mod spans;
+mod stats;
+mod tui;
 mod visualize;
 
 fn main() {
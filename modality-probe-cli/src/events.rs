@@ -4,6 +4,7 @@ use crate::{
     exit_error,
 };
 use derivative::Derivative;
+use modality_probe::log_backend;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs::File;
@@ -146,6 +147,124 @@ impl Events {
                 file: String::new(),
                 line: String::new(),
             },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_INTERACTION_ID.get_raw()),
+                name: "MODALITY_INTERACTION_ID".to_string(),
+                description: "The snapshot merged just before this event was tagged with an \
+                    interaction id, which is stored in the payload."
+                    .to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_CLOCK_EVICTED.get_raw()),
+                name: "MODALITY_CLOCK_EVICTED".to_string(),
+                description: "A neighbor's logical clock was evicted from the probe's full table \
+                    of clocks to make room for another's. The evicted probe id is stored in the \
+                    payload."
+                    .to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_PRIOR_LOG_RETAINED.get_raw()),
+                name: "MODALITY_PRIOR_LOG_RETAINED".to_string(),
+                description: "The probe kept the log a previous run left in retained memory, \
+                    rather than starting with an empty one. The number of log entries kept is \
+                    stored in the payload."
+                    .to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_EVENTS_RECORDED.get_raw()),
+                name: "MODALITY_STATS_EVENTS_RECORDED".to_string(),
+                description: "The probe's count of events recorded, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_ENTRIES_OVERWRITTEN.get_raw()),
+                name: "MODALITY_STATS_ENTRIES_OVERWRITTEN".to_string(),
+                description: "The probe's count of log entries overwritten before being reported, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_REPORTS_PRODUCED.get_raw()),
+                name: "MODALITY_STATS_REPORTS_PRODUCED".to_string(),
+                description: "The probe's count of reports produced, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_BYTES_REPORTED.get_raw()),
+                name: "MODALITY_STATS_BYTES_REPORTED".to_string(),
+                description: "The probe's count of bytes reported, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_SNAPSHOTS_PRODUCED.get_raw()),
+                name: "MODALITY_STATS_SNAPSHOTS_PRODUCED".to_string(),
+                description: "The probe's count of snapshots produced, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_SNAPSHOTS_MERGED.get_raw()),
+                name: "MODALITY_STATS_SNAPSHOTS_MERGED".to_string(),
+                description: "The probe's count of snapshots merged, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_CLOCK_OVERFLOWS.get_raw()),
+                name: "MODALITY_STATS_CLOCK_OVERFLOWS".to_string(),
+                description: "The probe's count of logical clock overflows, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
+            Event {
+                component_id,
+                id: EventId(modality_probe::EventId::EVENT_STATS_FRONTIER_CLOCK_OVERFLOWS.get_raw()),
+                name: "MODALITY_STATS_FRONTIER_CLOCK_OVERFLOWS".to_string(),
+                description: "The probe's count of neighbor clocks dropped because the clock table was full, stored in the payload.".to_string(),
+                tags: "INTERNAL".to_string(),
+                type_hint: "u32".to_string(),
+                file: String::new(),
+                line: String::new(),
+            },
         ]
     }
 
@@ -192,7 +311,13 @@ impl Events {
             .events
             .iter()
             .map(|event| event.id.0)
-            .map(|id| if internal_events.contains(&id) { 0 } else { id })
+            .map(|id| {
+                if internal_events.contains(&id) || log_backend::is_log_callsite_id(id) {
+                    0
+                } else {
+                    id
+                }
+            })
             .max()
             .unwrap_or(0)
     }
@@ -5,8 +5,9 @@ use crate::{
     lang::Lang,
     probes::{Probe, Probes},
 };
-use modality_probe::{EventId, ProbeId};
+use modality_probe::{EventId, EventMask, ProbeId};
 use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -173,6 +174,88 @@ impl ConstGenerator for Event {
     }
 }
 
+/// The mask of events with each tag, for runtime event filtering.
+/// Events the mask can't contain are left out.
+fn tag_event_masks<'a>(
+    events: impl Iterator<Item = &'a Event>,
+    internal_events: &[u32],
+) -> BTreeMap<String, EventMask> {
+    let mut masks = BTreeMap::new();
+    for event in events.filter(|e| !internal_events.contains(&e.id.0)) {
+        let event_id = match EventId::new(event.id.0) {
+            Some(id) if EventMask::can_contain(id) => id,
+            _ => continue,
+        };
+        for tag in event
+            .tags
+            .split(';')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+        {
+            let mask = masks.entry(tag.to_string()).or_insert(EventMask::EMPTY);
+            *mask = mask.with_event(event_id);
+        }
+    }
+    masks
+}
+
+fn tag_mask_definition_name(tag: &str) -> String {
+    let tag: String = tag
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("TAG_{}_EVENT_MASK", tag)
+}
+
+fn generate_tag_mask_definition(
+    tag: &str,
+    mask: &EventMask,
+    lang: Lang,
+    rust_u32_types: bool,
+) -> String {
+    let definition_name = tag_mask_definition_name(tag);
+    let words = |suffix: &str| {
+        mask.words()
+            .iter()
+            .map(|w| format!("0x{:08X}{}", w, suffix))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match lang {
+        Lang::C => format!(
+            "/*\n * Tag: {}\n */\nstatic const uint32_t {}[{}] = {{ {} }};",
+            tag,
+            definition_name,
+            mask.words().len(),
+            words("UL")
+        ),
+        Lang::Rust => {
+            if rust_u32_types {
+                format!(
+                    "/// Tag: {}\npub const {}: [u32; {}] = [{}];",
+                    tag,
+                    definition_name,
+                    mask.words().len(),
+                    words("")
+                )
+            } else {
+                format!(
+                    "/// Tag: {}\npub const {}: EventMask = EventMask::from_words([{}]);",
+                    tag,
+                    definition_name,
+                    words("")
+                )
+            }
+        }
+    }
+}
+
 fn pad_nonempty(s: &str) -> String {
     if !s.is_empty() {
         format!(" {}", s)
@@ -278,6 +361,7 @@ pub fn generate_output<W: io::Write>(
     let component = Component::from_toml(&component_manifest_path);
     let probes = Probes::from_csv(&probes_manifest_path);
     let events = Events::from_csv(&events_manifest_path);
+    let tag_masks = tag_event_masks(events.iter(), &internal_events);
 
     writeln!(w, "/*")?;
     writeln!(w, " * GENERATED CODE, DO NOT EDIT")?;
@@ -310,11 +394,19 @@ pub fn generate_output<W: io::Write>(
             opt.include_guard_prefix
         )?;
         writeln!(w)?;
+        if !tag_masks.is_empty() {
+            writeln!(w, "#include <stdint.h>")?;
+            writeln!(w)?;
+        }
         writeln!(w, "#ifdef __cplusplus")?;
         writeln!(w, "extern \"C\" {{")?;
         writeln!(w, "#endif")?;
     } else if !opt.rust_u32_types {
-        writeln!(w, "use modality_probe::{{EventId, ProbeId}};")?;
+        if tag_masks.is_empty() {
+            writeln!(w, "use modality_probe::{{EventId, ProbeId}};")?;
+        } else {
+            writeln!(w, "use modality_probe::{{EventId, EventMask, ProbeId}};")?;
+        }
     }
 
     writeln!(w)?;
@@ -351,6 +443,22 @@ pub fn generate_output<W: io::Write>(
         )?;
     }
 
+    if !tag_masks.is_empty() {
+        writeln!(w)?;
+        writeln!(w, "/*")?;
+        writeln!(w, " * Event masks by tag, for runtime event filtering")?;
+        writeln!(w, " */")?;
+
+        for (tag, mask) in tag_masks.iter() {
+            writeln!(w)?;
+            writeln!(
+                w,
+                "{}",
+                generate_tag_mask_definition(tag, mask, opt.lang, opt.rust_u32_types)
+            )?;
+        }
+    }
+
     if opt.lang == Lang::C {
         writeln!(w)?;
         writeln!(w, "#ifdef __cplusplus")?;
@@ -389,3 +497,52 @@ pub fn run(opt: HeaderGen, internal_events: Option<Vec<Event>>) {
 
     generate_output(opt, internal_event_ids, io_out).expect("Can't generate output");
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{component::ComponentUuid, events::EventId as CliEventId};
+
+    fn event(id: u32, tags: &str) -> Event {
+        Event {
+            component_id: ComponentUuid::nil(),
+            id: CliEventId(id),
+            name: format!("event_{}", id),
+            description: String::new(),
+            tags: tags.to_string(),
+            type_hint: String::new(),
+            file: String::new(),
+            line: String::new(),
+        }
+    }
+
+    #[test]
+    fn tag_masks() {
+        let events = vec![
+            event(1, "network;verbose"),
+            event(2, "verbose"),
+            event(33, "network"),
+            event(4, ""),
+            event(257, "network;unmaskable"),
+            event(EventId::EVENT_PROBE_INITIALIZED.get_raw(), "INTERNAL"),
+        ];
+        let internal = [EventId::EVENT_PROBE_INITIALIZED.get_raw()];
+        let masks = tag_event_masks(events.iter(), &internal);
+        assert_eq!(masks.keys().collect::<Vec<_>>(), vec!["network", "verbose"]);
+        assert_eq!(masks["network"].words()[..2], [0b1, 0b1]);
+        assert_eq!(masks["verbose"].words()[..2], [0b11, 0]);
+
+        assert_eq!(
+            generate_tag_mask_definition("verbose", &masks["verbose"], Lang::C, false),
+            "/*\n * Tag: verbose\n */\nstatic const uint32_t TAG_VERBOSE_EVENT_MASK[8] = { \
+             0x00000003UL, 0x00000000UL, 0x00000000UL, 0x00000000UL, \
+             0x00000000UL, 0x00000000UL, 0x00000000UL, 0x00000000UL };"
+        );
+        assert_eq!(
+            generate_tag_mask_definition("my-tag", &masks["verbose"], Lang::Rust, false),
+            "/// Tag: my-tag\npub const TAG_MY_TAG_EVENT_MASK: EventMask = EventMask::from_words([\
+             0x00000003, 0x00000000, 0x00000000, 0x00000000, \
+             0x00000000, 0x00000000, 0x00000000, 0x00000000]);"
+        );
+    }
+}
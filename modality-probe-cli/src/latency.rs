@@ -0,0 +1,484 @@
+//! Measure the wall-clock latency between causally related events.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::File,
+    path::PathBuf,
+};
+
+use structopt::StructOpt;
+
+use modality_probe_collector_common::{json, ReportIter, ReportLogEntry};
+use modality_probe_graph::{EventDigraph, Graph, GraphEvent};
+
+use crate::{
+    give_up, hopefully,
+    meta::{self, MetaMeter},
+};
+
+/// Measure the wall-clock latency between pairs of causally related
+/// events in a collected trace.
+#[derive(Debug, PartialEq, StructOpt)]
+pub struct Latency {
+    /// The path to a component directory. To include multiple
+    /// components, provide this switch multiple times.
+    #[structopt(short, long, required = true)]
+    pub component_path: Vec<PathBuf>,
+    /// The path to the collected trace.
+    #[structopt(short, long, required = true)]
+    pub report: PathBuf,
+    /// The event that starts the measured path, given as an event
+    /// name or a raw event id.
+    #[structopt(long)]
+    pub from: String,
+    /// The event that ends the measured path, given as an event name
+    /// or a raw event id.
+    #[structopt(long)]
+    pub to: String,
+    /// The number of buckets to use in the printed histogram.
+    #[structopt(long, default_value = "10")]
+    pub buckets: usize,
+}
+
+pub fn run(mut lat: Latency) -> Result<(), Box<dyn std::error::Error>> {
+    let cfg = meta::assemble_components(&mut lat.component_path)?;
+    let mut log_file = hopefully!(
+        File::open(&lat.report),
+        format!("Failed to open the report file at {}", lat.report.display())
+    )?;
+    let graph = log_to_predecessor_graph(json::read_log_entries(&mut log_file)?)?;
+
+    let is_from = event_matcher(&cfg, &lat.from);
+    let is_to = event_matcher(&cfg, &lat.to);
+    if !graph.nodes.iter().any(&is_from) {
+        give_up!(format!(
+            "No occurrences of {} were found in the trace",
+            lat.from
+        ));
+    }
+    if !graph.nodes.iter().any(&is_to) {
+        give_up!(format!(
+            "No occurrences of {} were found in the trace",
+            lat.to
+        ));
+    }
+
+    let pairs = graph.causal_pairs(is_from, is_to);
+    let measurements = pairs
+        .iter()
+        .map(|(from, to)| measure(from, to))
+        .collect::<Vec<_>>();
+
+    print!("{}", render(&lat, &measurements));
+    Ok(())
+}
+
+/// Build the trace into a graph that can be walked backwards from any
+/// event to its causal predecessors.
+pub fn log_to_predecessor_graph(
+    entries: Vec<ReportLogEntry>,
+) -> Result<PredecessorGraph, Box<dyn std::error::Error>> {
+    let mut graph = EventDigraph::new(PredecessorGraph::default());
+    for report in ReportIter::new(entries.into_iter().peekable()) {
+        hopefully!(
+            graph.add_report(&report, false),
+            "Encountered an error reconstructing the graph"
+        )?;
+    }
+    Ok(graph.graph)
+}
+
+fn event_matcher<'a>(cfg: &'a dyn MetaMeter, event: &'a str) -> impl Fn(&GraphEvent) -> bool + 'a {
+    let raw_id = event.parse::<u32>().ok();
+    move |node| {
+        raw_id == Some(node.id.get_raw())
+            || cfg.event_name(&node.probe_id, &node.id).as_deref() == Some(event)
+    }
+}
+
+/// An `EventDigraph` inner graph which indexes each event's immediate
+/// causal predecessors.
+#[derive(Debug, Default)]
+pub struct PredecessorGraph {
+    nodes: HashSet<GraphEvent>,
+    predecessors: HashMap<GraphEvent, HashSet<GraphEvent>>,
+}
+
+impl Graph for PredecessorGraph {
+    fn add_node(&mut self, node: GraphEvent) {
+        self.nodes.insert(node);
+    }
+
+    fn add_edge(&mut self, source: GraphEvent, target: GraphEvent) {
+        self.predecessors.entry(target).or_default().insert(source);
+    }
+}
+
+impl PredecessorGraph {
+    /// Find every `(from, to)` pair where `from` is one of the
+    /// nearest events in `to`'s causal past matching `is_from`. The
+    /// search backwards from each `to` stops on every path at the
+    /// first event matching either predicate, so each `to` is only
+    /// paired with the `from` events that most recently led to it.
+    pub fn causal_pairs<F, T>(&self, is_from: F, is_to: T) -> Vec<(GraphEvent, GraphEvent)>
+    where
+        F: Fn(&GraphEvent) -> bool,
+        T: Fn(&GraphEvent) -> bool,
+    {
+        let mut targets = self.nodes.iter().filter(|n| is_to(n)).collect::<Vec<_>>();
+        targets.sort_by_key(|n| (n.probe_id, n.seq, n.seq_idx));
+
+        let mut pairs = Vec::new();
+        for target in targets {
+            let mut sources = Vec::new();
+            let mut visited = HashSet::new();
+            let mut queue = VecDeque::new();
+            queue.push_back(*target);
+            while let Some(node) = queue.pop_front() {
+                if let Some(preds) = self.predecessors.get(&node) {
+                    for pred in preds {
+                        if !visited.insert(*pred) {
+                            continue;
+                        }
+                        if is_from(pred) {
+                            sources.push(*pred);
+                        } else if !is_to(pred) {
+                            queue.push_back(*pred);
+                        }
+                    }
+                }
+            }
+            sources.sort_by_key(|n| (n.probe_id, n.seq, n.seq_idx));
+            pairs.extend(sources.into_iter().map(|s| (s, *target)));
+        }
+        pairs
+    }
+}
+
+/// The outcome of measuring a single causally related pair of events.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Measurement {
+    /// Nanoseconds of wall-clock time elapsed between the events.
+    Elapsed(u64),
+    /// At least one of the events was recorded without a time.
+    MissingTime,
+    /// The events' times come from different wall-clock domains.
+    DifferentClocks,
+    /// The later event has an earlier time than the event that
+    /// caused it.
+    ClockSkew,
+}
+
+/// Measure the wall-clock time between two events. Times recorded by
+/// different probes are only comparable when the probes share a
+/// wall clock id that isn't local-only.
+pub fn measure(from: &GraphEvent, to: &GraphEvent) -> Measurement {
+    match (from.wall_time, to.wall_time) {
+        (Some(from_time), Some(to_time)) => {
+            let same_domain = from.probe_id == to.probe_id
+                || (from.wall_clock_id == to.wall_clock_id && !from.wall_clock_id.is_local_only());
+            if !same_domain {
+                Measurement::DifferentClocks
+            } else if to_time < from_time {
+                Measurement::ClockSkew
+            } else {
+                Measurement::Elapsed(to_time.get() - from_time.get())
+            }
+        }
+        _ => Measurement::MissingTime,
+    }
+}
+
+/// Summary statistics over a set of latencies, in nanoseconds.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: u64,
+    pub mean: u64,
+    pub max: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+impl Summary {
+    /// Summarize a sorted, non-empty slice of latencies.
+    pub fn from_sorted(sorted: &[u64]) -> Option<Self> {
+        if sorted.is_empty() {
+            return None;
+        }
+        let sum: u128 = sorted.iter().map(|v| *v as u128).sum();
+        Some(Summary {
+            count: sorted.len(),
+            min: sorted[0],
+            mean: (sum / sorted.len() as u128) as u64,
+            max: sorted[sorted.len() - 1],
+            p50: percentile(sorted, 50),
+            p90: percentile(sorted, 90),
+            p95: percentile(sorted, 95),
+            p99: percentile(sorted, 99),
+        })
+    }
+}
+
+/// Nearest-rank percentile of a sorted, non-empty slice.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    let rank = (p * sorted.len()).div_ceil(100);
+    sorted[rank.saturating_sub(1).min(sorted.len() - 1)]
+}
+
+/// A histogram bucket covering `[start, end)` nanoseconds.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Bucket {
+    pub start: u64,
+    pub end: u64,
+    pub count: usize,
+}
+
+/// Split a sorted, non-empty slice of latencies into at most
+/// `n_buckets` equally sized buckets.
+pub fn histogram(sorted: &[u64], n_buckets: usize) -> Vec<Bucket> {
+    if sorted.is_empty() || n_buckets == 0 {
+        return Vec::new();
+    }
+    let min = sorted[0];
+    let span = sorted[sorted.len() - 1] - min + 1;
+    let width = span.div_ceil(n_buckets as u64);
+    let n_used = span.div_ceil(width) as usize;
+    let mut buckets = (0..n_used as u64)
+        .map(|i| Bucket {
+            start: min + i * width,
+            end: min + (i + 1) * width,
+            count: 0,
+        })
+        .collect::<Vec<_>>();
+    for v in sorted {
+        buckets[((v - min) / width) as usize].count += 1;
+    }
+    buckets
+}
+
+const HISTOGRAM_WIDTH: usize = 40;
+
+fn render(lat: &Latency, measurements: &[Measurement]) -> String {
+    use std::fmt::Write;
+
+    let mut elapsed = measurements
+        .iter()
+        .filter_map(|m| match m {
+            Measurement::Elapsed(ns) => Some(*ns),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    elapsed.sort_unstable();
+    let count = |kind: Measurement| measurements.iter().filter(|m| **m == kind).count();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{} -> {}", lat.from, lat.to);
+    let _ = writeln!(out, "    causal pairs: {}", measurements.len());
+    let _ = writeln!(out, "    measured:     {}", elapsed.len());
+    let _ = writeln!(
+        out,
+        "    skipped:      {} missing a time, {} on different wall clocks, {} with clock skew",
+        count(Measurement::MissingTime),
+        count(Measurement::DifferentClocks),
+        count(Measurement::ClockSkew),
+    );
+
+    let summary = match Summary::from_sorted(&elapsed) {
+        Some(s) => s,
+        None => return out,
+    };
+    let _ = writeln!(out);
+    for (label, ns) in &[
+        ("min", summary.min),
+        ("mean", summary.mean),
+        ("p50", summary.p50),
+        ("p90", summary.p90),
+        ("p95", summary.p95),
+        ("p99", summary.p99),
+        ("max", summary.max),
+    ] {
+        let _ = writeln!(out, "    {:<5} {}", label, fmt_duration(*ns));
+    }
+
+    let buckets = histogram(&elapsed, lat.buckets);
+    let biggest = buckets.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+    let labels = buckets
+        .iter()
+        .map(|b| format!("[{}, {})", fmt_duration(b.start), fmt_duration(b.end)))
+        .collect::<Vec<_>>();
+    let label_width = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+    let _ = writeln!(out);
+    for (bucket, label) in buckets.iter().zip(labels) {
+        let bar = (bucket.count * HISTOGRAM_WIDTH).div_ceil(biggest);
+        let _ = writeln!(
+            out,
+            "    {:<width$} {} {}",
+            label,
+            "#".repeat(bar),
+            bucket.count,
+            width = label_width
+        );
+    }
+    out
+}
+
+pub(crate) fn fmt_duration(ns: u64) -> String {
+    if ns >= 1_000_000_000 {
+        format!("{:.3}s", ns as f64 / 1_000_000_000.0)
+    } else if ns >= 1_000_000 {
+        format!("{:.3}ms", ns as f64 / 1_000_000.0)
+    } else if ns >= 1_000 {
+        format!("{:.3}µs", ns as f64 / 1_000.0)
+    } else {
+        format!("{}ns", ns)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+
+    use modality_probe::{
+        EventId, LogicalClock, NanosecondResolution, Nanoseconds, ProbeEpoch, ProbeId, ProbeTicks,
+        WallClockId,
+    };
+    use modality_probe_collector_common::{LogEntryData, SequenceNumber, SessionId};
+
+    use super::*;
+    use crate::visualize::graph::test::cfg;
+
+    fn entry(
+        probe: u32,
+        seq: u64,
+        seq_idx: u32,
+        wall_clock_id: u16,
+        data: LogEntryData,
+    ) -> ReportLogEntry {
+        let probe_id = ProbeId::new(probe).unwrap();
+        ReportLogEntry {
+            session_id: SessionId(1),
+            sequence_number: SequenceNumber(seq),
+            sequence_index: seq_idx,
+            time_resolution: NanosecondResolution(0),
+            wall_clock_id: WallClockId(wall_clock_id),
+            receive_time: Utc::now(),
+            clock: clock(probe, 0),
+            persistent_epoch_counting: false,
+            probe_id,
+            data,
+        }
+    }
+
+    fn clock(probe: u32, ticks: u16) -> LogicalClock {
+        LogicalClock {
+            id: ProbeId::new(probe).unwrap(),
+            epoch: ProbeEpoch(0),
+            ticks: ProbeTicks(ticks),
+        }
+    }
+
+    fn timed(ns: u64, event: u32) -> LogEntryData {
+        LogEntryData::EventWithTime(Nanoseconds::new(ns).unwrap(), EventId::new(event).unwrap())
+    }
+
+    // Probe 1 records event 1, then sends a snapshot to probe 2,
+    // which merges it and records event 2; twice over.
+    fn ping_trace(probe_two_wall_clock: u16) -> Vec<ReportLogEntry> {
+        let wc2 = probe_two_wall_clock;
+        vec![
+            entry(1, 1, 0, 1, LogEntryData::FrontierClock(clock(1, 0))),
+            entry(1, 1, 1, 1, timed(1_000, 1)),
+            entry(1, 1, 2, 1, LogEntryData::TraceClock(clock(1, 1))),
+            entry(1, 1, 3, 1, timed(2_000, 1)),
+            entry(1, 1, 4, 1, LogEntryData::TraceClock(clock(1, 2))),
+            entry(2, 1, 0, wc2, LogEntryData::FrontierClock(clock(2, 0))),
+            entry(2, 1, 1, wc2, LogEntryData::TraceClock(clock(2, 1))),
+            entry(2, 1, 2, wc2, LogEntryData::TraceClock(clock(1, 0))),
+            entry(2, 1, 3, wc2, timed(1_500, 2)),
+            entry(2, 1, 4, wc2, LogEntryData::TraceClock(clock(2, 2))),
+            entry(2, 1, 5, wc2, LogEntryData::TraceClock(clock(1, 1))),
+            entry(2, 1, 6, wc2, timed(2_700, 2)),
+        ]
+    }
+
+    fn measurements(log: Vec<ReportLogEntry>) -> Vec<Measurement> {
+        let cfg = cfg();
+        let graph = log_to_predecessor_graph(log).unwrap();
+        graph
+            .causal_pairs(event_matcher(&cfg, "one"), event_matcher(&cfg, "two"))
+            .iter()
+            .map(|(from, to)| measure(from, to))
+            .collect()
+    }
+
+    #[test]
+    fn shared_wall_clock_latency() {
+        assert_eq!(
+            measurements(ping_trace(1)),
+            vec![Measurement::Elapsed(500), Measurement::Elapsed(700)]
+        );
+    }
+
+    #[test]
+    fn different_wall_clocks_are_not_compared() {
+        assert_eq!(
+            measurements(ping_trace(2)),
+            vec![Measurement::DifferentClocks, Measurement::DifferentClocks]
+        );
+        assert_eq!(
+            measurements(ping_trace(0)),
+            vec![Measurement::DifferentClocks, Measurement::DifferentClocks]
+        );
+    }
+
+    #[test]
+    fn summary_percentiles() {
+        let sorted = (1..=100).collect::<Vec<u64>>();
+        assert_eq!(
+            Summary::from_sorted(&sorted),
+            Some(Summary {
+                count: 100,
+                min: 1,
+                mean: 50,
+                max: 100,
+                p50: 50,
+                p90: 90,
+                p95: 95,
+                p99: 99,
+            })
+        );
+        assert_eq!(Summary::from_sorted(&[]), None);
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let buckets = histogram(&[10, 11, 15, 19, 20], 2);
+        assert_eq!(
+            buckets,
+            vec![
+                Bucket {
+                    start: 10,
+                    end: 16,
+                    count: 3
+                },
+                Bucket {
+                    start: 16,
+                    end: 22,
+                    count: 2
+                },
+            ]
+        );
+        assert_eq!(
+            histogram(&[5, 5, 5], 10),
+            vec![Bucket {
+                start: 5,
+                end: 6,
+                count: 3
+            }]
+        );
+    }
+}
@@ -4,9 +4,14 @@ pub mod error;
 pub mod events;
 pub mod header_gen;
 pub mod lang;
+pub mod latency;
 pub mod log;
 pub mod manifest_gen;
 pub mod meta;
 pub mod opts;
 pub mod probes;
+pub mod report_html;
+pub mod spans;
+pub mod stats;
+pub mod tui;
 pub mod visualize;
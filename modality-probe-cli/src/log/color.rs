@@ -11,9 +11,14 @@ lazy_static! {
 
 const PROBE_SET: [Color; 12] = colorous::SET3;
 
+/// The color assigned to the probe at `idx`.
+pub fn probe_color(idx: usize) -> Color {
+    PROBE_SET[idx % PROBE_SET.len()]
+}
+
 pub fn colorize_probe(idx: usize, content: &str) -> String {
     if COLORIZE.read().map(|b| *b).unwrap_or(false) {
-        let c = PROBE_SET[idx % PROBE_SET.len()];
+        let c = probe_color(idx);
         content.truecolor(c.r, c.g, c.b).to_string()
     } else {
         content.to_string()
@@ -33,8 +38,8 @@ pub fn colorize_info(key: &str, val: &str) -> String {
 
 pub fn colorize_merge(from: &str, from_idx: usize, to: &str, to_idx: usize) -> String {
     if COLORIZE.read().map(|b| *b).unwrap_or(false) {
-        let from_color = PROBE_SET[from_idx % PROBE_SET.len()];
-        let to_color = PROBE_SET[to_idx % PROBE_SET.len()];
+        let from_color = probe_color(from_idx);
+        let to_color = probe_color(to_idx);
         format!(
             "{} merged a snapshot from {}",
             to.truecolor(to_color.r, to_color.g, to_color.b),
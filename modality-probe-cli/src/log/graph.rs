@@ -34,6 +34,10 @@ pub(super) fn print_as_graph<W: WriteIo>(
     // end of an egde to show up.
     let mut blocked_tls: HashMap<ProbeId, HashSet<(ProbeId, LogicalClock)>> = HashMap::new();
 
+    // The interaction ids of merged snapshots, keyed by the merging
+    // probe and the foreign clock it merged.
+    let mut interactions: HashMap<(ProbeId, LogicalClock), u32> = HashMap::new();
+
     // How many timelines are there?
     let n_probes = probes.len();
     // Probes mapped to their indices.
@@ -194,6 +198,7 @@ pub(super) fn print_as_graph<W: WriteIo>(
                                                     n_probes,
                                                     &clock_rows,
                                                     &mut blocked_tls,
+                                                    &mut interactions,
                                                     idx,
                                                     &indices,
                                                     &mut stream,
@@ -208,6 +213,7 @@ pub(super) fn print_as_graph<W: WriteIo>(
                                                 n_probes,
                                                 &clock_rows,
                                                 &mut blocked_tls,
+                                                &mut interactions,
                                                 idx,
                                                 &indices,
                                                 &mut stream,
@@ -223,6 +229,7 @@ pub(super) fn print_as_graph<W: WriteIo>(
                                         n_probes,
                                         &clock_rows,
                                         &mut blocked_tls,
+                                        &mut interactions,
                                         idx,
                                         &indices,
                                         &mut stream,
@@ -231,6 +238,14 @@ pub(super) fn print_as_graph<W: WriteIo>(
                             }
                         // This is a foreign clock: `lc.id != probe_id`.
                         } else {
+                            // An interaction id recorded with the
+                            // merge labels its edge.
+                            if let Some(interaction_id) =
+                                log.last().and_then(|next| interaction_id(&next.data))
+                            {
+                                log.pop();
+                                interactions.insert((*probe_id, lc), interaction_id);
+                            }
                             let from_name = cfg
                                 .probe_name(&lc.id)
                                 .unwrap_or_else(|| lc.id.get_raw().to_string());
@@ -240,11 +255,15 @@ pub(super) fn print_as_graph<W: WriteIo>(
                             let from_idx = indices.get(&lc.id);
                             if let Some(mut neighbor) = blocked_tls.remove(&lc.id) {
                                 if neighbor.remove(&(*probe_id, lc)) {
+                                    let interaction = interactions
+                                        .remove(&(*probe_id, lc))
+                                        .map(|id| meta::interaction_name(cfg, probe_id, id));
                                     print_edge_line(
                                         *indices.get(&lc.id).unwrap(),
                                         idx,
                                         &from_name,
                                         &to_name,
+                                        interaction.as_deref(),
                                         n_probes,
                                         &mut stream,
                                     )?;
@@ -308,6 +327,20 @@ pub(super) fn print_as_graph<W: WriteIo>(
     Ok(())
 }
 
+/// The interaction id carried by a merged snapshot, if this entry
+/// records one.
+pub(super) fn interaction_id(data: &LogEntryData) -> Option<u32> {
+    match data {
+        LogEntryData::EventWithPayload(id, pl)
+        | LogEntryData::EventWithPayloadWithTime(.., id, pl)
+            if *id == EventId::EVENT_INTERACTION_ID =>
+        {
+            Some(*pl)
+        }
+        _ => None,
+    }
+}
+
 pub fn handle_graph_verbosity<W: WriteIo>(
     verbosity: u8,
     probe_id: &ProbeId,
@@ -393,6 +426,7 @@ pub fn handle_source_edge<W, C>(
     n_probes: usize,
     clock_rows: &[(ProbeId, C)],
     blocked_tls: &mut HashMap<ProbeId, HashSet<(ProbeId, C)>>,
+    interactions: &mut HashMap<(ProbeId, C), u32>,
     idx: usize,
     indices: &HashMap<ProbeId, usize>,
     mut stream: W,
@@ -421,11 +455,15 @@ where
                 let to_name = cfg
                     .probe_name(pid)
                     .unwrap_or_else(|| pid.get_raw().to_string());
+                let interaction = interactions
+                    .remove(&(*pid, *c))
+                    .map(|id| meta::interaction_name(cfg, pid, id));
                 print_edge_line(
                     idx,
                     *indices.get(&pid).unwrap(),
                     &from_name,
                     &to_name,
+                    interaction.as_deref(),
                     n_probes,
                     &mut stream,
                 )?;
@@ -502,6 +540,7 @@ pub fn print_edge_line<W: WriteIo>(
     to: usize,
     from_pname: &str,
     to_pname: &str,
+    interaction: Option<&str>,
     n_probes: usize,
     mut stream: W,
 ) -> Result<(), Box<dyn std::error::Error>> {
@@ -594,6 +633,12 @@ pub fn print_edge_line<W: WriteIo>(
         ),
         "Internal error formatting graph"
     )?;
+    if let Some(interaction) = interaction {
+        hopefully!(
+            write!(s, " over {}", color::white(interaction)),
+            "Internal error formatting graph"
+        )?;
+    }
     hopefully!(writeln!(stream, "{}", s), "Internal error formatting graph")?;
     Ok(())
 }
@@ -1535,4 +1580,45 @@ pub(crate) mod test {
         print_as_graph(probes, clock_rows, &cfg, &l, &mut out).unwrap();
         assert_eq!(EXPECTED_FANOUT, std::str::from_utf8(&out).unwrap());
     }
+
+    #[test]
+    fn interaction_labels() {
+        let probe = |id| ProbeId::new(id).unwrap();
+        let mut trace = fanout_trace();
+        modality_probe_graph::test_support::tag_interaction(&mut trace, probe(2), probe(1), 0x123);
+        modality_probe_graph::test_support::tag_interaction(&mut trace, probe(3), probe(1), 3);
+        let cfg = graph::test::cfg();
+        let l = Log {
+            probe: None,
+            component: None,
+            component_path: vec![],
+            report: PathBuf::default(),
+            graph: true,
+            verbose: 0,
+            format: None,
+            radius: None,
+            from: None,
+            no_color: true,
+        };
+        {
+            let mut b = color::COLORIZE.write().unwrap();
+            *b = false;
+        }
+        let (probes, clock_rows) = log::sort_probes(&cfg, &l, trace).unwrap();
+        let mut out = Vec::new();
+        print_as_graph(probes, clock_rows, &cfg, &l, &mut out).unwrap();
+        let out = std::str::from_utf8(&out).unwrap();
+        assert!(
+            out.contains("+->+  |  two merged a snapshot from one over 0x123\n"),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains("+---->+  three merged a snapshot from one over three\n"),
+            "{}",
+            out
+        );
+        // The interaction ids aren't shown as events of their own
+        assert_eq!(out.lines().count(), EXPECTED_FANOUT.lines().count());
+    }
 }
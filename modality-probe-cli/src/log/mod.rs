@@ -181,13 +181,19 @@ fn sort_probes(
         None
     };
 
+    // Internal events are left out, save for the interaction ids
+    // that label a graph's merges.
+    let is_shown = |ev: &ReportLogEntry| {
+        !ev.is_internal_event() || (l.graph && graph::interaction_id(&ev.data).is_some())
+    };
+
     match (cid, pid) {
         (Some(c), Some(p)) => {
             for ev in report {
                 if let Some(id) = cfg.probes_to_components.get(&ev.probe_id.get_raw()) {
                     if ev.probe_id == p && &id.to_string() == c {
                         let p = probes.entry(ev.probe_id).or_insert_with(Vec::new);
-                        if !ev.is_internal_event() {
+                        if is_shown(&ev) {
                             p.push(ev);
                         }
                     }
@@ -199,7 +205,7 @@ fn sort_probes(
                 if let Some(id) = cfg.probes_to_components.get(&ev.probe_id.get_raw()) {
                     if &id.to_string() == c {
                         let p = probes.entry(ev.probe_id).or_insert_with(Vec::new);
-                        if !ev.is_internal_event() {
+                        if is_shown(&ev) {
                             p.push(ev);
                         }
                     }
@@ -210,7 +216,7 @@ fn sort_probes(
             for ev in report {
                 if ev.probe_id == p {
                     let p = probes.entry(ev.probe_id).or_insert_with(Vec::new);
-                    if !ev.is_internal_event() {
+                    if is_shown(&ev) {
                         p.push(ev);
                     }
                 }
@@ -219,7 +225,7 @@ fn sort_probes(
         (None, None) => {
             for ev in report {
                 let p = probes.entry(ev.probe_id).or_insert_with(Vec::new);
-                if !ev.is_internal_event() {
+                if is_shown(&ev) {
                     p.push(ev);
                 }
             }
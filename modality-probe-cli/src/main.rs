@@ -1,5 +1,6 @@
 use modality_probe_cli::{
-    error::GracefulExit, header_gen, log, manifest_gen, opts::Opts, visualize,
+    error::GracefulExit, header_gen, latency, log, manifest_gen, opts::Opts, report_html, spans,
+    stats, tui, visualize,
 };
 use structopt::StructOpt;
 
@@ -16,6 +17,11 @@ fn main() {
         Opts::HeaderGen(opt) => header_gen::run(opt, None),
         Opts::Log(opt) => log::run(opt).unwrap_or_exit("log"),
         Opts::Visualize(opt) => visualize::run(opt).unwrap_or_exit("visualize"),
+        Opts::Latency(opt) => latency::run(opt).unwrap_or_exit("latency"),
+        Opts::Tui(opt) => tui::run(opt).unwrap_or_exit("tui"),
+        Opts::ReportHtml(opt) => report_html::run(opt).unwrap_or_exit("report-html"),
+        Opts::Spans(opt) => spans::run(opt).unwrap_or_exit("spans"),
+        Opts::Stats(opt) => stats::run(opt).unwrap_or_exit("stats"),
     }
 }
 
@@ -46,6 +46,7 @@ pub enum Error {
     EmptyTags(SourceLocation),
     EmptySeverity(SourceLocation),
     SeverityNotNumeric(SourceLocation),
+    SpanEventName(SourceLocation),
 }
 
 impl Error {
@@ -59,6 +60,7 @@ impl Error {
             Error::EmptyTags(l) => l,
             Error::EmptySeverity(l) => l,
             Error::SeverityNotNumeric(l) => l,
+            Error::SpanEventName(l) => l,
         }
     }
 }
@@ -127,6 +129,11 @@ fn parse_input<T>(
 fn parse_record_event_call_exp(input: Span) -> ParserResult<Span, EventMetadata> {
     let prefix = input.extra.as_ref().unwrap().prefix;
     let (input, _) = comments_and_spacing(input)?;
+    let span_tag = format!("{}_SPAN_", prefix);
+    let (input, found_span) = peek(opt(tag(span_tag.as_str())))(input)?;
+    if found_span.is_some() {
+        return span_call_exp(input);
+    }
     let expect_w_time_tag = format!("{}_EXPECT_W_TIME", prefix);
     let (input, found_expect_w_time) = peek(opt(tag(expect_w_time_tag.as_str())))(input)?;
     let expect_tag = format!("{}_EXPECT", prefix);
@@ -163,6 +170,82 @@ fn parse_record_event_call_exp(input: Span) -> ParserResult<Span, EventMetadata>
     }
 }
 
+/// Parses all of the span macros: `<PREFIX>_SPAN_BEGIN`,
+/// `<PREFIX>_SPAN_END` and their `_W_TIME` variants.
+fn span_call_exp(input: Span) -> ParserResult<Span, EventMetadata> {
+    let prefix = input.extra.as_ref().unwrap().prefix;
+    let tag_string = format!("{}_SPAN_", prefix);
+    let (input, _) = comments_and_spacing(input)?;
+    let (input, pos) = position(input)?;
+    let (input, _) = tag(tag_string.as_str())(input)?;
+    let (input, edge) = alt((tag("BEGIN"), tag("END")))(input)?;
+    let (input, found_with_time) = opt(tag("_W_TIME"))(input)?;
+    let (input, _) = opt(line_ending)(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, _) = tag("(")(input)?;
+    let (input, args) = take_until(");")(input)
+        .map_err(|e| convert_error(e, Error::MissingSemicolon(pos.into())))?;
+    let (input, _) =
+        tag(");")(input).map_err(|e| convert_error(e, Error::MissingSemicolon(pos.into())))?;
+    let (args, probe_instance) = variable_call_exp_arg(args)?;
+    let (args, name) = variable_call_exp_arg(args)?;
+    if !event_name_valid(&name) {
+        return Err(make_failure(input, Error::Syntax(pos.into())));
+    }
+    let (span_tag, suffix) = if *edge.fragment() == "BEGIN" {
+        ("SPAN_BEGIN", "_BEGIN")
+    } else {
+        ("SPAN_END", "_END")
+    };
+    if !name.ends_with(suffix) {
+        return Err(make_failure(input, Error::SpanEventName(pos.into())));
+    }
+    let mut arg_vec: Vec<String> = Vec::new();
+    let mut iter = iterator(args, multi_variable_call_exp_arg_literal);
+    iter.for_each(|s| arg_vec.push(s));
+    let (_args, _) = iter.finish()?;
+    let required_args = if found_with_time.is_some() { 2 } else { 1 };
+    if arg_vec.len() < required_args || arg_vec.len() > required_args + 2 {
+        // A span id, maybe a time, tags and description
+        return Err(make_failure(input, Error::Syntax(pos.into())));
+    }
+    let span_id = arg_vec.remove(0).trim().to_string();
+    if found_with_time.is_some() {
+        let _time = arg_vec.remove(0);
+    }
+    let mut tags_and_desc = arg_vec;
+    for s in tags_and_desc.iter_mut() {
+        *s = truncate_and_trim(s).map_err(|_| make_failure(input, Error::Syntax(pos.into())))?;
+    }
+    let tags_pos = tags_and_desc.iter().position(|s| s.contains("tags="));
+    let mut tags = tags_pos
+        .map(|index| tags_and_desc.swap_remove(index))
+        .map(|s| s.replace("tags=", ""));
+    if let Some(t) = &mut tags {
+        if t.is_empty() {
+            return Err(make_failure(input, Error::EmptyTags(pos.into())));
+        }
+        if !t.contains(span_tag) {
+            t.insert_str(0, &format!("{};", span_tag));
+        }
+        *t = remove_double_quotes(t);
+    } else {
+        tags = Some(String::from(span_tag));
+    }
+    let description = tags_and_desc.pop();
+    Ok((
+        input,
+        EventMetadata {
+            name,
+            probe_instance,
+            payload: Some((TypeHint::U32, span_id).into()),
+            description,
+            tags,
+            location: pos.into(),
+        },
+    ))
+}
+
 fn event_with_time(input: Span) -> ParserResult<Span, EventMetadata> {
     let prefix = input.extra.as_ref().unwrap().prefix;
     let tag_string = format!("{}_RECORD_W_TIME", prefix);
@@ -912,6 +995,10 @@ impl fmt::Display for Error {
                 f,
                 "Enountered an invalid non-numeric severity level statement while parsing a record event call-site",
             ),
+            Error::SpanEventName(_) => write!(
+                f,
+                "Span begin and end event names must end in _BEGIN and _END respectively",
+            ),
         }
     }
 }
@@ -1493,6 +1580,40 @@ assert(err == MODALITY_PROBE_ERROR_OK);
         assert_eq!(tokens, Err(Error::EmptyTags((57, 1, 58).into())));
     }
 
+    #[test]
+    fn span_events() {
+        let parser = CParser::default();
+        let input = r#"
+MODALITY_PROBE_SPAN_BEGIN(probe, SENSOR_READ_BEGIN, span_id, "desc");
+MODALITY_PROBE_SPAN_END_W_TIME(probe, SENSOR_READ_END, span_id, now, MODALITY_TAGS("sensor"));
+"#;
+        let tokens = parser.parse_event_md(input);
+        assert_eq!(
+            tokens,
+            Ok(vec![
+                EventMetadata {
+                    name: "SENSOR_READ_BEGIN".to_string(),
+                    probe_instance: "probe".to_string(),
+                    payload: Some((TypeHint::U32, "span_id").into()),
+                    description: Some("desc".to_string()),
+                    tags: Some("SPAN_BEGIN".to_string()),
+                    location: (1, 2, 1).into(),
+                },
+                EventMetadata {
+                    name: "SENSOR_READ_END".to_string(),
+                    probe_instance: "probe".to_string(),
+                    payload: Some((TypeHint::U32, "span_id").into()),
+                    description: None,
+                    tags: Some("SPAN_END;sensor".to_string()),
+                    location: (71, 3, 1).into(),
+                },
+            ])
+        );
+        let input = "MODALITY_PROBE_SPAN_END(probe, SENSOR_READ, span_id);";
+        let tokens = parser.parse_event_md(input);
+        assert_eq!(tokens, Err(Error::SpanEventName((0, 1, 1).into())));
+    }
+
     #[test]
     fn severity_clamps() {
         let input = Span::new_extra("MODALITY_SEVERITY(0)", None);
@@ -4,6 +4,7 @@ use crate::manifest_gen::{
     id_gen::IdGen,
     in_source_event::InSourceEvent,
     in_source_probe::InSourceProbe,
+    log_callsites,
     parser::{self, Parser},
     rust_parser::RustParser,
 };
@@ -42,6 +43,8 @@ pub enum ProbeCheckError {
 pub enum EventCheckError {
     DuplicateNameInSource(InSourceEvent, InSourceEvent),
     NameNotUpperCase(InSourceEvent),
+    UnpairedSpan(InSourceEvent),
+    LogCallsiteIdCollision(InSourceEvent, InSourceEvent),
 }
 
 pub struct Invocations {
@@ -69,6 +72,7 @@ pub struct Config<'cfg> {
     pub rust_parser: RustParser<'cfg>,
     pub file_extensions: Option<Vec<String>>,
     pub exclude_patterns: Option<Vec<String>>,
+    pub log_callsites: bool,
     pub internal_events: Vec<Event>,
 }
 
@@ -80,6 +84,7 @@ impl<'cfg> Default for Config<'cfg> {
             rust_parser: RustParser::default(),
             file_extensions: None,
             exclude_patterns: None,
+            log_callsites: false,
             internal_events: Events::internal_events(),
         }
     }
@@ -192,6 +197,21 @@ impl Invocations {
                         })
                         .collect();
                     events.append(&mut events_in_file);
+
+                    let is_rust = match config.lang {
+                        Some(lang) => lang == Lang::Rust,
+                        None => entry.path().extension() == Some(OsStr::new("rs")),
+                    };
+                    if config.log_callsites && is_rust {
+                        events.extend(
+                            log_callsites::parse_events(&file_path.full_path, &buffer)
+                                .into_iter()
+                                .map(|md| InSourceEvent {
+                                    file: file_path.clone(),
+                                    metadata: md,
+                                }),
+                        );
+                    }
                 }
             }
         }
@@ -245,6 +265,46 @@ impl Invocations {
             }
         }
 
+        // Every span begin needs a matching end, and vice versa
+        let span_edges: HashMap<String, &str> = self
+            .events
+            .iter()
+            .filter_map(|e| span_edge(e).map(|edge| (e.canonical_name(), edge)))
+            .collect();
+        for e in self.events.iter() {
+            let counterpart = match span_edge(e) {
+                Some("SPAN_BEGIN") => e
+                    .canonical_name()
+                    .strip_suffix("_BEGIN")
+                    .map(|span| (format!("{}_END", span), "SPAN_END")),
+                Some(_) => e
+                    .canonical_name()
+                    .strip_suffix("_END")
+                    .map(|span| (format!("{}_BEGIN", span), "SPAN_BEGIN")),
+                None => continue,
+            };
+            let paired = counterpart
+                .map(|(name, edge)| span_edges.get(&name) == Some(&edge))
+                .unwrap_or(false);
+            if !paired {
+                return Err(EventCheckError::UnpairedSpan(e.clone()));
+            }
+        }
+
+        // Log callsite ids are hashed from their location, so two
+        // callsites can end up with the same one
+        let mut log_ids = HashMap::new();
+        for e in self.events.iter() {
+            if let Some(id) = log_callsites::event_id(e) {
+                if let Some(prev_e) = log_ids.insert(id, e) {
+                    return Err(EventCheckError::LogCallsiteIdCollision(
+                        prev_e.clone(),
+                        e.clone(),
+                    ));
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -451,7 +511,13 @@ impl Invocations {
 
         self.events.iter().for_each(|src_event| {
             if mf_events.events.iter().find(|e| src_event.eq(e)).is_none() {
-                let event = src_event.to_event(EventId(next_available_event_id));
+                // Log callsite ids are derived from their location, and
+                // aren't allocated sequentially
+                let id = log_callsites::event_id(src_event).unwrap_or_else(|| {
+                    next_available_event_id += 1;
+                    EventId(next_available_event_id - 1)
+                });
+                let event = src_event.to_event(id);
                 println!(
                     "Adding event {}, ID {} to {}",
                     event.name,
@@ -459,7 +525,6 @@ impl Invocations {
                     mf_path.display(),
                 );
                 mf_events.events.push(event);
-                next_available_event_id += 1;
             }
         });
 
@@ -515,6 +580,15 @@ impl Invocations {
     }
 }
 
+/// Which end of a span an event marks, if any.
+fn span_edge(e: &InSourceEvent) -> Option<&'static str> {
+    let tags = e.metadata.tags.as_deref()?;
+    ["SPAN_BEGIN", "SPAN_END"]
+        .iter()
+        .copied()
+        .find(|edge| tags.split(';').any(|t| t.trim() == *edge))
+}
+
 fn is_hidden(entry: &DirEntry) -> bool {
     entry
         .file_name()
@@ -584,6 +658,37 @@ impl fmt::Display for EventCheckError {
                     e.file.path, e.metadata.location.line, e.metadata.location.column
                 )
             }
+            EventCheckError::UnpairedSpan(e) => {
+                writeln!(
+                    f,
+                    "The span event '{}' has no matching span begin or end event",
+                    e.name(),
+                )?;
+                writeln!(
+                    f,
+                    "{}:{}:{}",
+                    e.file.path, e.metadata.location.line, e.metadata.location.column
+                )
+            }
+            EventCheckError::LogCallsiteIdCollision(first, dup) => {
+                writeln!(
+                    f,
+                    "The log callsites {} and {} hash to the same event id; move one of them to another line",
+                    first.name(),
+                    dup.name(),
+                )?;
+                writeln!(
+                    f,
+                    "{}:{}:{}",
+                    first.file.path, first.metadata.location.line, first.metadata.location.column
+                )?;
+                writeln!(f)?;
+                writeln!(
+                    f,
+                    "{}:{}:{}",
+                    dup.file.path, dup.metadata.location.line, dup.metadata.location.column
+                )
+            }
         }
     }
 }
@@ -917,6 +1022,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn event_log_callsite_id_collision_error() {
+        let log_event = |path: &str, line: usize| InSourceEvent {
+            file: FilePath {
+                full_path: path.to_string(),
+                path: path.to_string(),
+            },
+            metadata: log_callsites::parse_events(
+                path,
+                &format!("{}info!(\"hi\");", "\n".repeat(line - 1)),
+            )
+            .remove(0),
+        };
+
+        // Same file name and line in different directories
+        let invcs = Invocations {
+            probes: Vec::new(),
+            events: vec![log_event("src/a/mod.rs", 10), log_event("src/b/mod.rs", 10)],
+            ..Default::default()
+        };
+        assert_eq!(invcs.check_events(), Ok(()));
+
+        // Find two lines of the same file whose ids collide
+        let mut seen = HashMap::new();
+        let (first, dup) = (1..)
+            .find_map(|line| {
+                let id = modality_probe::log_backend::callsite_event_id("src/lib.rs", line);
+                seen.insert(id, line).map(|prev| (prev, line))
+            })
+            .unwrap();
+        let e0 = log_event("src/lib.rs", first as usize);
+        let e1 = log_event("src/lib.rs", dup as usize);
+        let invcs = Invocations {
+            probes: Vec::new(),
+            events: vec![e0.clone(), e1.clone()],
+            ..Default::default()
+        };
+        assert_eq!(
+            invcs.check_events(),
+            Err(EventCheckError::LogCallsiteIdCollision(e0, e1))
+        );
+    }
+
+    #[test]
+    fn event_unpaired_span_error() {
+        let span_event = |name: &str, tags: &str| InSourceEvent {
+            file: FilePath {
+                full_path: "main.c".to_string(),
+                path: "main.c".to_string(),
+            },
+            metadata: EventMetadata {
+                name: name.to_string(),
+                probe_instance: "probe".to_string(),
+                payload: Some((TypeHint::U32, "span_id").into()),
+                description: None,
+                tags: Some(tags.to_string()),
+                location: (1, 2, 3).into(),
+            },
+        };
+        let begin = span_event("SENSOR_READ_BEGIN", "SPAN_BEGIN");
+        let end = span_event("SENSOR_READ_END", "SPAN_END;sensor");
+        let invcs = Invocations {
+            probes: Vec::new(),
+            events: vec![begin.clone(), end.clone()],
+            ..Default::default()
+        };
+        assert_eq!(invcs.check_events(), Ok(()));
+
+        let invcs = Invocations {
+            probes: Vec::new(),
+            events: vec![begin.clone()],
+            ..Default::default()
+        };
+        assert_eq!(
+            invcs.check_events(),
+            Err(EventCheckError::UnpairedSpan(begin))
+        );
+
+        let invcs = Invocations {
+            probes: Vec::new(),
+            events: vec![end.clone()],
+            ..Default::default()
+        };
+        assert_eq!(
+            invcs.check_events(),
+            Err(EventCheckError::UnpairedSpan(end))
+        );
+    }
+
     #[test]
     fn event_merge_file_location_change() {
         let in_src_event = InSourceEvent {
@@ -1116,4 +1310,64 @@ mod tests {
         });
         assert_eq!(mf_events.events, expected);
     }
+
+    #[test]
+    fn event_merge_log_callsites() {
+        let file = FilePath {
+            full_path: "src/main.rs".to_string(),
+            path: "src/main.rs".to_string(),
+        };
+        let invcs = Invocations {
+            probes: Vec::new(),
+            events: vec![
+                InSourceEvent {
+                    file: file.clone(),
+                    metadata: EventMetadata {
+                        name: "LOG_MAIN_RS_4".to_string(),
+                        probe_instance: "log".to_string(),
+                        payload: Some((TypeHint::U32, "level").into()),
+                        description: Some("Starting up".to_string()),
+                        tags: Some("log;INFO".to_string()),
+                        location: (40, 4, 5).into(),
+                    },
+                },
+                InSourceEvent {
+                    file,
+                    metadata: EventMetadata {
+                        name: "EVENT_A".to_string(),
+                        probe_instance: "probe".to_string(),
+                        payload: None,
+                        description: None,
+                        tags: None,
+                        location: (60, 6, 5).into(),
+                    },
+                },
+            ],
+            ..Default::default()
+        };
+        let mut mf_events = Events {
+            path: PathBuf::new(),
+            events: Vec::new(),
+        };
+        invcs.merge_events_into(None, &mut mf_events);
+        let ids: Vec<(&str, u32)> = mf_events
+            .events
+            .iter()
+            .filter(|e| e.id.0 <= modality_probe::EventId::MAX_USER_ID)
+            .map(|e| (e.name.as_str(), e.id.0))
+            .collect();
+        let log_id = modality_probe::log_backend::callsite_event_id("main.rs", 4).get_raw();
+        assert_eq!(ids, vec![("LOG_MAIN_RS_4", log_id), ("EVENT_A", 1)]);
+
+        // Log callsite ids don't push out the next sequential id
+        assert_eq!(
+            mf_events.next_available_event_id(
+                &Events::internal_events()
+                    .iter()
+                    .map(|e| e.id.0)
+                    .collect::<Vec<_>>()
+            ),
+            2
+        );
+    }
 }
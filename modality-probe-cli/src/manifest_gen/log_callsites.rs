@@ -0,0 +1,406 @@
+//! Finds `log` crate macro invocations (`info!`, `log::warn!`, ...)
+//! in Rust source. These are recorded by
+//! `modality_probe::log_backend::ProbeLogger` with an event id derived
+//! from the callsite's path and line.
+
+use crate::{
+    events::EventId,
+    manifest_gen::{
+        event_metadata::EventMetadata, in_source_event::InSourceEvent,
+        source_location::SourceLocation, type_hint::TypeHint,
+    },
+};
+use modality_probe::log_backend;
+
+/// Tag given to every log callsite event
+pub const LOG_TAG: &str = "log";
+
+const NAME_PREFIX: &str = "LOG_";
+
+const LEVEL_MACROS: &[(&str, Option<&str>)] = &[
+    ("error", Some("ERROR")),
+    ("warn", Some("WARN")),
+    ("info", Some("INFO")),
+    ("debug", Some("DEBUG")),
+    ("trace", Some("TRACE")),
+    ("log", None),
+];
+
+/// The event id for a log callsite event, or `None` if it isn't one.
+pub fn event_id(event: &InSourceEvent) -> Option<EventId> {
+    let is_log_event = event.canonical_name().starts_with(NAME_PREFIX)
+        && event
+            .metadata
+            .tags
+            .iter()
+            .flat_map(|tags| tags.split(';'))
+            .any(|t| t.trim() == LOG_TAG);
+    if is_log_event {
+        let id = log_backend::callsite_event_id(
+            &event.file.full_path,
+            event.metadata.location.line as u32,
+        );
+        Some(EventId(id.get_raw()))
+    } else {
+        None
+    }
+}
+
+/// Event metadata for each log macro invocation in `input`.
+///
+/// Events are named `LOG_<PATH>_<LINE>`, where the path is the
+/// callsite path (see `log_backend::callsite_path`) with its
+/// directories included, carry the log level as a u32 payload, and
+/// use the message's format string as their description.
+pub fn parse_events(file_path: &str, input: &str) -> Vec<EventMetadata> {
+    let callsite_path = log_backend::callsite_path(file_path);
+    let mut scanner = Scanner::new(input);
+    let mut events = Vec::new();
+    while let Some(location) = scanner.next_identifier() {
+        let mut ident = scanner.identifier();
+        if ident == "log" && scanner.eat("::") {
+            if !scanner.at_identifier_start() {
+                continue;
+            }
+            ident = scanner.identifier();
+        } else if scanner.prev_is(':') {
+            // Some other crate's macro, e.g. `tracing::info!`
+            continue;
+        }
+        let level = match LEVEL_MACROS.iter().find(|(m, _)| *m == ident) {
+            Some((_, level)) => *level,
+            None => continue,
+        };
+        if !scanner.eat("!") {
+            continue;
+        }
+        scanner.skip_whitespace();
+        if !scanner.eat("(") {
+            continue;
+        }
+        let description = scanner.format_string(level.is_none());
+        events.push(EventMetadata {
+            name: format!(
+                "{}{}_{}",
+                NAME_PREFIX,
+                sanitize(callsite_path),
+                location.line
+            ),
+            probe_instance: LOG_TAG.to_string(),
+            payload: Some((TypeHint::U32, "level").into()),
+            description,
+            tags: Some(match level {
+                Some(level) => format!("{};{}", LOG_TAG, level),
+                None => LOG_TAG.to_string(),
+            }),
+            location,
+        });
+    }
+    events
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A minimal Rust tokenizer that skips over comments and literals
+struct Scanner<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    line_start: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str) -> Self {
+        Scanner {
+            input,
+            pos: 0,
+            line: 1,
+            line_start: 0,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_is(&self, f: impl Fn(char) -> bool) -> bool {
+        matches!(self.peek(), Some(c) if f(c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.pos;
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            for _ in s.chars() {
+                self.bump();
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    fn prev_is(&self, c: char) -> bool {
+        self.input[..self.pos]
+            .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+            .ends_with(c)
+    }
+
+    fn at_identifier_start(&self) -> bool {
+        self.peek_is(|c| c.is_alphabetic() || c == '_')
+    }
+
+    fn location(&self) -> SourceLocation {
+        SourceLocation {
+            offset: self.pos,
+            line: self.line,
+            column: self.pos - self.line_start + 1,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek_is(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    /// Skip a comment or literal at the current position, if there is one
+    fn skip_non_code(&mut self) -> bool {
+        if self.eat("//") {
+            while self.peek_is(|c| c != '\n') {
+                self.bump();
+            }
+        } else if self.eat("/*") {
+            while !self.rest().is_empty() && !self.eat("*/") {
+                self.bump();
+            }
+        } else if self.peek() == Some('"') {
+            self.string_literal();
+        } else if self.rest().starts_with("r\"") || self.rest().starts_with("r#") {
+            self.bump();
+            self.raw_string_literal();
+        } else if self.peek() == Some('\'') {
+            // A char literal, or a lifetime, which is skipped like an identifier
+            self.bump();
+            self.eat("\\");
+            self.bump();
+            self.eat("'");
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Advance to the next identifier outside of comments and literals,
+    /// returning its location
+    fn next_identifier(&mut self) -> Option<SourceLocation> {
+        loop {
+            if self.skip_non_code() {
+                continue;
+            }
+            let c = self.peek()?;
+            if c.is_alphabetic() || c == '_' {
+                return Some(self.location());
+            }
+            self.bump();
+        }
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.peek_is(|c| c.is_alphanumeric() || c == '_') {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn string_literal(&mut self) -> String {
+        let mut s = String::new();
+        self.bump();
+        while let Some(c) = self.bump() {
+            match c {
+                '"' => break,
+                '\\' => {
+                    if let Some(escaped) = self.bump() {
+                        match escaped {
+                            'n' => s.push('\n'),
+                            't' => s.push('\t'),
+                            '\n' => self.skip_whitespace(),
+                            other => s.push(other),
+                        }
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+        s
+    }
+
+    fn raw_string_literal(&mut self) -> String {
+        let mut hashes = 0;
+        while self.eat("#") {
+            hashes += 1;
+        }
+        self.eat("\"");
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let start = self.pos;
+        while !self.rest().is_empty() && !self.rest().starts_with(&terminator) {
+            self.bump();
+        }
+        let s = self.input[start..self.pos].to_string();
+        self.eat(&terminator);
+        s
+    }
+
+    /// Skip one macro argument, stopping at the comma that ends it
+    fn skip_argument(&mut self) {
+        let mut depth = 0;
+        loop {
+            if self.skip_non_code() {
+                continue;
+            }
+            match self.peek() {
+                None => return,
+                Some(',') if depth == 0 => {
+                    self.bump();
+                    return;
+                }
+                Some(')') if depth == 0 => return,
+                Some('(') | Some('[') | Some('{') => depth += 1,
+                Some(')') | Some(']') | Some('}') => depth -= 1,
+                _ => (),
+            }
+            self.bump();
+        }
+    }
+
+    /// The format string of a log macro invocation, just past its
+    /// opening parenthesis
+    fn format_string(&mut self, has_level_arg: bool) -> Option<String> {
+        self.skip_whitespace();
+        if self.rest().starts_with("target") {
+            let after_target = self.rest()["target".len()..].trim_start();
+            if after_target.starts_with(':') && !after_target.starts_with("::") {
+                self.skip_argument();
+            }
+        }
+        if has_level_arg {
+            self.skip_argument();
+        }
+        self.skip_whitespace();
+        if self.peek() == Some('"') {
+            Some(self.string_literal())
+        } else if self.rest().starts_with("r\"") || self.rest().starts_with("r#") {
+            self.bump();
+            Some(self.raw_string_literal())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest_gen::file_path::FilePath;
+    use pretty_assertions::assert_eq;
+
+    const MY_FILE: &str = r#"
+use log::{info, warn};
+
+fn main() {
+    // info!("not a callsite");
+    info!("Starting up");
+    let s = "warn!(\"also not a callsite\")";
+    log::error!(target: "net", "Failed to connect to {}", addr);
+    warn!(
+        "Retrying {}",
+        n
+    );
+    tracing::info!("someone else's");
+    log!(Level::Debug, r"raw {}", x);
+    debug!(x);
+}
+"#;
+
+    #[test]
+    fn log_callsites() {
+        let events = parse_events("src/main.rs", MY_FILE);
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| {
+                (
+                    e.name.as_str(),
+                    e.description.as_deref(),
+                    e.tags.as_deref(),
+                    e.location.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("LOG_MAIN_RS_6", Some("Starting up"), Some("log;INFO"), 6),
+                (
+                    "LOG_MAIN_RS_8",
+                    Some("Failed to connect to {}"),
+                    Some("log;ERROR"),
+                    8
+                ),
+                ("LOG_MAIN_RS_9", Some("Retrying {}"), Some("log;WARN"), 9),
+                ("LOG_MAIN_RS_14", Some("raw {}"), Some("log"), 14),
+                ("LOG_MAIN_RS_15", None, Some("log;DEBUG"), 15),
+            ]
+        );
+        assert!(events
+            .iter()
+            .all(|e| e.payload == Some((TypeHint::U32, "level").into())));
+    }
+
+    #[test]
+    fn log_callsite_event_ids() {
+        let metadata = parse_events("src/main.rs", MY_FILE).remove(0);
+        let event = InSourceEvent {
+            file: FilePath {
+                full_path: "/my-crate/src/main.rs".to_string(),
+                path: "src/main.rs".to_string(),
+            },
+            metadata,
+        };
+        assert_eq!(
+            event_id(&event),
+            Some(EventId(
+                log_backend::callsite_event_id("main.rs", 6).get_raw()
+            ))
+        );
+
+        let nested = parse_events("/my-crate/src/net/mod.rs", MY_FILE).remove(0);
+        assert_eq!(nested.name, "LOG_NET_MOD_RS_6");
+
+        let mut not_log = event;
+        not_log.metadata.tags = Some("INFO".to_string());
+        assert_eq!(event_id(&not_log), None);
+    }
+}
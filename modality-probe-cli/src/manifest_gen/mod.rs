@@ -21,6 +21,7 @@ pub mod id_gen;
 pub mod in_source_event;
 pub mod in_source_probe;
 pub mod invocations;
+pub mod log_callsites;
 pub mod parser;
 pub mod probe_metadata;
 pub mod rust_parser;
@@ -48,6 +49,11 @@ pub struct ManifestGen {
     /// Event ID offset, starts at 1 if not specified
     #[structopt(long)]
     pub event_id_offset: Option<u32>,
+    /// Also generate events for `log` crate macro invocations
+    /// (`info!`, `log::warn!`, ...) in Rust source, as recorded by
+    /// `modality_probe::log_backend::ProbeLogger`
+    #[structopt(long)]
+    pub log_callsites: bool,
 
     /// Constrain the generated probe ID to an specific range.
     ///
@@ -79,6 +85,7 @@ impl Default for ManifestGen {
             exclude_patterns: None,
             component_name: String::from("component"),
             event_id_offset: None,
+            log_callsites: false,
             probe_id_range: None,
             regen_component_id: false,
             output_path: PathBuf::from("component"),
@@ -122,6 +129,7 @@ pub fn run(opt: ManifestGen, internal_events: Option<Vec<Event>>) {
         lang: opt.lang,
         file_extensions: opt.file_extensions,
         exclude_patterns: opt.exclude_patterns,
+        log_callsites: opt.log_callsites,
         internal_events: internal_events.unwrap_or_else(Events::internal_events),
         ..Default::default()
     };
@@ -44,6 +44,7 @@ pub enum Error {
     EmptyTags(SourceLocation),
     EmptySeverity(SourceLocation),
     SeverityNotNumeric(SourceLocation),
+    SpanEventName(SourceLocation),
 }
 
 impl Error {
@@ -55,6 +56,7 @@ impl Error {
             Error::EmptyTags(l) => l,
             Error::EmptySeverity(l) => l,
             Error::SeverityNotNumeric(l) => l,
+            Error::SpanEventName(l) => l,
         }
     }
 }
@@ -123,6 +125,10 @@ fn parse_input<T>(
 fn parse_record_event_call_exp(input: Span) -> ParserResult<Span, EventMetadata> {
     let (input, _) = comments_and_spacing(input)?;
     let (input, _) = imports(input)?;
+    let (input, found_span) = peek(opt(alt((tag("try_span_"), tag("span_")))))(input)?;
+    if found_span.is_some() {
+        return span_call_exp(input);
+    }
     let (input, found_try) = peek(opt(alt((
         tag("try_record"),
         tag("try_record_w_"),
@@ -182,6 +188,100 @@ fn parse_record_event_call_exp(input: Span) -> ParserResult<Span, EventMetadata>
     }
 }
 
+/// Parses all of the span macros: `span_begin!`, `span_end!` and
+/// their `try_` and `_w_time` variants.
+fn span_call_exp(input: Span) -> ParserResult<Span, EventMetadata> {
+    let (input, pos) = position(input)?;
+    let (input, found_try) = opt(tag("try_"))(input)?;
+    let (input, _) = tag("span_")(input)?;
+    let (input, edge) = alt((tag("begin"), tag("end")))(input)?;
+    let (input, found_with_time) = opt(tag("_w_time"))(input)?;
+    let (input, _) = tag("!")(input)?;
+    let (input, _) = opt(line_ending)(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, _) = tag("(")(input)?;
+    let terminator = if found_try.is_some() { ";" } else { ");" };
+    let (input, args) = take_until(terminator)(input)
+        .map_err(|e| convert_error(e, Error::MissingSemicolon(pos.into())))?;
+    let (input, _) = tag(terminator)(input)
+        .map_err(|e| convert_error(e, Error::MissingSemicolon(pos.into())))?;
+    let (args, probe_instance) =
+        variable_call_exp_arg(args).map_err(|e| convert_error(e, Error::Syntax(pos.into())))?;
+    let (args, full_name) =
+        variable_call_exp_arg(args).map_err(|e| convert_error(e, Error::Syntax(pos.into())))?;
+    let arg = Span::new_extra(&full_name, input.extra);
+    let (_, name) = alt((
+        reduced_event_id_exp_alt_a,
+        reduced_event_id_exp_alt_b,
+        reduced_event_id_exp_alt_c,
+    ))(arg)
+    .map_err(|_| make_failure(input, Error::Syntax(pos.into())))?;
+    let name =
+        reduce_namespace(&name).map_err(|_| make_failure(input, Error::Syntax(pos.into())))?;
+    if !event_name_valid(&name) {
+        return Err(make_failure(input, Error::Syntax(pos.into())));
+    }
+    let (span_tag, suffix) = if *edge.fragment() == "begin" {
+        ("SPAN_BEGIN", "_BEGIN")
+    } else {
+        ("SPAN_END", "_END")
+    };
+    if !name.ends_with(suffix) {
+        return Err(make_failure(input, Error::SpanEventName(pos.into())));
+    }
+    let mut arg_vec: Vec<String> = Vec::new();
+    let mut iter = iterator(args, multi_variable_call_exp_arg_literal);
+    iter.for_each(|s| {
+        if !s.is_empty() {
+            arg_vec.push(s)
+        }
+    });
+    let (_args, _) = iter.finish()?;
+    let required_args = if found_with_time.is_some() { 2 } else { 1 };
+    if arg_vec.len() < required_args || arg_vec.len() > required_args + 2 {
+        // A span id, maybe a time, tags and description
+        return Err(make_failure(input, Error::Syntax(pos.into())));
+    }
+    let span_id = arg_vec.remove(0).trim().to_string();
+    if span_id.is_empty() {
+        return Err(make_failure(input, Error::Syntax(pos.into())));
+    }
+    if found_with_time.is_some() {
+        let _time = arg_vec.remove(0);
+    }
+    let mut tags_and_desc = arg_vec;
+    for s in tags_and_desc.iter_mut() {
+        *s = truncate_and_trim(s).map_err(|_| make_failure(input, Error::Syntax(pos.into())))?;
+    }
+    let tags_pos = tags_and_desc.iter().position(|s| s.contains("tags="));
+    let mut tags = tags_pos
+        .map(|index| tags_and_desc.swap_remove(index))
+        .map(|s| s.replace("tags=", ""));
+    if let Some(t) = &mut tags {
+        if t.is_empty() {
+            return Err(make_failure(input, Error::EmptyTags(pos.into())));
+        }
+        if !t.contains(span_tag) {
+            t.insert_str(0, &format!("{};", span_tag));
+        }
+        *t = remove_double_quotes(t);
+    } else {
+        tags = Some(String::from(span_tag));
+    }
+    let description = tags_and_desc.pop();
+    Ok((
+        input,
+        EventMetadata {
+            name,
+            probe_instance,
+            payload: Some((TypeHint::U32, span_id).into()),
+            description,
+            tags,
+            location: pos.into(),
+        },
+    ))
+}
+
 fn expect_try_call_exp(input: Span) -> ParserResult<Span, EventMetadata> {
     let (input, pos) = position(input)?;
     let (input, _) = tag("try_expect!")(input)?;
@@ -1466,6 +1566,10 @@ impl fmt::Display for Error {
                 f,
                 "Enountered an invalid non-numeric severity level statement while parsing a record event call-site",
             ),
+            Error::SpanEventName(_) => write!(
+                f,
+                "Span begin and end event names must end in _BEGIN and _END respectively",
+            ),
         }
     }
 }
@@ -2198,6 +2302,69 @@ record_w_i8!(probe, EventId::try_from(events::more::EVENT_D).unwrap(), 1_i8, "de
         assert_eq!(tokens, Err(Error::EmptyTags((9, 2, 9).into())));
     }
 
+    #[test]
+    fn span_events() {
+        let parser = RustParser::default();
+        let input = r#"
+span_begin!(probe, EventId::try_from(SENSOR_READ_BEGIN).unwrap(), span_id, "desc");
+
+try_span_end!(probe, events::SENSOR_READ_END, span_id, tags!("sensor")).unwrap();
+
+span_begin_w_time!(probe, FILTER_BEGIN, 2, now, tags!("a", "b"), "desc");
+
+try_span_end_w_time!(probe, FILTER_END, 2, now)?;
+"#;
+        let tokens = parser.parse_event_md(input);
+        assert_eq!(
+            tokens,
+            Ok(vec![
+                EventMetadata {
+                    name: "SENSOR_READ_BEGIN".to_string(),
+                    probe_instance: "probe".to_string(),
+                    payload: Some((TypeHint::U32, "span_id").into()),
+                    description: Some("desc".to_string()),
+                    tags: Some("SPAN_BEGIN".to_string()),
+                    location: (1, 2, 1).into(),
+                },
+                EventMetadata {
+                    name: "SENSOR_READ_END".to_string(),
+                    probe_instance: "probe".to_string(),
+                    payload: Some((TypeHint::U32, "span_id").into()),
+                    description: None,
+                    tags: Some("SPAN_END;sensor".to_string()),
+                    location: (86, 4, 1).into(),
+                },
+                EventMetadata {
+                    name: "FILTER_BEGIN".to_string(),
+                    probe_instance: "probe".to_string(),
+                    payload: Some((TypeHint::U32, "2").into()),
+                    description: Some("desc".to_string()),
+                    tags: Some("SPAN_BEGIN;a;b".to_string()),
+                    location: (169, 6, 1).into(),
+                },
+                EventMetadata {
+                    name: "FILTER_END".to_string(),
+                    probe_instance: "probe".to_string(),
+                    payload: Some((TypeHint::U32, "2").into()),
+                    description: None,
+                    tags: Some("SPAN_END".to_string()),
+                    location: (244, 8, 1).into(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn span_event_name_errors() {
+        let parser = RustParser::default();
+        let input = "span_begin!(probe, SENSOR_READ, span_id);";
+        let tokens = parser.parse_event_md(input);
+        assert_eq!(tokens, Err(Error::SpanEventName((0, 1, 1).into())));
+        let input = "try_span_end!(probe, SENSOR_READ_BEGIN, span_id).unwrap();";
+        let tokens = parser.parse_event_md(input);
+        assert_eq!(tokens, Err(Error::SpanEventName((0, 1, 1).into())));
+    }
+
     #[test]
     fn severity_clamps() {
         let input = Span::new_extra("severity!(0)", None);
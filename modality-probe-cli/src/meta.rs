@@ -241,6 +241,15 @@ pub fn get_event_meta<'a>(
     )?)
 }
 
+/// A name for the interaction id carried by a snapshot `probe` merged:
+/// the name of the event in the probe's component with that id, if
+/// there is one, otherwise the id in hex.
+pub fn interaction_name(cfg: &dyn MetaMeter, probe: &ProbeId, interaction_id: u32) -> String {
+    EventId::new(interaction_id)
+        .and_then(|id| cfg.event_name(probe, &id))
+        .unwrap_or_else(|| format!("0x{:x}", interaction_id))
+}
+
 pub fn parsed_payload(
     th: Option<&str>,
     pl: Option<u32>,
@@ -1,4 +1,7 @@
-use crate::{header_gen::HeaderGen, log::Log, manifest_gen::ManifestGen, visualize::Visualize};
+use crate::{
+    header_gen::HeaderGen, latency::Latency, log::Log, manifest_gen::ManifestGen,
+    report_html::ReportHtml, spans::Spans, stats::Stats, tui::Tui, visualize::Visualize,
+};
 use structopt::StructOpt;
 
 #[derive(Debug, PartialEq, StructOpt)]
@@ -15,8 +18,22 @@ pub enum Opts {
     /// Inspect a trace in the terminal as a log or an ASCII-based
     /// graph.
     Log(Log),
-    /// Visualize a collected trace as a Graphviz dot file.
+    /// Visualize a collected trace as a Graphviz dot file or a Chrome
+    /// trace.
     Visualize(Visualize),
+    /// Measure the wall-clock latency between causally related
+    /// events.
+    Latency(Latency),
+    /// Browse a collected trace interactively in the terminal.
+    Tui(Tui),
+    /// Generate a self-contained HTML report from a collected trace.
+    ReportHtml(ReportHtml),
+    /// Reconstruct the spans in a collected trace and report their
+    /// durations.
+    Spans(Spans),
+    /// Report the self-statistics probes included in a collected
+    /// trace, e.g. to tune their buffer sizes.
+    Stats(Stats),
 }
 
 #[cfg(test)]
@@ -25,7 +42,11 @@ mod test {
 
     use pretty_assertions::assert_eq;
 
-    use crate::{lang::Lang, manifest_gen::id_gen::NonZeroIdRange, visualize::GraphType};
+    use crate::{
+        lang::Lang,
+        manifest_gen::id_gen::NonZeroIdRange,
+        visualize::{GraphType, OutputFormat},
+    };
 
     use super::*;
 
@@ -38,6 +59,7 @@ mod test {
                 file_extensions: None,
                 exclude_patterns: None,
                 event_id_offset: None,
+                log_callsites: false,
                 probe_id_range: None,
                 regen_component_id: false,
                 component_name: "component".to_string(),
@@ -56,6 +78,7 @@ mod test {
                     "10",
                     "--file-extension=c",
                     "--regen-component-id",
+                    "--log-callsites",
                     "--exclude=stuff.txt",
                     "--file-extension=cpp",
                     "--probe-id-range=1..=12",
@@ -71,6 +94,7 @@ mod test {
             Opts::ManifestGen(ManifestGen {
                 lang: Some(Lang::C),
                 event_id_offset: Some(10),
+                log_callsites: true,
                 file_extensions: Some(vec!["c".to_string(), "cpp".to_string()]),
                 exclude_patterns: Some(vec!["stuff.txt".to_string(), "file.dep".to_string()]),
                 probe_id_range: Some(
@@ -151,6 +175,7 @@ mod test {
                 component_path: vec![PathBuf::from("component")],
                 report: PathBuf::from("report.csv"),
                 graph_type: GraphType::Acyclic,
+                output_format: OutputFormat::Dot,
             })
         );
         assert_eq!(
@@ -173,6 +198,31 @@ mod test {
                 component_path: vec![PathBuf::from("component")],
                 report: PathBuf::from("report.csv"),
                 graph_type: GraphType::Cyclic,
+                output_format: OutputFormat::Dot,
+            })
+        );
+        assert_eq!(
+            Opts::from_iter(
+                [
+                    "modality-probe",
+                    "visualize",
+                    "acyclic",
+                    "--component-path",
+                    "component",
+                    "--report",
+                    "report.csv",
+                    "--output-format",
+                    "chrome-trace",
+                ]
+                .iter()
+            ),
+            Opts::Visualize(Visualize {
+                interactions_only: false,
+                include_internal_events: false,
+                component_path: vec![PathBuf::from("component")],
+                report: PathBuf::from("report.csv"),
+                graph_type: GraphType::Acyclic,
+                output_format: OutputFormat::ChromeTrace,
             })
         );
     }
@@ -209,4 +259,130 @@ mod test {
             })
         );
     }
+
+    #[test]
+    fn parse_opts_latency() {
+        assert_eq!(
+            Opts::from_iter(
+                [
+                    "modality-probe",
+                    "latency",
+                    "--component-path",
+                    "component",
+                    "--report",
+                    "r.jsonl",
+                    "--from",
+                    "SENSOR_SAMPLED",
+                    "--to",
+                    "ACTUATOR_SET",
+                ]
+                .iter()
+            ),
+            Opts::Latency(Latency {
+                component_path: vec![PathBuf::from("component")],
+                report: PathBuf::from("r.jsonl"),
+                from: "SENSOR_SAMPLED".to_string(),
+                to: "ACTUATOR_SET".to_string(),
+                buckets: 10,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_opts_tui() {
+        assert_eq!(
+            Opts::from_iter(
+                [
+                    "modality-probe",
+                    "tui",
+                    "--component-path",
+                    "component",
+                    "--report",
+                    "r.jsonl",
+                    "--radius",
+                    "3",
+                ]
+                .iter()
+            ),
+            Opts::Tui(Tui {
+                component_path: vec![PathBuf::from("component")],
+                report: PathBuf::from("r.jsonl"),
+                include_internal_events: false,
+                radius: 3,
+                no_color: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_opts_report_html() {
+        assert_eq!(
+            Opts::from_iter(
+                [
+                    "modality-probe",
+                    "report-html",
+                    "--component-path",
+                    "component",
+                    "--report",
+                    "r.jsonl",
+                    "--output",
+                    "report.html",
+                ]
+                .iter()
+            ),
+            Opts::ReportHtml(ReportHtml {
+                component_path: vec![PathBuf::from("component")],
+                report: PathBuf::from("r.jsonl"),
+                output: Some(PathBuf::from("report.html")),
+                title: None,
+                include_internal_events: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_opts_spans() {
+        assert_eq!(
+            Opts::from_iter(
+                [
+                    "modality-probe",
+                    "spans",
+                    "--component-path",
+                    "component",
+                    "--report",
+                    "r.jsonl",
+                    "--span",
+                    "SENSOR_READ",
+                ]
+                .iter()
+            ),
+            Opts::Spans(Spans {
+                component_path: vec![PathBuf::from("component")],
+                report: PathBuf::from("r.jsonl"),
+                span: Some("SENSOR_READ".to_string()),
+                summary_only: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_opts_stats() {
+        assert_eq!(
+            Opts::from_iter(
+                [
+                    "modality-probe",
+                    "stats",
+                    "--component-path",
+                    "component",
+                    "--report",
+                    "r.jsonl",
+                ]
+                .iter()
+            ),
+            Opts::Stats(Stats {
+                component_path: vec![PathBuf::from("component")],
+                report: PathBuf::from("r.jsonl"),
+            })
+        );
+    }
 }
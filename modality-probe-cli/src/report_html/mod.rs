@@ -0,0 +1,391 @@
+//! Generate a self-contained HTML report from a collected trace.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::Write,
+    path::PathBuf,
+};
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use modality_probe::ProbeId;
+use modality_probe_collector_common::json;
+use modality_probe_graph::GraphEvent;
+
+use crate::{
+    hopefully,
+    meta::{self, Cfg, MetaMeter},
+    visualize::graph::{self, causal_depths},
+};
+
+const TEMPLATE: &str = include_str!("template.html");
+const TITLE_PLACEHOLDER: &str = "{{title}}";
+const DATA_PLACEHOLDER: &str = "{{trace_data}}";
+
+/// Generate a single, self-contained HTML file summarizing a
+/// collected trace.
+#[derive(Debug, PartialEq, StructOpt)]
+pub struct ReportHtml {
+    /// The path to a component directory. To include multiple
+    /// components, provide this switch multiple times.
+    #[structopt(short, long, required = true)]
+    pub component_path: Vec<PathBuf>,
+    /// The path to the collected trace.
+    #[structopt(short, long, required = true)]
+    pub report: PathBuf,
+    /// The file to write the HTML to. If no file is given, the HTML is
+    /// written to stdout.
+    #[structopt(short, long)]
+    pub output: Option<PathBuf>,
+    /// The title to give the report. Defaults to the name of the
+    /// trace file.
+    #[structopt(long)]
+    pub title: Option<String>,
+    /// Include probe-generated events in the output.
+    #[structopt(long)]
+    pub include_internal_events: bool,
+}
+
+/// Everything the page's script needs to render the report.
+#[derive(Debug, Serialize)]
+pub struct TraceReport {
+    pub summary: Summary,
+    pub probes: Vec<ProbeRow>,
+    /// Events in causal order.
+    pub events: Vec<EventRow>,
+    /// Interactions between probes, as indices into `events`.
+    pub interactions: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub components: usize,
+    pub probes: usize,
+    pub events: usize,
+    pub interactions: usize,
+    pub expectations_passed: usize,
+    pub expectations_failed: usize,
+    pub failures: usize,
+    /// The wall-clock time between the earliest and latest timestamped
+    /// events, if any events were timestamped.
+    pub duration_ns: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProbeRow {
+    pub id: u32,
+    pub name: String,
+    pub component: String,
+    /// The probe's column in the interaction graph.
+    pub lane: usize,
+    pub events: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Event,
+    ExpectationPassed,
+    ExpectationFailed,
+    Failure,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventRow {
+    pub probe_id: u32,
+    pub probe: String,
+    pub lane: usize,
+    /// The length of the longest causal path leading to this event.
+    pub depth: u64,
+    pub id: u32,
+    pub name: String,
+    pub description: Option<String>,
+    pub coordinate: String,
+    pub payload: Option<String>,
+    pub tags: Vec<String>,
+    pub severity: Option<u32>,
+    pub kind: EventKind,
+    pub wall_time: Option<u64>,
+}
+
+pub fn run(mut r: ReportHtml) -> Result<(), Box<dyn std::error::Error>> {
+    let cfg = meta::assemble_components(&mut r.component_path)?;
+    let mut log_file = hopefully!(
+        File::open(&r.report),
+        format!("Failed to open the report file at {}", r.report.display())
+    )?;
+    let graph = graph::log_to_graph(
+        json::read_log_entries(&mut log_file)?
+            .into_iter()
+            .peekable(),
+        r.include_internal_events,
+    )?;
+
+    let complete = graph.graph.as_complete();
+    let report = build_report(&cfg, complete.nodes(), complete.edges());
+    let report_path = r.report;
+    let title = r.title.unwrap_or_else(|| {
+        report_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "trace".to_string())
+    });
+    let html = render(&title, &report)?;
+
+    match r.output {
+        Some(path) => {
+            let mut f = hopefully!(
+                File::create(&path),
+                format!("Failed to create {}", path.display())
+            )?;
+            hopefully!(
+                f.write_all(html.as_bytes()),
+                format!("Failed to write the report to {}", path.display())
+            )?;
+        }
+        None => print!("{}", html),
+    }
+    Ok(())
+}
+
+pub fn build_report(
+    cfg: &Cfg,
+    nodes: &HashSet<&GraphEvent>,
+    edges: &HashSet<(&GraphEvent, &GraphEvent)>,
+) -> TraceReport {
+    let mut sorted_nodes = nodes.iter().copied().collect::<Vec<_>>();
+    sorted_nodes.sort_by_key(|n| (n.probe_id, n.seq, n.seq_idx));
+    let depths = causal_depths(&sorted_nodes, edges);
+    sorted_nodes.sort_by_key(|n| (depths[n], n.probe_id, n.seq, n.seq_idx));
+
+    let mut probes = BTreeMap::new();
+    for node in sorted_nodes.iter() {
+        probes.entry(node.probe_id).or_insert(0usize);
+    }
+    let lanes = probes
+        .keys()
+        .enumerate()
+        .map(|(lane, id)| (*id, lane))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut summary = Summary::default();
+    let mut events = Vec::with_capacity(sorted_nodes.len());
+    for node in sorted_nodes.iter() {
+        *probes.get_mut(&node.probe_id).unwrap() += 1;
+        let row = event_row(cfg, node, lanes[&node.probe_id], depths[node]);
+        match row.kind {
+            EventKind::ExpectationPassed => summary.expectations_passed += 1,
+            EventKind::ExpectationFailed => summary.expectations_failed += 1,
+            EventKind::Failure => summary.failures += 1,
+            EventKind::Event => (),
+        }
+        events.push(row);
+    }
+
+    let indices = sorted_nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, n)| (*n, idx))
+        .collect::<HashMap<_, _>>();
+    let mut interactions = edges
+        .iter()
+        .filter(|(s, t)| s.probe_id != t.probe_id)
+        .filter_map(|(s, t)| Some((*indices.get(s)?, *indices.get(t)?)))
+        .collect::<Vec<_>>();
+    interactions.sort_unstable();
+
+    let times = sorted_nodes
+        .iter()
+        .filter_map(|n| n.wall_time.map(|t| t.get()))
+        .collect::<Vec<_>>();
+    summary.duration_ns = match (times.iter().min(), times.iter().max()) {
+        (Some(min), Some(max)) => Some(max - min),
+        _ => None,
+    };
+    summary.events = events.len();
+    summary.interactions = interactions.len();
+    summary.probes = probes.len();
+    summary.components = probes
+        .keys()
+        .map(|id| cfg.probe_component_name(id))
+        .collect::<HashSet<_>>()
+        .len();
+
+    let probes = probes
+        .into_iter()
+        .map(|(id, count)| ProbeRow {
+            id: id.get_raw(),
+            name: probe_name(cfg, &id),
+            component: cfg
+                .probe_component_name(&id)
+                .unwrap_or_else(|| "UNKNOWN_COMPONENT".to_string()),
+            lane: lanes[&id],
+            events: count,
+        })
+        .collect();
+
+    TraceReport {
+        summary,
+        probes,
+        events,
+        interactions,
+    }
+}
+
+fn probe_name(cfg: &Cfg, id: &ProbeId) -> String {
+    cfg.probe_name(id)
+        .unwrap_or_else(|| id.get_raw().to_string())
+}
+
+fn event_row(cfg: &Cfg, node: &GraphEvent, lane: usize, depth: u64) -> EventRow {
+    let tags = cfg
+        .event_tags(&node.probe_id, &node.id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>();
+    let severity = tags
+        .iter()
+        .filter_map(|t| t.strip_prefix("SEVERITY_"))
+        .find_map(|s| s.parse::<u32>().ok());
+    // Expectations carry the outcome of their expression as the
+    // payload.
+    let kind = if tags.iter().any(|t| t == "FAILURE") {
+        EventKind::Failure
+    } else if tags.iter().any(|t| t == "EXPECTATION") {
+        if node.payload.unwrap_or(0) != 0 {
+            EventKind::ExpectationPassed
+        } else {
+            EventKind::ExpectationFailed
+        }
+    } else {
+        EventKind::Event
+    };
+    let type_hint = cfg.event_type_hint(&node.probe_id, &node.id);
+    let payload = meta::parsed_payload(type_hint.as_deref(), node.payload)
+        .ok()
+        .flatten();
+
+    EventRow {
+        probe_id: node.probe_id.get_raw(),
+        probe: probe_name(cfg, &node.probe_id),
+        lane,
+        depth,
+        id: node.id.get_raw(),
+        name: cfg
+            .event_name(&node.probe_id, &node.id)
+            .unwrap_or_else(|| node.id.get_raw().to_string()),
+        description: cfg
+            .event_description(&node.probe_id, &node.id)
+            .filter(|d| !d.is_empty()),
+        coordinate: format!(
+            "{}:{}:{}",
+            node.probe_id.get_raw(),
+            node.seq.0,
+            node.seq_idx
+        ),
+        payload,
+        tags,
+        severity,
+        kind,
+        wall_time: node.wall_time.map(|t| t.get()),
+    }
+}
+
+/// Fill in the page template. The trace data is embedded as JSON in
+/// a script tag, so any `</` in it is escaped to keep it from closing
+/// the tag early.
+pub fn render(title: &str, report: &TraceReport) -> Result<String, Box<dyn std::error::Error>> {
+    let data = serde_json::to_string(report)?.replace("</", "<\\/");
+    Ok(TEMPLATE
+        .replace(TITLE_PLACEHOLDER, &escape_html(title))
+        .replace(DATA_PLACEHOLDER, &data))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
+
+    use crate::meta::EventMeta;
+    use crate::visualize::graph::{log_to_graph, test::cfg};
+
+    use super::*;
+
+    #[test]
+    fn diamond_report() {
+        let mut cfg = cfg();
+        // Turn event "four" into a failed expectation.
+        let a_uuid = Uuid::parse_str("146dd760-fc41-4418-bc59-e1320fb7f43d").unwrap();
+        cfg.events.insert(
+            (a_uuid, 4),
+            EventMeta {
+                component_id: a_uuid,
+                id: 4,
+                name: "four".to_string(),
+                type_hint: Some("u32".to_string()),
+                tags: "EXPECTATION;SEVERITY_10".to_string(),
+                description: "four".to_string(),
+                file: "four.c".to_string(),
+                line: "4".to_string(),
+            },
+        );
+        let diamond_log = modality_probe_graph::test_support::diamond()
+            .into_iter()
+            .peekable();
+        let graph = log_to_graph(diamond_log, false).unwrap();
+        let complete = graph.graph.as_complete();
+        let report = build_report(&cfg, complete.nodes(), complete.edges());
+
+        assert_eq!(report.summary.probes, 4);
+        assert_eq!(report.summary.components, 1);
+        assert_eq!(report.summary.events, report.events.len());
+        assert!(report.summary.interactions > 0);
+        assert_eq!(report.summary.expectations_failed, 1);
+        let four = report.events.iter().find(|e| e.id == 4).unwrap();
+        assert_eq!(four.kind, EventKind::ExpectationFailed);
+        assert_eq!(four.severity, Some(10));
+
+        // Events are in causal order.
+        for (s, t) in report.interactions.iter() {
+            assert!(report.events[*s].depth < report.events[*t].depth);
+        }
+    }
+
+    #[test]
+    fn render_escapes_its_inputs() {
+        let report = TraceReport {
+            summary: Summary::default(),
+            probes: vec![],
+            events: vec![EventRow {
+                probe_id: 1,
+                probe: "one".to_string(),
+                lane: 0,
+                depth: 0,
+                id: 1,
+                name: "</script><b>".to_string(),
+                description: None,
+                coordinate: "1:0:0".to_string(),
+                payload: None,
+                tags: vec![],
+                severity: None,
+                kind: EventKind::Event,
+                wall_time: None,
+            }],
+            interactions: vec![],
+        };
+        let html = render("<b>bug</b>", &report).unwrap();
+        assert!(html.contains("<title>&lt;b&gt;bug&lt;/b&gt;</title>"));
+        assert!(!html.contains("</script><b>"));
+        assert!(html.contains("<\\/script><b>"));
+    }
+}
@@ -0,0 +1,494 @@
+//! Reconstruct the spans recorded by each probe and report their
+//! durations.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    path::PathBuf,
+};
+
+use structopt::StructOpt;
+
+use modality_probe::ProbeId;
+use modality_probe_collector_common::{json, ReportIter, ReportLogEntry};
+use modality_probe_graph::{EventDigraph, Graph, GraphEvent};
+
+use crate::{
+    give_up, hopefully,
+    latency::fmt_duration,
+    meta::{self, MetaMeter},
+};
+
+/// Reconstruct the spans recorded with the span begin and end macros
+/// and report how long each one took.
+#[derive(Debug, PartialEq, StructOpt)]
+pub struct Spans {
+    /// The path to a component directory. To include multiple
+    /// components, provide this switch multiple times.
+    #[structopt(short, long, required = true)]
+    pub component_path: Vec<PathBuf>,
+    /// The path to the collected trace.
+    #[structopt(short, long, required = true)]
+    pub report: PathBuf,
+    /// Only report the spans with this name, given without its
+    /// `_BEGIN` or `_END` suffix.
+    #[structopt(long)]
+    pub span: Option<String>,
+    /// Only print the per-span summary, not every individual span.
+    #[structopt(long)]
+    pub summary_only: bool,
+}
+
+pub fn run(mut s: Spans) -> Result<(), Box<dyn std::error::Error>> {
+    let cfg = meta::assemble_components(&mut s.component_path)?;
+    let mut log_file = hopefully!(
+        File::open(&s.report),
+        format!("Failed to open the report file at {}", s.report.display())
+    )?;
+    let events = log_to_events(json::read_log_entries(&mut log_file)?, false)?;
+
+    let mut probes = reconstruct(&cfg, &events);
+    if let Some(ref name) = s.span {
+        for p in probes.iter_mut() {
+            p.spans.retain(|span| &span.name == name);
+            p.unmatched_ends
+                .retain(|(span_name, _, _)| span_name == name);
+        }
+        probes.retain(|p| !p.spans.is_empty() || !p.unmatched_ends.is_empty());
+    }
+    if probes.is_empty() {
+        match s.span {
+            Some(name) => give_up!(format!("No {} spans were found in the trace", name)),
+            None => give_up!("No spans were found in the trace"),
+        }
+    }
+
+    print!("{}", render(&cfg, &probes, s.summary_only));
+    Ok(())
+}
+
+/// Collect every event in the trace, including the probes' internal
+/// events if `include_internals` is set.
+pub fn log_to_events(
+    entries: Vec<ReportLogEntry>,
+    include_internals: bool,
+) -> Result<Vec<GraphEvent>, Box<dyn std::error::Error>> {
+    let mut graph = EventDigraph::new(EventSet::default());
+    for report in ReportIter::new(entries.into_iter().peekable()) {
+        hopefully!(
+            graph.add_report(&report, include_internals),
+            "Encountered an error reconstructing the graph"
+        )?;
+    }
+    Ok(graph.graph.0.into_iter().collect())
+}
+
+/// An `EventDigraph` inner graph which only keeps the events.
+#[derive(Debug, Default)]
+struct EventSet(HashSet<GraphEvent>);
+
+impl Graph for EventSet {
+    fn add_node(&mut self, node: GraphEvent) {
+        self.0.insert(node);
+    }
+
+    fn add_edge(&mut self, _source: GraphEvent, _target: GraphEvent) {}
+}
+
+/// Which end of a span an event marks.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Edge {
+    Begin,
+    End,
+}
+
+/// The span name and edge of a span event, going by the tags and
+/// name manifest-gen gives it.
+fn span_edge(cfg: &dyn MetaMeter, ev: &GraphEvent) -> Option<(String, Edge)> {
+    let tags = cfg.event_tags(&ev.probe_id, &ev.id)?;
+    let name = cfg.event_name(&ev.probe_id, &ev.id)?;
+    if tags.iter().any(|t| t == "SPAN_BEGIN") {
+        name.strip_suffix("_BEGIN")
+            .map(|n| (n.to_string(), Edge::Begin))
+    } else if tags.iter().any(|t| t == "SPAN_END") {
+        name.strip_suffix("_END")
+            .map(|n| (n.to_string(), Edge::End))
+    } else {
+        None
+    }
+}
+
+/// How long a span took.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Duration {
+    /// Nanoseconds of wall-clock time between the begin and end
+    /// events.
+    Elapsed(u64),
+    /// The number of events the probe recorded after the begin
+    /// event, up to and including the end event. Used when either
+    /// event was recorded without a time.
+    Events(u64),
+}
+
+/// A span reconstructed from a probe's log.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Span {
+    pub name: String,
+    pub id: u32,
+    /// How many of the probe's spans were open when this one began.
+    pub depth: usize,
+    pub begin: GraphEvent,
+    /// The end event, or `None` if the span was never ended.
+    pub end: Option<GraphEvent>,
+    pub duration: Option<Duration>,
+}
+
+/// The spans recorded by a single probe.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProbeSpans {
+    pub probe_id: ProbeId,
+    /// Every span, in the order they began.
+    pub spans: Vec<Span>,
+    /// End events that don't match any open span, along with their
+    /// span name and id.
+    pub unmatched_ends: Vec<(String, u32, GraphEvent)>,
+}
+
+/// Pair up each probe's span begin and end events. An end closes the
+/// innermost open span with the same name and id; any spans opened
+/// inside it that are still open at that point are considered never
+/// ended.
+pub fn reconstruct(cfg: &dyn MetaMeter, events: &[GraphEvent]) -> Vec<ProbeSpans> {
+    let mut by_probe: BTreeMap<ProbeId, Vec<&GraphEvent>> = BTreeMap::new();
+    for ev in events {
+        by_probe.entry(ev.probe_id).or_default().push(ev);
+    }
+
+    let mut out = Vec::new();
+    for (probe_id, mut events) in by_probe {
+        events.sort_by_key(|ev| (ev.seq, ev.seq_idx));
+
+        let mut spans: Vec<Span> = Vec::new();
+        let mut unmatched_ends = Vec::new();
+        // Indices into `spans` of the open spans, along with the
+        // index of their begin event in `events`.
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        for (idx, ev) in events.iter().enumerate() {
+            let (name, edge) = match span_edge(cfg, ev) {
+                Some(e) => e,
+                None => continue,
+            };
+            let id = ev.payload.unwrap_or_default();
+            match edge {
+                Edge::Begin => {
+                    open.push((spans.len(), idx));
+                    spans.push(Span {
+                        name,
+                        id,
+                        depth: open.len() - 1,
+                        begin: **ev,
+                        end: None,
+                        duration: None,
+                    });
+                }
+                Edge::End => {
+                    let matching = open
+                        .iter()
+                        .rposition(|(s, _)| spans[*s].name == name && spans[*s].id == id);
+                    match matching {
+                        Some(pos) => {
+                            let (span_idx, begin_idx) = open[pos];
+                            open.truncate(pos);
+                            let span = &mut spans[span_idx];
+                            span.end = Some(**ev);
+                            span.duration = Some(duration(&span.begin, ev, idx - begin_idx));
+                        }
+                        None => unmatched_ends.push((name, id, **ev)),
+                    }
+                }
+            }
+        }
+
+        if !spans.is_empty() || !unmatched_ends.is_empty() {
+            out.push(ProbeSpans {
+                probe_id,
+                spans,
+                unmatched_ends,
+            });
+        }
+    }
+    out
+}
+
+/// Both events come from the same probe, so their times are always
+/// comparable when they're both present.
+fn duration(begin: &GraphEvent, end: &GraphEvent, event_distance: usize) -> Duration {
+    match (begin.wall_time, end.wall_time) {
+        (Some(b), Some(e)) if e >= b => Duration::Elapsed(e.get() - b.get()),
+        _ => Duration::Events(event_distance as u64),
+    }
+}
+
+/// Per-name statistics over every span that ended.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SpanStats {
+    count: usize,
+    never_ended: usize,
+    elapsed: Vec<u64>,
+    events: Vec<u64>,
+}
+
+fn render(cfg: &dyn MetaMeter, probes: &[ProbeSpans], summary_only: bool) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    let mut stats: BTreeMap<&str, SpanStats> = BTreeMap::new();
+    for p in probes {
+        for span in p.spans.iter() {
+            let s = stats.entry(&span.name).or_default();
+            s.count += 1;
+            match span.duration {
+                Some(Duration::Elapsed(ns)) => s.elapsed.push(ns),
+                Some(Duration::Events(n)) => s.events.push(n),
+                None => s.never_ended += 1,
+            }
+        }
+        if summary_only {
+            continue;
+        }
+
+        let probe_name = cfg
+            .probe_name(&p.probe_id)
+            .unwrap_or_else(|| p.probe_id.get_raw().to_string());
+        let _ = writeln!(out, "{}", probe_name);
+        for span in p.spans.iter() {
+            let duration = match span.duration {
+                Some(Duration::Elapsed(ns)) => fmt_duration(ns),
+                Some(Duration::Events(1)) => "1 event".to_string(),
+                Some(Duration::Events(n)) => format!("{} events", n),
+                None => "never ended".to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "    {}{} #{}  {}  (began at {})",
+                "    ".repeat(span.depth),
+                span.name,
+                span.id,
+                duration,
+                coordinate(&span.begin)
+            );
+        }
+        for (name, id, ev) in p.unmatched_ends.iter() {
+            let _ = writeln!(
+                out,
+                "    unmatched end of {} #{} at {}",
+                name,
+                id,
+                coordinate(ev)
+            );
+        }
+        let _ = writeln!(out);
+    }
+
+    for (name, s) in stats.iter_mut() {
+        s.elapsed.sort_unstable();
+        s.events.sort_unstable();
+        let _ = writeln!(out, "{}", name);
+        let _ = write!(out, "    spans:      {}", s.count);
+        if s.never_ended > 0 {
+            let _ = write!(out, " ({} never ended)", s.never_ended);
+        }
+        let _ = writeln!(out);
+        if let Some((min, mean, max)) = min_mean_max(&s.elapsed) {
+            let _ = writeln!(
+                out,
+                "    wall-clock: min {}, mean {}, max {} over {} span(s)",
+                fmt_duration(min),
+                fmt_duration(mean),
+                fmt_duration(max),
+                s.elapsed.len()
+            );
+        }
+        if let Some((min, mean, max)) = min_mean_max(&s.events) {
+            let _ = writeln!(
+                out,
+                "    events:     min {}, mean {}, max {} over {} untimed span(s)",
+                min,
+                mean,
+                max,
+                s.events.len()
+            );
+        }
+    }
+    out
+}
+
+fn min_mean_max(sorted: &[u64]) -> Option<(u64, u64, u64)> {
+    let sum: u128 = sorted.iter().map(|v| *v as u128).sum();
+    Some((
+        *sorted.first()?,
+        (sum / sorted.len() as u128) as u64,
+        *sorted.last()?,
+    ))
+}
+
+fn coordinate(ev: &GraphEvent) -> String {
+    format!("{}:{}:{}", ev.probe_id.get_raw(), ev.seq.0, ev.seq_idx)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    use modality_probe::{
+        EventId, LogicalClock, NanosecondResolution, Nanoseconds, ProbeEpoch, ProbeTicks,
+        WallClockId,
+    };
+    use modality_probe_collector_common::{LogEntryData, SequenceNumber, SessionId};
+
+    use super::*;
+    use crate::{
+        meta::{Cfg, EventMeta},
+        visualize::graph::test::cfg,
+    };
+
+    const OUTER_BEGIN: u32 = 10;
+    const OUTER_END: u32 = 11;
+    const INNER_BEGIN: u32 = 12;
+    const INNER_END: u32 = 13;
+
+    fn span_cfg() -> Cfg {
+        let mut cfg = cfg();
+        let a_uuid = Uuid::parse_str("146dd760-fc41-4418-bc59-e1320fb7f43d").unwrap();
+        for (id, name, tag) in &[
+            (OUTER_BEGIN, "OUTER_BEGIN", "SPAN_BEGIN"),
+            (OUTER_END, "OUTER_END", "SPAN_END"),
+            (INNER_BEGIN, "INNER_BEGIN", "SPAN_BEGIN"),
+            (INNER_END, "INNER_END", "SPAN_END"),
+        ] {
+            cfg.events.insert(
+                (a_uuid, *id),
+                EventMeta {
+                    component_id: a_uuid,
+                    id: *id,
+                    name: name.to_string(),
+                    type_hint: Some("u32".to_string()),
+                    tags: tag.to_string(),
+                    description: String::new(),
+                    file: String::new(),
+                    line: String::new(),
+                },
+            );
+        }
+        cfg
+    }
+
+    fn clock() -> LogicalClock {
+        LogicalClock {
+            id: ProbeId::new(1).unwrap(),
+            epoch: ProbeEpoch(0),
+            ticks: ProbeTicks(0),
+        }
+    }
+
+    fn entry(seq_idx: u32, data: LogEntryData) -> ReportLogEntry {
+        let probe_id = ProbeId::new(1).unwrap();
+        ReportLogEntry {
+            session_id: SessionId(1),
+            sequence_number: SequenceNumber(1),
+            sequence_index: seq_idx,
+            time_resolution: NanosecondResolution(0),
+            wall_clock_id: WallClockId(1),
+            receive_time: Utc::now(),
+            clock: clock(),
+            persistent_epoch_counting: false,
+            probe_id,
+            data,
+        }
+    }
+
+    fn span_event(event: u32, span_id: u32) -> LogEntryData {
+        LogEntryData::EventWithPayload(EventId::new(event).unwrap(), span_id)
+    }
+
+    fn timed_span_event(ns: u64, event: u32, span_id: u32) -> LogEntryData {
+        LogEntryData::EventWithPayloadWithTime(
+            Nanoseconds::new(ns).unwrap(),
+            EventId::new(event).unwrap(),
+            span_id,
+        )
+    }
+
+    /// Reconstruct the spans in a single report from probe 1.
+    fn spans(data: Vec<LogEntryData>) -> ProbeSpans {
+        let cfg = span_cfg();
+        let log = std::iter::once(LogEntryData::FrontierClock(clock()))
+            .chain(data)
+            .enumerate()
+            .map(|(idx, d)| entry(idx as u32, d))
+            .collect();
+        let events = log_to_events(log, false).unwrap();
+        let mut probes = reconstruct(&cfg, &events);
+        assert_eq!(probes.len(), 1);
+        probes.remove(0)
+    }
+
+    fn summary(spans: &ProbeSpans) -> Vec<(&str, u32, usize, Option<Duration>)> {
+        spans
+            .spans
+            .iter()
+            .map(|s| (s.name.as_str(), s.id, s.depth, s.duration))
+            .collect()
+    }
+
+    #[test]
+    fn nested_spans() {
+        let probe = spans(vec![
+            timed_span_event(1_000, OUTER_BEGIN, 1),
+            span_event(INNER_BEGIN, 2),
+            LogEntryData::Event(EventId::new(1).unwrap()),
+            span_event(INNER_END, 2),
+            span_event(INNER_BEGIN, 3),
+            span_event(INNER_END, 3),
+            timed_span_event(4_000, OUTER_END, 1),
+        ]);
+        assert_eq!(
+            summary(&probe),
+            vec![
+                ("OUTER", 1, 0, Some(Duration::Elapsed(3_000))),
+                ("INNER", 2, 1, Some(Duration::Events(2))),
+                ("INNER", 3, 1, Some(Duration::Events(1))),
+            ]
+        );
+        assert!(probe.unmatched_ends.is_empty());
+    }
+
+    #[test]
+    fn unended_and_unmatched_spans() {
+        let probe = spans(vec![
+            span_event(OUTER_BEGIN, 1),
+            span_event(INNER_BEGIN, 2),
+            span_event(INNER_END, 7),
+            span_event(OUTER_END, 1),
+            span_event(OUTER_BEGIN, 4),
+        ]);
+        assert_eq!(
+            summary(&probe),
+            vec![
+                ("OUTER", 1, 0, Some(Duration::Events(3))),
+                ("INNER", 2, 1, None),
+                ("OUTER", 4, 0, None),
+            ]
+        );
+        assert_eq!(
+            probe
+                .unmatched_ends
+                .iter()
+                .map(|(name, id, _)| (name.as_str(), *id))
+                .collect::<Vec<_>>(),
+            vec![("INNER", 7)]
+        );
+    }
+}
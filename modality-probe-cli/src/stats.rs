@@ -0,0 +1,223 @@
+//! Report the self-statistics each probe included in its reports.
+
+use std::{collections::BTreeMap, fs::File, path::PathBuf};
+
+use structopt::StructOpt;
+
+use modality_probe::{EventId, ProbeId, ProbeStats};
+use modality_probe_collector_common::json;
+use modality_probe_graph::GraphEvent;
+
+use crate::{
+    give_up, hopefully,
+    meta::{self, MetaMeter},
+    spans::log_to_events,
+};
+
+/// Report the counters each probe keeps about its own operation, as
+/// of the last report that carried them. Probes only report these
+/// when stats in reports are enabled.
+#[derive(Debug, PartialEq, StructOpt)]
+pub struct Stats {
+    /// The path to a component directory. To include multiple
+    /// components, provide this switch multiple times.
+    #[structopt(short, long, required = true)]
+    pub component_path: Vec<PathBuf>,
+    /// The path to the collected trace.
+    #[structopt(short, long, required = true)]
+    pub report: PathBuf,
+}
+
+pub fn run(mut s: Stats) -> Result<(), Box<dyn std::error::Error>> {
+    let cfg = meta::assemble_components(&mut s.component_path)?;
+    let mut log_file = hopefully!(
+        File::open(&s.report),
+        format!("Failed to open the report file at {}", s.report.display())
+    )?;
+    let events = log_to_events(json::read_log_entries(&mut log_file)?, true)?;
+
+    let probes = latest_stats(&events);
+    if probes.is_empty() {
+        give_up!("No probe stats were found in the trace; are stats in reports enabled?");
+    }
+
+    print!("{}", render(&cfg, &probes));
+    Ok(())
+}
+
+/// The most recently reported value of each stat, per probe. Probes
+/// which didn't report any stats are left out.
+pub fn latest_stats(events: &[GraphEvent]) -> BTreeMap<ProbeId, ProbeStats> {
+    let mut by_probe: BTreeMap<ProbeId, Vec<&GraphEvent>> = BTreeMap::new();
+    for ev in events {
+        by_probe.entry(ev.probe_id).or_default().push(ev);
+    }
+
+    let mut out = BTreeMap::new();
+    for (probe_id, mut events) in by_probe {
+        events.sort_by_key(|ev| (ev.seq, ev.seq_idx));
+        let mut stats: Option<ProbeStats> = None;
+        for ev in events {
+            let mut s = stats.unwrap_or_default();
+            if let (Some(field), Some(value)) = (stat_field(&mut s, ev.id), ev.payload) {
+                *field = value;
+                stats = Some(s);
+            }
+        }
+        if let Some(s) = stats {
+            out.insert(probe_id, s);
+        }
+    }
+    out
+}
+
+/// The counter a stats event reports, if it's one of them.
+fn stat_field(s: &mut ProbeStats, id: EventId) -> Option<&mut u32> {
+    Some(match id {
+        EventId::EVENT_STATS_EVENTS_RECORDED => &mut s.events_recorded,
+        EventId::EVENT_STATS_ENTRIES_OVERWRITTEN => &mut s.entries_overwritten,
+        EventId::EVENT_STATS_REPORTS_PRODUCED => &mut s.reports_produced,
+        EventId::EVENT_STATS_BYTES_REPORTED => &mut s.bytes_reported,
+        EventId::EVENT_STATS_SNAPSHOTS_PRODUCED => &mut s.snapshots_produced,
+        EventId::EVENT_STATS_SNAPSHOTS_MERGED => &mut s.snapshots_merged,
+        EventId::EVENT_STATS_CLOCK_OVERFLOWS => &mut s.clock_overflows,
+        EventId::EVENT_STATS_FRONTIER_CLOCK_OVERFLOWS => &mut s.frontier_clock_overflows,
+        _ => return None,
+    })
+}
+
+fn render(cfg: &dyn MetaMeter, probes: &BTreeMap<ProbeId, ProbeStats>) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    for (probe_id, s) in probes.iter() {
+        let probe_name = cfg
+            .probe_name(probe_id)
+            .unwrap_or_else(|| probe_id.get_raw().to_string());
+        let _ = writeln!(out, "{}", probe_name);
+        let _ = writeln!(out, "    events recorded:          {}", s.events_recorded);
+        let _ = write!(
+            out,
+            "    entries overwritten:      {}",
+            s.entries_overwritten
+        );
+        if s.events_recorded > 0 {
+            let _ = write!(
+                out,
+                " ({:.1}% of events recorded)",
+                f64::from(s.entries_overwritten) * 100.0 / f64::from(s.events_recorded)
+            );
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "    reports produced:         {}", s.reports_produced);
+        let _ = write!(out, "    bytes reported:           {}", s.bytes_reported);
+        if let Some(per_report) = s.bytes_reported.checked_div(s.reports_produced) {
+            let _ = write!(out, " ({} per report)", per_report);
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "    snapshots produced:       {}",
+            s.snapshots_produced
+        );
+        let _ = writeln!(out, "    snapshots merged:         {}", s.snapshots_merged);
+        let _ = writeln!(out, "    clock overflows:          {}", s.clock_overflows);
+        let _ = writeln!(
+            out,
+            "    frontier clock overflows: {}",
+            s.frontier_clock_overflows
+        );
+        let _ = writeln!(out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+
+    use modality_probe::{LogicalClock, NanosecondResolution, ProbeEpoch, ProbeTicks, WallClockId};
+    use modality_probe_collector_common::{
+        LogEntryData, ReportLogEntry, SequenceNumber, SessionId,
+    };
+
+    use super::*;
+
+    fn clock() -> LogicalClock {
+        LogicalClock {
+            id: ProbeId::new(1).unwrap(),
+            epoch: ProbeEpoch(0),
+            ticks: ProbeTicks(0),
+        }
+    }
+
+    /// A report from probe 1 with the given sequence number.
+    fn report(seq: u64, data: Vec<LogEntryData>) -> Vec<ReportLogEntry> {
+        std::iter::once(LogEntryData::FrontierClock(clock()))
+            .chain(data)
+            .enumerate()
+            .map(|(idx, data)| ReportLogEntry {
+                session_id: SessionId(1),
+                sequence_number: SequenceNumber(seq),
+                sequence_index: idx as u32,
+                time_resolution: NanosecondResolution(0),
+                wall_clock_id: WallClockId(1),
+                receive_time: Utc::now(),
+                clock: clock(),
+                persistent_epoch_counting: false,
+                probe_id: ProbeId::new(1).unwrap(),
+                data,
+            })
+            .collect()
+    }
+
+    fn stat(id: EventId, value: u32) -> LogEntryData {
+        LogEntryData::EventWithPayload(id, value)
+    }
+
+    #[test]
+    fn latest_reported_stats_win() {
+        let mut log = report(
+            1,
+            vec![
+                stat(EventId::EVENT_STATS_EVENTS_RECORDED, 10),
+                stat(EventId::EVENT_STATS_ENTRIES_OVERWRITTEN, 2),
+                LogEntryData::Event(EventId::new(1).unwrap()),
+            ],
+        );
+        log.extend(report(
+            2,
+            vec![
+                stat(EventId::EVENT_STATS_EVENTS_RECORDED, 20),
+                stat(EventId::EVENT_STATS_REPORTS_PRODUCED, 1),
+                stat(EventId::EVENT_STATS_BYTES_REPORTED, 96),
+            ],
+        ));
+        let events = log_to_events(log, true).unwrap();
+        let stats = latest_stats(&events);
+        assert_eq!(
+            stats.get(&ProbeId::new(1).unwrap()),
+            Some(&ProbeStats {
+                events_recorded: 20,
+                entries_overwritten: 2,
+                reports_produced: 1,
+                bytes_reported: 96,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn probes_without_stats_are_left_out() {
+        let log = report(
+            1,
+            vec![
+                LogEntryData::EventWithPayload(EventId::new(1).unwrap(), 5),
+                LogEntryData::Event(EventId::new(2).unwrap()),
+            ],
+        );
+        let events = log_to_events(log, true).unwrap();
+        assert!(latest_stats(&events).is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+//! Browse a trace interactively in the terminal.
+
+use std::{fs::File, io, path::PathBuf};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use structopt::StructOpt;
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+use modality_probe_collector_common::{json, ReportIter};
+use modality_probe_graph::{EventDigraph, GraphEvent};
+
+use crate::{
+    give_up, hopefully,
+    log::color,
+    meta::{self, Cfg, MetaMeter},
+};
+
+pub mod model;
+
+use model::{Browser, TraceGraph};
+
+/// Browse a trace interactively in the terminal.
+#[derive(Debug, PartialEq, StructOpt)]
+pub struct Tui {
+    /// The path to a component directory. To include multiple
+    /// components, provide this switch multiple times.
+    #[structopt(short, long, required = true)]
+    pub component_path: Vec<PathBuf>,
+    /// The path to the collected trace.
+    #[structopt(short, long, required = true)]
+    pub report: PathBuf,
+    /// Include probe-generated events in the output.
+    #[structopt(long)]
+    pub include_internal_events: bool,
+    /// The radius to start with when filtering the view down to the
+    /// events around the selected one.
+    #[structopt(long, default_value = "2")]
+    pub radius: usize,
+    /// Don't colorize the output.
+    #[structopt(long)]
+    pub no_color: bool,
+}
+
+const HELP: &str = "q quit  ←→↑↓/hjkl move  [ ] follow interaction  / search  n N next/prev match  r radius  + - resize radius";
+
+/// What keystrokes are currently being interpreted as.
+enum Mode {
+    Browse,
+    Search(String),
+}
+
+struct App<'a> {
+    cfg: &'a Cfg,
+    browser: Browser,
+    mode: Mode,
+    query: Option<String>,
+    radius: usize,
+    colorize: bool,
+    message: String,
+    list_states: Vec<ListState>,
+}
+
+/// Puts the terminal back the way we found it, even if drawing fails
+/// partway through.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+pub fn run(mut t: Tui) -> Result<(), Box<dyn std::error::Error>> {
+    let cfg = meta::assemble_components(&mut t.component_path)?;
+    let mut log_file = hopefully!(
+        File::open(&t.report),
+        format!("Failed to open the report file at {}", t.report.display())
+    )?;
+
+    let mut graph = EventDigraph::new(TraceGraph::default());
+    let entries = json::read_log_entries(&mut log_file)?;
+    for report in ReportIter::new(entries.into_iter().peekable()) {
+        hopefully!(
+            graph.add_report(&report, t.include_internal_events),
+            "Encountered an error reconstructing the graph"
+        )?;
+    }
+    let browser = Browser::new(graph.graph);
+    if browser.columns.is_empty() {
+        give_up!("The trace doesn't contain any events");
+    }
+
+    let color_term = std::env::var("COLORTERM").unwrap_or_else(|_| String::new());
+    let mut app = App {
+        cfg: &cfg,
+        list_states: browser
+            .columns
+            .iter()
+            .map(|_| ListState::default())
+            .collect(),
+        browser,
+        mode: Mode::Browse,
+        query: None,
+        radius: t.radius,
+        colorize: !t.no_color && (color_term == "truecolor" || color_term == "24bit"),
+        message: HELP.to_string(),
+    };
+
+    enable_raw_mode()?;
+    let _guard = TerminalGuard;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    terminal.clear()?;
+
+    loop {
+        terminal.draw(|f| draw(f, &mut app))?;
+        if let Event::Key(key) = event::read()? {
+            if !handle_key(&mut app, key) {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply a keystroke to the app. Returns false when it's time to
+/// quit.
+fn handle_key(app: &mut App, key: KeyEvent) -> bool {
+    if let Mode::Search(ref mut input) = app.mode {
+        match key.code {
+            KeyCode::Esc => app.mode = Mode::Browse,
+            KeyCode::Enter => {
+                let query = input.clone();
+                app.mode = Mode::Browse;
+                app.query = Some(query);
+                search(app, true);
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => (),
+        }
+        return true;
+    }
+
+    let page = 20;
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => return false,
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+        KeyCode::Up | KeyCode::Char('k') => app.browser.move_vertical(-1),
+        KeyCode::Down | KeyCode::Char('j') => app.browser.move_vertical(1),
+        KeyCode::PageUp => app.browser.move_vertical(-page),
+        KeyCode::PageDown => app.browser.move_vertical(page),
+        KeyCode::Left | KeyCode::Char('h') => app.browser.move_horizontal(-1),
+        KeyCode::Right | KeyCode::Char('l') => app.browser.move_horizontal(1),
+        KeyCode::Char(']') => app.browser.follow_interaction(true),
+        KeyCode::Char('[') => app.browser.follow_interaction(false),
+        KeyCode::Char('/') => app.mode = Mode::Search(String::new()),
+        KeyCode::Char('n') => search(app, true),
+        KeyCode::Char('N') => search(app, false),
+        KeyCode::Char('r') => {
+            app.browser.toggle_radius(app.radius);
+            app.message = match app.browser.radius {
+                Some(ref r) => format!("Showing events within {} of the selection", r.distance),
+                None => HELP.to_string(),
+            };
+        }
+        KeyCode::Char('+') | KeyCode::Char('=') => resize_radius(app, 1),
+        KeyCode::Char('-') => resize_radius(app, -1),
+        _ => (),
+    }
+    true
+}
+
+fn search(app: &mut App, forward: bool) {
+    if let Some(ref query) = app.query {
+        app.message = if app.browser.find(app.cfg, query, forward) {
+            format!("/{}", query)
+        } else {
+            format!("No events match {}", query)
+        };
+    }
+}
+
+fn resize_radius(app: &mut App, delta: isize) {
+    app.browser.resize_radius(delta);
+    if let Some(ref r) = app.browser.radius {
+        app.radius = r.distance;
+        app.message = format!("Showing events within {} of the selection", r.distance);
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)].as_ref())
+        .split(f.size());
+
+    let n_cols = app.browser.columns.len() as u32;
+    let col_areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            (0..n_cols)
+                .map(|_| Constraint::Ratio(1, n_cols))
+                .collect::<Vec<_>>(),
+        )
+        .split(rows[0]);
+
+    let selected = app.browser.selected;
+    for (col_idx, area) in col_areas.into_iter().enumerate() {
+        let column = &app.browser.columns[col_idx];
+        let probe_style = if app.colorize {
+            let c = color::probe_color(col_idx);
+            Style::default().fg(Color::Rgb(c.r, c.g, c.b))
+        } else {
+            Style::default()
+        };
+        let visible = app.browser.visible_rows(col_idx);
+        let items = visible
+            .iter()
+            .map(|row| {
+                let ev = &column.events[*row];
+                let (marker, style) = if app.browser.past.contains(ev) {
+                    ("↑ ", probe_style.add_modifier(Modifier::BOLD))
+                } else if app.browser.future.contains(ev) {
+                    ("↓ ", probe_style.add_modifier(Modifier::BOLD))
+                } else {
+                    ("  ", probe_style.add_modifier(Modifier::DIM))
+                };
+                ListItem::new(Spans::from(vec![
+                    Span::raw(marker),
+                    Span::styled(event_label(app.cfg, ev), style),
+                ]))
+            })
+            .collect::<Vec<_>>();
+
+        // Scroll each column to its most relevant event: the
+        // selection itself, or the last event in its causal past, or
+        // the first event in its causal future.
+        let focused = selected.map(|(c, _)| c == col_idx).unwrap_or(false);
+        let anchor = if focused {
+            selected.and_then(|(_, row)| visible.iter().position(|r| *r == row))
+        } else {
+            visible
+                .iter()
+                .rposition(|r| app.browser.past.contains(&column.events[*r]))
+                .or_else(|| {
+                    visible
+                        .iter()
+                        .position(|r| app.browser.future.contains(&column.events[*r]))
+                })
+        };
+        app.list_states[col_idx].select(anchor);
+
+        let probe_name = app
+            .cfg
+            .probe_name(&column.probe_id)
+            .unwrap_or_else(|| column.probe_id.get_raw().to_string());
+        let title_style = if focused {
+            probe_style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        } else {
+            probe_style
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Span::styled(probe_name, title_style)),
+            )
+            .highlight_style(if focused {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            });
+        f.render_stateful_widget(list, area, &mut app.list_states[col_idx]);
+    }
+
+    let status = match app.mode {
+        Mode::Search(ref input) => format!("/{}", input),
+        Mode::Browse => {
+            let location = app
+                .browser
+                .selected_event()
+                .map(|ev| format!("{}:{}:{} ", ev.probe_id.get_raw(), ev.seq.0, ev.seq_idx))
+                .unwrap_or_default();
+            format!("{}{}", location, app.message)
+        }
+    };
+    f.render_widget(
+        Paragraph::new(status).block(Block::default().borders(Borders::ALL)),
+        rows[1],
+    );
+}
+
+fn event_label(cfg: &dyn MetaMeter, ev: &GraphEvent) -> String {
+    let name = cfg
+        .event_name(&ev.probe_id, &ev.id)
+        .unwrap_or_else(|| ev.id.get_raw().to_string());
+    match ev.payload {
+        Some(pl) => format!("{} ({})", name, pl),
+        None => name,
+    }
+}
@@ -0,0 +1,427 @@
+//! The state behind the terminal UI, kept separate from the drawing
+//! and input handling so that it can be exercised without a terminal.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use modality_probe::ProbeId;
+use modality_probe_graph::{Graph, GraphEvent};
+
+use crate::meta::MetaMeter;
+
+/// An `EventDigraph` inner graph which indexes each event's immediate
+/// causal neighbors in both directions.
+#[derive(Debug, Default)]
+pub struct TraceGraph {
+    nodes: HashSet<GraphEvent>,
+    predecessors: HashMap<GraphEvent, Vec<GraphEvent>>,
+    successors: HashMap<GraphEvent, Vec<GraphEvent>>,
+}
+
+impl Graph for TraceGraph {
+    fn add_node(&mut self, node: GraphEvent) {
+        self.nodes.insert(node);
+    }
+
+    fn add_edge(&mut self, source: GraphEvent, target: GraphEvent) {
+        self.successors.entry(source).or_default().push(target);
+        self.predecessors.entry(target).or_default().push(source);
+    }
+}
+
+impl TraceGraph {
+    /// Every event reachable from `start` by following the given
+    /// adjacency, not including `start` itself.
+    fn reachable(
+        start: &GraphEvent,
+        adjacency: &HashMap<GraphEvent, Vec<GraphEvent>>,
+    ) -> HashSet<GraphEvent> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(*start);
+        while let Some(node) = queue.pop_front() {
+            for next in adjacency.get(&node).into_iter().flatten() {
+                if seen.insert(*next) {
+                    queue.push_back(*next);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Every event within `distance` edges of `start`, in either
+    /// direction.
+    fn within(&self, start: &GraphEvent, distance: usize) -> HashSet<GraphEvent> {
+        let mut seen = HashSet::new();
+        seen.insert(*start);
+        let mut queue = VecDeque::new();
+        queue.push_back((*start, 0));
+        while let Some((node, d)) = queue.pop_front() {
+            if d == distance {
+                continue;
+            }
+            let neighbors = self
+                .predecessors
+                .get(&node)
+                .into_iter()
+                .chain(self.successors.get(&node))
+                .flatten();
+            for next in neighbors {
+                if seen.insert(*next) {
+                    queue.push_back((*next, d + 1));
+                }
+            }
+        }
+        seen
+    }
+}
+
+/// A single probe's events, in log order.
+#[derive(Debug)]
+pub struct Column {
+    pub probe_id: ProbeId,
+    pub events: Vec<GraphEvent>,
+}
+
+/// The events around which the view is currently filtered.
+#[derive(Debug)]
+pub struct Radius {
+    pub center: GraphEvent,
+    pub distance: usize,
+    members: HashSet<GraphEvent>,
+}
+
+/// The browsable state of a trace: its probes as columns, the
+/// selected event, and that event's causal past and future.
+#[derive(Debug)]
+pub struct Browser {
+    graph: TraceGraph,
+    pub columns: Vec<Column>,
+    /// The selected column and the index of the selected event
+    /// within it.
+    pub selected: Option<(usize, usize)>,
+    pub past: HashSet<GraphEvent>,
+    pub future: HashSet<GraphEvent>,
+    pub radius: Option<Radius>,
+}
+
+impl Browser {
+    pub fn new(graph: TraceGraph) -> Self {
+        let mut by_probe: HashMap<ProbeId, Vec<GraphEvent>> = HashMap::new();
+        for node in graph.nodes.iter() {
+            by_probe.entry(node.probe_id).or_default().push(*node);
+        }
+        let mut columns = by_probe
+            .into_iter()
+            .map(|(probe_id, mut events)| {
+                events.sort_by_key(|e| (e.seq, e.seq_idx));
+                Column { probe_id, events }
+            })
+            .collect::<Vec<_>>();
+        columns.sort_by_key(|c| c.probe_id);
+
+        let mut browser = Browser {
+            graph,
+            columns,
+            selected: None,
+            past: HashSet::new(),
+            future: HashSet::new(),
+            radius: None,
+        };
+        if !browser.columns.is_empty() {
+            browser.select(0, 0);
+        }
+        browser
+    }
+
+    pub fn selected_event(&self) -> Option<&GraphEvent> {
+        self.selected
+            .map(|(col, row)| &self.columns[col].events[row])
+    }
+
+    /// Select the event at `row` in `col` and recompute its causal
+    /// past and future.
+    pub fn select(&mut self, col: usize, row: usize) {
+        let ev = self.columns[col].events[row];
+        self.selected = Some((col, row));
+        self.past = TraceGraph::reachable(&ev, &self.graph.predecessors);
+        self.future = TraceGraph::reachable(&ev, &self.graph.successors);
+    }
+
+    fn select_event(&mut self, ev: &GraphEvent) {
+        if let Some((col, row)) = self.position(ev) {
+            self.select(col, row);
+        }
+    }
+
+    fn position(&self, ev: &GraphEvent) -> Option<(usize, usize)> {
+        let col = self
+            .columns
+            .iter()
+            .position(|c| c.probe_id == ev.probe_id)?;
+        let row = self.columns[col].events.iter().position(|e| e == ev)?;
+        Some((col, row))
+    }
+
+    /// Whether the event is shown under the current radius filter.
+    pub fn is_visible(&self, ev: &GraphEvent) -> bool {
+        self.radius
+            .as_ref()
+            .map(|r| r.members.contains(ev))
+            .unwrap_or(true)
+    }
+
+    /// The indices of the events in a column that pass the current
+    /// radius filter.
+    pub fn visible_rows(&self, col: usize) -> Vec<usize> {
+        self.columns[col]
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| self.is_visible(e))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Move the selection `steps` visible events up (negative) or
+    /// down (positive) within its column.
+    pub fn move_vertical(&mut self, steps: isize) {
+        if let Some((col, row)) = self.selected {
+            let rows = self.visible_rows(col);
+            if rows.is_empty() {
+                return;
+            }
+            let pos = rows.iter().position(|r| *r >= row).unwrap_or(0) as isize;
+            let target = (pos + steps).max(0).min(rows.len() as isize - 1) as usize;
+            self.select(col, rows[target]);
+        }
+    }
+
+    /// Move the selection to a neighboring column, landing on the
+    /// event there that most recently led up to the current one, or
+    /// failing that, the first one it led to.
+    pub fn move_horizontal(&mut self, steps: isize) {
+        if let Some((col, _)) = self.selected {
+            let target = (col as isize + steps)
+                .max(0)
+                .min(self.columns.len() as isize - 1) as usize;
+            if target == col {
+                return;
+            }
+            let rows = self.visible_rows(target);
+            let events = &self.columns[target].events;
+            let row = rows
+                .iter()
+                .rev()
+                .find(|r| self.past.contains(&events[**r]))
+                .or_else(|| rows.iter().find(|r| self.future.contains(&events[**r])))
+                .or_else(|| rows.first())
+                .copied();
+            if let Some(row) = row {
+                self.select(target, row);
+            }
+        }
+    }
+
+    /// Jump across the next interaction edge. Going forward, this
+    /// walks down the selected probe's log to the first event that
+    /// was merged into another probe and selects the event it led to
+    /// there; going backward, it walks up to the first event which
+    /// merged another probe's snapshot and selects its source.
+    pub fn follow_interaction(&mut self, forward: bool) {
+        let (col, row) = match self.selected {
+            Some(s) => s,
+            None => return,
+        };
+        let (adjacency, candidates): (_, Box<dyn Iterator<Item = &GraphEvent>>) = if forward {
+            (
+                &self.graph.successors,
+                Box::new(self.columns[col].events[row..].iter()),
+            )
+        } else {
+            (
+                &self.graph.predecessors,
+                Box::new(self.columns[col].events[..=row].iter().rev()),
+            )
+        };
+        let probe_id = self.columns[col].probe_id;
+        let target = candidates
+            .filter_map(|ev| {
+                let mut remote = adjacency
+                    .get(ev)?
+                    .iter()
+                    .filter(|n| n.probe_id != probe_id)
+                    .collect::<Vec<_>>();
+                remote.sort_by_key(|n| (n.probe_id, n.seq, n.seq_idx));
+                remote.first().copied().copied()
+            })
+            .find(|ev| self.is_visible(ev));
+        if let Some(ev) = target {
+            self.select_event(&ev);
+        }
+    }
+
+    /// Select the next event, in column-major order, whose name
+    /// contains `query` or which has a tag equal to it. Matching is
+    /// case-insensitive. Returns whether a match was found.
+    pub fn find(&mut self, cfg: &dyn MetaMeter, query: &str, forward: bool) -> bool {
+        let query = query.to_lowercase();
+        let matches = |ev: &GraphEvent| {
+            let name_match = cfg
+                .event_name(&ev.probe_id, &ev.id)
+                .map(|n| n.to_lowercase().contains(&query))
+                .unwrap_or(false);
+            let tag_match = cfg
+                .event_tags(&ev.probe_id, &ev.id)
+                .map(|tags| tags.iter().any(|t| t.to_lowercase() == query))
+                .unwrap_or(false);
+            name_match || tag_match
+        };
+
+        let mut all = Vec::new();
+        for (col, column) in self.columns.iter().enumerate() {
+            for (row, ev) in column.events.iter().enumerate() {
+                if self.is_visible(ev) {
+                    all.push((col, row));
+                }
+            }
+        }
+        if all.is_empty() {
+            return false;
+        }
+        let start = self
+            .selected
+            .and_then(|s| all.iter().position(|p| *p == s))
+            .unwrap_or(0);
+        let n = all.len();
+        let found = (1..=n)
+            .map(|offset| {
+                if forward {
+                    all[(start + offset) % n]
+                } else {
+                    all[(start + n - offset) % n]
+                }
+            })
+            .find(|(col, row)| matches(&self.columns[*col].events[*row]));
+        if let Some((col, row)) = found {
+            self.select(col, row);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Filter the view down to the events within `distance` edges of
+    /// the selected event, or remove the filter if one is already in
+    /// place.
+    pub fn toggle_radius(&mut self, distance: usize) {
+        if self.radius.is_some() {
+            self.radius = None;
+        } else if let Some(center) = self.selected_event().copied() {
+            self.radius = Some(Radius {
+                center,
+                distance,
+                members: self.graph.within(&center, distance),
+            });
+        }
+    }
+
+    /// Grow (positive) or shrink (negative) the current radius
+    /// filter.
+    pub fn resize_radius(&mut self, delta: isize) {
+        if let Some(radius) = self.radius.take() {
+            let distance = (radius.distance as isize + delta).max(0) as usize;
+            self.radius = Some(Radius {
+                center: radius.center,
+                distance,
+                members: self.graph.within(&radius.center, distance),
+            });
+            if !self
+                .selected_event()
+                .map(|e| self.is_visible(e))
+                .unwrap_or(true)
+            {
+                self.select_event(&radius.center);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use modality_probe_graph::EventDigraph;
+
+    use crate::visualize::graph::test::cfg;
+
+    use super::*;
+
+    fn diamond() -> Browser {
+        let mut graph = EventDigraph::new(TraceGraph::default());
+        let log = modality_probe_graph::test_support::diamond();
+        for report in modality_probe_collector_common::ReportIter::new(log.into_iter().peekable()) {
+            graph.add_report(&report, false).unwrap();
+        }
+        Browser::new(graph.graph)
+    }
+
+    #[test]
+    fn columns_are_probes_in_order() {
+        let browser = diamond();
+        let ids = browser
+            .columns
+            .iter()
+            .map(|c| c.probe_id.get_raw())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+        assert_eq!(browser.selected, Some((0, 0)));
+    }
+
+    #[test]
+    fn selection_tracks_causal_past_and_future() {
+        let mut browser = diamond();
+        // The first event on probe one leads to every other probe.
+        assert!(browser.past.is_empty());
+        for probe in 2..=4 {
+            assert!(browser.future.iter().any(|e| e.probe_id.get_raw() == probe));
+        }
+
+        // Probe four's last event has all of the probes in its past.
+        browser.select(3, browser.columns[3].events.len() - 1);
+        assert!(browser.future.is_empty());
+        for probe in 1..=3 {
+            assert!(browser.past.iter().any(|e| e.probe_id.get_raw() == probe));
+        }
+    }
+
+    #[test]
+    fn follow_interactions() {
+        let mut browser = diamond();
+        browser.follow_interaction(true);
+        let (col, _) = browser.selected.unwrap();
+        assert_ne!(col, 0);
+
+        browser.follow_interaction(false);
+        assert_eq!(browser.selected.unwrap().0, 0);
+    }
+
+    #[test]
+    fn find_by_name() {
+        let cfg = cfg();
+        let mut browser = diamond();
+        assert!(browser.find(&cfg, "FOUR", true));
+        let ev = browser.selected_event().unwrap();
+        assert_eq!(ev.id.get_raw(), 4);
+        assert!(!browser.find(&cfg, "five", true));
+    }
+
+    #[test]
+    fn radius_filters_rows() {
+        let mut browser = diamond();
+        let all = browser.visible_rows(3).len();
+        browser.toggle_radius(1);
+        assert!(browser.visible_rows(3).is_empty());
+        browser.resize_radius(all as isize + 10);
+        assert_eq!(browser.visible_rows(3).len(), all);
+        browser.toggle_radius(1);
+        assert!(browser.radius.is_none());
+    }
+}
@@ -0,0 +1,290 @@
+//! Export a causal graph in the Chrome Trace Event format, which can
+//! be loaded into Perfetto or `about:tracing`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use modality_probe_graph::GraphEvent;
+
+use crate::meta::{Cfg, MetaMeter};
+
+use super::graph::causal_depths;
+
+/// The suffix on an event's name that marks it as the start of a
+/// span.
+const SPAN_BEGIN_SUFFIX: &str = "_BEGIN";
+/// The suffix on an event's name that marks it as the end of a span.
+const SPAN_END_SUFFIX: &str = "_END";
+
+/// The number of trace microseconds between logically adjacent events
+/// when no wall-clock time is available.
+const LOGICAL_STEP_US: f64 = 1.0;
+
+#[derive(Debug, Serialize)]
+struct Trace {
+    #[serde(rename = "traceEvents")]
+    trace_events: Vec<TraceEvent>,
+    #[serde(rename = "displayTimeUnit")]
+    display_time_unit: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct TraceEvent {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cat: Option<&'static str>,
+    ph: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<f64>,
+    pid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tid: Option<u32>,
+    /// The scope of an instant event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    s: Option<&'static str>,
+    /// The id that binds the two halves of a flow event together.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    /// The binding point of a flow's finishing event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bp: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<Value>,
+}
+
+impl TraceEvent {
+    fn new(name: String, ph: &'static str, pid: u32) -> Self {
+        TraceEvent {
+            name,
+            cat: None,
+            ph,
+            ts: None,
+            pid,
+            tid: None,
+            s: None,
+            id: None,
+            bp: None,
+            args: None,
+        }
+    }
+}
+
+/// Render the given nodes and edges as a Chrome Trace Event JSON
+/// document. Each component is a process, each probe is a thread
+/// (track) in that process, events are instants (or spans, when their
+/// names end in `_BEGIN` / `_END`), and edges between probes are flow
+/// arrows.
+///
+/// Wall-clock time is used if every event has it, otherwise the
+/// events are laid out on a synthesized logical timeline.
+pub(super) fn render(
+    nodes: &HashSet<&GraphEvent>,
+    edges: &HashSet<(&GraphEvent, &GraphEvent)>,
+    cfg: &Cfg,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut sorted_nodes = nodes.iter().copied().collect::<Vec<_>>();
+    sorted_nodes.sort_by_key(|n| (n.probe_id, n.seq, n.seq_idx));
+
+    let timestamps = if sorted_nodes.iter().all(|n| n.wall_time.is_some()) {
+        wall_clock_timestamps(&sorted_nodes)
+    } else {
+        logical_timestamps(&sorted_nodes, edges)
+    };
+
+    // Components become processes, numbered in name order so that the
+    // output is stable from run to run.
+    let component_names = sorted_nodes
+        .iter()
+        .map(|n| component_name(cfg, n))
+        .collect::<HashSet<_>>();
+    let mut component_names = component_names.into_iter().collect::<Vec<_>>();
+    component_names.sort();
+    let pids = component_names
+        .into_iter()
+        .enumerate()
+        .map(|(idx, name)| (name, idx as u32 + 1))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut trace_events = Vec::new();
+    for (name, pid) in pids.iter() {
+        let mut ev = TraceEvent::new("process_name".to_string(), "M", *pid);
+        ev.args = Some(json!({ "name": name }));
+        trace_events.push(ev);
+    }
+    let mut named_probes = HashSet::new();
+    for node in sorted_nodes.iter() {
+        if named_probes.insert(node.probe_id) {
+            let mut ev = TraceEvent::new(
+                "thread_name".to_string(),
+                "M",
+                pids[&component_name(cfg, node)],
+            );
+            ev.tid = Some(node.probe_id.get_raw());
+            ev.args = Some(json!({
+                "name": cfg
+                    .probe_name(&node.probe_id)
+                    .unwrap_or_else(|| node.probe_id.get_raw().to_string())
+            }));
+            trace_events.push(ev);
+        }
+    }
+
+    let mut timeline = Vec::new();
+    for node in sorted_nodes.iter() {
+        let name = cfg
+            .event_name(&node.probe_id, &node.id)
+            .unwrap_or_else(|| node.id.get_raw().to_string());
+        let (name, ph, s) = if let Some(span) = name.strip_suffix(SPAN_BEGIN_SUFFIX) {
+            (span.to_string(), "B", None)
+        } else if let Some(span) = name.strip_suffix(SPAN_END_SUFFIX) {
+            (span.to_string(), "E", None)
+        } else {
+            (name, "i", Some("t"))
+        };
+        let mut ev = TraceEvent::new(name, ph, pids[&component_name(cfg, node)]);
+        ev.cat = Some("event");
+        ev.ts = Some(timestamps[node]);
+        ev.tid = Some(node.probe_id.get_raw());
+        ev.s = s;
+        ev.args = Some(event_args(cfg, node));
+        timeline.push(ev);
+    }
+
+    let mut interactions = edges
+        .iter()
+        .filter(|(s, t)| s.probe_id != t.probe_id)
+        .collect::<Vec<_>>();
+    interactions.sort_by_key(|(s, t)| {
+        (
+            (s.probe_id, s.seq, s.seq_idx),
+            (t.probe_id, t.seq, t.seq_idx),
+        )
+    });
+    for (flow_id, (s, t)) in interactions.into_iter().enumerate() {
+        let mut start = TraceEvent::new(
+            "interaction".to_string(),
+            "s",
+            pids[&component_name(cfg, s)],
+        );
+        start.cat = Some("interaction");
+        start.ts = Some(timestamps[s]);
+        start.tid = Some(s.probe_id.get_raw());
+        start.id = Some(flow_id);
+        timeline.push(start);
+
+        let mut finish = TraceEvent::new(
+            "interaction".to_string(),
+            "f",
+            pids[&component_name(cfg, t)],
+        );
+        finish.cat = Some("interaction");
+        finish.ts = Some(timestamps[t]);
+        finish.tid = Some(t.probe_id.get_raw());
+        finish.id = Some(flow_id);
+        finish.bp = Some("e");
+        timeline.push(finish);
+    }
+    // Keep the original order for events with the same timestamp, so
+    // that the begin / end events of a zero-length span don't swap.
+    timeline.sort_by(|a, b| a.ts.partial_cmp(&b.ts).unwrap_or(std::cmp::Ordering::Equal));
+    trace_events.extend(timeline);
+
+    Ok(serde_json::to_string_pretty(&Trace {
+        trace_events,
+        display_time_unit: "ns",
+    })?)
+}
+
+fn component_name(cfg: &Cfg, node: &GraphEvent) -> String {
+    cfg.probe_component_name(&node.probe_id)
+        .unwrap_or_else(|| "UNKNOWN_COMPONENT".to_string())
+}
+
+fn event_args(cfg: &Cfg, node: &GraphEvent) -> Value {
+    let mut args = json!({
+        "probe_id": node.probe_id.get_raw(),
+        "event_id": node.id.get_raw(),
+        "clock": node.clock.pack().1,
+        "seq": node.seq.0,
+        "seq_idx": node.seq_idx,
+    });
+    if let Some(pl) = node.payload {
+        args["payload"] = json!(pl);
+    }
+    if let Some(desc) = cfg.event_description(&node.probe_id, &node.id) {
+        args["description"] = json!(desc);
+    }
+    args
+}
+
+/// Trace timestamps are in microseconds; start the trace at the
+/// earliest wall-clock time.
+fn wall_clock_timestamps<'a>(nodes: &[&'a GraphEvent]) -> HashMap<&'a GraphEvent, f64> {
+    let start = nodes
+        .iter()
+        .filter_map(|n| n.wall_time)
+        .map(|t| t.get())
+        .min()
+        .unwrap_or(0);
+    nodes
+        .iter()
+        .map(|n| {
+            let ns = n.wall_time.map(|t| t.get()).unwrap_or(start) - start;
+            (*n, ns as f64 / 1000.0)
+        })
+        .collect()
+}
+
+/// Place each event one step after the latest of its causal
+/// predecessors, so that every edge points forward in time.
+fn logical_timestamps<'a>(
+    nodes: &[&'a GraphEvent],
+    edges: &HashSet<(&'a GraphEvent, &'a GraphEvent)>,
+) -> HashMap<&'a GraphEvent, f64> {
+    causal_depths(nodes, edges)
+        .into_iter()
+        .map(|(n, d)| (n, d as f64 * LOGICAL_STEP_US))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use super::super::graph::test::cfg;
+
+    #[test]
+    fn diamond_chrome_trace() {
+        let cfg = cfg();
+        let diamond_log = modality_probe_graph::test_support::diamond()
+            .into_iter()
+            .peekable();
+        let graph = super::super::graph::log_to_graph(diamond_log, false).unwrap();
+
+        let trace: Value =
+            serde_json::from_str(&graph.graph.as_complete().chrome_trace(&cfg).unwrap()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+
+        let thread_names = events.iter().filter(|e| e["name"] == "thread_name").count();
+        assert_eq!(thread_names, 4);
+
+        let instants = events.iter().filter(|e| e["ph"] == "i").collect::<Vec<_>>();
+        assert!(!instants.is_empty());
+        assert!(instants.iter().all(|e| e["s"] == "t"));
+
+        // Every flow start has a matching finish, and causality points
+        // forward on the synthesized logical timeline.
+        let starts = events.iter().filter(|e| e["ph"] == "s").collect::<Vec<_>>();
+        assert!(!starts.is_empty());
+        for start in starts {
+            let finish = events
+                .iter()
+                .find(|e| e["ph"] == "f" && e["id"] == start["id"])
+                .unwrap();
+            assert_ne!(start["tid"], finish["tid"]);
+            assert!(start["ts"].as_f64().unwrap() < finish["ts"].as_f64().unwrap());
+        }
+    }
+}
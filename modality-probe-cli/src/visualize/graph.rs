@@ -1,4 +1,8 @@
-use std::{collections::HashSet, hash::Hash, iter::Peekable};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    iter::Peekable,
+};
 
 use tinytemplate::TinyTemplate;
 
@@ -11,8 +15,9 @@ use crate::{
     meta::{self, Cfg},
 };
 
-use super::templates::{
-    self, Component, ComponentSet, Context, Edge, EdgeSet, Event, Probe, ProbeSet,
+use super::{
+    chrome_trace, sequence,
+    templates::{self, Component, ComponentSet, Context, Edge, EdgeSet, Event, Probe, ProbeSet},
 };
 
 pub fn log_to_graph<I>(
@@ -25,6 +30,7 @@ where
     let mut graph = EventDigraph::new(NodeAndEdgeLists {
         nodes: HashSet::new(),
         edges: HashSet::new(),
+        interactions: HashMap::new(),
     });
     let report_iter = ReportIter::new(log);
     for report in report_iter {
@@ -43,6 +49,25 @@ where
 {
     nodes: HashSet<G>,
     edges: HashSet<(G, G)>,
+    /// The interaction ids that label some of the edges.
+    interactions: HashMap<(G, G), u32>,
+}
+
+impl<G> NodeAndEdgeLists<G>
+where
+    G: Hash + Eq,
+{
+    pub fn nodes(&self) -> &HashSet<G> {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &HashSet<(G, G)> {
+        &self.edges
+    }
+
+    pub fn interactions(&self) -> &HashMap<(G, G), u32> {
+        &self.interactions
+    }
 }
 
 impl NodeAndEdgeLists<GraphEvent> {
@@ -50,6 +75,11 @@ impl NodeAndEdgeLists<GraphEvent> {
         NodeAndEdgeLists {
             nodes: self.nodes.iter().collect(),
             edges: self.edges.iter().map(|(s, t)| (s, t)).collect(),
+            interactions: self
+                .interactions
+                .iter()
+                .map(|((s, t), id)| ((s, t), *id))
+                .collect(),
         }
     }
 
@@ -99,13 +129,23 @@ impl NodeAndEdgeLists<GraphEvent> {
     {
         let nodes = self.nodes.iter().filter(|n| node_filter(n)).collect();
 
-        let edges = self
+        let edges: HashSet<(&GraphEvent, &GraphEvent)> = self
             .edges
             .iter()
             .filter(|(s, t)| edge_filter(s, t))
             .map(|(s, t)| (s, t))
             .collect();
-        NodeAndEdgeLists { nodes, edges }
+        let interactions = self
+            .interactions
+            .iter()
+            .map(|((s, t), id)| ((s, t), *id))
+            .filter(|(edge, _)| edges.contains(edge))
+            .collect();
+        NodeAndEdgeLists {
+            nodes,
+            edges,
+            interactions,
+        }
     }
 }
 
@@ -116,7 +156,7 @@ impl<'a> NodeAndEdgeLists<&'a GraphEvent> {
         name: &'static str,
         temp: &'static str,
     ) -> Result<String, Box<dyn std::error::Error>> {
-        let ctx = graph_to_tree(&self.nodes, &self.edges, cfg);
+        let ctx = graph_to_tree(&self.nodes, &self.edges, &self.interactions, cfg);
         let mut tt = TinyTemplate::new();
         tt.add_formatter(
             "discrete_color_formatter",
@@ -129,6 +169,30 @@ impl<'a> NodeAndEdgeLists<&'a GraphEvent> {
         tt.add_template(name, temp)?;
         Ok(tt.render(name, &ctx)?)
     }
+
+    pub fn chrome_trace(&self, cfg: &Cfg) -> Result<String, Box<dyn std::error::Error>> {
+        chrome_trace::render(&self.nodes, &self.edges, cfg)
+    }
+
+    pub fn mermaid(&self, cfg: &Cfg) -> String {
+        sequence::render(
+            &self.nodes,
+            &self.edges,
+            &self.interactions,
+            cfg,
+            sequence::Style::Mermaid,
+        )
+    }
+
+    pub fn plantuml(&self, cfg: &Cfg) -> String {
+        sequence::render(
+            &self.nodes,
+            &self.edges,
+            &self.interactions,
+            cfg,
+            sequence::Style::PlantUml,
+        )
+    }
 }
 
 impl Graph for NodeAndEdgeLists<GraphEvent> {
@@ -139,11 +203,61 @@ impl Graph for NodeAndEdgeLists<GraphEvent> {
     fn add_edge(&mut self, source: GraphEvent, target: GraphEvent) {
         self.edges.insert((source, target));
     }
+
+    fn add_interaction_edge(
+        &mut self,
+        source: GraphEvent,
+        target: GraphEvent,
+        interaction_id: u32,
+    ) {
+        self.edges.insert((source, target));
+        self.interactions.insert((source, target), interaction_id);
+    }
+}
+
+/// The length of the longest causal path leading up to each node,
+/// considering only the edges between the given nodes.
+pub(crate) fn causal_depths<'a>(
+    nodes: &[&'a GraphEvent],
+    edges: &HashSet<(&'a GraphEvent, &'a GraphEvent)>,
+) -> HashMap<&'a GraphEvent, u64> {
+    let mut in_degree = nodes
+        .iter()
+        .map(|n| (*n, 0usize))
+        .collect::<HashMap<_, _>>();
+    let mut successors: HashMap<&GraphEvent, Vec<&GraphEvent>> = HashMap::new();
+    for (s, t) in edges {
+        if in_degree.contains_key(s) && in_degree.contains_key(t) {
+            *in_degree.get_mut(t).unwrap() += 1;
+            successors.entry(s).or_default().push(t);
+        }
+    }
+
+    let mut depth = nodes.iter().map(|n| (*n, 0u64)).collect::<HashMap<_, _>>();
+    let mut queue = nodes
+        .iter()
+        .copied()
+        .filter(|n| in_degree[n] == 0)
+        .collect::<VecDeque<_>>();
+    while let Some(node) = queue.pop_front() {
+        let d = depth[node];
+        for succ in successors.get(node).into_iter().flatten() {
+            let succ_depth = depth.get_mut(succ).unwrap();
+            *succ_depth = (*succ_depth).max(d + 1);
+            let deg = in_degree.get_mut(succ).unwrap();
+            *deg -= 1;
+            if *deg == 0 {
+                queue.push_back(succ);
+            }
+        }
+    }
+    depth
 }
 
 fn graph_to_tree<'a>(
     nodes: &HashSet<&GraphEvent>,
     edges: &HashSet<(&GraphEvent, &GraphEvent)>,
+    interactions: &HashMap<(&GraphEvent, &GraphEvent), u32>,
     cfg: &'a Cfg,
 ) -> Context<'a> {
     let mut ctx = Context {
@@ -324,7 +438,15 @@ fn graph_to_tree<'a>(
             }
         };
 
-        ctx.edges.insert(Edge { from, to });
+        let label = interactions
+            .get(&(*s, *t))
+            .map(|id| meta::interaction_name(cfg, &t.probe_id, *id));
+        ctx.edges.insert(Edge {
+            from,
+            to,
+            has_label: label.is_some(),
+            label,
+        });
     }
     ctx
 }
@@ -489,6 +611,39 @@ pub(crate) mod test {
         assert!(dot.contains("one_0 -> two_1"), dot);
     }
 
+    #[test]
+    fn labeled_interactions_dot() {
+        let cfg = cfg();
+        let probe = |id| modality_probe::ProbeId::new(id).unwrap();
+        let mut diamond_log = modality_probe_graph::test_support::diamond();
+        modality_probe_graph::test_support::tag_interaction(
+            &mut diamond_log,
+            probe(2),
+            probe(1),
+            0x123,
+        );
+        let graph = super::log_to_graph(diamond_log.into_iter().peekable(), false).unwrap();
+
+        let dot = graph
+            .graph
+            .as_interactions()
+            .dot(&cfg, "interactions", templates::INTERACTIONS)
+            .unwrap();
+        assert!(
+            dot.contains("one_0 -> two_1 [ label = \"0x123\" ]"),
+            "{}",
+            dot
+        );
+        assert!(dot.contains("one_0 -> three_1\n"), "{}", dot);
+
+        let dot = graph
+            .graph
+            .as_topology()
+            .dot(&cfg, "topology", templates::TOPO)
+            .unwrap();
+        assert!(dot.contains("one -> two [ label = \"0x123\" ]"), "{}", dot);
+    }
+
     #[test]
     fn states_dot() {
         let cfg = cfg();
@@ -1,4 +1,5 @@
-//! Visualize a causal graph using the Graphiz / Dot
+//! Visualize a causal graph using the Graphiz / Dot, the Chrome
+//! Trace Event format, or a sequence diagram
 
 use std::{fs::File, path::PathBuf, str::FromStr};
 
@@ -8,7 +9,9 @@ use modality_probe_collector_common::json;
 
 use crate::{give_up, hopefully, meta};
 
+mod chrome_trace;
 pub mod graph;
+mod sequence;
 mod templates;
 
 /// Visualize a textual representation of a causal graph using the
@@ -37,6 +40,15 @@ pub struct Visualize {
     /// events or the interactions between probes in the system.
     #[structopt(required = true)]
     pub graph_type: GraphType,
+    /// The format to output the graph in.
+    ///
+    /// This can be one of `dot`, `chrome-trace`, `mermaid`, or
+    /// `plantuml`. A Chrome trace can be loaded into Perfetto or
+    /// `about:tracing`. Mermaid and PlantUML output is a sequence
+    /// diagram of the interactions between probes. All formats other
+    /// than `dot` are only available for acyclic graphs.
+    #[structopt(long, default_value = "dot")]
+    pub output_format: OutputFormat,
 }
 
 #[derive(Debug, PartialEq, StructOpt)]
@@ -56,6 +68,27 @@ impl FromStr for GraphType {
     }
 }
 
+#[derive(Debug, PartialEq, StructOpt)]
+pub enum OutputFormat {
+    Dot,
+    ChromeTrace,
+    Mermaid,
+    PlantUml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Box<dyn std::error::Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(OutputFormat::Dot),
+            "chrome-trace" => Ok(OutputFormat::ChromeTrace),
+            "mermaid" => Ok(OutputFormat::Mermaid),
+            "plantuml" => Ok(OutputFormat::PlantUml),
+            _ => give_up!(format!("{} is not a valid output format", s)),
+        }
+    }
+}
+
 pub fn run(mut viz: Visualize) -> Result<(), Box<dyn std::error::Error>> {
     let cfg = meta::assemble_components(&mut viz.component_path)?;
     let mut log_file = hopefully!(
@@ -70,35 +103,50 @@ pub fn run(mut viz: Visualize) -> Result<(), Box<dyn std::error::Error>> {
         viz.include_internal_events,
     )?;
 
-    match (viz.graph_type, viz.interactions_only) {
-        (GraphType::Acyclic, false) => println!(
+    match (viz.output_format, viz.graph_type, viz.interactions_only) {
+        (OutputFormat::Dot, GraphType::Acyclic, false) => println!(
             "{}",
             graph
                 .graph
                 .as_complete()
                 .dot(&cfg, "complete", templates::COMPLETE)?
         ),
-        (GraphType::Acyclic, true) => println!(
+        (OutputFormat::Dot, GraphType::Acyclic, true) => println!(
             "{}",
             graph
                 .graph
                 .as_interactions()
                 .dot(&cfg, "interactions", templates::INTERACTIONS)?
         ),
-        (GraphType::Cyclic, false) => println!(
+        (OutputFormat::Dot, GraphType::Cyclic, false) => println!(
             "{}",
             graph
                 .graph
                 .as_states()
                 .dot(&cfg, "states", templates::STATES)?
         ),
-        (GraphType::Cyclic, true) => println!(
+        (OutputFormat::Dot, GraphType::Cyclic, true) => println!(
             "{}",
             graph
                 .graph
                 .as_topology()
                 .dot(&cfg, "topo", templates::TOPO)?
         ),
+        (_, GraphType::Cyclic, _) => {
+            give_up!("Only the dot output format supports cyclic graphs")
+        }
+        (OutputFormat::ChromeTrace, GraphType::Acyclic, false) => {
+            println!("{}", graph.graph.as_complete().chrome_trace(&cfg)?)
+        }
+        (OutputFormat::ChromeTrace, GraphType::Acyclic, true) => {
+            println!("{}", graph.graph.as_interactions().chrome_trace(&cfg)?)
+        }
+        (OutputFormat::Mermaid, GraphType::Acyclic, _) => {
+            print!("{}", graph.graph.as_interactions().mermaid(&cfg))
+        }
+        (OutputFormat::PlantUml, GraphType::Acyclic, _) => {
+            print!("{}", graph.graph.as_interactions().plantuml(&cfg))
+        }
     }
 
     Ok(())
@@ -0,0 +1,227 @@
+//! Render the interactions between probes as a Mermaid or PlantUML
+//! sequence diagram.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Write,
+};
+
+use modality_probe::ProbeId;
+use modality_probe_graph::GraphEvent;
+
+use crate::meta::{self, Cfg, MetaMeter};
+
+use super::graph::causal_depths;
+
+/// The sequence diagram dialect to render.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Style {
+    Mermaid,
+    PlantUml,
+}
+
+/// Render a sequence diagram in which each probe is a lifeline and
+/// each edge between two probes is a message, labeled with the name
+/// of the event before the snapshot was produced and the name of the
+/// event after it was merged, followed by the snapshot's interaction,
+/// if it carried one.
+pub(super) fn render(
+    nodes: &HashSet<&GraphEvent>,
+    edges: &HashSet<(&GraphEvent, &GraphEvent)>,
+    interactions: &HashMap<(&GraphEvent, &GraphEvent), u32>,
+    cfg: &Cfg,
+    style: Style,
+) -> String {
+    let probes = nodes
+        .iter()
+        .map(|n| n.probe_id)
+        .chain(edges.iter().flat_map(|(s, t)| vec![s.probe_id, t.probe_id]))
+        .collect::<BTreeSet<_>>();
+
+    let mut messages = edges
+        .iter()
+        .filter(|(s, t)| s.probe_id != t.probe_id)
+        .copied()
+        .collect::<Vec<_>>();
+    let depths = message_depths(&messages);
+    messages.sort_by_key(|(s, t)| {
+        (
+            depths[s],
+            depths[t],
+            (s.probe_id, s.seq, s.seq_idx),
+            (t.probe_id, t.seq, t.seq_idx),
+        )
+    });
+
+    let mut out = String::new();
+    match style {
+        Style::Mermaid => {
+            let _ = writeln!(out, "sequenceDiagram");
+            for probe in probes.iter() {
+                let _ = writeln!(
+                    out,
+                    "    participant p{} as {}",
+                    probe.get_raw(),
+                    probe_name(cfg, probe)
+                );
+            }
+            for (s, t) in messages {
+                let _ = writeln!(
+                    out,
+                    "    p{}->>p{}: {} / {}{}",
+                    s.probe_id.get_raw(),
+                    t.probe_id.get_raw(),
+                    event_name(cfg, s),
+                    event_name(cfg, t),
+                    interaction(interactions, cfg, s, t)
+                );
+            }
+        }
+        Style::PlantUml => {
+            let _ = writeln!(out, "@startuml");
+            for probe in probes.iter() {
+                let _ = writeln!(
+                    out,
+                    "participant \"{}\" as p{}",
+                    probe_name(cfg, probe),
+                    probe.get_raw()
+                );
+            }
+            for (s, t) in messages {
+                let _ = writeln!(
+                    out,
+                    "p{} -> p{} : {} / {}{}",
+                    s.probe_id.get_raw(),
+                    t.probe_id.get_raw(),
+                    event_name(cfg, s),
+                    event_name(cfg, t),
+                    interaction(interactions, cfg, s, t)
+                );
+            }
+            let _ = writeln!(out, "@enduml");
+        }
+    }
+    out
+}
+
+/// Order the messages' endpoints causally: chain together the
+/// endpoints that share a probe, in log order, then find how deep
+/// each one sits in the resulting graph.
+fn message_depths<'a>(
+    messages: &[(&'a GraphEvent, &'a GraphEvent)],
+) -> HashMap<&'a GraphEvent, u64> {
+    let mut endpoints = messages
+        .iter()
+        .flat_map(|(s, t)| vec![*s, *t])
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    endpoints.sort_by_key(|n| (n.probe_id, n.seq, n.seq_idx));
+
+    let mut edges = messages.iter().copied().collect::<HashSet<_>>();
+    for pair in endpoints.windows(2) {
+        if pair[0].probe_id == pair[1].probe_id {
+            edges.insert((pair[0], pair[1]));
+        }
+    }
+    causal_depths(&endpoints, &edges)
+}
+
+fn probe_name(cfg: &Cfg, probe: &ProbeId) -> String {
+    cfg.probe_name(probe)
+        .unwrap_or_else(|| probe.get_raw().to_string())
+}
+
+fn event_name(cfg: &Cfg, node: &GraphEvent) -> String {
+    cfg.event_name(&node.probe_id, &node.id)
+        .unwrap_or_else(|| node.id.get_raw().to_string())
+}
+
+fn interaction(
+    interactions: &HashMap<(&GraphEvent, &GraphEvent), u32>,
+    cfg: &Cfg,
+    source: &GraphEvent,
+    target: &GraphEvent,
+) -> String {
+    interactions
+        .get(&(source, target))
+        .map(|id| {
+            format!(
+                " over {}",
+                meta::interaction_name(cfg, &target.probe_id, *id)
+            )
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::graph::{log_to_graph, test::cfg};
+
+    #[test]
+    fn interactions_mermaid() {
+        let cfg = cfg();
+        let diamond_log = modality_probe_graph::test_support::diamond()
+            .into_iter()
+            .peekable();
+        let graph = log_to_graph(diamond_log, false).unwrap();
+
+        let mermaid = graph.graph.as_interactions().mermaid(&cfg);
+        assert!(mermaid.starts_with("sequenceDiagram\n"), "{}", mermaid);
+        assert!(
+            mermaid.contains("    participant p1 as one\n"),
+            "{}",
+            mermaid
+        );
+        assert!(mermaid.contains("    p1->>p2: one / two\n"), "{}", mermaid);
+        // The diamond's fan-out happens before its fan-in.
+        let first_out = mermaid.find("p1->>").unwrap();
+        let last_in = mermaid.rfind("->>p4").unwrap();
+        assert!(first_out < last_in, "{}", mermaid);
+    }
+
+    #[test]
+    fn interactions_plantuml() {
+        let cfg = cfg();
+        let diamond_log = modality_probe_graph::test_support::diamond()
+            .into_iter()
+            .peekable();
+        let graph = log_to_graph(diamond_log, false).unwrap();
+
+        let plantuml = graph.graph.as_interactions().plantuml(&cfg);
+        assert!(plantuml.starts_with("@startuml\n"), "{}", plantuml);
+        assert!(plantuml.ends_with("@enduml\n"), "{}", plantuml);
+        assert!(
+            plantuml.contains("participant \"two\" as p2\n"),
+            "{}",
+            plantuml
+        );
+        assert!(plantuml.contains("p1 -> p2 : one / two\n"), "{}", plantuml);
+    }
+
+    #[test]
+    fn labeled_interactions() {
+        let cfg = cfg();
+        let probe = |id| modality_probe::ProbeId::new(id).unwrap();
+        let mut diamond_log = modality_probe_graph::test_support::diamond();
+        modality_probe_graph::test_support::tag_interaction(
+            &mut diamond_log,
+            probe(2),
+            probe(1),
+            4,
+        );
+        let graph = log_to_graph(diamond_log.into_iter().peekable(), false).unwrap();
+
+        let mermaid = graph.graph.as_interactions().mermaid(&cfg);
+        assert!(
+            mermaid.contains("    p1->>p2: one / two over four\n"),
+            "{}",
+            mermaid
+        );
+        assert!(
+            mermaid.contains("    p1->>p3: one / three\n"),
+            "{}",
+            mermaid
+        );
+    }
+}
@@ -157,6 +157,8 @@ pub struct Event<'a> {
 pub struct Edge<'a> {
     pub from: Event<'a>,
     pub to: Event<'a>,
+    pub has_label: bool,
+    pub label: Option<String>,
 }
 
 pub fn discrete_color_formatter(
@@ -267,7 +269,7 @@ pub const COMPLETE: &str = "digraph G \\{
 
     {{ for edge in edges }}
     {{ if edge.from.is_known }}{ edge.from.meta.name }{{ else }}UNKNOWN_EVENT_{ edge.from.raw_id }{{ endif }}_{ edge.from.probe_name }_{ edge.from.seq }_{ edge.from.seq_idx } ->
-    {{ if edge.to.is_known }}{ edge.to.meta.name }{{ else }}UNKNOWN_EVENT_{ edge.to.raw_id }{{ endif }}_{ edge.to.probe_name }_{ edge.to.seq }_{ edge.to.seq_idx };
+    {{ if edge.to.is_known }}{ edge.to.meta.name }{{ else }}UNKNOWN_EVENT_{ edge.to.raw_id }{{ endif }}_{ edge.to.probe_name }_{ edge.to.seq }_{ edge.to.seq_idx }{{ if edge.has_label }} [ label = \"{ edge.label }\" ]{{ endif }};
     {{ endfor }}
 }";
 
@@ -298,7 +300,7 @@ pub const INTERACTIONS: &str = "digraph G \\{
     {{ endfor }}
 
     {{ for edge in edges }}
-    {{ if not edge.from.is_known }}UNKNOWN_EVENT_{{ endif }}{ edge.from.probe_name }_{ edge.from.clock } -> {{ if not edge.to.is_known }}UNKNOWN_EVENT_{{ endif }}{ edge.to.probe_name }_{ edge.to.clock }
+    {{ if not edge.from.is_known }}UNKNOWN_EVENT_{{ endif }}{ edge.from.probe_name }_{ edge.from.clock } -> {{ if not edge.to.is_known }}UNKNOWN_EVENT_{{ endif }}{ edge.to.probe_name }_{ edge.to.clock }{{ if edge.has_label }} [ label = \"{ edge.label }\" ]{{ endif }}
     {{ endfor }}
 }";
 
@@ -374,6 +376,6 @@ pub const TOPO: &str = "digraph G \\{
     {{ endfor }}
 
     {{ for edge in edges }}
-    {{ if not edge.from.is_known }}UNKNOWN_PROBE_{{ endif }}{ edge.from.probe_name } -> {{ if not edge.to.is_known }}UNKNOWN_PROBE_{{ endif }}{ edge.to.probe_name }
+    {{ if not edge.from.is_known }}UNKNOWN_PROBE_{{ endif }}{ edge.from.probe_name } -> {{ if not edge.to.is_known }}UNKNOWN_PROBE_{{ endif }}{ edge.to.probe_name }{{ if edge.has_label }} [ label = \"{ edge.label }\" ]{{ endif }}
     {{ endfor }}
 }";
@@ -5,7 +5,7 @@ use std::{collections::HashMap, hash::Hash};
 
 use err_derive::Error;
 
-use modality_probe::{EventId, LogicalClock, ProbeId};
+use modality_probe::{EventId, LogicalClock, Nanoseconds, ProbeId, WallClockId};
 use modality_probe_collector_common::{EventLogEntry, Report, SequenceNumber};
 
 /// A trait for the inner graph type of `EventDiagraph`. This enables
@@ -16,6 +16,18 @@ pub trait Graph {
     fn add_node(&mut self, node: GraphEvent);
     /// Add an edge to the inner graph.
     fn add_edge(&mut self, source: GraphEvent, target: GraphEvent);
+    /// Add an edge between two probes whose snapshot was tagged with
+    /// an interaction id. Graphs that don't label their edges can
+    /// rely on the default, which adds a plain edge.
+    fn add_interaction_edge(
+        &mut self,
+        source: GraphEvent,
+        target: GraphEvent,
+        interaction_id: u32,
+    ) {
+        let _ = interaction_id;
+        self.add_edge(source, target);
+    }
 }
 
 /// A node in the event digraph.
@@ -27,6 +39,8 @@ pub struct GraphEvent {
     pub probe_id: ProbeId,
     pub seq: SequenceNumber,
     pub seq_idx: usize,
+    pub wall_time: Option<Nanoseconds>,
+    pub wall_clock_id: WallClockId,
 }
 
 /// Errors returned by the `EventDigraph` methods.
@@ -61,8 +75,9 @@ pub struct EventDigraph<G: Graph> {
     /// sequence number it appeared in the neighbor's log. That way,
     /// when we get the next chunk from that probe (the neighbor), we
     /// can draw the edge from this source to the first event in the
-    /// next chunk.
-    tail_pending_edge_sources: HashMap<(ProbeId, SequenceNumber), GraphEvent>,
+    /// next chunk. The interaction id, if the snapshot carried one,
+    /// rides along with the source.
+    tail_pending_edge_sources: HashMap<(ProbeId, SequenceNumber), (GraphEvent, Option<u32>)>,
 
     /// This is to carry a clock-span across chunks.
     last_event_by_probe_and_seq_num: HashMap<(ProbeId, SequenceNumber), GraphEvent>,
@@ -102,6 +117,11 @@ impl<G: Graph> EventDigraph<G> {
         let num_frontier_clocks = report.frontier_clocks.len();
 
         for (idx, ev) in report.event_log.iter().enumerate() {
+            let wall_time = match ev {
+                EventLogEntry::EventWithTime(t, ..)
+                | EventLogEntry::EventWithPayloadWithTime(t, ..) => Some(*t),
+                _ => None,
+            };
             match ev {
                 EventLogEntry::Event(id) | EventLogEntry::EventWithTime(.., id) => {
                     if include_internals || !id.is_internal() {
@@ -112,6 +132,8 @@ impl<G: Graph> EventDigraph<G> {
                             payload: None,
                             seq: seq_num,
                             seq_idx: idx.saturating_add(num_frontier_clocks),
+                            wall_time,
+                            wall_clock_id: report.wall_clock_id,
                         };
                         self.add_event_to_graph(
                             node,
@@ -125,6 +147,22 @@ impl<G: Graph> EventDigraph<G> {
                     }
                 }
                 EventLogEntry::EventWithPayload(id, payload)
+                | EventLogEntry::EventWithPayloadWithTime(_, id, payload)
+                    if *id == EventId::EVENT_INTERACTION_ID =>
+                {
+                    // Interaction ids label the edge of the merge
+                    // they follow rather than standing as events.
+                    if let Some((_, interaction)) = pending_edges.last_mut() {
+                        *interaction = Some(*payload);
+                    } else if idx == 0 {
+                        if let Some((_, interaction)) =
+                            self.tail_pending_edge_sources.get_mut(&(probe_id, seq_num))
+                        {
+                            *interaction = Some(*payload);
+                        }
+                    }
+                }
+                EventLogEntry::EventWithPayload(id, payload)
                 | EventLogEntry::EventWithPayloadWithTime(.., id, payload) => {
                     if include_internals || !id.is_internal() {
                         let node = GraphEvent {
@@ -133,7 +171,9 @@ impl<G: Graph> EventDigraph<G> {
                             clock: *self_clock,
                             payload: Some(*payload),
                             seq: seq_num,
-                            seq_idx: idx,
+                            seq_idx: idx.saturating_add(num_frontier_clocks),
+                            wall_time,
+                            wall_clock_id: report.wall_clock_id,
                         };
                         self.add_event_to_graph(
                             node,
@@ -172,8 +212,10 @@ impl<G: Graph> EventDigraph<G> {
                         }
                         self_clock = lc;
                     } else {
-                        pending_edges
-                            .push((lc.id, modality_probe::pack_clock_word(lc.epoch, lc.ticks)));
+                        pending_edges.push((
+                            (lc.id, modality_probe::pack_clock_word(lc.epoch, lc.ticks)),
+                            None,
+                        ));
                     }
                     prev_tc = Some(*lc);
                 }
@@ -191,7 +233,7 @@ impl<G: Graph> EventDigraph<G> {
                     modality_probe::pack_clock_word(ptc.epoch, ptc.ticks),
                 )) {
                     self.tail_pending_edge_sources
-                        .insert((probe_id, seq_num), *ev);
+                        .insert((probe_id, seq_num), (*ev, None));
                 }
             }
         }
@@ -202,7 +244,7 @@ impl<G: Graph> EventDigraph<G> {
     fn add_event_to_graph(
         &mut self,
         node: GraphEvent,
-        pending_edges: &mut Vec<(ProbeId, u32)>,
+        pending_edges: &mut Vec<PendingEdge>,
         prev_event: &mut Option<GraphEvent>,
         prev_tc: &mut Option<LogicalClock>,
         first_event: &mut bool,
@@ -211,8 +253,10 @@ impl<G: Graph> EventDigraph<G> {
     ) {
         self.graph.add_node(node);
         if *first_event {
-            if let Some(tail) = self.tail_pending_edge_sources.remove(&(probe_id, seq_num)) {
-                self.graph.add_edge(tail, node);
+            if let Some((tail, interaction)) =
+                self.tail_pending_edge_sources.remove(&(probe_id, seq_num))
+            {
+                self.add_merge_edge(tail, node, interaction);
             }
             if let Some(tail) = self
                 .last_event_by_probe_and_seq_num
@@ -225,17 +269,29 @@ impl<G: Graph> EventDigraph<G> {
         if let Some(prev) = prev_event {
             self.graph.add_edge(*prev, node);
         }
-        for lc in pending_edges.iter() {
-            if let Some(e) = self.last_event_by_probe_and_clock.get(lc) {
-                self.graph.add_edge(*e, node);
+        for (lc, interaction) in pending_edges.iter() {
+            if let Some(e) = self.last_event_by_probe_and_clock.get(lc).copied() {
+                self.add_merge_edge(e, node, *interaction);
             }
         }
         pending_edges.clear();
         *prev_event = Some(node);
         *prev_tc = None;
     }
+
+    fn add_merge_edge(&mut self, source: GraphEvent, target: GraphEvent, interaction: Option<u32>) {
+        match interaction {
+            Some(id) => self.graph.add_interaction_edge(source, target, id),
+            None => self.graph.add_edge(source, target),
+        }
+    }
 }
 
+/// A foreign clock (probe id and packed clock word) waiting for the
+/// next event to draw its edge to, along with the interaction id its
+/// snapshot carried, if any.
+type PendingEdge = ((ProbeId, u32), Option<u32>);
+
 #[cfg(any(test, feature = "test_support"))]
 pub mod test_support {
     use chrono::prelude::*;
@@ -629,6 +685,40 @@ pub mod test_support {
             },
         ]
     }
+
+    /// Tag the snapshot `receiver` merged from `sender` with an
+    /// interaction id, as though the snapshot had carried one.
+    pub fn tag_interaction(
+        log: &mut Vec<ReportLogEntry>,
+        receiver: ProbeId,
+        sender: ProbeId,
+        interaction_id: u32,
+    ) {
+        let merge_idx = log
+            .iter()
+            .position(|e| {
+                e.probe_id == receiver
+                    && matches!(e.data, LogEntryData::TraceClock(lc) if lc.id == sender)
+            })
+            .expect("receiver never merged a snapshot from sender");
+        let merge = log[merge_idx].clone();
+        for e in log.iter_mut() {
+            if e.probe_id == receiver
+                && e.sequence_number == merge.sequence_number
+                && e.sequence_index > merge.sequence_index
+            {
+                e.sequence_index += 1;
+            }
+        }
+        log.insert(
+            merge_idx + 1,
+            ReportLogEntry {
+                sequence_index: merge.sequence_index + 1,
+                data: LogEntryData::EventWithPayload(EventId::EVENT_INTERACTION_ID, interaction_id),
+                ..merge
+            },
+        );
+    }
 }
 
 #[cfg(test)]
@@ -652,6 +742,32 @@ mod test {
         edges: HashSet<(GraphEvent, GraphEvent)>,
     }
 
+    #[derive(Default)]
+    struct LabeledEdgeList {
+        nodes: HashSet<GraphEvent>,
+        edges: HashMap<(ProbeId, ProbeId), Option<u32>>,
+    }
+
+    impl Graph for LabeledEdgeList {
+        fn add_node(&mut self, node: GraphEvent) {
+            self.nodes.insert(node);
+        }
+
+        fn add_edge(&mut self, source: GraphEvent, target: GraphEvent) {
+            self.edges.insert((source.probe_id, target.probe_id), None);
+        }
+
+        fn add_interaction_edge(
+            &mut self,
+            source: GraphEvent,
+            target: GraphEvent,
+            interaction_id: u32,
+        ) {
+            self.edges
+                .insert((source.probe_id, target.probe_id), Some(interaction_id));
+        }
+    }
+
     impl Graph for NodeAndEdgeList {
         fn add_node(&mut self, node: GraphEvent) {
             self.nodes.insert(node);
@@ -691,6 +807,8 @@ mod test {
             },
             seq: SequenceNumber(1),
             seq_idx: 1,
+            wall_time: None,
+            wall_clock_id: WallClockId(0),
         };
         let two = GraphEvent {
             id: EventId::new(2).unwrap(),
@@ -703,6 +821,8 @@ mod test {
             },
             seq: SequenceNumber(1),
             seq_idx: 3,
+            wall_time: None,
+            wall_clock_id: WallClockId(0),
         };
         let three = GraphEvent {
             id: EventId::new(3).unwrap(),
@@ -715,6 +835,8 @@ mod test {
             },
             seq: SequenceNumber(1),
             seq_idx: 3,
+            wall_time: None,
+            wall_clock_id: WallClockId(0),
         };
         let four = GraphEvent {
             id: EventId::new(4).unwrap(),
@@ -727,6 +849,8 @@ mod test {
             },
             seq: SequenceNumber(1),
             seq_idx: 5,
+            wall_time: None,
+            wall_clock_id: WallClockId(0),
         };
         expected.add_node(one);
         expected.add_node(two);
@@ -745,6 +869,31 @@ mod test {
         );
     }
 
+    #[test]
+    fn interaction_edges() {
+        let probe = |id| ProbeId::new(id).unwrap();
+        let mut log = test_support::diamond();
+        test_support::tag_interaction(&mut log, probe(2), probe(1), 0x123);
+        let report_iter = ReportIter::new(log.into_iter().peekable());
+
+        let mut graph = EventDigraph::new(LabeledEdgeList::default());
+        for report in report_iter {
+            graph.add_report(&report, true).unwrap();
+        }
+
+        assert_eq!(
+            graph.graph.edges.get(&(probe(1), probe(2))),
+            Some(&Some(0x123))
+        );
+        assert_eq!(graph.graph.edges.get(&(probe(1), probe(3))), Some(&None));
+        // The interaction id labels the edge instead of being a node
+        assert!(graph
+            .graph
+            .nodes
+            .iter()
+            .all(|n| n.id != EventId::EVENT_INTERACTION_ID));
+    }
+
     #[test]
     fn internals() {
         let now = Utc::now();
@@ -862,6 +1011,8 @@ mod test {
             },
             seq: SequenceNumber(1),
             seq_idx: 1,
+            wall_time: None,
+            wall_clock_id: WallClockId(0),
         };
         let one_prime = GraphEvent {
             id: EventId::new(1).unwrap(),
@@ -874,6 +1025,8 @@ mod test {
             },
             seq: SequenceNumber(1),
             seq_idx: 4,
+            wall_time: None,
+            wall_clock_id: WallClockId(0),
         };
         expected.add_node(one);
         expected.add_node(one_prime);
@@ -882,4 +1035,59 @@ mod test {
 
         assert_eq!(graph.graph, expected);
     }
+
+    #[test]
+    fn payload_events_keep_their_log_index() {
+        let now = Utc::now();
+        let probe1 = ProbeId::new(1).unwrap();
+        let clock = LogicalClock {
+            id: probe1,
+            epoch: ProbeEpoch(0),
+            ticks: ProbeTicks(0),
+        };
+        let log: Vec<ReportLogEntry> = vec![
+            LogEntryData::FrontierClock(clock),
+            LogEntryData::Event(EventId::new(1).unwrap()),
+            LogEntryData::EventWithPayload(EventId::new(2).unwrap(), 7),
+            LogEntryData::EventWithPayloadWithTime(
+                Nanoseconds::new(10).unwrap(),
+                EventId::new(3).unwrap(),
+                8,
+            ),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(idx, data)| ReportLogEntry {
+            session_id: SessionId(1),
+            sequence_number: SequenceNumber(1),
+            sequence_index: idx as u32,
+            time_resolution: NanosecondResolution(0),
+            wall_clock_id: WallClockId(0),
+            receive_time: now,
+            clock,
+            persistent_epoch_counting: false,
+            probe_id: probe1,
+            data,
+        })
+        .collect();
+
+        let mut graph = EventDigraph::new(NodeAndEdgeList {
+            nodes: HashSet::new(),
+            edges: HashSet::new(),
+        });
+        for report in ReportIter::new(log.into_iter().peekable()) {
+            graph.add_report(&report, false).unwrap();
+        }
+
+        // Events with and without payloads are both offset by the
+        // frontier clock, so each keeps its own place in the log
+        let mut indices: Vec<(u32, usize)> = graph
+            .graph
+            .nodes
+            .iter()
+            .map(|n| (n.id.get_raw(), n.seq_idx))
+            .collect();
+        indices.sort_unstable();
+        assert_eq!(indices, vec![(1, 1), (2, 2), (3, 3)]);
+    }
 }
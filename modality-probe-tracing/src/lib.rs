@@ -0,0 +1,862 @@
+//! A [`tracing_subscriber::Layer`] that records `tracing` spans and
+//! events with Modality probes.
+//!
+//! Each thread that emits tracing data gets its own probe, which is
+//! handed on to the next new thread once it exits. Events are
+//! recorded as-is, and entering or exiting a span records a
+//! `<NAME>_BEGIN` / `<NAME>_END` event pair carrying the tracing span
+//! id, following the same conventions as the `span_begin!` and
+//! `span_end!` macros. A causal snapshot travels with every span in
+//! its extensions, so a span that is entered on a different thread
+//! than the one it was created or last exited on merges that history
+//! into the entering thread's probe.
+//!
+//! Event ids are derived from the callsite metadata, so they are
+//! stable across runs of the same program. The layer can write out
+//! the manifest (events.csv, probes.csv, and Component.toml) for the
+//! callsites and threads it has seen so far, for use with the
+//! `modality-probe` CLI.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem::MaybeUninit;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::thread::{self, ThreadId};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing_core::{
+    callsite::Identifier,
+    span::{Attributes, Id},
+    subscriber::{Interest, Subscriber},
+    Event, Metadata,
+};
+use tracing_subscriber::{
+    layer::{Context, Layer},
+    registry::LookupSpan,
+};
+
+use modality_probe::{
+    CausalSnapshot, EventId, ModalityProbe, NanosecondResolution, Nanoseconds, Probe, ProbeId,
+    ReportError, RestartCounterProvider, WallClockId,
+};
+
+/// Tag attached to every event generated by this layer
+pub const TRACING_TAG: &str = "tracing";
+/// Tag attached to the events recorded when a span is entered
+pub const SPAN_BEGIN_TAG: &str = "SPAN_BEGIN";
+/// Tag attached to the events recorded when a span is exited
+pub const SPAN_END_TAG: &str = "SPAN_END";
+
+/// Configuration for a [`ModalityLayer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// The probe id given to the first thread that emits tracing
+    /// data; later threads reuse the lowest id left by a thread that
+    /// has exited, or take the next consecutive id
+    pub first_probe_id: ProbeId,
+    /// Size, in bytes, of the storage given to each thread's probe
+    pub probe_storage_bytes: usize,
+    /// Wall clock id reported by every probe
+    pub wall_clock_id: WallClockId,
+    /// Whether to attach the system time to recorded events
+    pub record_time: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            first_probe_id: ProbeId::new(1).unwrap(),
+            probe_storage_bytes: 4096,
+            wall_clock_id: WallClockId::local_only(),
+            record_time: false,
+        }
+    }
+}
+
+/// An event generated by the layer, as it would appear in the
+/// component's events.csv
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventEntry {
+    pub id: EventId,
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub type_hint: Option<&'static str>,
+    pub file: Option<&'static str>,
+    pub line: Option<u32>,
+}
+
+/// A probe created by the layer, as it would appear in the
+/// component's probes.csv
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeEntry {
+    pub id: ProbeId,
+    pub name: String,
+    pub description: String,
+}
+
+/// The ids recorded for a single callsite
+#[derive(Debug, Clone, Copy)]
+enum CallsiteIds {
+    Event(EventId),
+    Span { begin: EventId, end: EventId },
+}
+
+#[derive(Default)]
+struct Callsites {
+    by_callsite: HashMap<Identifier, CallsiteIds>,
+    events: BTreeMap<u32, EventEntry>,
+}
+
+impl Callsites {
+    fn ids(&mut self, metadata: &'static Metadata<'static>) -> CallsiteIds {
+        if let Some(ids) = self.by_callsite.get(&metadata.callsite()) {
+            return *ids;
+        }
+
+        let name = event_name(metadata);
+        let mut tags = vec![
+            TRACING_TAG.to_string(),
+            metadata.level().to_string().to_uppercase(),
+        ];
+        let ids = if metadata.is_span() {
+            let mut begin_tags = tags.clone();
+            begin_tags.push(SPAN_BEGIN_TAG.to_string());
+            tags.push(SPAN_END_TAG.to_string());
+            CallsiteIds::Span {
+                begin: self.insert(metadata, format!("{}_BEGIN", name), begin_tags),
+                end: self.insert(metadata, format!("{}_END", name), tags),
+            }
+        } else {
+            CallsiteIds::Event(self.insert(metadata, name, tags))
+        };
+        self.by_callsite.insert(metadata.callsite(), ids);
+        ids
+    }
+
+    fn insert(
+        &mut self,
+        metadata: &'static Metadata<'static>,
+        name: String,
+        tags: Vec<String>,
+    ) -> EventId {
+        // A name that is already present (e.g. the same span declared at
+        // two callsites) keeps its existing id.
+        if let Some(id) = self.existing_id(&name) {
+            return id;
+        }
+        let raw_id = event_id_for_name(&name);
+        let id = EventId::new(raw_id).expect("Generated event id is within the user range");
+        self.events.insert(
+            raw_id,
+            EventEntry {
+                id,
+                name,
+                description: metadata.name().to_string(),
+                tags,
+                type_hint: if metadata.is_span() {
+                    Some("u32")
+                } else {
+                    None
+                },
+                file: metadata.file(),
+                line: metadata.line(),
+            },
+        );
+        id
+    }
+
+    /// The id already given to the event named `name`, if any
+    ///
+    /// Panics if `name` hashes to the id of a different event. Probing
+    /// for a free id instead would make the ids depend on the order the
+    /// callsites happen to be registered in.
+    fn existing_id(&self, name: &str) -> Option<EventId> {
+        let entry = self.events.get(&event_id_for_name(name))?;
+        assert!(
+            entry.name == name,
+            "The tracing events {} and {} hash to the same event id ({}); rename one of them",
+            entry.name,
+            name,
+            entry.id.get_raw()
+        );
+        Some(entry.id)
+    }
+}
+
+/// A thread's probe, along with the storage it lives in
+struct ThreadProbe {
+    id: ProbeId,
+    storage: *mut [MaybeUninit<u8>],
+    probe: *mut ModalityProbe<'static>,
+}
+
+// The probe lives in storage owned by this struct, is only reachable
+// through the mutex that guards it, and is always created with
+// `RestartCounterProvider::NoRestartTracking`, so it holds no
+// references to thread-local state.
+unsafe impl Send for ThreadProbe {}
+
+impl ThreadProbe {
+    fn new(storage_bytes: usize, id: ProbeId, wall_clock_id: WallClockId) -> Option<Self> {
+        let storage = Box::into_raw(vec![MaybeUninit::uninit(); storage_bytes].into_boxed_slice());
+        // Safe because the storage is only freed when this struct is
+        // dropped, and the probe is never handed out beyond a borrow of it.
+        let probe = ModalityProbe::initialize_at(
+            unsafe { &mut *storage },
+            id,
+            NanosecondResolution::UNSPECIFIED,
+            wall_clock_id,
+            RestartCounterProvider::NoRestartTracking,
+        );
+        match probe {
+            Ok(probe) => Some(ThreadProbe { id, storage, probe }),
+            Err(_) => {
+                drop(unsafe { Box::from_raw(storage) });
+                None
+            }
+        }
+    }
+
+    fn get(&mut self) -> &mut ModalityProbe<'static> {
+        unsafe { &mut *self.probe }
+    }
+}
+
+impl Drop for ThreadProbe {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.storage) });
+    }
+}
+
+#[derive(Default)]
+struct Probes {
+    by_thread: HashMap<ThreadId, Arc<Mutex<ThreadProbe>>>,
+    /// Probes left behind by threads that have exited, by raw id
+    idle: BTreeMap<u32, Arc<Mutex<ThreadProbe>>>,
+    entries: Vec<ProbeEntry>,
+}
+
+thread_local! {
+    /// The probes the current thread holds, released when it exits
+    static LEASES: RefCell<Vec<ProbeLease>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A thread's hold on one of a layer's probes
+struct ProbeLease {
+    inner: Weak<Inner>,
+    thread_id: ThreadId,
+    probe_id: ProbeId,
+}
+
+impl Drop for ProbeLease {
+    fn drop(&mut self) {
+        // Only runs once the thread is gone, so nothing can be using the
+        // probe anymore; park it for the next new thread to pick up.
+        if let Some(inner) = self.inner.upgrade() {
+            let mut probes = inner.probes.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(probe) = probes.by_thread.remove(&self.thread_id) {
+                probes.idle.insert(self.probe_id.get_raw(), probe);
+            }
+        }
+    }
+}
+
+/// The causal history of a span, stored in its extensions
+#[derive(Debug, Clone)]
+struct SpanSnapshot(CausalSnapshot);
+
+/// A `tracing_subscriber::Layer` that records spans and events with
+/// a Modality probe per thread
+///
+/// Clones share the same probes, so a clone can be kept around to
+/// produce reports and the manifest after the layer is installed.
+#[derive(Clone)]
+pub struct ModalityLayer {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: Config,
+    callsites: RwLock<Callsites>,
+    probes: Mutex<Probes>,
+}
+
+impl ModalityLayer {
+    /// Create a layer with the default configuration
+    pub fn new() -> Self {
+        Self::with_config(Config::default())
+    }
+
+    /// Create a layer with the given configuration
+    pub fn with_config(config: Config) -> Self {
+        ModalityLayer {
+            inner: Arc::new(Inner {
+                config,
+                callsites: RwLock::new(Callsites::default()),
+                probes: Mutex::new(Probes::default()),
+            }),
+        }
+    }
+
+    /// The events generated so far, ordered by id
+    pub fn events(&self) -> Vec<EventEntry> {
+        self.inner
+            .callsites
+            .read()
+            .unwrap()
+            .events
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// The probes created so far, ordered by id
+    pub fn probes(&self) -> Vec<ProbeEntry> {
+        self.inner.probes.lock().unwrap().entries.clone()
+    }
+
+    /// Drain the logs of every probe into reports, handing each one to
+    /// `sink` along with the id of the probe that produced it
+    pub fn report<F>(&self, buffer: &mut [u8], mut sink: F) -> Result<(), ReportError>
+    where
+        F: FnMut(ProbeId, &[u8]),
+    {
+        let probes: Vec<_> = {
+            let probes = self.inner.probes.lock().unwrap();
+            probes
+                .by_thread
+                .values()
+                .chain(probes.idle.values())
+                .cloned()
+                .collect()
+        };
+        for probe in probes {
+            let mut probe = probe.lock().unwrap();
+            while let Some(n) = probe.get().report(buffer)? {
+                sink(probe.id, &buffer[..n.get()]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the events.csv, probes.csv, and Component.toml files for
+    /// the callsites and threads seen so far into `component_path`
+    pub fn write_manifest<P: AsRef<Path>>(
+        &self,
+        component_path: P,
+        component_name: &str,
+        component_id: &str,
+    ) -> io::Result<()> {
+        let component_path = component_path.as_ref();
+        fs::create_dir_all(component_path)?;
+        fs::write(
+            component_path.join("Component.toml"),
+            format!("name = \"{}\"\nid = \"{}\"\n", component_name, component_id),
+        )?;
+        write_events_csv(
+            fs::File::create(component_path.join("events.csv"))?,
+            component_id,
+            &self.events(),
+        )?;
+        write_probes_csv(
+            fs::File::create(component_path.join("probes.csv"))?,
+            component_id,
+            &self.probes(),
+        )
+    }
+
+    fn ids(&self, metadata: &'static Metadata<'static>) -> CallsiteIds {
+        if let Some(ids) = self
+            .inner
+            .callsites
+            .read()
+            .unwrap()
+            .by_callsite
+            .get(&metadata.callsite())
+        {
+            return *ids;
+        }
+        self.inner.callsites.write().unwrap().ids(metadata)
+    }
+
+    /// Run `f` with the current thread's probe, taking over an idle
+    /// one or creating one on first use. Nothing is run if the probe
+    /// could not be created.
+    fn with_probe<R>(&self, f: impl FnOnce(&mut ThreadProbe) -> R) -> Option<R> {
+        let probe = {
+            let mut probes = self.inner.probes.lock().unwrap();
+            let thread_id = thread::current().id();
+            match probes.by_thread.get(&thread_id) {
+                Some(p) => p.clone(),
+                None => {
+                    let (probe_id, probe) = match probes.idle.keys().next().copied() {
+                        Some(raw_id) => {
+                            let probe = probes.idle.remove(&raw_id).unwrap();
+                            (ProbeId::new(raw_id)?, probe)
+                        }
+                        None => {
+                            let probe = self.new_probe(&mut probes)?;
+                            (probe.id, Arc::new(Mutex::new(probe)))
+                        }
+                    };
+                    probes.by_thread.insert(thread_id, probe.clone());
+                    // The lease is built inside the closure so that it is
+                    // never dropped while the lock above is held. If the
+                    // thread is already tearing down its thread-locals, the
+                    // probe stays with it until the layer is dropped.
+                    let inner = Arc::downgrade(&self.inner);
+                    let _ = LEASES.try_with(|leases| {
+                        leases.borrow_mut().push(ProbeLease {
+                            inner,
+                            thread_id,
+                            probe_id,
+                        })
+                    });
+                    probe
+                }
+            }
+        };
+        let mut probe = probe.lock().unwrap();
+        Some(f(&mut probe))
+    }
+
+    fn new_probe(&self, probes: &mut Probes) -> Option<ThreadProbe> {
+        let n = probes.entries.len() as u32;
+        let probe_id = ProbeId::new(self.inner.config.first_probe_id.get_raw().checked_add(n)?)?;
+        let probe = ThreadProbe::new(
+            self.inner.config.probe_storage_bytes,
+            probe_id,
+            self.inner.config.wall_clock_id,
+        )?;
+
+        let thread = thread::current();
+        let thread_name = thread.name().unwrap_or("thread");
+        probes.entries.push(ProbeEntry {
+            id: probe_id,
+            name: format!("{}_{}", sanitize(thread_name), n),
+            description: format!("Tracing probe for thread '{}'", thread_name),
+        });
+        Some(probe)
+    }
+
+    fn now(&self) -> Option<Nanoseconds> {
+        if !self.inner.config.record_time {
+            return None;
+        }
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        Nanoseconds::new(since_epoch.as_nanos() as u64)
+    }
+}
+
+impl Default for ModalityLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ModalityLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModalityLayer")
+            .field("config", &self.inner.config)
+            .finish()
+    }
+}
+
+impl<S> Layer<S> for ModalityLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.ids(metadata);
+        Interest::always()
+    }
+
+    fn new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(snapshot) = self.with_probe(|p| p.get().produce_snapshot()) {
+                span.extensions_mut().insert(SpanSnapshot(snapshot));
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if let CallsiteIds::Event(event_id) = self.ids(event.metadata()) {
+            let now = self.now();
+            self.with_probe(|p| match now {
+                Some(t) => p.get().record_event_with_time(event_id, t),
+                None => p.get().record_event(event_id),
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        if let CallsiteIds::Span { begin, .. } = self.ids(span.metadata()) {
+            let snapshot = span.extensions().get::<SpanSnapshot>().map(|s| s.0.clone());
+            let span_id = id.into_u64() as u32;
+            let now = self.now();
+            self.with_probe(|p| {
+                if let Some(snapshot) = snapshot {
+                    if snapshot.clock.id != p.id {
+                        p.get().merge_snapshot(&snapshot);
+                    }
+                }
+                match now {
+                    Some(t) => p
+                        .get()
+                        .record_event_with_payload_with_time(begin, span_id, t),
+                    None => p.get().record_event_with_payload(begin, span_id),
+                }
+            });
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        if let CallsiteIds::Span { end, .. } = self.ids(span.metadata()) {
+            let span_id = id.into_u64() as u32;
+            let now = self.now();
+            let snapshot = self.with_probe(|p| {
+                match now {
+                    Some(t) => p.get().record_event_with_payload_with_time(end, span_id, t),
+                    None => p.get().record_event_with_payload(end, span_id),
+                }
+                p.get().produce_snapshot()
+            });
+            if let Some(snapshot) = snapshot {
+                span.extensions_mut().replace(SpanSnapshot(snapshot));
+            }
+        }
+    }
+}
+
+/// The manifest name for a callsite: its target and name, upper-cased
+/// with anything that isn't alphanumeric collapsed into underscores
+pub fn event_name(metadata: &Metadata<'_>) -> String {
+    sanitize(&format!("{}_{}", metadata.target(), metadata.name()))
+}
+
+/// A stable id in the user event id range derived from an event name
+pub fn event_id_for_name(name: &str) -> u32 {
+    // 32-bit FNV-1a
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, b| {
+        (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+    });
+    hash % EventId::MAX_USER_ID + 1
+}
+
+fn sanitize(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_uppercase());
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    while out.ends_with('_') {
+        out.pop();
+    }
+    out
+}
+
+fn write_events_csv<W: io::Write>(
+    w: W,
+    component_id: &str,
+    events: &[EventEntry],
+) -> io::Result<()> {
+    let mut w = csv::Writer::from_writer(w);
+    w.write_record([
+        "component_id",
+        "id",
+        "name",
+        "description",
+        "tags",
+        "type_hint",
+        "file",
+        "line",
+    ])?;
+    for e in events {
+        w.write_record([
+            component_id,
+            &e.id.get_raw().to_string(),
+            &e.name,
+            &e.description,
+            &e.tags.join(";"),
+            e.type_hint.unwrap_or(""),
+            e.file.unwrap_or(""),
+            &e.line.map(|l| l.to_string()).unwrap_or_default(),
+        ])?;
+    }
+    w.flush()
+}
+
+fn write_probes_csv<W: io::Write>(
+    w: W,
+    component_id: &str,
+    probes: &[ProbeEntry],
+) -> io::Result<()> {
+    let mut w = csv::Writer::from_writer(w);
+    w.write_record([
+        "component_id",
+        "id",
+        "name",
+        "description",
+        "tags",
+        "file",
+        "line",
+    ])?;
+    for p in probes {
+        w.write_record([
+            component_id,
+            &p.id.get_raw().to_string(),
+            &p.name,
+            &p.description,
+            TRACING_TAG,
+            "",
+            "",
+        ])?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modality_probe_collector_common::{EventLogEntry, Report};
+    use pretty_assertions::assert_eq;
+    use std::convert::TryFrom;
+    use tracing::{dispatcher, info, info_span, warn, Dispatch};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn dispatch(layer: &ModalityLayer) -> Dispatch {
+        Dispatch::new(Registry::default().with(layer.clone()))
+    }
+
+    fn reports(layer: &ModalityLayer) -> Vec<Report> {
+        let mut buf = vec![0u8; 1024];
+        let mut reports = Vec::new();
+        layer
+            .report(&mut buf, |_, bytes| {
+                reports.push(Report::try_from(bytes).unwrap())
+            })
+            .unwrap();
+        reports.sort_by_key(|r| r.probe_id);
+        reports
+    }
+
+    fn id_of(layer: &ModalityLayer, suffix: &str) -> EventId {
+        layer
+            .events()
+            .into_iter()
+            .find(|e| e.name.ends_with(suffix))
+            .unwrap_or_else(|| panic!("No event named *{}", suffix))
+            .id
+    }
+
+    #[test]
+    fn records_spans_and_events() {
+        let layer = ModalityLayer::new();
+        dispatcher::with_default(&dispatch(&layer), || {
+            let span = info_span!("work");
+            let _guard = span.enter();
+            info!("hello");
+        });
+
+        let begin = id_of(&layer, "_WORK_BEGIN");
+        let end = id_of(&layer, "_WORK_END");
+        let event = layer
+            .events()
+            .into_iter()
+            .find(|e| e.tags == vec!["tracing".to_string(), "INFO".to_string()])
+            .unwrap();
+        assert!(event
+            .name
+            .starts_with("MODALITY_PROBE_TRACING_TESTS_EVENT_"));
+        assert!(event.name.contains("_SRC_LIB_RS_"));
+        assert_eq!(event.type_hint, None);
+
+        let reports = reports(&layer);
+        assert_eq!(reports.len(), 1);
+        let recorded: Vec<_> = reports[0]
+            .event_log
+            .iter()
+            .filter(|e| match e {
+                EventLogEntry::Event(id) | EventLogEntry::EventWithPayload(id, _) => {
+                    !id.is_internal()
+                }
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        assert_eq!(recorded.len(), 3);
+        match (&recorded[0], &recorded[1], &recorded[2]) {
+            (
+                EventLogEntry::EventWithPayload(b, b_id),
+                EventLogEntry::Event(e),
+                EventLogEntry::EventWithPayload(x, x_id),
+            ) => {
+                assert_eq!((*b, *e, *x), (begin, event.id, end));
+                assert_eq!(b_id, x_id);
+            }
+            other => panic!("Unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn event_ids_are_stable_and_unique() {
+        let name = "MY_APP_EVENT_SRC_MAIN_RS_12";
+        assert_eq!(event_id_for_name(name), event_id_for_name(name));
+        assert!(event_id_for_name(name) <= EventId::MAX_USER_ID);
+        assert_eq!(sanitize("my_app::net"), "MY_APP_NET");
+        assert_eq!(sanitize("event src/main.rs:12"), "EVENT_SRC_MAIN_RS_12");
+
+        let layer = ModalityLayer::new();
+        dispatcher::with_default(&dispatch(&layer), || {
+            info!("a");
+            warn!("b");
+            info_span!("c").in_scope(|| ());
+        });
+        let mut ids: Vec<_> = layer.events().iter().map(|e| e.id).collect();
+        assert_eq!(ids.len(), 4);
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "hash to the same event id")]
+    fn event_id_collisions_fail_loudly() {
+        let first = "MODALITY_PROBE_TRACING_TESTS_COLLIDE_9717_BEGIN";
+        let second = "MODALITY_PROBE_TRACING_TESTS_COLLIDE_31160_END";
+        let raw_id = event_id_for_name(first);
+        assert_eq!(raw_id, event_id_for_name(second));
+
+        let mut callsites = Callsites::default();
+        callsites.events.insert(
+            raw_id,
+            EventEntry {
+                id: EventId::new(raw_id).unwrap(),
+                name: first.to_string(),
+                description: "collide_9717".to_string(),
+                tags: vec![TRACING_TAG.to_string()],
+                type_hint: Some("u32"),
+                file: None,
+                line: None,
+            },
+        );
+        assert_eq!(callsites.existing_id(first), EventId::new(raw_id));
+        callsites.existing_id(second);
+    }
+
+    #[test]
+    fn span_snapshots_carry_causality_across_threads() {
+        let layer = ModalityLayer::new();
+        let d = dispatch(&layer);
+        let span = dispatcher::with_default(&d, || {
+            info!("before");
+            info_span!("handoff")
+        });
+        let main_probe = layer.probes()[0].id;
+
+        let d2 = d.clone();
+        std::thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(move || {
+                dispatcher::with_default(&d2, || span.in_scope(|| info!("inside")));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let probes = layer.probes();
+        assert_eq!(probes.len(), 2);
+        assert_eq!(probes[1].name, "WORKER_1");
+
+        let reports = reports(&layer);
+        assert_eq!(reports.len(), 2);
+        let worker = reports.iter().find(|r| r.probe_id == probes[1].id).unwrap();
+        assert!(worker
+            .event_log
+            .iter()
+            .any(|e| e.trace_clock().map(|c| c.id) == Some(main_probe)));
+    }
+
+    #[test]
+    fn exited_threads_hand_their_probes_on() {
+        let layer = ModalityLayer::new();
+        let d = dispatch(&layer);
+        dispatcher::with_default(&d, || info!("main"));
+
+        for name in &["first", "second", "third"] {
+            let d = d.clone();
+            std::thread::Builder::new()
+                .name(name.to_string())
+                .spawn(move || dispatcher::with_default(&d, || info!("worker")))
+                .unwrap()
+                .join()
+                .unwrap();
+        }
+
+        let probes = layer.probes();
+        assert_eq!(probes.len(), 2);
+        assert_eq!(probes[1].name, "FIRST_1");
+        let reports = reports(&layer);
+        assert_eq!(reports.len(), 2);
+        let worker = reports.iter().find(|r| r.probe_id == probes[1].id).unwrap();
+        let recorded = worker
+            .event_log
+            .iter()
+            .filter(|e| matches!(e, EventLogEntry::Event(id) if !id.is_internal()))
+            .count();
+        assert_eq!(recorded, 3);
+    }
+
+    #[test]
+    fn writes_manifest() {
+        let layer = ModalityLayer::new();
+        dispatcher::with_default(&dispatch(&layer), || {
+            info_span!("manifest").in_scope(|| ());
+        });
+        let dir = std::env::temp_dir().join(format!("modality-tracing-{}", std::process::id()));
+        layer
+            .write_manifest(&dir, "tracing-test", "146dd760-fc41-4418-bc59-e1320fb7f43d")
+            .unwrap();
+
+        let component = fs::read_to_string(dir.join("Component.toml")).unwrap();
+        assert_eq!(
+            component,
+            "name = \"tracing-test\"\nid = \"146dd760-fc41-4418-bc59-e1320fb7f43d\"\n"
+        );
+
+        let events = fs::read_to_string(dir.join("events.csv")).unwrap();
+        let mut lines = events.lines();
+        assert_eq!(
+            lines.next(),
+            Some("component_id,id,name,description,tags,type_hint,file,line")
+        );
+        let begin = id_of(&layer, "_MANIFEST_BEGIN");
+        assert!(events.contains(&format!(
+            "146dd760-fc41-4418-bc59-e1320fb7f43d,{},MODALITY_PROBE_TRACING_TESTS_MANIFEST_BEGIN,manifest,tracing;INFO;SPAN_BEGIN,u32,",
+            begin.get_raw()
+        )));
+
+        let probes = fs::read_to_string(dir.join("probes.csv")).unwrap();
+        assert_eq!(
+            probes.lines().next(),
+            Some("component_id,id,name,description,tags,file,line")
+        );
+        assert_eq!(probes.lines().count(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -196,6 +196,10 @@ pub enum MergeError {
     /// The external history violated a semantic rule of the protocol,
     /// such as by having a probe_id out of the allowed value range.
     ExternalHistorySemantics,
+    /// The external history was a compact snapshot with a truncated
+    /// epoch, from a probe whose epoch isn't known locally, so its
+    /// whole epoch couldn't be reconstructed.
+    AmbiguousEpoch,
 }
 
 #[cfg(feature = "std")]
@@ -209,6 +213,7 @@ impl fmt::Display for MergeError {
             MergeError::ExternalHistorySemantics => {
                 f.write_str("External history semantic violation")
             }
+            MergeError::AmbiguousEpoch => f.write_str("Ambiguous external history epoch"),
         }
     }
 }
@@ -236,6 +241,108 @@ impl fmt::Display for ReportError {
     }
 }
 
+/// The errors that can occur when using the `handle_control` method
+/// to apply a control message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlError {
+    /// The control message was truncated or otherwise malformed.
+    InvalidMessage,
+    /// The control message was addressed to a different probe.
+    ProbeIdMismatch,
+    /// The control message carried a command this probe doesn't
+    /// understand.
+    UnknownCommand,
+    /// The control message carried a command for a feature this
+    /// probe was built without.
+    UnsupportedCommand,
+    /// The probe has a report key, and the control message's MAC was
+    /// missing or didn't match it.
+    MacMismatch,
+    /// The probe has a report key, and the control message's counter
+    /// wasn't greater than that of the last one applied, as when a
+    /// message is replayed.
+    StaleCounter,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ControlError {}
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlError::InvalidMessage => f.write_str("Invalid control message"),
+            ControlError::ProbeIdMismatch => f.write_str("Control message probe id mismatch"),
+            ControlError::UnknownCommand => f.write_str("Unknown control command"),
+            ControlError::UnsupportedCommand => f.write_str("Unsupported control command"),
+            ControlError::MacMismatch => f.write_str("Control message MAC mismatch"),
+            ControlError::StaleCounter => f.write_str("Stale control message counter"),
+        }
+    }
+}
+
+/// The error relating to using the `save_state` method to save a
+/// probe's state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaveStateError {
+    /// The destination that is receiving the state is not big enough.
+    ///
+    /// Indicates that the end user should provide a larger destination buffer.
+    InsufficientDestinationSize,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SaveStateError {}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::InsufficientDestinationSize => {
+                f.write_str("Insufficient destination size")
+            }
+        }
+    }
+}
+
+/// The errors that can occur when using the `restore_state` method to
+/// restore a probe from a saved state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestoreStateError {
+    /// The saved state was truncated, corrupted, or written by a newer
+    /// version of the probe.
+    InvalidState,
+    /// The provided memory can't hold all of the saved frontier clocks.
+    ExceededAvailableClocks,
+    /// A problem with the backing memory setup.
+    StorageSetupError(StorageSetupError),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RestoreStateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RestoreStateError::InvalidState => None,
+            RestoreStateError::ExceededAvailableClocks => None,
+            RestoreStateError::StorageSetupError(e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for RestoreStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreStateError::InvalidState => f.write_str("Invalid saved probe state"),
+            RestoreStateError::ExceededAvailableClocks => f.write_str("Exceeded available clocks"),
+            RestoreStateError::StorageSetupError(_) => f.write_str("Storage Setup Error"),
+        }
+    }
+}
+
+impl From<StorageSetupError> for RestoreStateError {
+    fn from(e: StorageSetupError) -> Self {
+        RestoreStateError::StorageSetupError(e)
+    }
+}
+
 /// General purpose error that captures all errors that arise
 /// from using the ModalityProbe APIs.
 ///
@@ -262,6 +369,15 @@ pub enum ModalityProbeError {
     /// The error relating to using the `report` method to
     /// produce a full causal history log report.
     ReportError(ReportError),
+    /// The errors that can occur when using the `handle_control`
+    /// method to apply a control message.
+    ControlError(ControlError),
+    /// The error relating to using the `save_state` method to save
+    /// a probe's state.
+    SaveStateError(SaveStateError),
+    /// The errors that can occur when using the `restore_state`
+    /// method to restore a probe from a saved state.
+    RestoreStateError(RestoreStateError),
 }
 
 #[cfg(feature = "std")]
@@ -274,6 +390,9 @@ impl std::error::Error for ModalityProbeError {
             ModalityProbeError::ProduceError(e) => Some(e),
             ModalityProbeError::MergeError(e) => Some(e),
             ModalityProbeError::ReportError(e) => Some(e),
+            ModalityProbeError::ControlError(e) => Some(e),
+            ModalityProbeError::SaveStateError(e) => Some(e),
+            ModalityProbeError::RestoreStateError(e) => Some(e),
         }
     }
 }
@@ -287,6 +406,9 @@ impl fmt::Display for ModalityProbeError {
             ModalityProbeError::ProduceError(_) => f.write_str("Produce Snapshot Error"),
             ModalityProbeError::MergeError(_) => f.write_str("Merge Snapshot Error"),
             ModalityProbeError::ReportError(_) => f.write_str("Report Error"),
+            ModalityProbeError::ControlError(_) => f.write_str("Control Error"),
+            ModalityProbeError::SaveStateError(_) => f.write_str("Save State Error"),
+            ModalityProbeError::RestoreStateError(_) => f.write_str("Restore State Error"),
         }
     }
 }
@@ -333,6 +455,27 @@ impl From<ReportError> for ModalityProbeError {
     }
 }
 
+impl From<ControlError> for ModalityProbeError {
+    #[inline]
+    fn from(e: ControlError) -> Self {
+        ModalityProbeError::ControlError(e)
+    }
+}
+
+impl From<SaveStateError> for ModalityProbeError {
+    #[inline]
+    fn from(e: SaveStateError) -> Self {
+        ModalityProbeError::SaveStateError(e)
+    }
+}
+
+impl From<RestoreStateError> for ModalityProbeError {
+    #[inline]
+    fn from(e: RestoreStateError) -> Self {
+        ModalityProbeError::RestoreStateError(e)
+    }
+}
+
 impl From<StorageSetupError> for ModalityProbeError {
     #[inline]
     fn from(e: StorageSetupError) -> Self {
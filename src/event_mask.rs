@@ -0,0 +1,161 @@
+//! Runtime event filtering.
+//!
+//! A probe holds a fixed-size mask of disabled events which is
+//! consulted before each event is written to the log. Events whose
+//! bit is set are dropped entirely, so verbose instrumentation can be
+//! left in place and switched on only when it is needed.
+//!
+//! The mask is a 256-bit bitmap: event id `n` maps to bit `n - 1`, so
+//! only ids 1 through 256 can be filtered. Larger ids, including
+//! internal events and log callsite events, are always recorded; give
+//! events that need to be switched on and off ids in that range.
+
+use crate::EventId;
+
+/// The number of 32-bit words in an `EventMask`
+pub const EVENT_MASK_WORDS: usize = 8;
+
+/// The number of distinct bits in an `EventMask`
+pub const EVENT_MASK_BITS: u32 = EVENT_MASK_WORDS as u32 * 32;
+
+/// A set of event ids, as used for the probe's runtime event filter
+///
+/// Word `w`, bit `b` represents the event id `w * 32 + b + 1`. Ids
+/// above `EVENT_MASK_BITS` can't be represented.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct EventMask(pub [u32; EVENT_MASK_WORDS]);
+
+impl EventMask {
+    /// A mask containing no events
+    pub const EMPTY: EventMask = EventMask([0; EVENT_MASK_WORDS]);
+
+    /// A mask containing every event it can represent
+    pub const ALL: EventMask = EventMask([u32::MAX; EVENT_MASK_WORDS]);
+
+    /// Create a mask from its raw words
+    #[inline]
+    pub const fn from_words(words: [u32; EVENT_MASK_WORDS]) -> Self {
+        EventMask(words)
+    }
+
+    /// The raw words of this mask
+    #[inline]
+    pub const fn words(&self) -> &[u32; EVENT_MASK_WORDS] {
+        &self.0
+    }
+
+    /// Can the given event be added to a mask, i.e. is its id at most
+    /// `EVENT_MASK_BITS`?
+    #[inline]
+    pub const fn can_contain(event_id: EventId) -> bool {
+        event_id.get_raw() <= EVENT_MASK_BITS
+    }
+
+    /// This mask with the given event added.
+    ///
+    /// Events which the mask [can't contain](EventMask::can_contain)
+    /// are ignored.
+    #[inline]
+    pub const fn with_event(mut self, event_id: EventId) -> Self {
+        if Self::can_contain(event_id) {
+            let (word, bit) = word_and_bit(event_id.get_raw());
+            self.0[word] |= bit;
+        }
+        self
+    }
+
+    /// Does this mask contain the given event?
+    ///
+    /// Always false for events the mask can't contain, including
+    /// internal events.
+    #[inline]
+    pub fn contains(&self, event_id: EventId) -> bool {
+        if !Self::can_contain(event_id) {
+            return false;
+        }
+        let (word, bit) = word_and_bit(event_id.get_raw());
+        self.0[word] & bit != 0
+    }
+
+    /// Add every event in `other` to this mask
+    #[inline]
+    pub fn insert_all(&mut self, other: &EventMask) {
+        for (w, o) in self.0.iter_mut().zip(other.0.iter()) {
+            *w |= *o;
+        }
+    }
+
+    /// Remove every event in `other` from this mask
+    #[inline]
+    pub fn remove_all(&mut self, other: &EventMask) {
+        for (w, o) in self.0.iter_mut().zip(other.0.iter()) {
+            *w &= !*o;
+        }
+    }
+
+    /// Is this mask empty?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|w| *w == 0)
+    }
+}
+
+/// The word and bit for an id in `1..=EVENT_MASK_BITS`
+#[inline]
+const fn word_and_bit(raw_id: u32) -> (usize, u32) {
+    let index = raw_id - 1;
+    ((index / 32) as usize, 1 << (index % 32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(raw: u32) -> EventId {
+        EventId::new(raw).unwrap()
+    }
+
+    #[test]
+    fn bit_layout() {
+        assert_eq!(EventMask::EMPTY.with_event(id(1)).0[0], 1);
+        assert_eq!(EventMask::EMPTY.with_event(id(32)).0[0], 1 << 31);
+        assert_eq!(EventMask::EMPTY.with_event(id(33)).0[1], 1);
+        assert_eq!(EventMask::EMPTY.with_event(id(256)).0[7], 1 << 31);
+    }
+
+    #[test]
+    fn large_ids_are_never_contained() {
+        assert!(EventMask::can_contain(id(EVENT_MASK_BITS)));
+        assert!(!EventMask::can_contain(id(EVENT_MASK_BITS + 1)));
+        assert_eq!(EventMask::EMPTY.with_event(id(257)), EventMask::EMPTY);
+        assert!(!EventMask::ALL.contains(id(257)));
+        assert!(!EventMask::ALL.contains(id(EventId::MAX_USER_ID)));
+        assert!(EventMask::ALL.contains(id(256)));
+    }
+
+    #[test]
+    fn contains_and_set_operations() {
+        let mut mask = EventMask::EMPTY.with_event(id(3)).with_event(id(40));
+        assert!(mask.contains(id(3)));
+        assert!(mask.contains(id(40)));
+        assert!(!mask.contains(id(3 + EVENT_MASK_BITS)));
+        assert!(!mask.contains(id(4)));
+
+        mask.remove_all(&EventMask::EMPTY.with_event(id(3)));
+        assert!(!mask.contains(id(3)));
+        assert!(mask.contains(id(40)));
+
+        mask.insert_all(&EventMask::EMPTY.with_event(id(5)));
+        assert!(mask.contains(id(5)));
+
+        mask.remove_all(&EventMask::ALL);
+        assert!(mask.is_empty());
+    }
+
+    #[test]
+    fn internal_events_are_never_contained() {
+        assert!(!EventMask::ALL.contains(EventId::EVENT_PROBE_INITIALIZED));
+        assert!(!EventMask::ALL.contains(EventId::EVENT_PRODUCED_EXTERNAL_REPORT));
+    }
+}
@@ -1,6 +1,7 @@
 //! Exposes offsets of ModalityProbe and DynamicHistory fields for
 //! use in the debug collector
 use crate::{history::DynamicHistory, log::LogEntry, ModalityProbe};
+use core::mem::size_of;
 use fenced_ring_buffer::{FencedRingBuffer, SeqNum};
 use field_offset::offset_of;
 
@@ -34,6 +35,21 @@ pub fn persistent_epoch_counting_offset() -> u64 {
     offset_of!(DynamicHistory => persistent_epoch_counting).get_byte_offset() as u64
 }
 
+/// Offset of disabled_events field in DynamicHistory struct, which
+/// directly follows the log. Only probes built with the
+/// `event-filtering` feature have this field.
+/// This offset depends on the word size of the target system
+pub fn disabled_events_offset(n_word_bytes: u8) -> u64 {
+    let n_word_bytes = u64::from(n_word_bytes);
+    // The log's storage slice is followed by its read sequence number
+    // and a bool, then padded to word alignment
+    let log_end = log_storage_addr_offset()
+        + 2 * n_word_bytes
+        + size_of::<SeqNum>() as u64
+        + size_of::<bool>() as u64;
+    (log_end + n_word_bytes - 1) / n_word_bytes * n_word_bytes
+}
+
 /// Offset of the high word (u32) of the write sequence number field in DynamicHistory's FencedRingBuffer
 pub fn write_seqn_high_offset() -> u64 {
     offset_of!(DynamicHistory => log: FencedRingBuffer<LogEntry> => write_seqn: SeqNum => high)
@@ -69,3 +85,16 @@ pub fn log_storage_addr_offset() -> u64 {
 pub fn log_storage_cap_offset(n_word_bytes: u8) -> u64 {
     log_storage_addr_offset() + n_word_bytes as u64
 }
+
+#[cfg(all(test, feature = "event-filtering"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_events_offset_matches_layout() {
+        assert_eq!(
+            disabled_events_offset(size_of::<usize>() as u8),
+            offset_of!(DynamicHistory => disabled_events).get_byte_offset() as u64
+        );
+    }
+}
@@ -2,28 +2,46 @@ use core::{
     cmp,
     convert::TryFrom,
     mem::{align_of, size_of, MaybeUninit},
-    num::NonZeroUsize,
+    num::{NonZeroU32, NonZeroUsize},
+    ptr,
 };
 
 use fixed_slice_vec::{
     single::{EmbedValueError, SplitUninitError},
     FixedSliceVec, StorageError,
 };
+use memoffset::offset_of;
 use static_assertions::{assert_eq_align, assert_eq_size, const_assert, const_assert_eq};
 
-use fenced_ring_buffer::{FencedRingBuffer, WholeEntry};
+use fenced_ring_buffer::{Entry, FencedRingBuffer, RetainedSeqNums, SeqNum, WholeEntry};
 
+#[cfg(feature = "event-filtering")]
+use crate::event_mask::EventMask;
+#[cfg(feature = "log-watermark")]
+use crate::log_watermark::{LogWatermark, LogWatermarkCallback};
 use crate::{
     log::{LogBuffer, LogEntry},
+    log_watermark::LogFillLevel,
     restart_counter::RestartCounterProvider,
+    stats::ProbeStats,
     time::{NanosecondResolution, Nanoseconds, WallClockId},
-    wire::{report::WireReport, WireCausalSnapshot},
-    CausalSnapshot, EventId, LogicalClock, MergeError, ModalityProbeInstant, OrdClock, ProbeEpoch,
-    ProbeId, ProbeTicks, ProduceError, ReportError, RestartCounter, StorageSetupError,
+    wire::{
+        compression::{self, LogEncoder},
+        report::WireReport,
+        siphash, CompactCausalSnapshot, ControlCommand, EpochEncoding, WireCausalSnapshot,
+        WireControlMessage, WireProbeState,
+    },
+    CausalSnapshot, ClockEvictionPolicy, ControlError, EventId, LogicalClock, MergeError,
+    ModalityProbeInstant, OrdClock, ProbeEpoch, ProbeId, ProbeTicks, ProduceError, ReportError,
+    RestartCounter, RestoreStateError, SaveStateError, StorageSetupError,
 };
 
 pub const MIN_CLOCKS_LEN: usize = 2;
 pub const MIN_LOG_LEN: usize = MIN_CLOCKS_LEN * 16;
+/// The most clock evictions a single report can cause; the report is
+/// cut short before another.
+const MAX_EVICTIONS_PER_REPORT: usize = 4;
+
 pub const MIN_HISTORY_SIZE_BYTES: usize = size_of::<DynamicHistory>()
     + 3 * size_of::<u32>()
     + MIN_CLOCKS_LEN * size_of::<LogicalClock>()
@@ -49,7 +67,18 @@ const_assert_eq!(12, size_of::<ModalityProbeInstant>());
 const_assert_eq!(4, align_of::<ModalityProbeInstant>());
 
 // 5 bytes of padding required to get the size (99) up to 104, 8-byte aligned
-#[cfg(target_pointer_width = "32")]
+#[cfg(all(
+    target_pointer_width = "32",
+    not(any(
+        feature = "event-filtering",
+        feature = "acknowledged-reporting",
+        feature = "clock-eviction",
+        feature = "report-authentication",
+        feature = "report-compression",
+        feature = "probe-stats",
+        feature = "log-watermark"
+    ))
+))]
 const_assert_eq!(
     size_of::<u32>()
         + size_of::<ProbeId>()
@@ -68,7 +97,18 @@ const_assert_eq!(
 );
 
 // 9 bytes of padding required to get the size (135) up to 144, 8-byte aligned
-#[cfg(target_pointer_width = "64")]
+#[cfg(all(
+    target_pointer_width = "64",
+    not(any(
+        feature = "event-filtering",
+        feature = "acknowledged-reporting",
+        feature = "clock-eviction",
+        feature = "report-authentication",
+        feature = "report-compression",
+        feature = "probe-stats",
+        feature = "log-watermark"
+    ))
+))]
 const_assert_eq!(
     size_of::<u32>()
         + size_of::<ProbeId>()
@@ -86,6 +126,86 @@ const_assert_eq!(
     size_of::<DynamicHistory>()
 );
 
+// With every optional feature, 4 bytes of padding required to get the
+// size (252) up to 256, 8-byte aligned
+#[cfg(all(
+    target_pointer_width = "32",
+    feature = "event-filtering",
+    feature = "acknowledged-reporting",
+    feature = "clock-eviction",
+    feature = "report-authentication",
+    feature = "report-compression",
+    feature = "probe-stats",
+    feature = "log-watermark"
+))]
+const_assert_eq!(
+    size_of::<u32>()
+        + size_of::<ProbeId>()
+        + size_of::<NanosecondResolution>()
+        + size_of::<WallClockId>()
+        + size_of::<u8>()
+        + size_of::<LogBuffer<'_>>()
+        + size_of::<EventMask>()
+        + size_of::<u32>()
+        + size_of::<LogicalClock>()
+        + size_of::<FixedSliceVec<'_, LogicalClock>>()
+        + size_of::<RestartCounterProvider<'_>>()
+        + size_of::<u64>()
+        + size_of::<u64>()
+        + size_of::<u32>()
+        + size_of::<bool>()
+        + size_of::<ClockEvictionPolicy>()
+        + size_of::<Option<UnackedReport>>()
+        + size_of::<usize>()
+        + size_of::<Option<siphash::Key>>()
+        + size_of::<bool>()
+        + size_of::<bool>()
+        + size_of::<LogWatermark<'_>>()
+        + size_of::<ProbeStats>()
+        + 4,
+    size_of::<DynamicHistory>()
+);
+
+// With every optional feature, 12 bytes of padding required to get the
+// size (308) up to 320, 8-byte aligned
+#[cfg(all(
+    target_pointer_width = "64",
+    feature = "event-filtering",
+    feature = "acknowledged-reporting",
+    feature = "clock-eviction",
+    feature = "report-authentication",
+    feature = "report-compression",
+    feature = "probe-stats",
+    feature = "log-watermark"
+))]
+const_assert_eq!(
+    size_of::<u32>()
+        + size_of::<ProbeId>()
+        + size_of::<NanosecondResolution>()
+        + size_of::<WallClockId>()
+        + size_of::<u8>()
+        + size_of::<LogBuffer<'_>>()
+        + size_of::<EventMask>()
+        + size_of::<u32>()
+        + size_of::<LogicalClock>()
+        + size_of::<FixedSliceVec<'_, LogicalClock>>()
+        + size_of::<RestartCounterProvider<'_>>()
+        + size_of::<u64>()
+        + size_of::<u64>()
+        + size_of::<u32>()
+        + size_of::<bool>()
+        + size_of::<ClockEvictionPolicy>()
+        + size_of::<Option<UnackedReport>>()
+        + size_of::<usize>()
+        + size_of::<Option<siphash::Key>>()
+        + size_of::<bool>()
+        + size_of::<bool>()
+        + size_of::<LogWatermark<'_>>()
+        + size_of::<ProbeStats>()
+        + 12,
+    size_of::<DynamicHistory>()
+);
+
 /// Manages the core of a probe in-memory implementation
 /// backed by runtime-sized arrays of current logical clocks
 /// and probe log items.
@@ -101,6 +221,11 @@ const_assert_eq!(
 /// * wall_clock_id
 /// * persistent_epoch_counting
 /// * log
+/// * disabled_events
+///
+/// `disabled_events` comes right after the log, so that leaving it out
+/// doesn't move the fields before it. The state behind each optional
+/// feature is only present when that feature is enabled.
 #[derive(Debug)]
 #[repr(C)]
 pub struct DynamicHistory<'a> {
@@ -113,6 +238,9 @@ pub struct DynamicHistory<'a> {
     pub(crate) persistent_epoch_counting: u8,
     /// Log used to store events and trace clocks
     pub(crate) log: LogBuffer<'a>,
+    /// Events which are dropped instead of being recorded
+    #[cfg(feature = "event-filtering")]
+    pub(crate) disabled_events: EventMask,
     /// The number of events seen since the current
     /// probe's logical clock last increased.
     pub(crate) event_count: u32,
@@ -122,11 +250,176 @@ pub struct DynamicHistory<'a> {
     pub(crate) clocks: FixedSliceVec<'a, LogicalClock>,
     pub(crate) restart_counter: RestartCounterProvider<'a>,
     pub(crate) report_seq_num: u64,
+    /// The counter of the last authenticated control message applied,
+    /// which the next one must exceed
+    #[cfg(feature = "report-authentication")]
+    pub(crate) control_counter: u64,
     pub(crate) missed_log_entry_count: u32,
+    /// Whether reported log entries are kept until the report is acknowledged
+    #[cfg(feature = "acknowledged-reporting")]
+    pub(crate) acknowledged_reporting: bool,
+    /// How to make room in a full clock table
+    #[cfg(feature = "clock-eviction")]
+    pub(crate) clock_eviction_policy: ClockEvictionPolicy,
+    /// The report awaiting acknowledgement, if any
+    #[cfg(feature = "acknowledged-reporting")]
+    pub(crate) unacked_report: Option<UnackedReport>,
+    /// The number of neighbor clocks, following the self clock, which
+    /// were carried in a report and haven't been merged since. When
+    /// evicting, neighbor clocks are kept in order of last merge.
+    #[cfg(feature = "clock-eviction")]
+    pub(crate) n_reported_clocks: usize,
+    /// The key reports are authenticated with, if any
+    #[cfg(feature = "report-authentication")]
+    pub(crate) report_key: Option<siphash::Key>,
+    /// Whether reports carry their log entries compressed
+    #[cfg(feature = "report-compression")]
+    pub(crate) compressed_reports: bool,
+    /// Whether reports carry the stats as internal events
+    #[cfg(feature = "probe-stats")]
+    pub(crate) stats_in_reports: bool,
+    /// What to notify when the log fills up past a watermark
+    #[cfg(feature = "log-watermark")]
+    pub(crate) log_watermark: LogWatermark<'a>,
+    /// Counters about the probe's own operation
+    #[cfg(feature = "probe-stats")]
+    pub(crate) stats: ProbeStats,
 }
 
-#[derive(Debug)]
-struct ClocksFullError;
+/// A report which was sent without its log entries being consumed
+#[cfg(feature = "acknowledged-reporting")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct UnackedReport {
+    pub(crate) seq_num: u64,
+    /// Log position of the first entry in the report
+    pub(crate) log_start: SeqNum,
+    /// Log position just past the last entry in the report
+    pub(crate) log_end: SeqNum,
+    /// The missed log entry count carried by the report
+    pub(crate) missed_log_entry_count: u32,
+}
+
+/// Writes log entries into the log region of a report, either as raw
+/// words or compressed
+struct LogWriter<'r> {
+    region: &'r mut [u8],
+    encoder: Option<LogEncoder>,
+    n_entries: usize,
+}
+
+impl<'r> LogWriter<'r> {
+    fn new(region: &'r mut [u8], compressed: bool) -> Self {
+        LogWriter {
+            region,
+            encoder: if compressed {
+                Some(LogEncoder::new())
+            } else {
+                None
+            },
+            n_entries: 0,
+        }
+    }
+
+    /// Whether `n_entries` more entries are sure to fit
+    fn has_room_for(&self, n_entries: usize) -> bool {
+        match &self.encoder {
+            Some(encoder) => {
+                encoder.len() + n_entries * compression::MAX_ENCODED_WORD_LEN <= self.region.len()
+            }
+            None => (self.n_entries + n_entries) * size_of::<LogEntry>() <= self.region.len(),
+        }
+    }
+
+    fn write(&mut self, first: LogEntry, second: Option<LogEntry>) -> Result<(), ReportError> {
+        match &mut self.encoder {
+            Some(encoder) => encoder.encode(self.region, first.raw(), second.map(LogEntry::raw))?,
+            None => {
+                let start = self.n_entries * size_of::<LogEntry>();
+                let n_words = if second.is_some() { 2 } else { 1 };
+                let dest_bytes = self
+                    .region
+                    .get_mut(start..start + n_words * size_of::<LogEntry>())
+                    .ok_or(ReportError::InsufficientDestinationSize)?;
+                dest_bytes[0..4].copy_from_slice(&first.raw().to_le_bytes());
+                if let Some(second) = second {
+                    dest_bytes[4..8].copy_from_slice(&second.raw().to_le_bytes());
+                }
+            }
+        }
+        self.n_entries += if second.is_some() { 2 } else { 1 };
+        Ok(())
+    }
+
+    /// The value of the report's `n_log_entries` field: the number of
+    /// entries written, or the length of the compressed log in bytes
+    fn n_log_entries_field(&self) -> u32 {
+        match &self.encoder {
+            Some(encoder) => encoder.len() as u32,
+            None => self.n_entries as u32,
+        }
+    }
+}
+
+/// What a history left in its memory before a reset
+#[derive(Debug, Copy, Clone)]
+struct PriorHistory {
+    probe_id: u32,
+    epoch: ProbeEpoch,
+    report_seq_num: u64,
+    missed_log_entry_count: u32,
+    log_seqns: RetainedSeqNums,
+}
+
+impl PriorHistory {
+    /// Read what the history which was embedded in `destination` left
+    /// there. Only plain integer fields are read, so whatever is found
+    /// must still be checked for consistency.
+    fn read(destination: &mut [MaybeUninit<u8>]) -> Option<Self> {
+        let (_prefix, slot, _suffix) =
+            fixed_slice_vec::single::split_uninit_from_uninit_bytes::<DynamicHistory>(destination)
+                .ok()?;
+        let prior = slot.as_ptr() as *const u8;
+        let self_clock = offset_of!(DynamicHistory, self_clock);
+        // Safe because the slot is aligned and sized for a history, and
+        // every bit pattern is a valid value for the fields read
+        unsafe {
+            Some(PriorHistory {
+                probe_id: read_prior::<u32>(prior, offset_of!(DynamicHistory, probe_id)),
+                epoch: read_prior(prior, self_clock + offset_of!(LogicalClock, epoch)),
+                report_seq_num: read_prior(prior, offset_of!(DynamicHistory, report_seq_num)),
+                missed_log_entry_count: read_prior(
+                    prior,
+                    offset_of!(DynamicHistory, missed_log_entry_count),
+                ),
+                log_seqns: LogBuffer::read_retained_seqns(
+                    prior.add(offset_of!(DynamicHistory, log)) as *const LogBuffer<'_>,
+                ),
+            })
+        }
+    }
+}
+
+/// Read a `T` at `offset` bytes into what a previous run left at
+/// `base`. The read is volatile, since the compiler can't know what's
+/// there.
+///
+/// # Safety
+///
+/// `base` plus `offset` must be aligned and valid for reads of a `T`,
+/// and any bit pattern must be a valid `T`.
+#[inline]
+unsafe fn read_prior<T>(base: *const u8, offset: usize) -> T {
+    ptr::read_volatile(base.add(offset) as *const T)
+}
+
+/// Where a new history's state comes from
+#[derive(Debug, Copy, Clone)]
+enum Origin {
+    /// A new epoch, possibly keeping a prior history's log
+    Initialized(Option<PriorHistory>),
+    /// A saved state, continuing from its self clock
+    Restored(LogicalClock),
+}
 
 impl<'a> DynamicHistory<'a> {
     #[inline]
@@ -136,6 +429,120 @@ impl<'a> DynamicHistory<'a> {
         time_resolution: NanosecondResolution,
         wall_clock_id: WallClockId,
         restart_counter: RestartCounterProvider<'a>,
+    ) -> Result<&'a mut DynamicHistory<'a>, StorageSetupError> {
+        Self::new_at_with_prior_log(
+            destination,
+            probe_id,
+            time_resolution,
+            wall_clock_id,
+            restart_counter,
+            false,
+        )
+    }
+
+    /// Like `new_at`, but if `may_hold_prior_log`, keep the log a
+    /// previous history with the same probe id left in `destination`,
+    /// provided it's consistent
+    #[inline]
+    pub(crate) fn new_at_with_prior_log(
+        destination: &'a mut [MaybeUninit<u8>],
+        probe_id: ProbeId,
+        time_resolution: NanosecondResolution,
+        wall_clock_id: WallClockId,
+        restart_counter: RestartCounterProvider<'a>,
+        may_hold_prior_log: bool,
+    ) -> Result<&'a mut DynamicHistory<'a>, StorageSetupError> {
+        let prior = if may_hold_prior_log {
+            PriorHistory::read(destination).filter(|p| p.probe_id == probe_id.get_raw())
+        } else {
+            None
+        };
+        Self::embed_at(
+            destination,
+            probe_id,
+            time_resolution,
+            wall_clock_id,
+            restart_counter,
+            Origin::Initialized(prior),
+        )
+    }
+
+    /// Restore a history from a saved state, continuing its epoch,
+    /// clocks and report sequence instead of starting a new epoch
+    pub(crate) fn restore_at<S: AsRef<[u8]>>(
+        destination: &'a mut [MaybeUninit<u8>],
+        state: &WireProbeState<S>,
+        time_resolution: NanosecondResolution,
+        wall_clock_id: WallClockId,
+        restart_counter: RestartCounterProvider<'a>,
+    ) -> Result<&'a mut DynamicHistory<'a>, RestoreStateError> {
+        let probe_id = state.probe_id()?;
+        let (epoch, ticks) = crate::unpack_clock_word(state.clock());
+        let self_clock = LogicalClock {
+            id: probe_id,
+            epoch,
+            ticks,
+        };
+        // The frontier starts with this probe's own clock
+        match state.clocks().next() {
+            Some((id, _)) if id == probe_id.get_raw() => (),
+            _ => return Err(RestoreStateError::InvalidState),
+        }
+        if state.clocks().any(|(id, _)| ProbeId::new(id).is_none()) {
+            return Err(RestoreStateError::InvalidState);
+        }
+        let history = Self::embed_at(
+            destination,
+            probe_id,
+            time_resolution,
+            wall_clock_id,
+            restart_counter,
+            Origin::Restored(self_clock),
+        )?;
+        if state.n_clocks() as usize > history.clocks.capacity() {
+            return Err(RestoreStateError::ExceededAvailableClocks);
+        }
+        history.clocks.clear();
+        for (id, word) in state.clocks() {
+            let (epoch, ticks) = crate::unpack_clock_word(word);
+            history
+                .clocks
+                .try_push(LogicalClock {
+                    id: ProbeId::new(id).ok_or(RestoreStateError::InvalidState)?,
+                    epoch,
+                    ticks,
+                })
+                .map_err(|_| RestoreStateError::ExceededAvailableClocks)?;
+        }
+        history.event_count = state.event_count();
+        history.report_seq_num = state.seq_num();
+        history.missed_log_entry_count = state.missed_log_entry_count();
+        // Saved entries which don't fit are overwritten as usual, and
+        // counted as missed
+        let mut entries = state.log_entries();
+        while let Some(first) = entries.next() {
+            if first.is_prefix() {
+                let second = entries.next().ok_or(RestoreStateError::InvalidState)?;
+                let (first_overwritten, second_overwritten) =
+                    history.log.push_double(first, second);
+                history.process_overwritten_log_entries(first_overwritten, second_overwritten);
+            } else {
+                let overwritten = history.log.push(first);
+                history.process_overwritten_log_entries(overwritten, None);
+            }
+        }
+        // Anything recorded from here on follows the restored self clock
+        history.write_clocks_to_log(&[self_clock]);
+        Ok(history)
+    }
+
+    fn embed_at(
+        destination: &'a mut [MaybeUninit<u8>],
+        probe_id: ProbeId,
+        time_resolution: NanosecondResolution,
+        wall_clock_id: WallClockId,
+        restart_counter: RestartCounterProvider<'a>,
+        origin: Origin,
     ) -> Result<&'a mut DynamicHistory<'a>, StorageSetupError> {
         let remaining_bytes = destination.len();
         if remaining_bytes < MIN_HISTORY_SIZE_BYTES {
@@ -152,6 +559,7 @@ impl<'a> DynamicHistory<'a> {
                     time_resolution,
                     wall_clock_id,
                     restart_counter,
+                    origin,
                 )
             }) {
                 Ok(v) => Ok(v),
@@ -194,7 +602,12 @@ impl<'a> DynamicHistory<'a> {
         time_resolution: NanosecondResolution,
         wall_clock_id: WallClockId,
         mut restart_counter: RestartCounterProvider<'a>,
+        origin: Origin,
     ) -> Result<Self, StorageSetupError> {
+        let prior = match origin {
+            Origin::Initialized(prior) => prior,
+            Origin::Restored(_) => None,
+        };
         let max_n_clocks = cmp::max(
             MIN_CLOCKS_LEN,
             dynamic_region_slice.len() / 8 / size_of::<LogicalClock>(),
@@ -204,36 +617,69 @@ impl<'a> DynamicHistory<'a> {
             return Err(StorageSetupError::UnderMinimumAllowedSize);
         }
         let (clocks_region, log_region) = dynamic_region_slice.split_at_mut(clocks_region_bytes);
+        // The self clock is always first, so a prior one is where the
+        // first clock will go
+        let prior_self_clock = match prior {
+            Some(_) => Self::read_prior_self_clock(clocks_region),
+            None => None,
+        };
         let mut clocks = FixedSliceVec::from_uninit_bytes(clocks_region);
         // Create new FencedRingBuffer, using full log region instead of rounding to power of 2 length for
         // optimized indexing
         // Note: point of future improvement - a heuristic could be used to determine whether or not the memory cost
         // of rounding the log's storage space outweighs the runtime cost of using mod operations for indexing
-        let log = FencedRingBuffer::new_from_uninit_bytes(log_region, false)
+        let mut log = FencedRingBuffer::new_from_uninit_bytes(log_region, false)
             .map_err(|_| StorageSetupError::UnderMinimumAllowedSize)?;
         if clocks.capacity() < MIN_CLOCKS_LEN || (log.capacity() as usize) < MIN_LOG_LEN {
             return Err(StorageSetupError::UnderMinimumAllowedSize);
         }
-        let (initial_epoch, restart_counter_had_error) =
-            DynamicHistory::calculate_next_epoch(&mut restart_counter, probe_id, None);
+        // Keep the prior log if it's consistent. Its entries get reported
+        // first, under the prior self clock, which is where its oldest
+        // entries were recorded.
+        let retained = match (prior, prior_self_clock) {
+            (Some(prior), Some(prior_self_clock)) if prior_self_clock.id == probe_id => {
+                // Safe because the log's storage is where the prior
+                // history's log was, at the same capacity
+                if unsafe { log.restore_retained_seqns(prior.log_seqns) } {
+                    Some((prior, prior_self_clock))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let (self_clock, restart_counter_had_error) = match origin {
+            Origin::Initialized(_) => {
+                let (initial_epoch, restart_counter_had_error) =
+                    DynamicHistory::calculate_next_epoch(
+                        &mut restart_counter,
+                        probe_id,
+                        retained.map(|(prior, _)| prior.epoch),
+                    );
+                let self_clock = LogicalClock {
+                    id: probe_id,
+                    epoch: initial_epoch,
+                    ticks: ProbeTicks(0),
+                };
+                (self_clock, restart_counter_had_error)
+            }
+            Origin::Restored(self_clock) => {
+                (self_clock, RestartCounterProvidedInvalidEpochSeqId(false))
+            }
+        };
         clocks
-            .try_push(LogicalClock {
-                id: probe_id,
-                epoch: initial_epoch,
-                ticks: ProbeTicks(0),
-            })
+            .try_push(retained.map_or(self_clock, |(_, c)| c))
             .expect(
                 "The History.clocks field should always contain a clock for this probe instance",
             );
+        let n_retained_log_entries = log.len();
         let mut history = DynamicHistory {
             overwrite_priority: 0,
-            report_seq_num: 0,
+            report_seq_num: retained.map_or(0, |(prior, _)| prior.report_seq_num),
+            #[cfg(feature = "report-authentication")]
+            control_counter: 0,
             event_count: 0,
-            self_clock: LogicalClock {
-                id: probe_id,
-                epoch: initial_epoch,
-                ticks: ProbeTicks(0),
-            },
+            self_clock,
             probe_id,
             time_resolution,
             wall_clock_id,
@@ -245,16 +691,69 @@ impl<'a> DynamicHistory<'a> {
             clocks,
             log,
             restart_counter,
-            missed_log_entry_count: 0,
+            missed_log_entry_count: retained.map_or(0, |(prior, _)| prior.missed_log_entry_count),
+            #[cfg(feature = "event-filtering")]
+            disabled_events: EventMask::EMPTY,
+            #[cfg(feature = "acknowledged-reporting")]
+            acknowledged_reporting: false,
+            #[cfg(feature = "acknowledged-reporting")]
+            unacked_report: None,
+            #[cfg(feature = "clock-eviction")]
+            clock_eviction_policy: ClockEvictionPolicy::KeepExisting,
+            #[cfg(feature = "clock-eviction")]
+            n_reported_clocks: 0,
+            #[cfg(feature = "report-authentication")]
+            report_key: None,
+            #[cfg(feature = "report-compression")]
+            compressed_reports: false,
+            #[cfg(feature = "probe-stats")]
+            stats_in_reports: false,
+            #[cfg(feature = "log-watermark")]
+            log_watermark: LogWatermark::default(),
+            #[cfg(feature = "probe-stats")]
+            stats: ProbeStats::default(),
         };
+        // A restored history's log and clocks are filled in from the
+        // saved state
+        if let Origin::Restored(_) = origin {
+            return Ok(history);
+        }
         history.write_clocks_to_log(&[history.self_clock]);
         history.record_event(EventId::EVENT_PROBE_INITIALIZED);
         if restart_counter_had_error.0 {
             history.record_event(EventId::EVENT_INVALID_NEXT_EPOCH_SEQ_ID);
         }
+        if retained.is_some() {
+            history.record_event_with_payload(
+                EventId::EVENT_PRIOR_LOG_RETAINED,
+                n_retained_log_entries as u32,
+            );
+        }
         Ok(history)
     }
 
+    /// Read the self clock a prior history left at the start of its
+    /// clocks region
+    fn read_prior_self_clock(clocks_region: &[MaybeUninit<u8>]) -> Option<LogicalClock> {
+        let offset = clocks_region
+            .as_ptr()
+            .align_offset(align_of::<LogicalClock>());
+        if offset.checked_add(size_of::<LogicalClock>())? > clocks_region.len() {
+            return None;
+        }
+        // Safe because the clock is in bounds and aligned, and the id is
+        // checked before it's made a ProbeId
+        unsafe {
+            let prior = clocks_region.as_ptr().add(offset) as *const u8;
+            let id = read_prior::<u32>(prior, offset_of!(LogicalClock, id));
+            Some(LogicalClock {
+                id: ProbeId::new(id)?,
+                epoch: read_prior(prior, offset_of!(LogicalClock, epoch)),
+                ticks: read_prior(prior, offset_of!(LogicalClock, ticks)),
+            })
+        }
+    }
+
     #[inline]
     fn merge_overwritten_clock(&mut self, overwritten: Option<WholeEntry<LogEntry>>) {
         if let Some(WholeEntry::Double(one, two)) = overwritten {
@@ -271,19 +770,60 @@ impl<'a> DynamicHistory<'a> {
     /// Merge overwritten logical clock entries as needed, then check
     /// for overwritten paired wall clock time entries, removing their
     /// buddy entries as needed, managing
-    /// the missed entry counter along the way
+    /// the missed entry counter and stats along the way
     #[inline]
     fn process_overwritten_log_entries(
         &mut self,
         first_overwritten: Option<WholeEntry<LogEntry>>,
         second_overwritten: Option<WholeEntry<LogEntry>>,
+    ) {
+        #[cfg(feature = "log-watermark")]
+        self.log_watermark
+            .check(self.probe_id, Self::fill_level(&self.log));
+        let missed_before = self.missed_log_entry_count;
+        self.drop_overwritten_log_entries(first_overwritten, second_overwritten);
+        self.count_stat(
+            |s| &mut s.entries_overwritten,
+            self.missed_log_entry_count.saturating_sub(missed_before),
+        );
+    }
+
+    /// Add `n` to one of the stats, if they're being kept
+    #[inline]
+    fn count_stat(&mut self, stat: fn(&mut ProbeStats) -> &mut u32, n: u32) {
+        #[cfg(feature = "probe-stats")]
+        {
+            let s = stat(&mut self.stats);
+            *s = s.saturating_add(n);
+        }
+        #[cfg(not(feature = "probe-stats"))]
+        let _ = (stat, n);
+    }
+
+    /// Whether the event is currently filtered out
+    #[inline]
+    fn is_disabled(&self, event_id: EventId) -> bool {
+        #[cfg(feature = "event-filtering")]
+        return self.disabled_events.contains(event_id);
+        #[cfg(not(feature = "event-filtering"))]
+        {
+            let _ = event_id;
+            false
+        }
+    }
+
+    #[inline]
+    fn drop_overwritten_log_entries(
+        &mut self,
+        first_overwritten: Option<WholeEntry<LogEntry>>,
+        second_overwritten: Option<WholeEntry<LogEntry>>,
     ) {
         self.merge_overwritten_clock(first_overwritten);
         self.merge_overwritten_clock(second_overwritten);
 
         // Fenced-ring-buffer keeps track of missed entries until the log is pop'd
         self.missed_log_entry_count =
-            cmp::max(self.missed_log_entry_count, self.log.num_missed() as u32);
+            cmp::max(self.missed_log_entry_count, self.num_missed_unreported());
 
         // Fenced-ring-buffer will yield overwritten entries regardless
         // of whether or not the buffer is full, only increment probe-local
@@ -300,13 +840,7 @@ impl<'a> DynamicHistory<'a> {
                 // The buddy entry is either already pop'd in second_overwritten
                 // or the next tail entry in the log
                 if second_overwritten.is_none() {
-                    let buddy_entry = self.log.pop();
-
-                    if let Some(e) = buddy_entry {
-                        self.missed_log_entry_count =
-                            self.missed_log_entry_count.saturating_add(e.size().into());
-                    }
-
+                    let buddy_entry = self.pop_overwritten_buddy();
                     self.merge_overwritten_clock(buddy_entry);
                 }
 
@@ -325,16 +859,59 @@ impl<'a> DynamicHistory<'a> {
                 && log_was_full
             {
                 // The buddy entry is the next tail entry in the log
-                let buddy_entry = self.log.pop();
+                let buddy_entry = self.pop_overwritten_buddy();
+                self.merge_overwritten_clock(buddy_entry);
+            }
+        }
+    }
 
-                if let Some(e) = buddy_entry {
-                    self.missed_log_entry_count =
-                        self.missed_log_entry_count.saturating_add(e.size().into());
-                }
+    /// The number of log entries the log has overwritten since it was
+    /// last popped, leaving out those of a report awaiting
+    /// acknowledgement, which were sent before they were overwritten
+    #[inline]
+    fn num_missed_unreported(&self) -> u32 {
+        let missed = self.log.num_missed();
+        #[cfg(feature = "acknowledged-reporting")]
+        let missed = match self.unacked_report {
+            Some(unacked) => cmp::min(
+                missed,
+                u64::from(self.log.tail_seqn()).saturating_sub(unacked.log_end.into()),
+            ),
+            None => missed,
+        };
+        missed as u32
+    }
 
-                self.merge_overwritten_clock(buddy_entry);
+    /// Pop the tail entry, the second half of a pair whose first half
+    /// was overwritten, counting it as missed unless it was sent in a
+    /// report awaiting acknowledgement
+    #[inline]
+    fn pop_overwritten_buddy(&mut self) -> Option<WholeEntry<LogEntry>> {
+        let seqn = self.log.tail_seqn();
+        let buddy_entry = self.log.pop();
+        if let Some(e) = buddy_entry {
+            if !self.is_unacked(seqn) {
+                self.missed_log_entry_count =
+                    self.missed_log_entry_count.saturating_add(e.size().into());
             }
         }
+        buddy_entry
+    }
+
+    /// Whether the log entry at `seqn` was sent in the report awaiting
+    /// acknowledgement
+    #[inline]
+    fn is_unacked(&self, seqn: SeqNum) -> bool {
+        #[cfg(feature = "acknowledged-reporting")]
+        return matches!(
+            self.unacked_report,
+            Some(unacked) if unacked.log_start <= seqn && seqn < unacked.log_end
+        );
+        #[cfg(not(feature = "acknowledged-reporting"))]
+        {
+            let _ = seqn;
+            false
+        }
     }
 
     /// Isolated function for figuring out what the next epoch should be for the probe.
@@ -375,10 +952,14 @@ impl<'a> DynamicHistory<'a> {
     /// is full.
     #[inline]
     pub(crate) fn record_event(&mut self, event_id: EventId) {
+        if self.is_disabled(event_id) {
+            return;
+        }
         // N.B. point for future improvement - basic compression here
         let overwritten = self.log.push(LogEntry::event(event_id));
         self.process_overwritten_log_entries(overwritten, None);
         self.event_count = self.event_count.saturating_add(1);
+        self.count_stat(|s| &mut s.events_recorded, 1);
     }
 
     /// Add the event and its payload to the internal log, recording
@@ -388,10 +969,14 @@ impl<'a> DynamicHistory<'a> {
     /// is full.
     #[inline]
     pub(crate) fn record_event_with_payload(&mut self, event_id: EventId, payload: u32) {
+        if self.is_disabled(event_id) {
+            return;
+        }
         let (first, second) = LogEntry::event_with_payload(event_id, payload);
         let (first_overwritten, second_overwritten) = self.log.push_double(first, second);
         self.process_overwritten_log_entries(first_overwritten, second_overwritten);
         self.event_count = self.event_count.saturating_add(1);
+        self.count_stat(|s| &mut s.events_recorded, 1);
     }
 
     #[inline]
@@ -401,6 +986,9 @@ impl<'a> DynamicHistory<'a> {
 
     #[inline]
     pub fn record_event_with_time(&mut self, event_id: EventId, time: Nanoseconds) {
+        if self.is_disabled(event_id) {
+            return;
+        }
         self.record_paired_wall_clock_time(time);
         self.record_event(event_id);
     }
@@ -412,6 +1000,9 @@ impl<'a> DynamicHistory<'a> {
         payload: u32,
         time: Nanoseconds,
     ) {
+        if self.is_disabled(event_id) {
+            return;
+        }
         self.record_paired_wall_clock_time(time);
         self.record_event_with_payload(event_id, payload);
     }
@@ -431,6 +1022,7 @@ impl<'a> DynamicHistory<'a> {
         let (first_overwritten, second_overwritten) = self.log.push_double(first, second);
         self.process_overwritten_log_entries(first_overwritten, second_overwritten);
         self.event_count = self.event_count.saturating_add(1);
+        self.count_stat(|s| &mut s.events_recorded, 1);
     }
 
     /// Increments the clock in the logical clock corresponding to this probe instance
@@ -441,6 +1033,7 @@ impl<'a> DynamicHistory<'a> {
         self.event_count = 0;
 
         if did_overflow {
+            self.count_stat(|s| &mut s.clock_overflows, 1);
             let (fresh_epoch, restart_counter_had_error) = DynamicHistory::calculate_next_epoch(
                 &mut self.restart_counter,
                 self.probe_id,
@@ -465,34 +1058,347 @@ impl<'a> DynamicHistory<'a> {
         }
     }
 
+    pub(crate) fn handle_control(
+        &mut self,
+        message: &[u8],
+    ) -> Result<ControlCommand, ControlError> {
+        let message = WireControlMessage::new(message)?;
+        if message.probe_id()? != self.probe_id {
+            return Err(ControlError::ProbeIdMismatch);
+        }
+        if let Some(key) = self.active_report_key() {
+            message.check_mac(key)?;
+            self.advance_control_counter(message.counter().unwrap_or(0))?;
+        }
+        let command = message.command()?;
+        match command {
+            ControlCommand::RequestReport => (),
+            ControlCommand::SetOverwritePriority(level) => self.overwrite_priority = level,
+            #[cfg(feature = "event-filtering")]
+            ControlCommand::SetDisabledEvents(mask) => self.disabled_events = mask,
+            #[cfg(not(feature = "event-filtering"))]
+            ControlCommand::SetDisabledEvents(_) => return Err(ControlError::UnsupportedCommand),
+            ControlCommand::ResetMissedLogEntryCount => self.missed_log_entry_count = 0,
+            #[cfg(feature = "acknowledged-reporting")]
+            ControlCommand::AcknowledgeReport(seq_num) => {
+                self.acknowledge_report(seq_num);
+            }
+            #[cfg(not(feature = "acknowledged-reporting"))]
+            ControlCommand::AcknowledgeReport(_) => return Err(ControlError::UnsupportedCommand),
+        }
+        Ok(command)
+    }
+
+    #[cfg(feature = "acknowledged-reporting")]
+    pub(crate) fn set_acknowledged_reporting(&mut self, enabled: bool) {
+        if !enabled {
+            // Give up on the outstanding report, as if it were acknowledged
+            if let Some(unacked) = self.unacked_report {
+                self.acknowledge_report(unacked.seq_num);
+            }
+        }
+        self.acknowledged_reporting = enabled;
+    }
+
+    #[cfg(feature = "clock-eviction")]
+    pub(crate) fn set_clock_eviction_policy(&mut self, policy: ClockEvictionPolicy) {
+        // The clocks aren't kept in order of last merge without an
+        // evicting policy, so start counting reported clocks afresh
+        self.clock_eviction_policy = policy;
+        self.n_reported_clocks = 0;
+    }
+
+    #[cfg(feature = "report-authentication")]
+    pub(crate) fn set_report_key(&mut self, key: Option<siphash::Key>) {
+        self.report_key = key;
+    }
+
+    /// Accept the counter of an authenticated control message, unless
+    /// it's no greater than the last one, as when a message is replayed
+    #[inline]
+    fn advance_control_counter(&mut self, counter: u64) -> Result<(), ControlError> {
+        #[cfg(feature = "report-authentication")]
+        {
+            if counter <= self.control_counter {
+                return Err(ControlError::StaleCounter);
+            }
+            self.control_counter = counter;
+        }
+        #[cfg(not(feature = "report-authentication"))]
+        let _ = counter;
+        Ok(())
+    }
+
+    /// The key reports are authenticated with, if any
+    #[inline]
+    fn active_report_key(&self) -> Option<&siphash::Key> {
+        #[cfg(feature = "report-authentication")]
+        return self.report_key.as_ref();
+        #[cfg(not(feature = "report-authentication"))]
+        None
+    }
+
+    #[cfg(feature = "report-compression")]
+    pub(crate) fn set_compressed_reports(&mut self, compressed: bool) {
+        self.compressed_reports = compressed;
+    }
+
+    /// Whether reports carry their log entries compressed
+    #[inline]
+    fn compresses_reports(&self) -> bool {
+        #[cfg(feature = "report-compression")]
+        return self.compressed_reports;
+        #[cfg(not(feature = "report-compression"))]
+        false
+    }
+
+    #[cfg(feature = "probe-stats")]
+    pub(crate) fn set_stats_in_reports(&mut self, enabled: bool) {
+        self.stats_in_reports = enabled;
+    }
+
+    #[cfg(feature = "log-watermark")]
+    pub(crate) fn set_log_watermark(&mut self, percent: u8, callback: LogWatermarkCallback<'a>) {
+        self.log_watermark = LogWatermark::new(percent, callback);
+    }
+
+    #[inline]
+    pub(crate) fn log_fill_level(&self) -> LogFillLevel {
+        Self::fill_level(&self.log)
+    }
+
+    #[cfg(feature = "probe-stats")]
+    #[inline]
+    pub(crate) fn stats(&self) -> ProbeStats {
+        self.stats
+    }
+
+    #[inline]
+    fn fill_level(log: &LogBuffer<'_>) -> LogFillLevel {
+        LogFillLevel {
+            len: log.len(),
+            capacity: log.capacity(),
+        }
+    }
+
+    #[cfg(feature = "acknowledged-reporting")]
+    pub(crate) fn acknowledge_report(&mut self, seq_num: u64) -> bool {
+        match self.unacked_report {
+            Some(unacked) if unacked.seq_num == seq_num => {
+                self.log.set_tail_seqn(unacked.log_end);
+                self.unacked_report = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Write the probe's state to `destination`, so it can be restored
+    /// later with `restore_at`. Without `include_log`, the log entries
+    /// which haven't been reported are left out and counted as missed.
+    pub(crate) fn save_state(
+        &self,
+        destination: &mut [u8],
+        include_log: bool,
+    ) -> Result<usize, SaveStateError> {
+        let n_log_entries = if include_log { self.log.len() } else { 0 };
+        let missed_log_entry_count = if include_log {
+            self.missed_log_entry_count
+        } else {
+            self.missed_log_entry_count
+                .saturating_add(self.log.len() as u32)
+        };
+        let len = WireProbeState::<&[u8]>::buffer_len(self.clocks.len(), n_log_entries);
+        let destination = destination
+            .get_mut(..len)
+            .ok_or(SaveStateError::InsufficientDestinationSize)?;
+        let mut state = WireProbeState::new_unchecked(destination);
+        state.set_fingerprint_and_version();
+        state.set_n_clocks(self.clocks.len() as u16);
+        state.set_probe_id(self.probe_id);
+        state.set_clock(crate::pack_clock_word(
+            self.self_clock.epoch,
+            self.self_clock.ticks,
+        ));
+        state.set_event_count(self.event_count);
+        state.set_seq_num(self.report_seq_num);
+        state.set_missed_log_entry_count(missed_log_entry_count);
+        state.set_n_log_entries(n_log_entries as u32);
+        let payload = state.payload_mut();
+        let (clocks_region, log_region) =
+            payload.split_at_mut(self.clocks.len() * size_of::<LogicalClock>());
+        for (c, dest) in self
+            .clocks
+            .iter()
+            .zip(clocks_region.chunks_exact_mut(size_of::<LogicalClock>()))
+        {
+            dest.copy_from_slice(&c.to_le_bytes());
+        }
+        if include_log {
+            let mut words = log_region.chunks_exact_mut(size_of::<LogEntry>());
+            for entry in self.log.iter() {
+                let (first, second) = match entry {
+                    WholeEntry::Single(e) => (e, None),
+                    WholeEntry::Double(first, second) => (first, Some(second)),
+                };
+                for e in core::iter::once(first).chain(second) {
+                    if let Some(dest) = words.next() {
+                        dest.copy_from_slice(&e.raw().to_le_bytes());
+                    }
+                }
+            }
+        }
+        state.set_checksum();
+        Ok(len)
+    }
+
     pub(crate) fn report(
         &mut self,
         destination: &mut [u8],
+    ) -> Result<Option<NonZeroUsize>, ReportError> {
+        #[cfg(feature = "acknowledged-reporting")]
+        {
+            if self.unacked_report.is_some() {
+                return self.retransmit_unacked(destination);
+            }
+        }
+
+        #[cfg(feature = "probe-stats")]
+        {
+            if self.stats_in_reports && !self.log_is_drained() {
+                self.record_stats();
+            }
+        }
+
+        #[cfg(feature = "acknowledged-reporting")]
+        let log_start = self.log.tail_seqn();
+        #[cfg(feature = "acknowledged-reporting")]
+        let missed_log_entry_count = self.missed_log_entry_count;
+        let seq_num = self.report_seq_num;
+        let report_len = match self.write_report(destination, seq_num, None)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        self.count_report(report_len);
+
+        #[cfg(feature = "acknowledged-reporting")]
+        {
+            if self.acknowledged_reporting {
+                // Keep the reported entries around until the collector
+                // acknowledges them
+                self.unacked_report = Some(UnackedReport {
+                    seq_num,
+                    log_start,
+                    log_end: self.log.tail_seqn(),
+                    missed_log_entry_count,
+                });
+                self.log.set_tail_seqn(log_start);
+            }
+        }
+
+        self.report_seq_num = self.report_seq_num.wrapping_add(1);
+        self.record_event(EventId::EVENT_PRODUCED_EXTERNAL_REPORT);
+
+        Ok(Some(report_len))
+    }
+
+    #[cfg(feature = "acknowledged-reporting")]
+    pub(crate) fn retransmit_unacked(
+        &mut self,
+        destination: &mut [u8],
+    ) -> Result<Option<NonZeroUsize>, ReportError> {
+        let unacked = match self.unacked_report {
+            Some(unacked) => unacked,
+            None => return Ok(None),
+        };
+        // Reproduce the original report, counting its entries which
+        // have been overwritten since as missed, and leaving anything
+        // else missed since then for the next one
+        self.log.set_tail_seqn(unacked.log_start);
+        let overwritten = u64::from(cmp::min(self.log.tail_seqn(), unacked.log_end))
+            - u64::from(unacked.log_start);
+        let missed_log_entry_count = self.missed_log_entry_count;
+        self.missed_log_entry_count = unacked
+            .missed_log_entry_count
+            .saturating_add(overwritten as u32);
+        let result = self.write_report(destination, unacked.seq_num, Some(unacked.log_end));
+        self.missed_log_entry_count = missed_log_entry_count;
+        self.log.set_tail_seqn(unacked.log_start);
+        if let Ok(Some(report_len)) = result {
+            self.count_report(report_len);
+        }
+        result
+    }
+
+    #[inline]
+    fn count_report(&mut self, report_len: NonZeroUsize) {
+        self.count_stat(|s| &mut s.reports_produced, 1);
+        self.count_stat(
+            |s| &mut s.bytes_reported,
+            u32::try_from(report_len.get()).unwrap_or(u32::MAX),
+        );
+    }
+
+    /// Record each of the stats as an internal event, as they stand
+    /// before any of them are recorded
+    #[cfg(feature = "probe-stats")]
+    fn record_stats(&mut self) {
+        for (id, value) in self.stats.as_events().iter() {
+            self.record_event_with_payload(*id, *value);
+        }
+    }
+
+    /// Whether there's nothing left in the log to report, besides the
+    /// record of the last report
+    fn log_is_drained(&self) -> bool {
+        match self.log.len() {
+            0 => true,
+            1 => matches!(
+                self.log.peek(),
+                Some(WholeEntry::Single(e))
+                    if e.interpret_as_event_id() == Some(EventId::EVENT_PRODUCED_EXTERNAL_REPORT)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Write a report with the given sequence number, consuming the
+    /// log entries it holds, up to `log_end` if given
+    fn write_report(
+        &mut self,
+        destination: &mut [u8],
+        seq_num: u64,
+        log_end: Option<SeqNum>,
     ) -> Result<Option<NonZeroUsize>, ReportError> {
         // The log has been drained if there are no events to report
         // (excluding the expected EventId::EVENT_PRODUCED_EXTERNAL_REPORT)
-        match self.log.len() {
-            0 => return Ok(None),
-            1 => {
-                if let Some(WholeEntry::Single(peeked_entry)) = self.log.peek() {
-                    if peeked_entry.interpret_as_event_id()
-                        == Some(EventId::EVENT_PRODUCED_EXTERNAL_REPORT)
-                    {
-                        return Ok(None);
-                    }
-                }
-            }
-            _ => (),
+        if self.log_is_drained() {
+            return Ok(None);
         }
 
+        let (buffer_len, flags): (fn(usize, usize) -> usize, u8) = match self.active_report_key() {
+            Some(_) => (
+                WireReport::<&[u8]>::authenticated_buffer_len,
+                WireReport::<&[u8]>::AUTHENTICATED_FLAG,
+            ),
+            None => (WireReport::<&[u8]>::buffer_len, 0),
+        };
+        let flags = if self.compresses_reports() {
+            flags | WireReport::<&[u8]>::COMPRESSED_FLAG
+        } else {
+            flags
+        };
+
         // If we can't store at least a header and one event, it's a hard error
-        if destination.len() < WireReport::<&[u8]>::buffer_len(0, 1) {
+        if destination.len() < buffer_len(0, 1) {
             return Err(ReportError::InsufficientDestinationSize);
         }
 
         let self_clock = self.self_clock;
         let mut report = WireReport::new_unchecked(destination);
-        report.set_fingerprint();
+        report.set_versioned_fingerprint();
+        report.set_version(WireReport::<&[u8]>::VERSION);
+        report.set_flags(flags);
         report.set_probe_id(self.probe_id);
         report.set_clock(crate::pack_clock_word(self_clock.epoch, self_clock.ticks));
         report.set_persistent_epoch_counting(self.restart_counter.is_tracking_restarts());
@@ -501,8 +1407,10 @@ impl<'a> DynamicHistory<'a> {
 
         // We can't store at least the frontier clocks and a pair of
         // two-word items.
-        if report.as_ref().len() < WireReport::<&[u8]>::buffer_len(self.clocks.len(), 4) {
-            report.set_seq_num(self.report_seq_num);
+        if report.as_ref().len() < buffer_len(self.clocks.len(), 4) {
+            // The lone event is written uncompressed
+            report.set_flags(flags & !WireReport::<&[u8]>::COMPRESSED_FLAG);
+            report.set_seq_num(seq_num);
             report.set_n_clocks(0);
             report.set_n_log_entries(1);
             let payload = report.payload_mut();
@@ -513,7 +1421,7 @@ impl<'a> DynamicHistory<'a> {
             );
         } else {
             let clocks_len = self.clocks.len();
-            report.set_seq_num(self.report_seq_num);
+            report.set_seq_num(seq_num);
             report.set_n_clocks(clocks_len as u16);
 
             let payload = report.payload_mut();
@@ -528,10 +1436,20 @@ impl<'a> DynamicHistory<'a> {
                 dest_bytes[0..4].copy_from_slice(&first.raw().to_le_bytes());
                 dest_bytes[4..8].copy_from_slice(&second.raw().to_le_bytes());
             }
+            #[cfg(feature = "clock-eviction")]
+            {
+                self.n_reported_clocks = clocks_len - 1;
+            }
 
-            let clocks = &mut self.clocks;
             let mut did_clocks_overflow = false;
-            let mut n_copied = 0;
+            let mut evicted = [None; MAX_EVICTIONS_PER_REPORT];
+            let mut n_evicted = 0;
+
+            // Leave room for the MAC and checksum after the log entries
+            let trailer_len = buffer_len(0, 0) - WireReport::<&[u8]>::header_len();
+            let log_region_len = log_region.len() - trailer_len;
+            let mut log_writer =
+                LogWriter::new(&mut log_region[..log_region_len], self.compresses_reports());
 
             // Log missed entries event
             if self.missed_log_entry_count != 0 {
@@ -540,19 +1458,20 @@ impl<'a> DynamicHistory<'a> {
                     self.missed_log_entry_count,
                 );
                 self.missed_log_entry_count = 0;
-                let dest_bytes = &mut log_region[0..2 * size_of::<LogEntry>()];
-                dest_bytes[0..4].copy_from_slice(&first.raw().to_le_bytes());
-                dest_bytes[4..8].copy_from_slice(&second.raw().to_le_bytes());
-                n_copied += 2;
+                log_writer.write(first, Some(second))?;
             }
 
-            let n_entries_possible = log_region.len() / size_of::<LogEntry>();
             // We peek the next entry so that we never throw away an item we don't have space for,
             // since the size of the next entry isn't known until it is peeked
             while let Some(entry) = self.log.peek() {
+                if let Some(end) = log_end {
+                    if self.log.tail_seqn() >= end {
+                        break;
+                    }
+                }
                 match entry {
                     WholeEntry::Double(first, second) => {
-                        if n_copied > n_entries_possible - 2 {
+                        if !log_writer.has_room_for(2) {
                             // Not enough space for the double-item entry, break
                             // out of the loop here, and don't consume the peeked entries
                             break;
@@ -562,7 +1481,7 @@ impl<'a> DynamicHistory<'a> {
                         // associated other entry across reports
                         if first.has_wall_clock_time_paired_bit_set() {
                             // Bail out early if we don't have room another double-item entry
-                            if n_copied + 4 > n_entries_possible {
+                            if !log_writer.has_room_for(4) {
                                 break;
                             }
                         }
@@ -593,54 +1512,60 @@ impl<'a> DynamicHistory<'a> {
                             // a self clock
                             if id == self_probe_id
                                 && next_entry_is_foreign_clock
-                                && (n_copied + 4 > n_entries_possible)
+                                && !log_writer.has_room_for(4)
                             {
                                 break;
                             }
 
+                            // Leave the rest for the next report rather
+                            // than lose track of what was evicted
+                            if n_evicted == MAX_EVICTIONS_PER_REPORT && self.would_evict(id) {
+                                break;
+                            }
+
                             let (epoch, ticks) = crate::unpack_clock_word(second.raw());
-                            if Self::merge_clocks(clocks, LogicalClock { id, epoch, ticks })
-                                .is_err()
-                            {
-                                did_clocks_overflow = true;
+                            match self.merge_into_clocks(LogicalClock { id, epoch, ticks }) {
+                                Ok(Some(evicted_id)) => {
+                                    evicted[n_evicted] = Some(evicted_id);
+                                    n_evicted += 1;
+                                }
+                                Ok(None) => (),
+                                Err(_) => did_clocks_overflow = true,
                             }
                         }
 
-                        let dest_bytes = &mut log_region[n_copied * size_of::<LogEntry>()
-                            ..(n_copied + 2) * size_of::<LogEntry>()];
-                        dest_bytes[0..4].copy_from_slice(&first.raw().to_le_bytes());
-                        dest_bytes[4..8].copy_from_slice(&second.raw().to_le_bytes());
-                        n_copied += 2;
+                        log_writer.write(first, Some(second))?;
                     }
                     WholeEntry::Single(entry) => {
-                        if n_copied > n_entries_possible - 1 {
+                        if !log_writer.has_room_for(1) {
                             // Not enough space for the entry, break out of the loop
                             // here, and don't consume the peeked entry
                             break;
                         }
-                        let dest_bytes = &mut log_region[n_copied * size_of::<LogEntry>()
-                            ..(n_copied + 1) * size_of::<LogEntry>()];
-                        dest_bytes.copy_from_slice(&entry.raw().to_le_bytes());
-                        n_copied += 1;
+                        log_writer.write(entry, None)?;
                     }
                 }
                 let consumed_entry = self.log.pop();
                 debug_assert_eq!(consumed_entry, Some(entry));
             }
 
-            report.set_n_log_entries(n_copied as u32);
+            let n_log_entries = log_writer.n_log_entries_field();
+            report.set_n_log_entries(n_log_entries);
 
             if did_clocks_overflow {
+                self.count_stat(|s| &mut s.frontier_clock_overflows, 1);
                 self.record_event(EventId::EVENT_NUM_CLOCKS_OVERFLOWED);
             }
+            for evicted_id in evicted.iter().flatten() {
+                self.record_event_with_payload(EventId::EVENT_CLOCK_EVICTED, evicted_id.get_raw());
+            }
         }
 
-        self.report_seq_num = self.report_seq_num.wrapping_add(1);
-        self.record_event(EventId::EVENT_PRODUCED_EXTERNAL_REPORT);
-
-        Ok(NonZeroUsize::new(
-            WireReport::<&[u8]>::header_len() + report.payload_len(),
-        ))
+        if let Some(key) = self.active_report_key() {
+            report.set_mac(key);
+        }
+        report.set_checksum();
+        Ok(NonZeroUsize::new(report.report_len()))
     }
 
     #[inline]
@@ -651,6 +1576,7 @@ impl<'a> DynamicHistory<'a> {
             reserved_1: [0, 0],
         };
         self.increment_local_clock();
+        self.count_stat(|s| &mut s.snapshots_produced, 1);
         self.write_clocks_to_log(&[self.self_clock]);
         snap
     }
@@ -663,6 +1589,7 @@ impl<'a> DynamicHistory<'a> {
             reserved_1: [0, 0],
         };
         self.increment_local_clock();
+        self.count_stat(|s| &mut s.snapshots_produced, 1);
         self.record_paired_wall_clock_time(time);
         self.write_clocks_to_log(&[self.self_clock]);
         snap
@@ -681,6 +1608,7 @@ impl<'a> DynamicHistory<'a> {
         s.set_reserved_0([0, 0]);
         s.set_reserved_1([0, 0]);
         self.increment_local_clock();
+        self.count_stat(|s| &mut s.snapshots_produced, 1);
         self.write_clocks_to_log(&[self.self_clock]);
         Ok(WireCausalSnapshot::<&[u8]>::min_buffer_len())
     }
@@ -699,6 +1627,7 @@ impl<'a> DynamicHistory<'a> {
         s.set_reserved_0([0, 0]);
         s.set_reserved_1([0, 0]);
         self.increment_local_clock();
+        self.count_stat(|s| &mut s.snapshots_produced, 1);
         self.record_paired_wall_clock_time(time);
         self.write_clocks_to_log(&[self.self_clock]);
         Ok(WireCausalSnapshot::<&[u8]>::min_buffer_len())
@@ -710,6 +1639,7 @@ impl<'a> DynamicHistory<'a> {
             external_history.clock.id,
             external_history.clock.epoch,
             external_history.clock.ticks,
+            external_history.interaction_id(),
             None,
         );
     }
@@ -721,6 +1651,7 @@ impl<'a> DynamicHistory<'a> {
             external_history.clock.id,
             external_history.clock.epoch,
             external_history.clock.ticks,
+            external_history.interaction_id(),
             None,
         );
         Ok(())
@@ -736,6 +1667,7 @@ impl<'a> DynamicHistory<'a> {
             external_history.clock.id,
             external_history.clock.epoch,
             external_history.clock.ticks,
+            external_history.interaction_id(),
             Some(time),
         );
     }
@@ -751,19 +1683,95 @@ impl<'a> DynamicHistory<'a> {
             external_history.clock.id,
             external_history.clock.epoch,
             external_history.clock.ticks,
+            external_history.interaction_id(),
             Some(time),
         );
         Ok(())
     }
 
+    #[inline]
+    pub(crate) fn produce_compact_snapshot_bytes(
+        &mut self,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        let n = CompactCausalSnapshot::new(self.self_clock, epoch_encoding)
+            .write_into_le_bytes(destination)?;
+        self.increment_local_clock();
+        self.count_stat(|s| &mut s.snapshots_produced, 1);
+        self.write_clocks_to_log(&[self.self_clock]);
+        Ok(n)
+    }
+
+    #[inline]
+    pub(crate) fn produce_compact_snapshot_bytes_with_time(
+        &mut self,
+        time: Nanoseconds,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        let n = CompactCausalSnapshot::new(self.self_clock, epoch_encoding)
+            .write_into_le_bytes(destination)?;
+        self.increment_local_clock();
+        self.count_stat(|s| &mut s.snapshots_produced, 1);
+        self.record_paired_wall_clock_time(time);
+        self.write_clocks_to_log(&[self.self_clock]);
+        Ok(n)
+    }
+
+    #[inline]
+    pub(crate) fn merge_compact_snapshot_bytes(&mut self, source: &[u8]) -> Result<(), MergeError> {
+        let clock = self.compact_snapshot_clock(source)?;
+        self.merge_internal(clock.id, clock.epoch, clock.ticks, None, None);
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn merge_compact_snapshot_bytes_with_time(
+        &mut self,
+        source: &[u8],
+        time: Nanoseconds,
+    ) -> Result<(), MergeError> {
+        let clock = self.compact_snapshot_clock(source)?;
+        self.merge_internal(clock.id, clock.epoch, clock.ticks, None, Some(time));
+        Ok(())
+    }
+
+    /// Decode a compact snapshot, reconstructing a truncated epoch
+    /// from the last epoch known for its probe
+    fn compact_snapshot_clock(&self, source: &[u8]) -> Result<LogicalClock, MergeError> {
+        let snapshot = CompactCausalSnapshot::try_from(source)?;
+        snapshot
+            .clock(self.last_known_epoch(snapshot.id))
+            .ok_or(MergeError::AmbiguousEpoch)
+    }
+
+    /// The most recent epoch seen for `id`, from the frontier clocks or
+    /// any later clock still in the log
+    fn last_known_epoch(&self, id: ProbeId) -> Option<ProbeEpoch> {
+        let mut epoch = self.clocks.iter().find(|c| c.id == id).map(|c| c.epoch);
+        for entry in self.log.iter() {
+            if let WholeEntry::Double(first, second) = entry {
+                if first.has_clock_bit_set()
+                    && first.interpret_as_logical_clock_probe_id() == id.get_raw()
+                {
+                    epoch = Some(crate::unpack_clock_word(second.raw()).0);
+                }
+            }
+        }
+        epoch
+    }
+
     // NOTE: if paired_wall_clock_time is provided (via a snapshot merge/produce_with_time),
-    // then it will be inserted into the log before the local logical clock
+    // then it will be inserted into the log before the local logical clock.
+    // An interaction id, if any, is recorded right after the merged clocks.
     #[inline]
     fn merge_internal(
         &mut self,
         external_id: ProbeId,
         external_epoch: ProbeEpoch,
         external_clock: ProbeTicks,
+        interaction_id: Option<NonZeroU32>,
         paired_wall_clock_time: Option<Nanoseconds>,
     ) {
         if external_id == self.probe_id {
@@ -774,6 +1782,7 @@ impl<'a> DynamicHistory<'a> {
             return;
         }
         self.increment_local_clock();
+        self.count_stat(|s| &mut s.snapshots_merged, 1);
         if let Some(t) = paired_wall_clock_time {
             self.record_paired_wall_clock_time(t);
         }
@@ -785,6 +1794,9 @@ impl<'a> DynamicHistory<'a> {
                 ticks: external_clock,
             },
         ]);
+        if let Some(id) = interaction_id {
+            self.record_event_with_payload(EventId::EVENT_INTERACTION_ID, id.get());
+        }
     }
 
     // NOTE: if there was an associated paired wall clock time entry
@@ -800,30 +1812,107 @@ impl<'a> DynamicHistory<'a> {
 
     #[inline]
     fn merge_clock(&mut self, ext_clock: LogicalClock) {
-        if Self::merge_clocks(&mut self.clocks, ext_clock).is_err() {
-            self.record_event(EventId::EVENT_NUM_CLOCKS_OVERFLOWED);
+        match self.merge_into_clocks(ext_clock) {
+            Ok(Some(evicted_id)) => {
+                self.record_event_with_payload(EventId::EVENT_CLOCK_EVICTED, evicted_id.get_raw())
+            }
+            Ok(None) => (),
+            Err(_) => {
+                self.count_stat(|s| &mut s.frontier_clock_overflows, 1);
+                self.record_event(EventId::EVENT_NUM_CLOCKS_OVERFLOWED)
+            }
+        }
+    }
+
+    /// Merge `ext_clock` into the frontier clocks under the probe's
+    /// eviction policy
+    #[inline]
+    fn merge_into_clocks(
+        &mut self,
+        ext_clock: LogicalClock,
+    ) -> Result<Option<ProbeId>, StorageError<LogicalClock>> {
+        #[cfg(feature = "clock-eviction")]
+        return Self::merge_clocks(
+            &mut self.clocks,
+            self.clock_eviction_policy,
+            &mut self.n_reported_clocks,
+            ext_clock,
+        );
+        #[cfg(not(feature = "clock-eviction"))]
+        Self::merge_clocks(
+            &mut self.clocks,
+            ClockEvictionPolicy::KeepExisting,
+            &mut 0,
+            ext_clock,
+        )
+    }
+
+    /// Whether merging a clock from `id` would evict another
+    #[inline]
+    fn would_evict(&self, id: ProbeId) -> bool {
+        #[cfg(feature = "clock-eviction")]
+        return self.clocks.is_full()
+            && !self.clocks.iter().any(|c| c.id == id)
+            && Self::can_evict(
+                &self.clocks,
+                self.clock_eviction_policy,
+                self.n_reported_clocks,
+            );
+        #[cfg(not(feature = "clock-eviction"))]
+        {
+            let _ = id;
+            false
         }
     }
 
+    #[inline]
+    fn can_evict(
+        clocks: &FixedSliceVec<LogicalClock>,
+        policy: ClockEvictionPolicy,
+        n_reported_clocks: usize,
+    ) -> bool {
+        match policy {
+            ClockEvictionPolicy::KeepExisting => false,
+            ClockEvictionPolicy::LeastRecentlyMerged => clocks.len() > 1,
+            ClockEvictionPolicy::Reported => n_reported_clocks > 0,
+        }
+    }
+
+    /// Merge `ext_clock` into `clocks`, returning the id of the probe
+    /// whose clock was evicted to make room for it, if any
     #[inline]
     pub(crate) fn merge_clocks(
         clocks: &mut FixedSliceVec<LogicalClock>,
+        policy: ClockEvictionPolicy,
+        n_reported_clocks: &mut usize,
         ext_clock: LogicalClock,
-    ) -> Result<(), StorageError<LogicalClock>> {
-        let mut existed = false;
-        for c in clocks.iter_mut() {
-            if c.id == ext_clock.id {
-                if OrdClock(ext_clock.epoch, ext_clock.ticks) > OrdClock(c.epoch, c.ticks) {
-                    c.epoch = ext_clock.epoch;
-                    c.ticks = ext_clock.ticks;
+    ) -> Result<Option<ProbeId>, StorageError<LogicalClock>> {
+        if let Some(idx) = clocks.iter().position(|c| c.id == ext_clock.id) {
+            let c = &mut clocks.as_mut_slice()[idx];
+            if OrdClock(ext_clock.epoch, ext_clock.ticks) > OrdClock(c.epoch, c.ticks) {
+                c.epoch = ext_clock.epoch;
+                c.ticks = ext_clock.ticks;
+            }
+            // Keep the neighbor clocks in order of last merge, the self
+            // clock always first
+            if policy != ClockEvictionPolicy::KeepExisting && idx != 0 {
+                clocks.as_mut_slice()[idx..].rotate_left(1);
+                if idx <= *n_reported_clocks {
+                    *n_reported_clocks -= 1;
                 }
-                existed = true;
             }
+            return Ok(None);
         }
-        if !existed {
-            clocks.try_push(ext_clock)?;
+        if let Err(e) = clocks.try_push(ext_clock) {
+            if !Self::can_evict(clocks, policy, *n_reported_clocks) {
+                return Err(e);
+            }
+            let evicted = clocks.remove(1);
+            *n_reported_clocks = n_reported_clocks.saturating_sub(1);
+            clocks.push(ext_clock);
+            return Ok(Some(evicted.id));
         }
-        Ok(())
+        Ok(None)
     }
 }
 
@@ -833,7 +1922,8 @@ struct RestartCounterProvidedInvalidEpochSeqId(bool);
 mod test {
     use super::*;
     use crate::restart_counter::RestartSequenceIdUnavailable;
-    use crate::{RestartCounter, RustRestartCounterProvider};
+    use crate::wire::{le_bytes, ReportWireError};
+    use crate::{LogWatermarkHandler, RestartCounter, RustRestartCounterProvider};
 
     struct PersistentRestartProvider {
         next_seq_id: u16,
@@ -949,7 +2039,10 @@ mod test {
     #[test]
     fn merged_clocks_overflow_error_event() {
         let probe_id = ProbeId::new(1).unwrap();
-        let mut storage = [MaybeUninit::new(0u8); 512];
+        #[cfg(target_pointer_width = "64")]
+        let mut storage = [MaybeUninit::new(0u8); 640];
+        #[cfg(target_pointer_width = "32")]
+        let mut storage = [MaybeUninit::new(0u8); 616];
         let h = DynamicHistory::new_at(
             &mut storage,
             probe_id,
@@ -1002,16 +2095,130 @@ mod test {
                 }
                 false
             });
-        assert_eq!(
-            found_internal_event,
-            Some(LogEntry::event(EventId::EVENT_NUM_CLOCKS_OVERFLOWED))
-        );
+        assert_eq!(
+            found_internal_event,
+            Some(LogEntry::event(EventId::EVENT_NUM_CLOCKS_OVERFLOWED))
+        );
+    }
+
+    #[test]
+    fn clock_eviction_policies() {
+        let lc = |id: u32, ticks: u16| LogicalClock {
+            id: ProbeId::new(id).unwrap(),
+            epoch: ProbeEpoch(0),
+            ticks: ProbeTicks(ticks),
+        };
+        let has_clock = |h: &DynamicHistory, id: u32| h.clocks.iter().any(|c| c.id.get_raw() == id);
+        let evicted = |id: u32| {
+            let (ev, payload) = LogEntry::event_with_payload(EventId::EVENT_CLOCK_EVICTED, id);
+            Some(WholeEntry::Double(ev, payload))
+        };
+
+        let mut storage = [MaybeUninit::new(0u8); 552];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        h.set_clock_eviction_policy(ClockEvictionPolicy::LeastRecentlyMerged);
+        let n_neighbors = h.clocks.capacity() as u32 - 1;
+        for id in 2..n_neighbors + 2 {
+            h.merge_clock(lc(id, 1));
+        }
+        assert!(h.clocks.is_full());
+        // Merging 2 again leaves 3 as the least recently merged
+        h.merge_clock(lc(2, 2));
+        h.merge_clock(lc(100, 1));
+        assert!(has_clock(h, 2));
+        assert!(!has_clock(h, 3));
+        assert!(has_clock(h, 100));
+        assert_eq!(h.clocks[0].id, h.probe_id);
+        assert_eq!(h.log.iter().last(), evicted(3));
+
+        let mut storage = [MaybeUninit::new(0u8); 552];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        h.set_clock_eviction_policy(ClockEvictionPolicy::Reported);
+        for id in 2..n_neighbors + 2 {
+            h.merge_clock(lc(id, 1));
+        }
+        // Nothing has been reported, so nothing can be evicted
+        h.merge_clock(lc(100, 1));
+        assert!(!has_clock(h, 100));
+        assert_eq!(
+            h.log.iter().last(),
+            Some(WholeEntry::Single(LogEntry::event(
+                EventId::EVENT_NUM_CLOCKS_OVERFLOWED
+            )))
+        );
+
+        let mut report_dest = [0_u8; 512];
+        h.report(&mut report_dest).unwrap().unwrap();
+        // 2 changed since it was reported, so 3 is the oldest reported clock
+        h.merge_clock(lc(2, 2));
+        h.merge_clock(lc(100, 1));
+        assert!(has_clock(h, 2));
+        assert!(!has_clock(h, 3));
+        assert!(has_clock(h, 100));
+        assert_eq!(h.log.iter().last(), evicted(3));
+        // Once the rest of the reported clocks are gone, the ones that
+        // haven't been reported are kept
+        for id in 101..101 + n_neighbors - 2 {
+            h.merge_clock(lc(id, 1));
+        }
+        assert_eq!(h.log.iter().last(), evicted(n_neighbors + 1));
+        h.merge_clock(lc(200, 1));
+        assert!(!has_clock(h, 200));
+        assert!(has_clock(h, 2));
+        assert!(has_clock(h, 100));
+
+        // Clocks merged while reporting are evicted the same way, with
+        // the evictions recorded after the report
+        let mut storage = [MaybeUninit::new(0u8); 552];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        h.set_clock_eviction_policy(ClockEvictionPolicy::LeastRecentlyMerged);
+        for id in 2..n_neighbors + 4 {
+            h.merge_snapshot(&CausalSnapshot {
+                clock: lc(id, 1),
+                reserved_0: [0, 0],
+                reserved_1: [0, 0],
+            });
+        }
+        h.report(&mut report_dest).unwrap().unwrap();
+        assert!(!has_clock(h, 2));
+        assert!(!has_clock(h, 3));
+        assert!(has_clock(h, n_neighbors + 3));
+        let n_evictions = h
+            .log
+            .iter()
+            .filter(|e| [evicted(2), evicted(3)].contains(&Some(*e)))
+            .count();
+        assert_eq!(n_evictions, 2);
     }
 
     #[test]
     fn drain_report_until_completion() {
         let probe_id = ProbeId::new(1).unwrap();
-        let mut storage = [MaybeUninit::new(0u8); 1036];
+        #[cfg(target_pointer_width = "64")]
+        let mut storage = [MaybeUninit::new(0u8); 1212];
+        #[cfg(target_pointer_width = "32")]
+        let mut storage = [MaybeUninit::new(0u8); 1188];
         let h = DynamicHistory::new_at(
             &mut storage,
             probe_id,
@@ -1060,9 +2267,9 @@ mod test {
         // One more to get the remainder
         let bytes_written = h.report(&mut report_dest).unwrap().unwrap();
         #[cfg(target_pointer_width = "64")]
-        assert_eq!(bytes_written.get(), 57);
+        assert_eq!(bytes_written.get(), 63);
         #[cfg(target_pointer_width = "32")]
-        assert_eq!(bytes_written.get(), 61);
+        assert_eq!(bytes_written.get(), 67);
         let log_report = WireReport::new(&report_dest[..bytes_written.get()]).unwrap();
         assert_eq!(log_report.n_clocks() as usize, h.clocks.len());
         #[cfg(target_pointer_width = "64")]
@@ -1430,9 +2637,9 @@ mod test {
     fn overwritten_paired_wall_clock_time_drops_buddy_entry() {
         let probe_id = ProbeId::new(1).unwrap();
         #[cfg(target_pointer_width = "64")]
-        let mut storage = [MaybeUninit::new(0u8); 512];
+        let mut storage = [MaybeUninit::new(0u8); 688];
         #[cfg(target_pointer_width = "32")]
-        let mut storage = [MaybeUninit::new(0u8); 478];
+        let mut storage = [MaybeUninit::new(0u8); 630];
         let h = DynamicHistory::new_at(
             &mut storage,
             probe_id,
@@ -1482,6 +2689,640 @@ mod test {
         }
     }
 
+    #[test]
+    fn disabled_events_are_not_recorded() {
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 512];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let event_a = EventId::new(1).unwrap();
+        let event_b = EventId::new(2).unwrap();
+        h.disabled_events = EventMask::EMPTY.with_event(event_a);
+
+        let len = h.log.len();
+        let count = h.event_count;
+        h.record_event(event_a);
+        h.record_event_with_payload(event_a, 1);
+        h.record_event_with_time(event_a, Nanoseconds::new(1).unwrap());
+        h.record_event_with_payload_with_time(event_a, 1, Nanoseconds::new(1).unwrap());
+        assert_eq!(h.log.len(), len);
+        assert_eq!(h.event_count, count);
+
+        h.record_event(event_b);
+        assert_eq!(h.log.len(), len + 1);
+        assert_eq!(h.event_count, count + 1);
+
+        h.disabled_events = EventMask::EMPTY;
+        h.record_event(event_a);
+        assert_eq!(h.log.len(), len + 2);
+    }
+
+    #[test]
+    fn acknowledged_reporting() {
+        fn reported_events(report: &[u8]) -> impl Iterator<Item = u32> + '_ {
+            let r = WireReport::new(report).unwrap();
+            let clocks_len = r.n_clocks() as usize * size_of::<LogicalClock>();
+            report[WireReport::<&[u8]>::header_len() + clocks_len..]
+                .chunks_exact(size_of::<LogEntry>())
+                .map(le_bytes::read_u32)
+        }
+
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        h.set_acknowledged_reporting(true);
+        h.record_event(EventId::new(1).unwrap());
+
+        let mut first = [0u8; 256];
+        let first_len = h.report(&mut first).unwrap().unwrap().get();
+        assert!(reported_events(&first[..first_len]).any(|e| e == 1));
+
+        // Until acknowledged, the same report is sent again
+        h.record_event(EventId::new(2).unwrap());
+        let mut again = [0u8; 256];
+        let again_len = h.report(&mut again).unwrap().unwrap().get();
+        assert_eq!(&first[..first_len], &again[..again_len]);
+        let again_len = h.retransmit_unacked(&mut again).unwrap().unwrap().get();
+        assert_eq!(&first[..first_len], &again[..again_len]);
+
+        assert!(!h.acknowledge_report(1));
+        assert!(h.acknowledge_report(0));
+        assert_eq!(h.retransmit_unacked(&mut again), Ok(None));
+
+        // The next report picks up after the acknowledged one
+        let mut next = [0u8; 256];
+        let next_len = h.report(&mut next).unwrap().unwrap().get();
+        assert_eq!(WireReport::new(&next[..next_len]).unwrap().seq_num(), 1);
+        assert!(reported_events(&next[..next_len]).any(|e| e == 2));
+        assert!(!reported_events(&next[..next_len]).any(|e| e == 1));
+
+        // Turning acknowledgement off lets go of the outstanding report
+        h.set_acknowledged_reporting(false);
+        assert_eq!(h.unacked_report, None);
+        h.record_event(EventId::new(3).unwrap());
+        let last_len = h.report(&mut next).unwrap().unwrap().get();
+        assert_eq!(WireReport::new(&next[..last_len]).unwrap().seq_num(), 2);
+        assert!(reported_events(&next[..last_len]).any(|e| e == 3));
+        assert!(!reported_events(&next[..last_len]).any(|e| e == 2));
+    }
+
+    #[test]
+    fn overwritten_unacked_entries_are_only_missed_once() {
+        fn missed_count(report: &[u8]) -> Option<u32> {
+            let r = WireReport::new(report).unwrap();
+            let clocks_len = r.n_clocks() as usize * size_of::<LogicalClock>();
+            let log = &report[WireReport::<&[u8]>::header_len() + clocks_len..];
+            let first = unsafe { LogEntry::new_unchecked(le_bytes::read_u32(&log[..4])) };
+            if first.interpret_as_event_id() == Some(EventId::EVENT_LOG_ITEMS_MISSED) {
+                Some(le_bytes::read_u32(&log[4..8]))
+            } else {
+                None
+            }
+        }
+
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        h.set_acknowledged_reporting(true);
+        for id in 1..=3 {
+            h.record_event(EventId::new(id).unwrap());
+        }
+        let mut report = [0u8; 512];
+        let len = h.report(&mut report).unwrap().unwrap().get();
+        assert_eq!(missed_count(&report[..len]), None);
+        let unacked = h.unacked_report.unwrap();
+        let n_reported = u64::from(unacked.log_end) - u64::from(unacked.log_start);
+
+        // Overwrite all of the unacknowledged report, the record of
+        // producing it, and the first two entries after that
+        let n_free = h.log.capacity() - h.log.len();
+        for _ in 0..n_free + n_reported as usize + 1 + 2 {
+            h.record_event(EventId::new(4).unwrap());
+        }
+        assert_eq!(h.missed_log_entry_count, 3);
+
+        // The retransmission counts its overwritten entries as missed
+        let len = h.report(&mut report).unwrap().unwrap().get();
+        assert_eq!(WireReport::new(&report[..len]).unwrap().seq_num(), 0);
+        assert_eq!(missed_count(&report[..len]), Some(n_reported as u32));
+
+        // The next report only counts the entries which were never sent
+        assert!(h.acknowledge_report(0));
+        let len = h.report(&mut report).unwrap().unwrap().get();
+        assert_eq!(WireReport::new(&report[..len]).unwrap().seq_num(), 1);
+        assert_eq!(missed_count(&report[..len]), Some(3));
+    }
+
+    #[test]
+    fn saved_state_restores_clocks_and_log() {
+        fn reported_events(report: &[u8]) -> Vec<u32> {
+            let r = WireReport::new(report).unwrap();
+            let clocks_len = r.n_clocks() as usize * size_of::<LogicalClock>();
+            report[WireReport::<&[u8]>::header_len() + clocks_len..]
+                .chunks_exact(size_of::<LogEntry>())
+                .map(le_bytes::read_u32)
+                .collect()
+        }
+
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        h.merge_snapshot(&CausalSnapshot {
+            clock: LogicalClock {
+                id: ProbeId::new(2).unwrap(),
+                epoch: ProbeEpoch(3),
+                ticks: ProbeTicks(4),
+            },
+            reserved_0: [0; 2],
+            reserved_1: [0; 2],
+        });
+        let mut report = [0u8; 512];
+        assert!(h.report(&mut report).unwrap().is_some());
+        h.record_event(EventId::new(5).unwrap());
+        h.record_event(EventId::new(6).unwrap());
+
+        let mut state = [0u8; 256];
+        let len = h.save_state(&mut state, true).unwrap();
+        assert_eq!(
+            h.save_state(&mut state[..len - 1], true),
+            Err(SaveStateError::InsufficientDestinationSize)
+        );
+        let mut restored_storage = [MaybeUninit::new(0u8); 1024];
+        let r = DynamicHistory::restore_at(
+            &mut restored_storage,
+            &WireProbeState::new(&state[..len]).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        assert_eq!(r.self_clock, h.self_clock);
+        assert_eq!(r.clocks.as_slice(), h.clocks.as_slice());
+        assert_eq!(r.event_count, h.event_count);
+
+        // The restored probe carries on where the saved one left off,
+        // without starting a new epoch
+        let report_len = r.report(&mut report).unwrap().unwrap().get();
+        let wire = WireReport::new(&report[..report_len]).unwrap();
+        assert_eq!(wire.seq_num(), 1);
+        let events = reported_events(&report[..report_len]);
+        assert!(events.contains(&5));
+        assert!(events.contains(&6));
+        assert!(!events.contains(&EventId::EVENT_PROBE_INITIALIZED.get_raw()));
+
+        // Without the log, unreported entries are counted as missed
+        h.record_event(EventId::new(7).unwrap());
+        let len = h.save_state(&mut state, false).unwrap();
+        let r = DynamicHistory::restore_at(
+            &mut restored_storage,
+            &WireProbeState::new(&state[..len]).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        assert_eq!(r.missed_log_entry_count, h.log.len() as u32);
+        let report_len = r.report(&mut report).unwrap().unwrap().get();
+        assert!(!reported_events(&report[..report_len]).contains(&7));
+    }
+
+    #[test]
+    fn log_watermark_fires_once_per_crossing() {
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let mut reached = 0;
+        let mut on_watermark = |id: ProbeId, level: LogFillLevel| {
+            assert_eq!(id, ProbeId::new(1).unwrap());
+            assert!(level.percent() >= 50);
+            reached += 1;
+        };
+        h.set_log_watermark(
+            50,
+            LogWatermarkCallback::from(&mut on_watermark as &mut dyn LogWatermarkHandler),
+        );
+        let capacity = h.log_fill_level().capacity;
+        for _ in 0..capacity {
+            h.record_event(EventId::new(1).unwrap());
+        }
+        assert_eq!(h.log_fill_level().len, capacity);
+
+        // Reporting drains the log below the watermark, re-arming it
+        let mut report = [0u8; 1024];
+        while h.log_fill_level().percent() >= 50 {
+            h.report(&mut report).unwrap();
+        }
+        for _ in 0..capacity {
+            h.record_event(EventId::new(1).unwrap());
+        }
+        assert_eq!(reached, 2);
+    }
+
+    #[test]
+    fn stats_count_probe_operations() {
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        // PROBE_INITIALIZED
+        assert_eq!(h.stats().events_recorded, 1);
+
+        let capacity = h.log_fill_level().capacity;
+        for _ in 0..capacity {
+            h.record_event(EventId::new(1).unwrap());
+        }
+        let stats = h.stats();
+        assert_eq!(stats.events_recorded, capacity as u32 + 1);
+        assert_eq!(stats.entries_overwritten, 3);
+
+        let snapshot = h.produce_snapshot();
+        h.merge_snapshot(&CausalSnapshot {
+            clock: LogicalClock {
+                id: ProbeId::new(2).unwrap(),
+                ..snapshot.clock
+            },
+            ..snapshot
+        });
+        let mut report = [0u8; 1024];
+        let report_len = h.report(&mut report).unwrap().unwrap().get();
+        let stats = h.stats();
+        assert_eq!(stats.snapshots_produced, 1);
+        assert_eq!(stats.snapshots_merged, 1);
+        assert_eq!(stats.reports_produced, 1);
+        assert_eq!(stats.bytes_reported, report_len as u32);
+        assert_eq!(stats.clock_overflows, 0);
+        assert_eq!(stats.frontier_clock_overflows, 0);
+
+        // Once enabled, the stats are reported as they stood before
+        // the report
+        h.set_stats_in_reports(true);
+        h.record_event(EventId::new(1).unwrap());
+        let stats = h.stats();
+        let report_len = h.report(&mut report).unwrap().unwrap().get();
+        let r = WireReport::new(&report[..report_len]).unwrap();
+        let log_start =
+            WireReport::<&[u8]>::header_len() + r.n_clocks() as usize * size_of::<LogicalClock>();
+        let words: Vec<u32> = report[log_start..report_len]
+            .chunks_exact(size_of::<LogEntry>())
+            .take(r.n_log_entries() as usize)
+            .map(le_bytes::read_u32)
+            .collect();
+        for (id, value) in stats.as_events().iter() {
+            let (first, second) = LogEntry::event_with_payload(*id, *value);
+            assert!(words.windows(2).any(|w| w == [first.raw(), second.raw()]));
+        }
+
+        // Stats alone don't make a report
+        assert_eq!(h.report(&mut report).unwrap(), None);
+    }
+
+    #[test]
+    fn authenticated_reports() {
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let key = [9; siphash::KEY_LEN];
+        h.set_report_key(Some(key));
+        for id in 1..=20 {
+            h.record_event(EventId::new(id).unwrap());
+        }
+
+        // The MAC takes the room of two log entries
+        let mut report = [0u8; 128];
+        let len = WireReport::<&[u8]>::authenticated_buffer_len(1, 8);
+        let report_len = h.report(&mut report[..len]).unwrap().unwrap().get();
+        assert_eq!(report_len, len);
+        let r = WireReport::new(&report[..report_len]).unwrap();
+        assert!(r.is_authenticated());
+        assert_eq!(r.n_log_entries(), 8);
+        assert_eq!(r.check_mac(&key), Ok(()));
+        assert_eq!(
+            r.check_mac(&[0; siphash::KEY_LEN]),
+            Err(ReportWireError::MacMismatch)
+        );
+
+        h.set_report_key(None);
+        let report_len = h.report(&mut report[..len]).unwrap().unwrap().get();
+        let r = WireReport::new(&report[..report_len]).unwrap();
+        assert!(!r.is_authenticated());
+        assert_eq!(r.mac(), None);
+        assert_eq!(r.n_log_entries(), 10);
+    }
+
+    #[test]
+    fn authenticated_control_messages() {
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let key = [9; siphash::KEY_LEN];
+        let command = ControlCommand::SetOverwritePriority(3);
+        let mut msg = [0u8; 64];
+
+        // Without a key, messages needn't be tagged
+        let len = command.write_message(probe_id, &mut msg).unwrap();
+        assert_eq!(h.handle_control(&msg[..len]), Ok(command));
+
+        h.set_report_key(Some(key));
+        h.overwrite_priority = 0;
+        assert_eq!(
+            h.handle_control(&msg[..len]),
+            Err(ControlError::MacMismatch)
+        );
+        let len = command
+            .write_authenticated_message(probe_id, &[8; siphash::KEY_LEN], 1, &mut msg)
+            .unwrap();
+        assert_eq!(
+            h.handle_control(&msg[..len]),
+            Err(ControlError::MacMismatch)
+        );
+        assert_eq!(h.overwrite_priority, 0);
+
+        let len = command
+            .write_authenticated_message(probe_id, &key, 2, &mut msg)
+            .unwrap();
+        assert_eq!(h.handle_control(&msg[..len]), Ok(command));
+        assert_eq!(h.overwrite_priority, 3);
+
+        // A replayed message, or one older than the last, is rejected
+        h.overwrite_priority = 0;
+        assert_eq!(
+            h.handle_control(&msg[..len]),
+            Err(ControlError::StaleCounter)
+        );
+        let len = command
+            .write_authenticated_message(probe_id, &key, 1, &mut msg)
+            .unwrap();
+        assert_eq!(
+            h.handle_control(&msg[..len]),
+            Err(ControlError::StaleCounter)
+        );
+        assert_eq!(h.overwrite_priority, 0);
+
+        let len = command
+            .write_authenticated_message(probe_id, &key, 3, &mut msg)
+            .unwrap();
+        assert_eq!(h.handle_control(&msg[..len]), Ok(command));
+        assert_eq!(h.overwrite_priority, 3);
+    }
+
+    fn history_with_repetitive_log(storage: &mut [MaybeUninit<u8>]) -> &mut DynamicHistory<'_> {
+        let h = DynamicHistory::new_at(
+            storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        for i in 0..20 {
+            h.record_time(Nanoseconds::new(1_000_000 + u64::from(i) * 1_000).unwrap());
+            h.record_event_with_payload(EventId::new(2).unwrap(), i);
+            h.record_event(EventId::new(3).unwrap());
+            h.record_event(EventId::new(3).unwrap());
+        }
+        h
+    }
+
+    #[test]
+    fn compressed_reports() {
+        let mut raw_storage = [MaybeUninit::new(0u8); 1024];
+        let raw_history = history_with_repetitive_log(&mut raw_storage);
+        let mut compressed_storage = [MaybeUninit::new(0u8); 1024];
+        let compressed_history = history_with_repetitive_log(&mut compressed_storage);
+        compressed_history.set_compressed_reports(true);
+
+        let mut raw_report = [0u8; 1024];
+        let raw_len = raw_history.report(&mut raw_report).unwrap().unwrap().get();
+        let raw = WireReport::new(&raw_report[..raw_len]).unwrap();
+        assert!(!raw.is_compressed());
+
+        let mut compressed_report = [0u8; 1024];
+        let compressed_len = compressed_history
+            .report(&mut compressed_report)
+            .unwrap()
+            .unwrap()
+            .get();
+        let compressed = WireReport::new(&compressed_report[..compressed_len]).unwrap();
+        assert!(compressed.is_compressed());
+        assert!(compressed.payload_len() * 3 < raw.payload_len());
+        assert_eq!(compressed.check_checksum(), Ok(()));
+
+        let mut words = Vec::new();
+        compression::decode(&compressed.payload()[8..compressed.payload_len()], |w| {
+            words.extend_from_slice(&w.to_le_bytes())
+        })
+        .unwrap();
+        assert_eq!(&words[..], &raw.payload()[8..raw.payload_len()]);
+    }
+
+    #[test]
+    fn compressed_reports_fit_more_entries() {
+        let probe_id = ProbeId::new(1).unwrap();
+        let mut storage = [MaybeUninit::new(0u8); 1024];
+        let h = DynamicHistory::new_at(
+            &mut storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        h.set_compressed_reports(true);
+        for id in 1..=20 {
+            h.record_event(EventId::new(id).unwrap());
+        }
+
+        // Room for 10 raw entries, which is plenty for the whole log
+        // of 23 words compressed
+        let mut report = [0u8; 128];
+        let len = WireReport::<&[u8]>::buffer_len(1, 10);
+        let report_len = h.report(&mut report[..len]).unwrap().unwrap().get();
+        let r = WireReport::new(&report[..report_len]).unwrap();
+        assert!(r.is_compressed());
+        let mut n_words = 0;
+        compression::decode(&r.payload()[8..r.payload_len()], |_| n_words += 1).unwrap();
+        assert_eq!(n_words, 23);
+        assert_eq!(h.report(&mut report[..len]).unwrap(), None);
+    }
+
+    #[test]
+    fn compact_snapshot_merging() {
+        fn last_logged_clock(h: &DynamicHistory, id: ProbeId) -> Option<LogicalClock> {
+            h.log
+                .iter()
+                .filter_map(|entry| match entry {
+                    WholeEntry::Double(first, second)
+                        if first.has_clock_bit_set()
+                            && first.interpret_as_logical_clock_probe_id() == id.get_raw() =>
+                    {
+                        let (epoch, ticks) = crate::unpack_clock_word(second.raw());
+                        Some(LogicalClock { id, epoch, ticks })
+                    }
+                    _ => None,
+                })
+                .last()
+        }
+
+        let sender_id = ProbeId::new(2).unwrap();
+        let mut sender_storage = [MaybeUninit::new(0u8); 512];
+        let sender = DynamicHistory::new_at(
+            &mut sender_storage,
+            sender_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        sender.self_clock.epoch = ProbeEpoch(0x0305);
+        let mut receiver_storage = [MaybeUninit::new(0u8); 512];
+        let receiver = DynamicHistory::new_at(
+            &mut receiver_storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+
+        let mut bytes = [0u8; 8];
+        let len = sender
+            .produce_compact_snapshot_bytes(EpochEncoding::Truncated, &mut bytes)
+            .unwrap();
+        assert_eq!(len, 5);
+        assert_eq!(
+            receiver.merge_compact_snapshot_bytes(&bytes[..len]),
+            Err(MergeError::AmbiguousEpoch)
+        );
+        assert_eq!(last_logged_clock(receiver, sender_id), None);
+
+        // Once a whole epoch is known, truncated ones can be reconstructed
+        let len = sender
+            .produce_compact_snapshot_bytes(EpochEncoding::Whole, &mut bytes)
+            .unwrap();
+        assert_eq!(len, 6);
+        receiver
+            .merge_compact_snapshot_bytes(&bytes[..len])
+            .unwrap();
+        let len = sender
+            .produce_compact_snapshot_bytes(EpochEncoding::Truncated, &mut bytes)
+            .unwrap();
+        let sent_clock = sender.self_clock.prev();
+        receiver
+            .merge_compact_snapshot_bytes(&bytes[..len])
+            .unwrap();
+        assert_eq!(last_logged_clock(receiver, sender_id), Some(sent_clock));
+        assert_eq!(sent_clock.epoch, ProbeEpoch(0x0305));
+
+        assert_eq!(
+            receiver.merge_compact_snapshot_bytes(&bytes[..len - 1]),
+            Err(MergeError::InsufficientSourceSize)
+        );
+    }
+
+    #[test]
+    fn interaction_id_merging() {
+        let mut sender_storage = [MaybeUninit::new(0u8); 512];
+        let sender = DynamicHistory::new_at(
+            &mut sender_storage,
+            ProbeId::new(2).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let mut receiver_storage = [MaybeUninit::new(0u8); 512];
+        let receiver = DynamicHistory::new_at(
+            &mut receiver_storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let interaction = NonZeroU32::new(0x123).unwrap();
+        let (ev, payload) = LogEntry::event_with_payload(EventId::EVENT_INTERACTION_ID, 0x123);
+
+        let mut snap = sender.produce_snapshot();
+        assert_eq!(snap.interaction_id(), None);
+        snap.set_interaction_id(Some(interaction));
+        receiver.merge_snapshot(&snap);
+        assert_eq!(
+            receiver.log.iter().last(),
+            Some(WholeEntry::Double(ev, payload))
+        );
+
+        let mut bytes = [0u8; 12];
+        let len = sender.produce_snapshot_bytes(&mut bytes).unwrap();
+        WireCausalSnapshot::new_unchecked(&mut bytes[..len]).set_interaction_id(Some(interaction));
+        receiver.merge_snapshot_bytes(&bytes[..len]).unwrap();
+        assert_eq!(
+            receiver.log.iter().last(),
+            Some(WholeEntry::Double(ev, payload))
+        );
+
+        // Untagged snapshots only record the clocks
+        let snap = sender.produce_snapshot();
+        receiver.merge_snapshot(&snap);
+        match receiver.log.iter().last() {
+            Some(WholeEntry::Double(first, _)) => assert!(first.has_clock_bit_set()),
+            e => panic!("Unexpected log entry {:?}", e),
+        }
+    }
+
     #[cfg(feature = "debug-collector-access")]
     #[test]
     fn debug_collector_offsets() {
@@ -1522,6 +3363,12 @@ mod test {
         let pec_addr = &probe.history.persistent_epoch_counting as *const _ as u64;
         assert_eq!(persistent_epoch_counting_offset(), pec_addr - history_addr);
 
+        let disabled_events_addr = &probe.history.disabled_events as *const _ as u64;
+        assert_eq!(
+            disabled_events_offset(),
+            disabled_events_addr - history_addr
+        );
+
         let write_seqn_high_addr = &probe.history.log.write_seqn.high as *const _ as u64;
         assert_eq!(
             write_seqn_high_offset(),
@@ -0,0 +1,718 @@
+//! A probe for hosted (`std`) environments that owns its storage and
+//! reports to a UDP collector from a background thread.
+
+use crate::{
+    wire::{siphash, ControlCommand, EpochEncoding},
+    CausalSnapshot, ClockEvictionPolicy, ControlError, EventId, MergeError, ModalityProbe,
+    NanosecondResolution, Nanoseconds, Probe, ProbeId, ProduceError, ReportError,
+    RestartCounterProvider, StorageSetupError, WallClockId,
+};
+use core::{mem::MaybeUninit, num::NonZeroUsize};
+use std::{
+    fmt, io,
+    net::{ToSocketAddrs, UdpSocket},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Configuration for a [`HostedProbe`](struct.HostedProbe.html)
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostedProbeConfig {
+    /// Size, in bytes, of the probe's storage
+    pub storage_bytes: usize,
+    /// Size, in bytes, of the buffer each report is written into
+    pub report_buffer_bytes: usize,
+    /// How often the background thread reports
+    pub report_interval: Duration,
+    /// Report early once the log is at least this full, as a
+    /// percentage of its capacity
+    pub report_watermark_percent: Option<u8>,
+    /// Time resolution reported by the probe
+    pub time_resolution: NanosecondResolution,
+    /// Wall clock id reported by the probe
+    pub wall_clock_id: WallClockId,
+    /// Keep reported log entries until the collector acknowledges
+    /// the report, resending it every `report_interval` until then.
+    /// The collector must be acknowledging reports.
+    pub acknowledged_reporting: bool,
+    /// How to make room for new neighbors' clocks once the probe's
+    /// clock table is full
+    pub clock_eviction_policy: ClockEvictionPolicy,
+    /// Authenticate reports with a MAC under this key, and only
+    /// accept control messages tagged with it. The collector must
+    /// have the same key for the probe.
+    pub report_key: Option<siphash::Key>,
+    /// Compress the log entries in reports
+    pub compressed_reports: bool,
+}
+
+impl Default for HostedProbeConfig {
+    fn default() -> Self {
+        HostedProbeConfig {
+            storage_bytes: 8192,
+            report_buffer_bytes: 1024,
+            report_interval: Duration::from_secs(1),
+            report_watermark_percent: Some(75),
+            time_resolution: NanosecondResolution::UNSPECIFIED,
+            wall_clock_id: WallClockId::local_only(),
+            acknowledged_reporting: false,
+            clock_eviction_policy: ClockEvictionPolicy::KeepExisting,
+            report_key: None,
+            compressed_reports: false,
+        }
+    }
+}
+
+/// An error relating to the setup of a `HostedProbe`
+#[derive(Debug)]
+pub enum HostedProbeError {
+    /// The collector address couldn't be resolved, or the reporting
+    /// socket couldn't be set up.
+    Io(io::Error),
+    /// A problem with the probe's storage setup.
+    StorageSetupError(StorageSetupError),
+}
+
+impl std::error::Error for HostedProbeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HostedProbeError::Io(e) => Some(e),
+            HostedProbeError::StorageSetupError(e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for HostedProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostedProbeError::Io(_) => f.write_str("Collector Socket Error"),
+            HostedProbeError::StorageSetupError(_) => f.write_str("Storage Setup Error"),
+        }
+    }
+}
+
+impl From<io::Error> for HostedProbeError {
+    fn from(e: io::Error) -> Self {
+        HostedProbeError::Io(e)
+    }
+}
+
+impl From<StorageSetupError> for HostedProbeError {
+    fn from(e: StorageSetupError) -> Self {
+        HostedProbeError::StorageSetupError(e)
+    }
+}
+
+/// A probe along with the heap storage it was initialized in.
+struct OwnedProbe {
+    storage: *mut [MaybeUninit<u8>],
+    probe: *mut ModalityProbe<'static>,
+}
+
+// The probe lives in storage owned by this struct, and is created with
+// `RestartCounterProvider::NoRestartTracking`, so it holds no
+// references to anything tied to the creating thread.
+unsafe impl Send for OwnedProbe {}
+
+impl OwnedProbe {
+    fn new(
+        storage_bytes: usize,
+        probe_id: ProbeId,
+        time_resolution: NanosecondResolution,
+        wall_clock_id: WallClockId,
+    ) -> Result<Self, StorageSetupError> {
+        let storage = Box::into_raw(vec![MaybeUninit::new(0u8); storage_bytes].into_boxed_slice());
+        // Safe because the storage is only freed when this struct is
+        // dropped, and the probe is never handed out beyond a borrow of it.
+        let probe = ModalityProbe::initialize_at(
+            unsafe { &mut *storage },
+            probe_id,
+            time_resolution,
+            wall_clock_id,
+            RestartCounterProvider::NoRestartTracking,
+        );
+        match probe {
+            Ok(probe) => Ok(OwnedProbe { storage, probe }),
+            Err(e) => {
+                drop(unsafe { Box::from_raw(storage) });
+                Err(e)
+            }
+        }
+    }
+
+    fn get(&mut self) -> &mut ModalityProbe<'static> {
+        unsafe { &mut *self.probe }
+    }
+}
+
+impl Drop for OwnedProbe {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.storage) });
+    }
+}
+
+struct State {
+    probe: OwnedProbe,
+    report_requested: bool,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+    watermark_percent: Option<u8>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A poisoned lock means a panic happened while the probe was in
+        // use; the probe itself is still consistent, so keep going.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A `ModalityProbe` for hosted environments.
+///
+/// It owns its storage and sends reports to a UDP collector from a
+/// background thread, every `report_interval` or as soon as the log
+/// fills past the configured watermark. Any remaining log entries are
+/// reported when it's dropped. Control messages the collector sends
+/// back to the reporting socket are applied by another background
+/// thread.
+///
+/// `HostedProbe` implements [`Probe`](trait.Probe.html), so the
+/// recording macros work with it directly. Use
+/// [`with_probe`](struct.HostedProbe.html#method.with_probe) for the
+/// rest of the `ModalityProbe` API.
+pub struct HostedProbe {
+    shared: Arc<Shared>,
+    reporter: Option<JoinHandle<()>>,
+    controller: Option<JoinHandle<()>>,
+}
+
+impl HostedProbe {
+    /// Create a probe with the default configuration that reports to
+    /// the collector at `collector_addr`
+    pub fn new<A: ToSocketAddrs>(
+        probe_id: ProbeId,
+        collector_addr: A,
+    ) -> Result<Self, HostedProbeError> {
+        Self::with_config(probe_id, collector_addr, HostedProbeConfig::default())
+    }
+
+    /// Create a probe with the given configuration that reports to
+    /// the collector at `collector_addr`
+    pub fn with_config<A: ToSocketAddrs>(
+        probe_id: ProbeId,
+        collector_addr: A,
+        config: HostedProbeConfig,
+    ) -> Result<Self, HostedProbeError> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(collector_addr)?;
+        let mut probe = OwnedProbe::new(
+            config.storage_bytes,
+            probe_id,
+            config.time_resolution,
+            config.wall_clock_id,
+        )?;
+        probe
+            .get()
+            .set_acknowledged_reporting(config.acknowledged_reporting);
+        probe
+            .get()
+            .set_clock_eviction_policy(config.clock_eviction_policy);
+        probe.get().set_report_key(config.report_key);
+        probe
+            .get()
+            .set_compressed_reports(config.compressed_reports);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                probe,
+                report_requested: false,
+                shutdown: false,
+            }),
+            wake: Condvar::new(),
+            watermark_percent: config.report_watermark_percent,
+        });
+
+        let control_socket = socket.try_clone()?;
+        control_socket.set_read_timeout(Some(CONTROL_POLL_INTERVAL))?;
+        let controller_shared = shared.clone();
+        let controller = thread::Builder::new()
+            .name(format!("modality-probe-control-{}", probe_id.get_raw()))
+            .spawn(move || control_loop(&controller_shared, &control_socket))?;
+
+        let reporter_shared = shared.clone();
+        let reporter = thread::Builder::new()
+            .name(format!("modality-probe-reporter-{}", probe_id.get_raw()))
+            .spawn(move || {
+                report_loop(
+                    &reporter_shared,
+                    &socket,
+                    config.report_buffer_bytes,
+                    config.report_interval,
+                )
+            });
+        let reporter = match reporter {
+            Ok(reporter) => reporter,
+            Err(e) => {
+                // Don't leave the control thread running without a probe
+                shared.lock().shutdown = true;
+                let _ = controller.join();
+                return Err(e.into());
+            }
+        };
+
+        Ok(HostedProbe {
+            shared,
+            reporter: Some(reporter),
+            controller: Some(controller),
+        })
+    }
+
+    /// Run `f` with exclusive access to the underlying probe
+    pub fn with_probe<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut ModalityProbe<'static>) -> R,
+    {
+        let mut state = self.shared.lock();
+        let result = f(state.probe.get());
+        self.check_watermark(&mut state);
+        result
+    }
+
+    /// Wake the background thread to report now, rather than at the
+    /// end of the current interval
+    pub fn request_report(&self) {
+        let mut state = self.shared.lock();
+        state.report_requested = true;
+        self.shared.wake.notify_one();
+    }
+
+    fn check_watermark(&self, state: &mut State) {
+        if let Some(watermark) = self.shared.watermark_percent {
+            let probe = state.probe.get();
+            // Reporting early won't help while the log is held up
+            // waiting for an acknowledgement
+            let past_watermark = probe.history.unacked_report.is_none()
+                && probe.log_fill_level().percent() >= watermark;
+            if !state.report_requested && past_watermark {
+                state.report_requested = true;
+                self.shared.wake.notify_one();
+            }
+        }
+    }
+}
+
+impl Drop for HostedProbe {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.lock();
+            state.shutdown = true;
+            self.shared.wake.notify_one();
+        }
+        if let Some(reporter) = self.reporter.take() {
+            let _ = reporter.join();
+        }
+        if let Some(controller) = self.controller.take() {
+            let _ = controller.join();
+        }
+    }
+}
+
+impl fmt::Debug for HostedProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostedProbe").finish()
+    }
+}
+
+/// Report every `interval` or when woken, until shut down, then drain
+/// whatever is left.
+///
+/// With acknowledged reporting, only one report is outstanding at a
+/// time, so at most one is sent per wakeup. That mode is read from the
+/// probe each time, since it can be changed through `with_probe`. No
+/// acknowledgements are applied once shut down, so the last wakeup
+/// sends the outstanding report once more, then turns the mode off to
+/// drain the rest.
+fn report_loop(shared: &Shared, socket: &UdpSocket, buffer_bytes: usize, interval: Duration) {
+    let mut buffer = vec![0u8; buffer_bytes];
+    loop {
+        let mut state = shared.lock();
+        if !state.report_requested && !state.shutdown {
+            state = shared
+                .wake
+                .wait_timeout(state, interval)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        state.report_requested = false;
+        let shutdown = state.shutdown;
+
+        // Produce the reports while holding the lock, but send them
+        // without it so recording isn't held up on the network.
+        let mut reports = Vec::new();
+        let probe = state.probe.get();
+        while let Ok(Some(n)) = probe.report(&mut buffer) {
+            reports.push(buffer[..n.get()].to_vec());
+            if probe.history.acknowledged_reporting {
+                if !shutdown {
+                    break;
+                }
+                probe.set_acknowledged_reporting(false);
+            }
+        }
+        drop(state);
+
+        for report in reports {
+            // Delivery is best effort; the collector notices any gaps
+            // from the report sequence numbers.
+            let _ = socket.send(&report);
+        }
+
+        if shutdown {
+            return;
+        }
+    }
+}
+
+/// How long the control thread waits for a message before checking
+/// whether it should shut down
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Apply control messages from the collector until shut down.
+fn control_loop(shared: &Shared, socket: &UdpSocket) {
+    let mut buffer = [0u8; 64];
+    loop {
+        let received = socket.recv(&mut buffer);
+        let mut state = shared.lock();
+        if state.shutdown {
+            return;
+        }
+        // Timeouts and errors from earlier sends both just mean there's
+        // nothing to apply; invalid messages are ignored.
+        if let Ok(n) = received {
+            if let Ok(ControlCommand::RequestReport) =
+                state.probe.get().handle_control(&buffer[..n])
+            {
+                state.report_requested = true;
+                shared.wake.notify_one();
+            }
+        }
+    }
+}
+
+impl Probe for HostedProbe {
+    fn record_time(&mut self, time: Nanoseconds) {
+        self.with_probe(|p| p.record_time(time))
+    }
+
+    fn record_event(&mut self, event_id: EventId) {
+        self.with_probe(|p| p.record_event(event_id))
+    }
+
+    fn record_event_with_time(&mut self, event_id: EventId, time: Nanoseconds) {
+        self.with_probe(|p| p.record_event_with_time(event_id, time))
+    }
+
+    fn record_event_with_payload(&mut self, event_id: EventId, payload: u32) {
+        self.with_probe(|p| p.record_event_with_payload(event_id, payload))
+    }
+
+    fn record_event_with_payload_with_time(
+        &mut self,
+        event_id: EventId,
+        payload: u32,
+        time: Nanoseconds,
+    ) {
+        self.with_probe(|p| p.record_event_with_payload_with_time(event_id, payload, time))
+    }
+
+    fn produce_snapshot(&mut self) -> CausalSnapshot {
+        self.with_probe(|p| p.produce_snapshot())
+    }
+
+    fn produce_snapshot_with_time(&mut self, time: Nanoseconds) -> CausalSnapshot {
+        self.with_probe(|p| p.produce_snapshot_with_time(time))
+    }
+
+    fn produce_snapshot_bytes(&mut self, destination: &mut [u8]) -> Result<usize, ProduceError> {
+        self.with_probe(|p| p.produce_snapshot_bytes(destination))
+    }
+
+    fn produce_snapshot_bytes_with_time(
+        &mut self,
+        time: Nanoseconds,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        self.with_probe(|p| p.produce_snapshot_bytes_with_time(time, destination))
+    }
+
+    fn merge_snapshot(&mut self, external_history: &CausalSnapshot) {
+        self.with_probe(|p| p.merge_snapshot(external_history))
+    }
+
+    fn merge_snapshot_with_time(&mut self, external_history: &CausalSnapshot, time: Nanoseconds) {
+        self.with_probe(|p| p.merge_snapshot_with_time(external_history, time))
+    }
+
+    fn merge_snapshot_bytes(&mut self, source: &[u8]) -> Result<(), MergeError> {
+        self.with_probe(|p| p.merge_snapshot_bytes(source))
+    }
+
+    fn merge_snapshot_bytes_with_time(
+        &mut self,
+        source: &[u8],
+        time: Nanoseconds,
+    ) -> Result<(), MergeError> {
+        self.with_probe(|p| p.merge_snapshot_bytes_with_time(source, time))
+    }
+
+    fn produce_compact_snapshot_bytes(
+        &mut self,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        self.with_probe(|p| p.produce_compact_snapshot_bytes(epoch_encoding, destination))
+    }
+
+    fn produce_compact_snapshot_bytes_with_time(
+        &mut self,
+        time: Nanoseconds,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        self.with_probe(|p| {
+            p.produce_compact_snapshot_bytes_with_time(time, epoch_encoding, destination)
+        })
+    }
+
+    fn merge_compact_snapshot_bytes(&mut self, source: &[u8]) -> Result<(), MergeError> {
+        self.with_probe(|p| p.merge_compact_snapshot_bytes(source))
+    }
+
+    fn merge_compact_snapshot_bytes_with_time(
+        &mut self,
+        source: &[u8],
+        time: Nanoseconds,
+    ) -> Result<(), MergeError> {
+        self.with_probe(|p| p.merge_compact_snapshot_bytes_with_time(source, time))
+    }
+
+    /// Reports are normally produced by the background thread; this
+    /// produces one on the caller's thread instead.
+    fn report(&mut self, destination: &mut [u8]) -> Result<Option<NonZeroUsize>, ReportError> {
+        self.with_probe(|p| p.report(destination))
+    }
+
+    fn handle_control(&mut self, message: &[u8]) -> Result<ControlCommand, ControlError> {
+        let command = self.with_probe(|p| p.handle_control(message))?;
+        if command == ControlCommand::RequestReport {
+            self.request_report();
+        }
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{log::LogEntry, wire::WireReport};
+    use std::time::Instant;
+
+    fn collector() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        socket
+    }
+
+    /// The raw log words of every report received until `done` says so
+    fn receive_until(collector: &UdpSocket, done: impl Fn(&[u32]) -> bool) -> Vec<u32> {
+        let mut words = Vec::new();
+        let mut buf = [0u8; 2048];
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done(&words) && Instant::now() < deadline {
+            let n = collector.recv(&mut buf).unwrap();
+            let report = WireReport::new(&buf[..n]).unwrap();
+            words.extend(
+                report
+                    .payload()
+                    .chunks_exact(4)
+                    .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])),
+            );
+        }
+        words
+    }
+
+    fn contains_event(words: &[u32], id: EventId) -> bool {
+        words
+            .iter()
+            .any(|w| unsafe { LogEntry::new_unchecked(*w) } == LogEntry::event(id))
+    }
+
+    #[test]
+    fn reports_remaining_entries_on_drop() {
+        let collector = collector();
+        let event = EventId::new(7).unwrap();
+        {
+            let mut probe = HostedProbe::with_config(
+                ProbeId::new(1).unwrap(),
+                collector.local_addr().unwrap(),
+                HostedProbeConfig {
+                    report_interval: Duration::from_secs(3600),
+                    report_watermark_percent: None,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            probe.record_event(event);
+        }
+        let words = receive_until(&collector, |w| contains_event(w, event));
+        assert!(contains_event(&words, event));
+    }
+
+    #[test]
+    fn reports_early_past_the_watermark() {
+        let collector = collector();
+        let event = EventId::new(9).unwrap();
+        let mut probe = HostedProbe::with_config(
+            ProbeId::new(2).unwrap(),
+            collector.local_addr().unwrap(),
+            HostedProbeConfig {
+                storage_bytes: 512,
+                report_interval: Duration::from_secs(3600),
+                report_watermark_percent: Some(50),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for _ in 0..100 {
+            probe.record_event(event);
+        }
+        // Received while the probe is still alive, so it can only have
+        // come from the watermark
+        let words = receive_until(&collector, |w| contains_event(w, event));
+        assert!(contains_event(&words, event));
+    }
+
+    #[test]
+    fn applies_control_messages_from_the_collector() {
+        let collector = collector();
+        let probe_id = ProbeId::new(4).unwrap();
+        let event = EventId::new(11).unwrap();
+        let mut probe = HostedProbe::with_config(
+            probe_id,
+            collector.local_addr().unwrap(),
+            HostedProbeConfig {
+                report_interval: Duration::from_secs(3600),
+                report_watermark_percent: None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // The probe's reporting socket address is only known once it
+        // has reported, so get it to report once
+        probe.request_report();
+        let mut buf = [0u8; 2048];
+        let (_, probe_addr) = collector.recv_from(&mut buf).unwrap();
+
+        let mut msg = [0u8; 64];
+        let n = ControlCommand::SetDisabledEvents(crate::EventMask::EMPTY.with_event(event))
+            .write_message(probe_id, &mut msg)
+            .unwrap();
+        collector.send_to(&msg[..n], probe_addr).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while probe.with_probe(|p| p.disabled_events()).is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(probe.with_probe(|p| p.disabled_events()).contains(event));
+
+        probe.record_event(event);
+        let other_event = EventId::new(12).unwrap();
+        probe.record_event(other_event);
+        let n = ControlCommand::RequestReport
+            .write_message(probe_id, &mut msg)
+            .unwrap();
+        collector.send_to(&msg[..n], probe_addr).unwrap();
+        let words = receive_until(&collector, |w| contains_event(w, other_event));
+        assert!(contains_event(&words, other_event));
+        assert!(!contains_event(&words, event));
+    }
+
+    #[test]
+    fn sends_one_unacked_report_per_wakeup() {
+        let collector = collector();
+        let event = EventId::new(13).unwrap();
+        let mut probe = HostedProbe::with_config(
+            ProbeId::new(5).unwrap(),
+            collector.local_addr().unwrap(),
+            HostedProbeConfig {
+                report_interval: Duration::from_secs(3600),
+                report_watermark_percent: None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        probe.with_probe(|p| p.set_acknowledged_reporting(true));
+        probe.record_event(event);
+        probe.request_report();
+
+        let mut buf = [0u8; 2048];
+        collector.recv(&mut buf).unwrap();
+        // Until it's acknowledged, the report is only resent on the
+        // next wakeup
+        collector
+            .set_read_timeout(Some(Duration::from_millis(300)))
+            .unwrap();
+        assert!(collector.recv(&mut buf).is_err());
+        probe.request_report();
+        collector.recv(&mut buf).unwrap();
+    }
+
+    #[test]
+    fn drains_unacked_reports_on_drop() {
+        let collector = collector();
+        let first = EventId::new(14).unwrap();
+        let second = EventId::new(15).unwrap();
+        {
+            let mut probe = HostedProbe::with_config(
+                ProbeId::new(6).unwrap(),
+                collector.local_addr().unwrap(),
+                HostedProbeConfig {
+                    acknowledged_reporting: true,
+                    report_interval: Duration::from_secs(3600),
+                    report_watermark_percent: None,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            probe.record_event(first);
+            probe.request_report();
+            let words = receive_until(&collector, |w| contains_event(w, first));
+            assert!(contains_event(&words, first));
+
+            // Never acknowledged, so held up behind the first report
+            probe.record_event(second);
+        }
+        let words = receive_until(&collector, |w| contains_event(w, second));
+        assert!(contains_event(&words, second));
+    }
+
+    #[test]
+    fn storage_too_small() {
+        let err = HostedProbe::with_config(
+            ProbeId::new(3).unwrap(),
+            "127.0.0.1:9",
+            HostedProbeConfig {
+                storage_bytes: 8,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, HostedProbeError::StorageSetupError(_)));
+    }
+}
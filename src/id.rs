@@ -225,6 +225,49 @@ impl EventId {
     /// Reserved for indicating wall clock time
     pub const EVENT_WALL_CLOCK_TIME_ONLY: EventId =
         EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 8) });
+    /// The snapshot merged just before this event was tagged with an
+    /// interaction id, which is stored in the payload.
+    pub const EVENT_INTERACTION_ID: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 9) });
+    /// A neighbor's logical clock was evicted from the probe's full
+    /// table of clocks to make room for another's. The evicted probe id
+    /// is stored in the payload.
+    pub const EVENT_CLOCK_EVICTED: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 10) });
+    /// The probe kept the log a previous run left in retained memory,
+    /// rather than starting with an empty one. The number of log
+    /// entries kept is stored in the payload.
+    pub const EVENT_PRIOR_LOG_RETAINED: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 11) });
+    /// The probe's count of events recorded, stored in the payload.
+    /// Reported only when stats are included in reports, as are the
+    /// other stats events.
+    pub const EVENT_STATS_EVENTS_RECORDED: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 12) });
+    /// The probe's count of log entries overwritten before being
+    /// reported, stored in the payload.
+    pub const EVENT_STATS_ENTRIES_OVERWRITTEN: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 13) });
+    /// The probe's count of reports produced, stored in the payload.
+    pub const EVENT_STATS_REPORTS_PRODUCED: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 14) });
+    /// The probe's count of bytes reported, stored in the payload.
+    pub const EVENT_STATS_BYTES_REPORTED: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 15) });
+    /// The probe's count of snapshots produced, stored in the payload.
+    pub const EVENT_STATS_SNAPSHOTS_PRODUCED: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 16) });
+    /// The probe's count of snapshots merged, stored in the payload.
+    pub const EVENT_STATS_SNAPSHOTS_MERGED: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 17) });
+    /// The probe's count of logical clock overflows, stored in the
+    /// payload.
+    pub const EVENT_STATS_CLOCK_OVERFLOWS: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 18) });
+    /// The probe's count of neighbor clocks dropped because the
+    /// clock table was full, stored in the payload.
+    pub const EVENT_STATS_FRONTIER_CLOCK_OVERFLOWS: EventId =
+        EventId(unsafe { NonZeroU32::new_unchecked(EventId::MAX_INTERNAL_ID - 19) });
 
     /// The events reserved for internal use
     pub const INTERNAL_EVENTS: &'static [EventId] = &[
@@ -236,6 +279,17 @@ impl EventId {
         EventId::EVENT_PROBE_INITIALIZED,
         EventId::EVENT_INVALID_NEXT_EPOCH_SEQ_ID,
         EventId::EVENT_WALL_CLOCK_TIME_ONLY,
+        EventId::EVENT_INTERACTION_ID,
+        EventId::EVENT_CLOCK_EVICTED,
+        EventId::EVENT_PRIOR_LOG_RETAINED,
+        EventId::EVENT_STATS_EVENTS_RECORDED,
+        EventId::EVENT_STATS_ENTRIES_OVERWRITTEN,
+        EventId::EVENT_STATS_REPORTS_PRODUCED,
+        EventId::EVENT_STATS_BYTES_REPORTED,
+        EventId::EVENT_STATS_SNAPSHOTS_PRODUCED,
+        EventId::EVENT_STATS_SNAPSHOTS_MERGED,
+        EventId::EVENT_STATS_CLOCK_OVERFLOWS,
+        EventId::EVENT_STATS_FRONTIER_CLOCK_OVERFLOWS,
     ];
 
     /// raw_id must be greater than 0 and less than EventId::MAX_USER_ID
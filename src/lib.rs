@@ -11,6 +11,7 @@ use core::{
     convert::TryFrom,
     mem::{align_of, size_of, MaybeUninit},
     num::NonZeroUsize,
+    ptr,
 };
 
 use fixed_slice_vec::single::{embed_uninit, EmbedValueError, SplitUninitError};
@@ -19,22 +20,46 @@ use serde::{Deserialize, Serialize};
 use static_assertions::{assert_cfg, const_assert};
 
 pub use error::*;
+pub use event_mask::{EventMask, EVENT_MASK_BITS, EVENT_MASK_WORDS};
 use history::DynamicHistory;
 pub use id::*;
+pub use log_storage::{LogStorage, RetainedRam, VolatileRam};
+pub use log_watermark::{
+    log_watermark_fn, CLogWatermarkHandler, LogFillLevel, LogWatermarkCallback,
+    LogWatermarkHandler, RustLogWatermarkHandler,
+};
 #[cfg(feature = "std")]
 pub use prop::*;
+#[cfg(feature = "std")]
+pub use restart_counter::FileRestartCounter;
 pub use restart_counter::{
-    next_sequence_id_fn, CRestartCounterProvider, RestartCounter, RestartCounterProvider,
+    flash, next_sequence_id_fn, CFlash, CRestartCounterProvider, Flash, FlashError,
+    FlashRestartCounter, RamFlash, RestartCounter, RestartCounterProvider,
     RestartSequenceIdUnavailable, RustRestartCounterProvider,
 };
+pub use stats::ProbeStats;
 pub use time::{NanosecondResolution, Nanoseconds, WallClockId};
+use wire::{
+    CompactCausalSnapshot, ControlCommand, EpochEncoding, ReportFragments, WireReportFragment,
+    MAX_FRAGMENTS,
+};
+
+#[cfg(feature = "std")]
+pub use hosted::{HostedProbe, HostedProbeConfig, HostedProbeError};
 
 mod error;
+mod event_mask;
 mod history;
+#[cfg(feature = "std")]
+mod hosted;
 mod id;
 pub mod log;
+pub mod log_backend;
+mod log_storage;
+mod log_watermark;
 mod macros;
 mod restart_counter;
+mod stats;
 pub mod time;
 pub mod wire;
 
@@ -51,9 +76,9 @@ pub struct CausalSnapshot {
     /// Probe id and tick-count at the probe which this history snapshot
     /// was created from
     pub clock: LogicalClock,
-    /// Reserved field
+    /// Reserved field, holding the low half of the interaction id
     pub reserved_0: [u8; 2],
-    /// Reserved field
+    /// Reserved field, holding the high half of the interaction id
     pub reserved_1: [u8; 2],
 }
 
@@ -392,6 +417,73 @@ pub trait Probe {
         time: Nanoseconds,
     ) -> Result<(), MergeError>;
 
+    /// Write a compact summary of this probe's causal history (see
+    /// the `wire::compact_snapshot` module) for use by another probe
+    /// elsewhere in the system, encoding the epoch as specified.
+    ///
+    /// If the write was successful, returns the number of bytes written.
+    ///
+    /// The default implementation encodes the clock of a snapshot
+    /// from `produce_snapshot`.
+    fn produce_compact_snapshot_bytes(
+        &mut self,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        let snapshot = self.produce_snapshot();
+        Ok(CompactCausalSnapshot::new(snapshot.clock, epoch_encoding)
+            .write_into_le_bytes(destination)?)
+    }
+
+    /// Write a compact summary of this probe's causal history with
+    /// time for use by another probe elsewhere in the system,
+    /// encoding the epoch as specified.
+    ///
+    /// If the write was successful, returns the number of bytes written.
+    ///
+    /// The default implementation encodes the clock of a snapshot
+    /// from `produce_snapshot_with_time`.
+    fn produce_compact_snapshot_bytes_with_time(
+        &mut self,
+        time: Nanoseconds,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        let snapshot = self.produce_snapshot_with_time(time);
+        Ok(CompactCausalSnapshot::new(snapshot.clock, epoch_encoding)
+            .write_into_le_bytes(destination)?)
+    }
+
+    /// Consume a compact causal history summary blob provided
+    /// by some other probe via `produce_compact_snapshot_bytes`.
+    ///
+    /// Fails with `MergeError::AmbiguousEpoch` if the blob's epoch is
+    /// truncated and no epoch is known for its probe yet. The default
+    /// implementation knows no epochs, so it only merges blobs
+    /// carrying a full epoch, via `merge_snapshot`.
+    fn merge_compact_snapshot_bytes(&mut self, source: &[u8]) -> Result<(), MergeError> {
+        let snapshot = compact_to_causal_snapshot(source)?;
+        self.merge_snapshot(&snapshot);
+        Ok(())
+    }
+
+    /// Consume a compact causal history summary blob with time
+    /// provided by some other probe via `produce_compact_snapshot_bytes`.
+    ///
+    /// Fails with `MergeError::AmbiguousEpoch` if the blob's epoch is
+    /// truncated and no epoch is known for its probe yet. The default
+    /// implementation knows no epochs, so it only merges blobs
+    /// carrying a full epoch, via `merge_snapshot_with_time`.
+    fn merge_compact_snapshot_bytes_with_time(
+        &mut self,
+        source: &[u8],
+        time: Nanoseconds,
+    ) -> Result<(), MergeError> {
+        let snapshot = compact_to_causal_snapshot(source)?;
+        self.merge_snapshot_with_time(&snapshot, time);
+        Ok(())
+    }
+
     /// Copies a wire-ready report into `destination`.
     ///
     /// A wire-ready report is a byte slice containing:
@@ -401,6 +493,83 @@ pub trait Probe {
     /// 3. As much of the event log that will fit in the remaining
     ///    chunk of `destination`.
     fn report(&mut self, destination: &mut [u8]) -> Result<Option<NonZeroUsize>, ReportError>;
+
+    /// Produces a report as `report` does, into `scratch`, then
+    /// splits it into fragments (see the `wire::fragment` module) no
+    /// longer than `fragment`, handing each one to `send` in order.
+    ///
+    /// Returns the number of fragments sent. Fails with
+    /// `ReportError::InsufficientDestinationSize`, before anything is
+    /// taken out of the log, if `fragment` is too small to split a
+    /// report as long as `scratch` with.
+    fn report_fragmented<F: FnMut(&[u8])>(
+        &mut self,
+        scratch: &mut [u8],
+        fragment: &mut [u8],
+        mut send: F,
+    ) -> Result<usize, ReportError> {
+        let chunk_len = fragment
+            .len()
+            .saturating_sub(WireReportFragment::<&[u8]>::header_len());
+        if chunk_len == 0 || (scratch.len() + chunk_len - 1) / chunk_len > MAX_FRAGMENTS {
+            return Err(ReportError::InsufficientDestinationSize);
+        }
+        let report_len = match self.report(scratch)? {
+            Some(len) => len.get(),
+            None => return Ok(0),
+        };
+        let fragments = ReportFragments::new(&scratch[..report_len], fragment.len())
+            .ok_or(ReportError::InsufficientDestinationSize)?;
+        for index in 0..fragments.fragment_count() {
+            let len = fragments.write_fragment(index, fragment)?;
+            send(&fragment[..len]);
+        }
+        Ok(fragments.fragment_count())
+    }
+
+    /// Apply a control message (see the `wire::control` module) sent
+    /// to this probe by a collector, returning the command it carried.
+    ///
+    /// A `ControlCommand::RequestReport` command doesn't change the
+    /// probe; the caller should report as soon as possible.
+    ///
+    /// The default implementation supports no commands, failing with
+    /// `ControlError::UnsupportedCommand`.
+    fn handle_control(&mut self, _message: &[u8]) -> Result<ControlCommand, ControlError> {
+        Err(ControlError::UnsupportedCommand)
+    }
+}
+
+/// Decode a compact snapshot that carries its probe's full epoch
+fn compact_to_causal_snapshot(source: &[u8]) -> Result<CausalSnapshot, MergeError> {
+    let clock = CompactCausalSnapshot::try_from(source)?
+        .clock(None)
+        .ok_or(MergeError::AmbiguousEpoch)?;
+    Ok(CausalSnapshot {
+        clock,
+        reserved_0: [0, 0],
+        reserved_1: [0, 0],
+    })
+}
+
+/// How a probe makes room in its table of neighbor clocks when the
+/// table is full and a clock from a new neighbor comes in.
+///
+/// Each eviction is recorded as an `EVENT_CLOCK_EVICTED` event, with
+/// the evicted neighbor's probe id as its payload.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ClockEvictionPolicy {
+    /// Keep the clocks already in the table, dropping the new
+    /// neighbor's and recording an `EVENT_NUM_CLOCKS_OVERFLOWED` event
+    #[default]
+    KeepExisting = 0,
+    /// Evict the clock which was least recently merged
+    LeastRecentlyMerged = 1,
+    /// Evict the least recently merged of the clocks which have been
+    /// carried in a report since they last changed, keeping the
+    /// existing clocks if there are none
+    Reported = 2,
 }
 
 /// Reference implementation of a `ModalityProbe`.
@@ -459,6 +628,31 @@ impl<'a> ModalityProbe<'a> {
         .map_err(InitializationError::StorageSetupError)
     }
 
+    /// Like `try_initialize_at`, but if `log_storage` may hold a log
+    /// left in `memory` by a probe with the same id before a reset,
+    /// keep its entries to be reported first.
+    #[inline]
+    pub fn try_initialize_at_with_log_storage<S: LogStorage>(
+        memory: &'a mut [MaybeUninit<u8>],
+        probe_id: u32,
+        time_resolution: NanosecondResolution,
+        wall_clock_id: WallClockId,
+        restart_counter: RestartCounterProvider<'a>,
+        log_storage: &mut S,
+    ) -> Result<&'a mut ModalityProbe<'a>, InitializationError> {
+        let probe_id = ProbeId::try_from(probe_id)
+            .map_err(|_: InvalidProbeId| InitializationError::InvalidProbeId)?;
+        ModalityProbe::initialize_at_with_log_storage(
+            memory,
+            probe_id,
+            time_resolution,
+            wall_clock_id,
+            restart_counter,
+            log_storage,
+        )
+        .map_err(InitializationError::StorageSetupError)
+    }
+
     /// Initialize a probe for this probe id.
     /// `probe_id` ought to be unique throughout the system.
     ///
@@ -479,13 +673,110 @@ impl<'a> ModalityProbe<'a> {
         wall_clock_id: WallClockId,
         restart_counter: RestartCounterProvider<'a>,
     ) -> Result<&'a mut ModalityProbe<'a>, StorageSetupError> {
+        ModalityProbe::initialize_at_with_log_storage(
+            memory,
+            probe_id,
+            time_resolution,
+            wall_clock_id,
+            restart_counter,
+            &mut VolatileRam,
+        )
+    }
+
+    /// Initialize a probe for this probe id in `memory`, which
+    /// `log_storage` describes.
+    ///
+    /// If `log_storage` may hold what a previous run left in it, and
+    /// `memory` holds a probe with the same id whose log is consistent,
+    /// that log's entries are kept and get reported first, under the
+    /// epoch they were recorded in. The probe records an
+    /// `EVENT_PRIOR_LOG_RETAINED` event when it keeps a log.
+    ///
+    /// `memory` must be the same slice, at the same address, that the
+    /// previous probe was initialized in.
+    #[inline]
+    pub fn initialize_at_with_log_storage<S: LogStorage>(
+        memory: &'a mut [MaybeUninit<u8>],
+        probe_id: ProbeId,
+        time_resolution: NanosecondResolution,
+        wall_clock_id: WallClockId,
+        restart_counter: RestartCounterProvider<'a>,
+        log_storage: &mut S,
+    ) -> Result<&'a mut ModalityProbe<'a>, StorageSetupError> {
+        let padding_offset = memory.as_ptr().align_offset(align_of::<Self>());
+        let aligned_memory = memory
+            .get(padding_offset..)
+            .ok_or(StorageSetupError::UnderMinimumAllowedSize)?;
+        // A prior probe's history is only worth looking for if its
+        // fingerprint survived
+        let may_hold_prior_log = log_storage.may_hold_prior_log()
+            && aligned_memory.len() >= size_of::<Self>()
+            // Safe because the memory is aligned for a probe and big
+            // enough to hold one, and the storage says it's a retained
+            // region, which the hardware holds real bytes in. The read is
+            // volatile so the compiler can't assume it's uninitialized.
+            && unsafe {
+                ptr::read_volatile(aligned_memory.as_ptr() as *const MaybeUninit<u32>)
+                    .assume_init()
+            } == Self::STRUCT_FINGERPRINT;
+        Self::embed_at(memory, |history_memory| {
+            DynamicHistory::new_at_with_prior_log(
+                history_memory,
+                probe_id,
+                time_resolution,
+                wall_clock_id,
+                restart_counter,
+                may_hold_prior_log,
+            )
+        })
+    }
+
+    /// Restore a probe from a state saved with `save_state`, e.g. after
+    /// waking from a deep sleep which powered down the probe's memory.
+    ///
+    /// Unlike initializing a new probe, this doesn't start a new
+    /// epoch, so causality carries on from where the saved probe left
+    /// off. The probe id comes from the saved state. The saved state
+    /// doesn't include settings such as disabled events, the clock
+    /// eviction policy or the report key, which must be applied again.
+    ///
+    /// `memory` doesn't have to be the same size as the saved probe's,
+    /// but it must have room for all of the saved frontier clocks.
+    /// Saved log entries which don't fit are counted as missed.
+    #[inline]
+    pub fn restore_state(
+        state: &[u8],
+        memory: &'a mut [MaybeUninit<u8>],
+        time_resolution: NanosecondResolution,
+        wall_clock_id: WallClockId,
+        restart_counter: RestartCounterProvider<'a>,
+    ) -> Result<&'a mut ModalityProbe<'a>, RestoreStateError> {
+        let state = wire::WireProbeState::new(state)?;
+        Self::embed_at(memory, |history_memory| {
+            DynamicHistory::restore_at(
+                history_memory,
+                &state,
+                time_resolution,
+                wall_clock_id,
+                restart_counter,
+            )
+        })
+    }
+
+    /// Embed a probe in `memory`, aligned and preceded by guard bytes,
+    /// around the history `new_history` embeds in the rest of it
+    #[inline]
+    fn embed_at<E: From<StorageSetupError>>(
+        memory: &'a mut [MaybeUninit<u8>],
+        new_history: impl FnOnce(&'a mut [MaybeUninit<u8>]) -> Result<&'a mut DynamicHistory<'a>, E>,
+    ) -> Result<&'a mut ModalityProbe<'a>, E> {
         // Align memory before embedding so that all padding is filled with guard bytes
         // Note: For future improvement, this step could be included in fixed-slice-vec as a variant
         // of the `embed` function
         let padding_offset = memory.as_ptr().align_offset(align_of::<Self>());
 
         if memory.len() < padding_offset {
-            return Err(StorageSetupError::UnderMinimumAllowedSize);
+            return Err(StorageSetupError::UnderMinimumAllowedSize.into());
         }
 
         let (padding, aligned_memory) = memory.split_at_mut(padding_offset);
@@ -494,13 +785,11 @@ impl<'a> ModalityProbe<'a> {
             *b = MaybeUninit::new(Self::PADDING_GUARD_BYTE);
         }
         match embed_uninit(memory, |history_memory| {
-            ModalityProbe::new_with_storage(
-                history_memory,
-                probe_id,
-                time_resolution,
-                wall_clock_id,
-                restart_counter,
-            )
+            Ok::<_, E>(ModalityProbe::<'a> {
+                fingerprint: Self::STRUCT_FINGERPRINT,
+                fingerprint_padding: 0,
+                history: new_history(history_memory)?,
+            })
         }) {
             Ok(v) => {
                 // Check there is no extra padding beyond guard bytes
@@ -508,10 +797,10 @@ impl<'a> ModalityProbe<'a> {
                 Ok(v)
             }
             Err(EmbedValueError::SplitUninitError(SplitUninitError::InsufficientSpace)) => {
-                Err(StorageSetupError::UnderMinimumAllowedSize)
+                Err(StorageSetupError::UnderMinimumAllowedSize.into())
             }
             Err(EmbedValueError::SplitUninitError(SplitUninitError::Unalignable)) => {
-                Err(StorageSetupError::UnderMinimumAllowedSize)
+                Err(StorageSetupError::UnderMinimumAllowedSize.into())
             }
             Err(EmbedValueError::SplitUninitError(SplitUninitError::ZeroSizedTypesUnsupported)) => {
                 const_assert!(size_of::<ModalityProbe>() > 0);
@@ -657,6 +946,163 @@ impl<'a> ModalityProbe<'a> {
         self.history.now()
     }
 
+    /// Stop recording the events in `mask`, in addition to any
+    /// events that are already disabled.
+    ///
+    /// See [`EventMask`] for how event ids map onto the mask.
+    /// Events with ids above [`EVENT_MASK_BITS`], including internal
+    /// events, are always recorded.
+    #[cfg(feature = "event-filtering")]
+    #[inline]
+    pub fn disable_events(&mut self, mask: &EventMask) {
+        self.history.disabled_events.insert_all(mask);
+    }
+
+    /// Resume recording the events in `mask`
+    #[cfg(feature = "event-filtering")]
+    #[inline]
+    pub fn enable_events(&mut self, mask: &EventMask) {
+        self.history.disabled_events.remove_all(mask);
+    }
+
+    /// Replace the set of events which are not recorded.
+    ///
+    /// Only events with ids up to [`EVENT_MASK_BITS`] can be disabled;
+    /// events with larger ids are always recorded.
+    #[cfg(feature = "event-filtering")]
+    #[inline]
+    pub fn set_disabled_events(&mut self, mask: EventMask) {
+        self.history.disabled_events = mask;
+    }
+
+    /// The set of events which are currently not recorded
+    #[cfg(feature = "event-filtering")]
+    #[inline]
+    pub fn disabled_events(&self) -> EventMask {
+        self.history.disabled_events
+    }
+
+    /// Turn acknowledged reporting on or off.
+    ///
+    /// With it on, the log entries in a report are kept in the log
+    /// until the report is acknowledged, with `acknowledge_report` or
+    /// a `ControlCommand::AcknowledgeReport` control message. Until
+    /// then, `report` and `retransmit_unacked` send the same report
+    /// again, so collectors should discard reports whose sequence
+    /// number they've already seen from the probe.
+    ///
+    /// Turning it off gives up on any report awaiting acknowledgement.
+    #[cfg(feature = "acknowledged-reporting")]
+    #[inline]
+    pub fn set_acknowledged_reporting(&mut self, enabled: bool) {
+        self.history.set_acknowledged_reporting(enabled);
+    }
+
+    /// Choose how the probe makes room for new neighbors' clocks once
+    /// its clock table is full. Long-lived probes that talk to many
+    /// peers can use an evicting policy to keep tracking recent ones.
+    #[cfg(feature = "clock-eviction")]
+    #[inline]
+    pub fn set_clock_eviction_policy(&mut self, policy: ClockEvictionPolicy) {
+        self.history.set_clock_eviction_policy(policy);
+    }
+
+    /// Authenticate reports with a MAC under `key`, so collectors
+    /// holding the same key can reject reports that didn't come from
+    /// this probe. Control messages must then carry a MAC under the
+    /// same key too. Set it right after initialization, before the
+    /// first report; `None` goes back to unauthenticated reports.
+    #[cfg(feature = "report-authentication")]
+    #[inline]
+    pub fn set_report_key(&mut self, key: Option<wire::siphash::Key>) {
+        self.history.set_report_key(key);
+    }
+
+    /// Compress the log entries in reports, fitting more of them into
+    /// each one. See the [compression](wire/compression/index.html)
+    /// module for the encoding.
+    #[cfg(feature = "report-compression")]
+    #[inline]
+    pub fn set_compressed_reports(&mut self, compressed: bool) {
+        self.history.set_compressed_reports(compressed);
+    }
+
+    /// Mark the report with the given sequence number as received,
+    /// letting go of its log entries.
+    ///
+    /// Returns false if that report isn't awaiting acknowledgement.
+    #[cfg(feature = "acknowledged-reporting")]
+    #[inline]
+    pub fn acknowledge_report(&mut self, seq_num: u64) -> bool {
+        self.history.acknowledge_report(seq_num)
+    }
+
+    /// Copy the report awaiting acknowledgement into `destination`
+    /// again, e.g. once it's gone unacknowledged for too long.
+    ///
+    /// Returns `Ok(None)` if no report is awaiting acknowledgement.
+    #[cfg(feature = "acknowledged-reporting")]
+    #[inline]
+    pub fn retransmit_unacked(
+        &mut self,
+        destination: &mut [u8],
+    ) -> Result<Option<NonZeroUsize>, ReportError> {
+        self.history.retransmit_unacked(destination)
+    }
+
+    /// Save the probe's state into `destination`, returning the number
+    /// of bytes written. See the
+    /// [probe_state](wire/probe_state/index.html) module for the format.
+    ///
+    /// The state holds the probe's clocks, its report sequence number
+    /// and its missed log entry count, so that a probe restored from it
+    /// with `restore_state` carries on in the same epoch. With
+    /// `include_log`, it also holds the log entries which haven't been
+    /// reported yet; without, those are counted as missed.
+    #[inline]
+    pub fn save_state(
+        &self,
+        destination: &mut [u8],
+        include_log: bool,
+    ) -> Result<usize, SaveStateError> {
+        self.history.save_state(destination, include_log)
+    }
+
+    /// Notify `callback` once each time the log fills up to `percent`
+    /// of its capacity, e.g. to schedule a report before entries start
+    /// getting overwritten. It fires again once the log has dropped
+    /// below the watermark, usually after a report, and crossed it
+    /// again. A `percent` of 0 stops watching the log.
+    #[cfg(feature = "log-watermark")]
+    #[inline]
+    pub fn set_log_watermark(&mut self, percent: u8, callback: LogWatermarkCallback<'a>) {
+        self.history.set_log_watermark(percent, callback);
+    }
+
+    /// How full the log is with entries which haven't been reported
+    #[inline]
+    pub fn log_fill_level(&self) -> LogFillLevel {
+        self.history.log_fill_level()
+    }
+
+    /// Counters about the probe's own operation since it was
+    /// initialized, e.g. how many log entries were overwritten before
+    /// they could be reported
+    #[cfg(feature = "probe-stats")]
+    #[inline]
+    pub fn stats(&self) -> ProbeStats {
+        self.history.stats()
+    }
+
+    /// Include the probe's stats in each report, as internal events
+    /// recorded just before the report is written. Reports which
+    /// would otherwise be empty aren't produced just for the stats.
+    #[cfg(feature = "probe-stats")]
+    #[inline]
+    pub fn set_stats_in_reports(&mut self, enabled: bool) {
+        self.history.set_stats_in_reports(enabled);
+    }
+
     /// Get current value of overwrite priority
     #[cfg(feature = "debug-collector-access")]
     pub fn get_overwrite_priority_level(&self) -> u32 {
@@ -769,10 +1215,51 @@ impl<'a> Probe for ModalityProbe<'a> {
         self.history.merge_snapshot_bytes_with_time(source, time)
     }
 
+    #[inline]
+    fn produce_compact_snapshot_bytes(
+        &mut self,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        self.history
+            .produce_compact_snapshot_bytes(epoch_encoding, destination)
+    }
+
+    #[inline]
+    fn produce_compact_snapshot_bytes_with_time(
+        &mut self,
+        time: Nanoseconds,
+        epoch_encoding: EpochEncoding,
+        destination: &mut [u8],
+    ) -> Result<usize, ProduceError> {
+        self.history
+            .produce_compact_snapshot_bytes_with_time(time, epoch_encoding, destination)
+    }
+
+    #[inline]
+    fn merge_compact_snapshot_bytes(&mut self, source: &[u8]) -> Result<(), MergeError> {
+        self.history.merge_compact_snapshot_bytes(source)
+    }
+
+    #[inline]
+    fn merge_compact_snapshot_bytes_with_time(
+        &mut self,
+        source: &[u8],
+        time: Nanoseconds,
+    ) -> Result<(), MergeError> {
+        self.history
+            .merge_compact_snapshot_bytes_with_time(source, time)
+    }
+
     #[inline]
     fn report(&mut self, destination: &mut [u8]) -> Result<Option<NonZeroUsize>, ReportError> {
         self.history.report(destination)
     }
+
+    #[inline]
+    fn handle_control(&mut self, message: &[u8]) -> Result<ControlCommand, ControlError> {
+        self.history.handle_control(message)
+    }
 }
 
 /// This module contains a proptest `Arbitrary` implementation for
@@ -1052,4 +1539,105 @@ mod tests {
         // Make sure that the original clock remains untouched.
         assert_eq!(init, l);
     }
+
+    /// A probe implementing only the required `Probe` methods
+    struct MinimalProbe {
+        clock: LogicalClock,
+        merged: Option<LogicalClock>,
+    }
+
+    impl Probe for MinimalProbe {
+        fn record_time(&mut self, _time: Nanoseconds) {}
+        fn record_event(&mut self, _event_id: EventId) {}
+        fn record_event_with_time(&mut self, _event_id: EventId, _time: Nanoseconds) {}
+        fn record_event_with_payload(&mut self, _event_id: EventId, _payload: u32) {}
+        fn record_event_with_payload_with_time(
+            &mut self,
+            _event_id: EventId,
+            _payload: u32,
+            _time: Nanoseconds,
+        ) {
+        }
+        fn produce_snapshot(&mut self) -> CausalSnapshot {
+            CausalSnapshot {
+                clock: self.clock,
+                reserved_0: [0, 0],
+                reserved_1: [0, 0],
+            }
+        }
+        fn produce_snapshot_with_time(&mut self, _time: Nanoseconds) -> CausalSnapshot {
+            self.produce_snapshot()
+        }
+        fn produce_snapshot_bytes(&mut self, _dest: &mut [u8]) -> Result<usize, ProduceError> {
+            Err(ProduceError::InsufficientDestinationSize)
+        }
+        fn produce_snapshot_bytes_with_time(
+            &mut self,
+            _time: Nanoseconds,
+            _dest: &mut [u8],
+        ) -> Result<usize, ProduceError> {
+            Err(ProduceError::InsufficientDestinationSize)
+        }
+        fn merge_snapshot(&mut self, external_history: &CausalSnapshot) {
+            self.merged = Some(external_history.clock);
+        }
+        fn merge_snapshot_with_time(&mut self, external_history: &CausalSnapshot, _: Nanoseconds) {
+            self.merge_snapshot(external_history);
+        }
+        fn merge_snapshot_bytes(&mut self, _source: &[u8]) -> Result<(), MergeError> {
+            Err(MergeError::InsufficientSourceSize)
+        }
+        fn merge_snapshot_bytes_with_time(
+            &mut self,
+            _source: &[u8],
+            _time: Nanoseconds,
+        ) -> Result<(), MergeError> {
+            Err(MergeError::InsufficientSourceSize)
+        }
+        fn report(&mut self, _dest: &mut [u8]) -> Result<Option<NonZeroUsize>, ReportError> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn probe_trait_defaults() {
+        let clock = LogicalClock {
+            id: ProbeId::new(3).unwrap(),
+            epoch: ProbeEpoch(300),
+            ticks: ProbeTicks(4),
+        };
+        let mut sender = MinimalProbe {
+            clock,
+            merged: None,
+        };
+        let mut receiver = MinimalProbe {
+            clock: LogicalClock {
+                id: ProbeId::new(4).unwrap(),
+                epoch: ProbeEpoch(0),
+                ticks: ProbeTicks(0),
+            },
+            merged: None,
+        };
+        let mut bytes = [0u8; 16];
+
+        let n = sender
+            .produce_compact_snapshot_bytes(EpochEncoding::Whole, &mut bytes)
+            .unwrap();
+        assert_eq!(receiver.merge_compact_snapshot_bytes(&bytes[..n]), Ok(()));
+        assert_eq!(receiver.merged, Some(clock));
+
+        // Without any epochs to refer to, a truncated one is ambiguous
+        let n = sender
+            .produce_compact_snapshot_bytes(EpochEncoding::Truncated, &mut bytes)
+            .unwrap();
+        assert_eq!(
+            receiver.merge_compact_snapshot_bytes(&bytes[..n]),
+            Err(MergeError::AmbiguousEpoch)
+        );
+
+        assert_eq!(
+            receiver.handle_control(&[]),
+            Err(ControlError::UnsupportedCommand)
+        );
+    }
 }
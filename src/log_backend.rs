@@ -0,0 +1,284 @@
+//! A backend for the `log` facade that records log calls as probe events.
+//!
+//! Each log callsite is recorded as an event whose id is derived from
+//! the callsite's path and line number, with the log level
+//! (`Error` = 1 through `Trace` = 5) as its payload. These ids live
+//! in a dedicated block at the top of the user event id range, so
+//! they don't interfere with sequentially assigned ids.
+//! `modality-probe manifest-gen --log-callsites` produces the
+//! manifest rows for them.
+//!
+//! The id derivation is always available; the `ProbeLogger` itself
+//! requires the `log` feature.
+
+use crate::EventId;
+
+/// The number of event ids reserved for log callsites
+pub const LOG_CALLSITE_ID_COUNT: u32 = 1 << 20;
+
+/// The first event id reserved for log callsites; the block extends to
+/// `EventId::MAX_USER_ID`, inclusive
+pub const FIRST_LOG_CALLSITE_ID: u32 = EventId::MAX_USER_ID - LOG_CALLSITE_ID_COUNT + 1;
+
+/// Is the given raw event id in the block reserved for log callsites?
+#[inline]
+pub fn is_log_callsite_id(raw_id: u32) -> bool {
+    (FIRST_LOG_CALLSITE_ID..=EventId::MAX_USER_ID).contains(&raw_id)
+}
+
+/// The part of a callsite's path that identifies it: the path below
+/// the last `src` directory, or the whole path if there isn't one.
+///
+/// The path reported by `file!()` depends on where the compiler was
+/// invoked from (e.g. a workspace or the crate itself), but the part
+/// below the crate's `src` directory doesn't.
+pub fn callsite_path(file: &str) -> &str {
+    let bytes = file.as_bytes();
+    let is_sep = |b: u8| b == b'/' || b == b'\\';
+    let start = (0..bytes.len())
+        .rev()
+        .find(|&i| {
+            bytes[i..].starts_with(b"src")
+                && matches!(bytes.get(i + 3), Some(&b) if is_sep(b))
+                && (i == 0 || is_sep(bytes[i - 1]))
+        })
+        .map_or(0, |i| i + 4);
+    &file[start..]
+}
+
+/// The event id for a log callsite; see [`callsite_path`] for which
+/// part of `file` is used.
+pub fn callsite_event_id(file: &str, line: u32) -> EventId {
+    // 32-bit FNV-1a over the callsite path, with `/` as the separator,
+    // and the little-endian line number
+    let hash = callsite_path(file)
+        .bytes()
+        .map(|b| if b == b'\\' { b'/' } else { b })
+        .chain(line.to_le_bytes().iter().copied())
+        .fold(0x811c_9dc5_u32, |hash, b| {
+            (hash ^ u32::from(b)).wrapping_mul(0x0100_0193)
+        });
+    // Safe because the result is within [FIRST_LOG_CALLSITE_ID, MAX_USER_ID]
+    unsafe { EventId::new_unchecked(FIRST_LOG_CALLSITE_ID + hash % LOG_CALLSITE_ID_COUNT) }
+}
+
+#[cfg(feature = "log")]
+pub use self::logger::ProbeLogger;
+
+#[cfg(feature = "log")]
+mod logger {
+    use super::callsite_event_id;
+    use crate::{ModalityProbe, Probe};
+    use core::ptr;
+    use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+    use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+    /// A `log::Log` implementation that records each log call with a
+    /// probe.
+    ///
+    /// The logger owns the probe once it is attached. Records logged
+    /// while the probe is in use elsewhere (e.g. from an interrupt
+    /// handler that preempted a log call) are dropped rather than
+    /// blocking. Use [`ProbeLogger::with_probe`] to access the probe
+    /// for anything besides logging.
+    ///
+    /// ```ignore
+    /// static LOGGER: ProbeLogger = ProbeLogger::new();
+    ///
+    /// LOGGER.init(probe, log::LevelFilter::Info)?;
+    /// log::info!("Starting up");
+    /// ```
+    pub struct ProbeLogger {
+        probe: AtomicPtr<ModalityProbe<'static>>,
+        busy: AtomicBool,
+    }
+
+    // Access to the probe is serialized by the `busy` flag.
+    unsafe impl Sync for ProbeLogger {}
+    unsafe impl Send for ProbeLogger {}
+
+    impl ProbeLogger {
+        /// Create a logger with no probe attached
+        pub const fn new() -> Self {
+            ProbeLogger {
+                probe: AtomicPtr::new(ptr::null_mut()),
+                busy: AtomicBool::new(false),
+            }
+        }
+
+        /// Attach a probe, replacing any previously attached one
+        pub fn attach(&self, probe: &'static mut ModalityProbe<'static>) {
+            self.probe.store(probe, Ordering::Release);
+        }
+
+        /// Attach a probe, install this logger as the global logger,
+        /// and set the maximum level
+        pub fn init(
+            &'static self,
+            probe: &'static mut ModalityProbe<'static>,
+            level: LevelFilter,
+        ) -> Result<(), SetLoggerError> {
+            self.attach(probe);
+            log::set_logger(self)?;
+            log::set_max_level(level);
+            Ok(())
+        }
+
+        /// Run `f` with exclusive access to the attached probe.
+        ///
+        /// Returns `None` if no probe is attached or it is currently
+        /// in use.
+        pub fn with_probe<F, R>(&self, f: F) -> Option<R>
+        where
+            F: FnOnce(&mut ModalityProbe<'static>) -> R,
+        {
+            if self.busy.swap(true, Ordering::Acquire) {
+                return None;
+            }
+            let probe = self.probe.load(Ordering::Acquire);
+            // Safe because the probe was handed over as a `&'static mut`
+            // and the busy flag guarantees exclusive access.
+            let result = unsafe { probe.as_mut() }.map(f);
+            self.busy.store(false, Ordering::Release);
+            result
+        }
+    }
+
+    impl Default for ProbeLogger {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Log for ProbeLogger {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.level() <= log::max_level() && !self.probe.load(Ordering::Relaxed).is_null()
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+            if let (Some(file), Some(line)) = (record.file(), record.line()) {
+                let event_id = callsite_event_id(file, line);
+                let level = record.level() as u32;
+                self.with_probe(|p| p.record_event_with_payload(event_id, level));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callsite_ids_are_in_the_log_block() {
+        for line in 0..1000 {
+            let id = callsite_event_id("src/main.rs", line).get_raw();
+            assert!(is_log_callsite_id(id));
+            assert!(EventId::new(id).is_some());
+        }
+        assert!(!is_log_callsite_id(FIRST_LOG_CALLSITE_ID - 1));
+        assert!(!is_log_callsite_id(EventId::MAX_USER_ID + 1));
+    }
+
+    #[test]
+    fn callsite_ids_ignore_the_path_above_src() {
+        assert_eq!(
+            callsite_event_id("src/main.rs", 12),
+            callsite_event_id("my-crate/src/main.rs", 12)
+        );
+        assert_eq!(
+            callsite_event_id("main.rs", 12),
+            callsite_event_id("src\\main.rs", 12)
+        );
+        assert_eq!(
+            callsite_event_id("src/net/mod.rs", 12),
+            callsite_event_id("/home/me/src/my-crate/src\\net\\mod.rs", 12)
+        );
+        assert_ne!(
+            callsite_event_id("main.rs", 12),
+            callsite_event_id("main.rs", 13)
+        );
+    }
+
+    #[test]
+    fn callsite_ids_include_the_directory() {
+        assert_ne!(
+            callsite_event_id("src/a/mod.rs", 10),
+            callsite_event_id("src/b/mod.rs", 10)
+        );
+        assert_eq!(callsite_path("src/a/mod.rs"), "a/mod.rs");
+        assert_eq!(callsite_path("a/src/b/mod.rs"), "b/mod.rs");
+        assert_eq!(callsite_path("my-src/lib.rs"), "my-src/lib.rs");
+        assert_eq!(callsite_path("lib.rs"), "lib.rs");
+    }
+
+    #[cfg(all(feature = "log", feature = "std"))]
+    #[test]
+    fn logger_records_callsite_and_level() {
+        use crate::{
+            log::LogEntry, ModalityProbe, NanosecondResolution, Probe, ProbeId,
+            RestartCounterProvider, WallClockId,
+        };
+        use core::mem::MaybeUninit;
+        use log::{Level, Log, Record};
+
+        let storage = Box::leak(Box::new([MaybeUninit::new(0u8); 1024]));
+        let probe = ModalityProbe::initialize_at(
+            storage,
+            ProbeId::new(1).unwrap(),
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+        )
+        .unwrap();
+        let logger = ProbeLogger::new();
+        log::set_max_level(log::LevelFilter::Info);
+        logger.attach(probe);
+
+        let record = |level| {
+            Record::builder()
+                .level(level)
+                .file(Some("src/main.rs"))
+                .line(Some(42))
+                .build()
+        };
+        logger.log(&record(Level::Warn));
+        logger.log(&record(Level::Debug));
+
+        let expected_id = callsite_event_id("src/main.rs", 42);
+        let entries = logger
+            .with_probe(|p| {
+                let mut buf = [0u8; 256];
+                let n = p.report(&mut buf).unwrap().unwrap().get();
+                buf[..n].to_vec()
+            })
+            .unwrap();
+        let report = crate::wire::WireReport::new(&entries[..]).unwrap();
+        let payload: Vec<u32> = report
+            .payload()
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        let (ev, level) = (
+            LogEntry::event_with_payload(expected_id, 0).0,
+            Level::Warn as u32,
+        );
+        let pos = payload
+            .iter()
+            .position(|w| unsafe { LogEntry::new_unchecked(*w) } == ev)
+            .expect("Log event recorded");
+        assert_eq!(payload[pos + 1], level);
+        assert_eq!(
+            payload
+                .iter()
+                .filter(|w| unsafe { LogEntry::new_unchecked(**w) } == ev)
+                .count(),
+            1
+        );
+    }
+}
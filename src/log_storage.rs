@@ -0,0 +1,43 @@
+/// Where a probe's memory lives, which decides whether its log can
+/// outlast a reset.
+///
+/// With storage that may hold the log a previous run wrote, the probe
+/// checks for it while initializing. If what it finds is consistent, it
+/// keeps those entries and reports them before its own, under the epoch
+/// they were recorded in, rather than starting with an empty log.
+pub trait LogStorage {
+    /// Whether the probe's memory may still hold what the previous run
+    /// left in it. Called once, while initializing.
+    ///
+    /// RAM in a section the startup code leaves alone (e.g. `.noinit`)
+    /// keeps its contents through a watchdog or software reset but not
+    /// through a power cycle, so implementations may want to check the
+    /// cause of the last reset.
+    ///
+    /// The probe reads its memory before initializing it when this
+    /// returns true, so only do so for memory the linker places in
+    /// such a region, never for stack or heap memory.
+    fn may_hold_prior_log(&mut self) -> bool;
+}
+
+/// Ordinary RAM, which holds nothing useful after a reset
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VolatileRam;
+
+impl LogStorage for VolatileRam {
+    fn may_hold_prior_log(&mut self) -> bool {
+        false
+    }
+}
+
+/// RAM which the startup code leaves alone, like a `.noinit` section,
+/// so it keeps its contents across resets. Only use it for a probe's
+/// memory when the linker places that memory in such a section.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetainedRam;
+
+impl LogStorage for RetainedRam {
+    fn may_hold_prior_log(&mut self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,152 @@
+use crate::ProbeId;
+use core::fmt;
+
+/// How full a probe's log is
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LogFillLevel {
+    /// The number of log entries which haven't been reported yet
+    pub len: usize,
+    /// The number of log entries the log can hold
+    pub capacity: usize,
+}
+
+impl LogFillLevel {
+    /// The fill level as a percentage of the log's capacity
+    pub fn percent(&self) -> u8 {
+        (self.len * 100 / self.capacity.max(1)) as u8
+    }
+}
+
+/// Notified when a probe's log fills up past its watermark, e.g. to
+/// schedule a report before entries start getting overwritten
+pub trait LogWatermarkHandler {
+    /// Called once each time the log's fill level crosses the
+    /// watermark, from within whichever probe call wrote the entry
+    /// that crossed it.
+    ///
+    /// The probe is in the middle of recording, so this shouldn't call
+    /// back into it; set a flag or wake a task to report instead.
+    fn log_watermark_reached(&mut self, probe_id: ProbeId, fill_level: LogFillLevel);
+}
+
+impl<F: FnMut(ProbeId, LogFillLevel)> LogWatermarkHandler for F {
+    fn log_watermark_reached(&mut self, probe_id: ProbeId, fill_level: LogFillLevel) {
+        self(probe_id, fill_level)
+    }
+}
+
+/// C function type notified when a probe's log fills up past its
+/// watermark
+#[allow(non_camel_case_types)]
+pub type log_watermark_fn = extern "C" fn(
+    probe_id: u32,
+    fill_len: usize,
+    fill_capacity: usize,
+    state: *mut core::ffi::c_void,
+);
+
+/// A log watermark handler backed by a C implementation
+pub struct CLogWatermarkHandler {
+    /// C interface notified when the watermark is reached
+    pub iface: log_watermark_fn,
+    /// User's state provided to the log_watermark_fn function call
+    pub state: *mut core::ffi::c_void,
+}
+
+impl LogWatermarkHandler for CLogWatermarkHandler {
+    fn log_watermark_reached(&mut self, probe_id: ProbeId, fill_level: LogFillLevel) {
+        (self.iface)(
+            probe_id.get_raw(),
+            fill_level.len,
+            fill_level.capacity,
+            self.state,
+        )
+    }
+}
+
+/// A log watermark handler backed by a Rust implementation
+pub struct RustLogWatermarkHandler<'a> {
+    /// Rust interface notified when the watermark is reached
+    pub iface: &'a mut dyn LogWatermarkHandler,
+}
+
+/// What, if anything, to notify when a probe's log reaches its
+/// watermark
+#[derive(Default)]
+pub enum LogWatermarkCallback<'a> {
+    /// Don't watch the log's fill level
+    #[default]
+    None,
+    /// A handler backed by a C implementation
+    C(CLogWatermarkHandler),
+    /// A handler backed by a Rust implementation
+    Rust(RustLogWatermarkHandler<'a>),
+}
+
+impl<'a> From<&'a mut dyn LogWatermarkHandler> for LogWatermarkCallback<'a> {
+    fn from(h: &'a mut dyn LogWatermarkHandler) -> Self {
+        LogWatermarkCallback::Rust(RustLogWatermarkHandler { iface: h })
+    }
+}
+
+impl<'a> LogWatermarkHandler for LogWatermarkCallback<'a> {
+    fn log_watermark_reached(&mut self, probe_id: ProbeId, fill_level: LogFillLevel) {
+        match self {
+            LogWatermarkCallback::None => (),
+            LogWatermarkCallback::C(c) => c.log_watermark_reached(probe_id, fill_level),
+            LogWatermarkCallback::Rust(r) => r.iface.log_watermark_reached(probe_id, fill_level),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for LogWatermarkCallback<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogWatermarkCallback::None => f.write_str("LogWatermarkCallback::None"),
+            LogWatermarkCallback::C(_) => f.write_str("LogWatermarkCallback::C"),
+            LogWatermarkCallback::Rust(_) => f.write_str("LogWatermarkCallback::Rust"),
+        }
+    }
+}
+
+/// A watermark on a probe's log fill level, and what to notify when
+/// it's crossed
+#[cfg(feature = "log-watermark")]
+#[derive(Debug, Default)]
+pub(crate) struct LogWatermark<'a> {
+    /// The fill level, as a percentage of the log's capacity, which
+    /// triggers the callback, or 0 when not watching
+    percent: u8,
+    /// Whether the fill level has been below the watermark since the
+    /// callback last fired
+    armed: bool,
+    callback: LogWatermarkCallback<'a>,
+}
+
+#[cfg(feature = "log-watermark")]
+impl<'a> LogWatermark<'a> {
+    /// Watch for the fill level reaching `percent` of the log's capacity
+    pub(crate) fn new(percent: u8, callback: LogWatermarkCallback<'a>) -> Self {
+        LogWatermark {
+            percent: percent.min(100),
+            armed: true,
+            callback,
+        }
+    }
+
+    /// Notify the callback if the fill level has just crossed the
+    /// watermark
+    #[inline]
+    pub(crate) fn check(&mut self, probe_id: ProbeId, fill_level: LogFillLevel) {
+        if self.percent == 0 {
+            return;
+        }
+        if fill_level.len * 100 < fill_level.capacity * usize::from(self.percent) {
+            self.armed = true;
+        } else if self.armed {
+            self.armed = false;
+            self.callback.log_watermark_reached(probe_id, fill_level);
+        }
+    }
+}
@@ -992,6 +992,186 @@ macro_rules! try_failure_w_time {
     };
 }
 
+/// Span begin recording convenience macro that calls
+/// [ModalityProbe::record_event_with_payload](struct.ModalityProbe.html#method.record_event_with_payload),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_BEGIN`. The span is closed by
+/// recording the event of the same name ending in `_END` with the
+/// same span id. Spans recorded by the same probe may nest.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! span_begin {
+    ($probe:expr, $event:expr, $span_id:expr) => {
+        $probe.record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags:expr) => {
+        $probe.record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.record_event_with_payload($event, $span_id)
+    };
+}
+
+/// Span begin recording convenience macro that calls
+/// [ModalityProbe::try_record_event_with_payload](struct.ModalityProbe.html#method.try_record_event_with_payload),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_BEGIN`. The span is closed by
+/// recording the event of the same name ending in `_END` with the
+/// same span id. Spans recorded by the same probe may nest.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! try_span_begin {
+    ($probe:expr, $event:expr, $span_id:expr) => {
+        $probe.try_record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags:expr) => {
+        $probe.try_record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.try_record_event_with_payload($event, $span_id)
+    };
+}
+
+/// Span begin with time recording convenience macro that calls
+/// [ModalityProbe::record_event_with_payload_with_time](struct.ModalityProbe.html#method.record_event_with_payload_with_time),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_BEGIN`. The span is closed by
+/// recording the event of the same name ending in `_END` with the
+/// same span id. Spans recorded by the same probe may nest.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! span_begin_w_time {
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr) => {
+        $probe.record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags:expr) => {
+        $probe.record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.record_event_with_payload_with_time($event, $span_id, $time)
+    };
+}
+
+/// Span begin with time recording convenience macro that calls
+/// [ModalityProbe::try_record_event_with_payload_with_time](struct.ModalityProbe.html#method.try_record_event_with_payload_with_time),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_BEGIN`. The span is closed by
+/// recording the event of the same name ending in `_END` with the
+/// same span id. Spans recorded by the same probe may nest.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! try_span_begin_w_time {
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr) => {
+        $probe.try_record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags:expr) => {
+        $probe.try_record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.try_record_event_with_payload_with_time($event, $span_id, $time)
+    };
+}
+
+/// Span end recording convenience macro that calls
+/// [ModalityProbe::record_event_with_payload](struct.ModalityProbe.html#method.record_event_with_payload),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_END`, and the span id must match
+/// the one given to the corresponding span begin.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! span_end {
+    ($probe:expr, $event:expr, $span_id:expr) => {
+        $probe.record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags:expr) => {
+        $probe.record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.record_event_with_payload($event, $span_id)
+    };
+}
+
+/// Span end recording convenience macro that calls
+/// [ModalityProbe::try_record_event_with_payload](struct.ModalityProbe.html#method.try_record_event_with_payload),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_END`, and the span id must match
+/// the one given to the corresponding span begin.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! try_span_end {
+    ($probe:expr, $event:expr, $span_id:expr) => {
+        $probe.try_record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags:expr) => {
+        $probe.try_record_event_with_payload($event, $span_id)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.try_record_event_with_payload($event, $span_id)
+    };
+}
+
+/// Span end with time recording convenience macro that calls
+/// [ModalityProbe::record_event_with_payload_with_time](struct.ModalityProbe.html#method.record_event_with_payload_with_time),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_END`, and the span id must match
+/// the one given to the corresponding span begin.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! span_end_w_time {
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr) => {
+        $probe.record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags:expr) => {
+        $probe.record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.record_event_with_payload_with_time($event, $span_id, $time)
+    };
+}
+
+/// Span end with time recording convenience macro that calls
+/// [ModalityProbe::try_record_event_with_payload_with_time](struct.ModalityProbe.html#method.try_record_event_with_payload_with_time),
+/// using the `u32` span id as the payload.
+///
+/// The event's name must end in `_END`, and the span id must match
+/// the one given to the corresponding span begin.
+///
+/// The optional description and tag arguments are only used
+/// by the CLI and compile away.
+#[macro_export]
+macro_rules! try_span_end_w_time {
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr) => {
+        $probe.try_record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags:expr) => {
+        $probe.try_record_event_with_payload_with_time($event, $span_id, $time)
+    };
+    ($probe:expr, $event:expr, $span_id:expr, $time:expr, $desc_or_tags_0:expr, $desc_or_tags_1:expr) => {
+        $probe.try_record_event_with_payload_with_time($event, $span_id, $time)
+    };
+}
+
 #[doc(hidden)]
 #[macro_export(local_inner_macros)]
 macro_rules! __record_with {
@@ -1348,6 +1528,35 @@ mod tests {
         try_failure!(probe, EVENT_D, "desc", tags!("my tag")).unwrap();
         try_failure!(probe, EVENT_D, severity!(3), "desc", tags!("my tag")).unwrap();
         try_failure_w_time!(probe, EVENT_D, TIME, severity!(3), "desc", tags!("my tag")).unwrap();
+
+        let span_id = 7_u32;
+        span_begin!(probe, EventId::new(EVENT_D).unwrap(), span_id);
+        span_begin!(probe, EventId::new(EVENT_D).unwrap(), span_id, "desc");
+        span_begin_w_time!(
+            probe,
+            EventId::new(EVENT_D).unwrap(),
+            span_id,
+            Nanoseconds::new(3).unwrap(),
+            tags!("tag-a"),
+            "desc"
+        );
+        span_end!(
+            probe,
+            EventId::new(EVENT_D).unwrap(),
+            span_id,
+            tags!("tag-a")
+        );
+        span_end_w_time!(
+            probe,
+            EventId::new(EVENT_D).unwrap(),
+            span_id,
+            Nanoseconds::new(4).unwrap()
+        );
+
+        try_span_begin!(probe, EVENT_D, span_id, "desc", tags!("my tag")).unwrap();
+        try_span_begin_w_time!(probe, EVENT_D, span_id, TIME, "desc").unwrap();
+        try_span_end!(probe, EVENT_D, span_id).unwrap();
+        try_span_end_w_time!(probe, EVENT_D, span_id, TIME, tags!("my tag")).unwrap();
     }
 
     #[test]
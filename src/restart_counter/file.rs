@@ -0,0 +1,136 @@
+//! A restart counter kept in a file, for hosted targets.
+
+use crate::{ProbeId, RestartCounter, RestartSequenceIdUnavailable};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// A persistent restart counter for any number of probes, kept in a
+/// file.
+///
+/// The file holds a line per probe with its id and the last sequence
+/// id handed out for it. It's replaced as a whole on each update, by
+/// writing a temporary file next to it and renaming that over it, so
+/// a crash part way through leaves the previous counters in place.
+///
+/// The file is read on every call, but access to it isn't
+/// synchronized, so it shouldn't be shared by processes which might
+/// initialize probes at the same time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRestartCounter {
+    path: PathBuf,
+}
+
+impl FileRestartCounter {
+    /// Keep counters in the file at `path`, which is created if it
+    /// doesn't exist
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        FileRestartCounter { path: path.into() }
+    }
+
+    /// The path of the file the counters are kept in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Like `next_sequence_id`, but with the reason for any failure
+    pub fn try_next_sequence_id(&mut self, probe_id: ProbeId) -> io::Result<u16> {
+        let mut counters = self.read_counters()?;
+        let key = probe_id.get_raw();
+        let next = match counters.iter_mut().find(|(id, _)| *id == key) {
+            Some((_, last)) => {
+                *last = last.wrapping_add(1);
+                *last
+            }
+            None => {
+                counters.push((key, 0));
+                0
+            }
+        };
+        self.write_counters(&counters)?;
+        Ok(next)
+    }
+
+    fn read_counters(&self) -> io::Result<Vec<(u32, u16)>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        contents
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                let mut fields = l.split_whitespace();
+                let id = fields.next().and_then(|f| f.parse().ok());
+                let last = fields.next().and_then(|f| f.parse().ok());
+                match (id, last, fields.next()) {
+                    (Some(id), Some(last), None) => Ok((id, last)),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid restart counter line: {:?}", l),
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    fn write_counters(&self, counters: &[(u32, u16)]) -> io::Result<()> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        {
+            let mut f = File::create(&tmp_path)?;
+            for (id, last) in counters {
+                writeln!(f, "{} {}", id, last)?;
+            }
+            f.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+impl RestartCounter for FileRestartCounter {
+    fn next_sequence_id(&mut self, probe_id: ProbeId) -> Result<u16, RestartSequenceIdUnavailable> {
+        self.try_next_sequence_id(probe_id)
+            .map_err(|_| RestartSequenceIdUnavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_per_probe_and_persists() {
+        let path = std::env::temp_dir().join(format!(
+            "modality-probe-restart-counter-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let a = ProbeId::new(1).unwrap();
+        let b = ProbeId::new(2).unwrap();
+
+        let mut rc = FileRestartCounter::new(&path);
+        assert_eq!(rc.next_sequence_id(a), Ok(0));
+        assert_eq!(rc.next_sequence_id(a), Ok(1));
+        assert_eq!(rc.next_sequence_id(b), Ok(0));
+
+        let mut rc = FileRestartCounter::new(&path);
+        assert_eq!(rc.next_sequence_id(b), Ok(1));
+        assert_eq!(rc.next_sequence_id(a), Ok(2));
+
+        // Counters wrap around
+        fs::write(&path, "1 65535\n").unwrap();
+        assert_eq!(rc.next_sequence_id(a), Ok(0));
+
+        fs::write(&path, "not a counter\n").unwrap();
+        assert_eq!(
+            rc.try_next_sequence_id(a).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
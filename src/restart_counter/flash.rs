@@ -0,0 +1,534 @@
+//! A restart counter kept in flash memory, for targets without a
+//! filesystem.
+//!
+//! Counters are appended to the flash region as 8 byte records, so a
+//! restart costs one write rather than an erase. The region is split
+//! into sectors, only one of which is in use at a time. When it fills
+//! up, the latest counter for each probe is carried over into the next
+//! sector, which spreads erases evenly across the whole region.
+//!
+//! Each sector starts with a header record holding its generation. The
+//! header is written only once the counters have been carried over, so
+//! a reset part way through leaves the previous sector in use.
+
+use crate::{wire::le_bytes, ProbeId, RestartCounter, RestartSequenceIdUnavailable};
+use core::fmt;
+
+/// The size, in bytes, of a record, which is the unit flash is
+/// written in
+pub const RECORD_LEN: usize = 8;
+
+/// Marks a sector header record
+const HEADER_MAGIC: u32 = 0x4d50_5243;
+
+/// Flash memory which can be erased a sector at a time and written to
+/// where it has been erased since it was last written.
+///
+/// Offsets are in bytes from the start of the region the counters are
+/// kept in, and are always a multiple of `RECORD_LEN`. Erased flash
+/// reads as `0xFF`.
+pub trait Flash {
+    /// The size, in bytes, of an erasable sector. Must be a multiple
+    /// of `RECORD_LEN`.
+    fn sector_len(&self) -> usize;
+
+    /// The number of sectors in the region. There must be at least two.
+    fn sector_count(&self) -> usize;
+
+    /// Read `dest.len()` bytes starting at `offset`
+    fn read(&mut self, offset: usize, dest: &mut [u8]) -> Result<(), FlashError>;
+
+    /// Write `src` starting at `offset`
+    fn write(&mut self, offset: usize, src: &[u8]) -> Result<(), FlashError>;
+
+    /// Erase the sector at `index`
+    fn erase(&mut self, index: usize) -> Result<(), FlashError>;
+}
+
+impl<F: Flash + ?Sized> Flash for &mut F {
+    fn sector_len(&self) -> usize {
+        (**self).sector_len()
+    }
+
+    fn sector_count(&self) -> usize {
+        (**self).sector_count()
+    }
+
+    fn read(&mut self, offset: usize, dest: &mut [u8]) -> Result<(), FlashError> {
+        (**self).read(offset, dest)
+    }
+
+    fn write(&mut self, offset: usize, src: &[u8]) -> Result<(), FlashError> {
+        (**self).write(offset, src)
+    }
+
+    fn erase(&mut self, index: usize) -> Result<(), FlashError> {
+        (**self).erase(index)
+    }
+}
+
+/// A flash operation failed
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct FlashError;
+
+impl fmt::Debug for FlashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("A flash operation failed")
+    }
+}
+
+impl From<FlashError> for RestartSequenceIdUnavailable {
+    fn from(_: FlashError) -> Self {
+        RestartSequenceIdUnavailable
+    }
+}
+
+/// A record read from flash
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Record {
+    /// Never written since the sector was erased
+    Erased,
+    /// A probe id and its counter, or in a sector's first record, the
+    /// header magic and the sector's generation
+    Valid { key: u32, value: u16 },
+    /// Written, but not all the way, e.g. because of a reset
+    Torn,
+}
+
+impl Record {
+    fn check(key: u32, value: u16) -> u16 {
+        !(value ^ key as u16 ^ (key >> 16) as u16)
+    }
+
+    fn encode(key: u32, value: u16) -> [u8; RECORD_LEN] {
+        let mut bytes = [0; RECORD_LEN];
+        le_bytes::write_u32(&mut bytes[0..4], key);
+        le_bytes::write_u16(&mut bytes[4..6], value);
+        le_bytes::write_u16(&mut bytes[6..8], Record::check(key, value));
+        bytes
+    }
+
+    fn decode(bytes: &[u8; RECORD_LEN]) -> Self {
+        if bytes.iter().all(|b| *b == 0xFF) {
+            return Record::Erased;
+        }
+        let key = le_bytes::read_u32(&bytes[0..4]);
+        let value = le_bytes::read_u16(&bytes[4..6]);
+        if le_bytes::read_u16(&bytes[6..8]) == Record::check(key, value) {
+            Record::Valid { key, value }
+        } else {
+            Record::Torn
+        }
+    }
+}
+
+/// A persistent restart counter for any number of probes, kept in
+/// wear-levelled flash memory (see the module docs).
+#[derive(Debug)]
+pub struct FlashRestartCounter<F: Flash> {
+    flash: F,
+}
+
+impl<F: Flash> FlashRestartCounter<F> {
+    /// Keep counters in `flash`. Flash holding neither counters nor
+    /// anything else is fine; it gets erased as needed.
+    pub fn new(flash: F) -> Self {
+        FlashRestartCounter { flash }
+    }
+
+    /// Consumes the counter, returning the underlying flash
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    fn records_per_sector(&self) -> usize {
+        self.flash.sector_len() / RECORD_LEN
+    }
+
+    fn read_record(&mut self, sector: usize, index: usize) -> Result<Record, FlashError> {
+        let mut bytes = [0; RECORD_LEN];
+        let offset = sector * self.flash.sector_len() + index * RECORD_LEN;
+        self.flash.read(offset, &mut bytes)?;
+        Ok(Record::decode(&bytes))
+    }
+
+    fn write_record(
+        &mut self,
+        sector: usize,
+        index: usize,
+        key: u32,
+        value: u16,
+    ) -> Result<(), FlashError> {
+        let offset = sector * self.flash.sector_len() + index * RECORD_LEN;
+        self.flash.write(offset, &Record::encode(key, value))
+    }
+
+    /// The sector in use and its generation, if any
+    fn active_sector(&mut self) -> Result<Option<(usize, u16)>, FlashError> {
+        let mut active: Option<(usize, u16)> = None;
+        for sector in 0..self.flash.sector_count() {
+            if let Record::Valid {
+                key: HEADER_MAGIC,
+                value: generation,
+            } = self.read_record(sector, 0)?
+            {
+                // Generations wrap, so the newer is the one that's
+                // less than half the range ahead
+                let is_newer = match active {
+                    Some((_, g)) => (generation.wrapping_sub(g) as i16) > 0,
+                    None => true,
+                };
+                if is_newer {
+                    active = Some((sector, generation));
+                }
+            }
+        }
+        Ok(active)
+    }
+
+    /// The latest counter for `key` in `sector`, searching from record
+    /// `start`, and the index of the first erased record after it
+    fn latest(
+        &mut self,
+        sector: usize,
+        start: usize,
+        key: u32,
+    ) -> Result<(Option<u16>, Option<usize>), FlashError> {
+        let mut latest = None;
+        for index in start..self.records_per_sector() {
+            match self.read_record(sector, index)? {
+                Record::Erased => return Ok((latest, Some(index))),
+                Record::Valid { key: k, value } if k == key => latest = Some(value),
+                Record::Valid { .. } | Record::Torn => (),
+            }
+        }
+        Ok((latest, None))
+    }
+
+    /// Erase sector `to` and carry the latest counter of each probe in
+    /// sector `from` over into it, returning the index of the first
+    /// free record
+    fn move_into(
+        &mut self,
+        from: Option<usize>,
+        to: usize,
+        generation: u16,
+    ) -> Result<usize, FlashError> {
+        self.flash.erase(to)?;
+        let mut next_free = 1;
+        if let Some(from) = from {
+            for index in 1..self.records_per_sector() {
+                let (key, value) = match self.read_record(from, index)? {
+                    Record::Erased => break,
+                    Record::Valid { key, value } => (key, value),
+                    Record::Torn => continue,
+                };
+                // Only carry over a probe's last record
+                if let (None, _) = self.latest(from, index + 1, key)? {
+                    self.write_record(to, next_free, key, value)?;
+                    next_free += 1;
+                }
+            }
+        }
+        self.write_record(to, 0, HEADER_MAGIC, generation)?;
+        Ok(next_free)
+    }
+
+    /// Like `next_sequence_id`, but distinguishes flash failures
+    pub fn try_next_sequence_id(&mut self, probe_id: ProbeId) -> Result<Option<u16>, FlashError> {
+        if self.flash.sector_count() < 2 || self.records_per_sector() < 2 {
+            return Ok(None);
+        }
+        let key = probe_id.get_raw();
+        let (sector, generation) = match self.active_sector()? {
+            Some(active) => active,
+            None => {
+                self.move_into(None, 0, 0)?;
+                (0, 0)
+            }
+        };
+        let (latest, free) = self.latest(sector, 1, key)?;
+        let index = match free {
+            Some(index) => index,
+            None => {
+                let next_sector = (sector + 1) % self.flash.sector_count();
+                let index =
+                    self.move_into(Some(sector), next_sector, generation.wrapping_add(1))?;
+                if index == self.records_per_sector() {
+                    // Every record is some other probe's latest counter
+                    return Ok(None);
+                }
+                return self.append(next_sector, index, key, latest).map(Some);
+            }
+        };
+        self.append(sector, index, key, latest).map(Some)
+    }
+
+    fn append(
+        &mut self,
+        sector: usize,
+        index: usize,
+        key: u32,
+        latest: Option<u16>,
+    ) -> Result<u16, FlashError> {
+        let next = latest.map_or(0, |v| v.wrapping_add(1));
+        self.write_record(sector, index, key, next)?;
+        Ok(next)
+    }
+}
+
+impl<F: Flash> RestartCounter for FlashRestartCounter<F> {
+    fn next_sequence_id(&mut self, probe_id: ProbeId) -> Result<u16, RestartSequenceIdUnavailable> {
+        self.try_next_sequence_id(probe_id)?
+            .ok_or(RestartSequenceIdUnavailable)
+    }
+}
+
+/// C function type for reading `len` bytes of flash at `offset` into
+/// `dest`. Returns 0 on success.
+#[allow(non_camel_case_types)]
+pub type flash_read_fn =
+    extern "C" fn(state: *mut core::ffi::c_void, offset: usize, dest: *mut u8, len: usize) -> usize;
+
+/// C function type for writing `len` bytes from `src` to flash at
+/// `offset`. Returns 0 on success.
+#[allow(non_camel_case_types)]
+pub type flash_write_fn = extern "C" fn(
+    state: *mut core::ffi::c_void,
+    offset: usize,
+    src: *const u8,
+    len: usize,
+) -> usize;
+
+/// C function type for erasing the flash sector at `index`. Returns 0
+/// on success.
+#[allow(non_camel_case_types)]
+pub type flash_erase_fn = extern "C" fn(state: *mut core::ffi::c_void, index: usize) -> usize;
+
+/// Flash memory backed by a C implementation
+#[repr(C)]
+pub struct CFlash {
+    /// The size, in bytes, of an erasable sector
+    pub sector_len: usize,
+    /// The number of sectors in the region
+    pub sector_count: usize,
+    /// C interface for reading flash
+    pub read: flash_read_fn,
+    /// C interface for writing flash
+    pub write: flash_write_fn,
+    /// C interface for erasing a flash sector
+    pub erase: flash_erase_fn,
+    /// User's state provided to the flash function calls
+    pub state: *mut core::ffi::c_void,
+}
+
+impl Flash for CFlash {
+    fn sector_len(&self) -> usize {
+        self.sector_len
+    }
+
+    fn sector_count(&self) -> usize {
+        self.sector_count
+    }
+
+    fn read(&mut self, offset: usize, dest: &mut [u8]) -> Result<(), FlashError> {
+        match (self.read)(self.state, offset, dest.as_mut_ptr(), dest.len()) {
+            0 => Ok(()),
+            _ => Err(FlashError),
+        }
+    }
+
+    fn write(&mut self, offset: usize, src: &[u8]) -> Result<(), FlashError> {
+        match (self.write)(self.state, offset, src.as_ptr(), src.len()) {
+            0 => Ok(()),
+            _ => Err(FlashError),
+        }
+    }
+
+    fn erase(&mut self, index: usize) -> Result<(), FlashError> {
+        match (self.erase)(self.state, index) {
+            0 => Ok(()),
+            _ => Err(FlashError),
+        }
+    }
+}
+
+/// Flash memory simulated in RAM, for testing. Like NOR flash, writes
+/// can only clear bits; setting them again takes an erase.
+#[derive(Debug)]
+pub struct RamFlash<'a> {
+    bytes: &'a mut [u8],
+    sector_len: usize,
+}
+
+impl<'a> RamFlash<'a> {
+    /// Simulate flash with sectors of `sector_len` bytes in `bytes`,
+    /// which are taken as they are rather than erased
+    pub fn new(bytes: &'a mut [u8], sector_len: usize) -> Self {
+        RamFlash { bytes, sector_len }
+    }
+
+    /// The simulated flash's contents
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+}
+
+impl<'a> Flash for RamFlash<'a> {
+    fn sector_len(&self) -> usize {
+        self.sector_len
+    }
+
+    fn sector_count(&self) -> usize {
+        self.bytes.len() / self.sector_len
+    }
+
+    fn read(&mut self, offset: usize, dest: &mut [u8]) -> Result<(), FlashError> {
+        let src = self
+            .bytes
+            .get(offset..offset + dest.len())
+            .ok_or(FlashError)?;
+        dest.copy_from_slice(src);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, src: &[u8]) -> Result<(), FlashError> {
+        let dest = self
+            .bytes
+            .get_mut(offset..offset + src.len())
+            .ok_or(FlashError)?;
+        for (d, s) in dest.iter_mut().zip(src) {
+            *d &= *s;
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, index: usize) -> Result<(), FlashError> {
+        let start = index * self.sector_len;
+        let sector = self
+            .bytes
+            .get_mut(start..start + self.sector_len)
+            .ok_or(FlashError)?;
+        for b in sector.iter_mut() {
+            *b = 0xFF;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts erases per sector
+    struct CountingFlash<'a> {
+        flash: RamFlash<'a>,
+        erases: [usize; 4],
+    }
+
+    impl<'a> Flash for CountingFlash<'a> {
+        fn sector_len(&self) -> usize {
+            self.flash.sector_len()
+        }
+
+        fn sector_count(&self) -> usize {
+            self.flash.sector_count()
+        }
+
+        fn read(&mut self, offset: usize, dest: &mut [u8]) -> Result<(), FlashError> {
+            self.flash.read(offset, dest)
+        }
+
+        fn write(&mut self, offset: usize, src: &[u8]) -> Result<(), FlashError> {
+            self.flash.write(offset, src)
+        }
+
+        fn erase(&mut self, index: usize) -> Result<(), FlashError> {
+            self.erases[index] += 1;
+            self.flash.erase(index)
+        }
+    }
+
+    #[test]
+    fn counts_per_probe_and_persists() {
+        let a = ProbeId::new(1).unwrap();
+        let b = ProbeId::new(2).unwrap();
+        let mut bytes = [0u8; 128];
+        {
+            // Whatever the flash held before is ignored
+            let mut rc = FlashRestartCounter::new(RamFlash::new(&mut bytes, 64));
+            assert_eq!(rc.next_sequence_id(a), Ok(0));
+            assert_eq!(rc.next_sequence_id(a), Ok(1));
+            assert_eq!(rc.next_sequence_id(b), Ok(0));
+        }
+        let mut rc = FlashRestartCounter::new(RamFlash::new(&mut bytes, 64));
+        assert_eq!(rc.next_sequence_id(a), Ok(2));
+        assert_eq!(rc.next_sequence_id(b), Ok(1));
+    }
+
+    #[test]
+    fn erases_are_spread_across_sectors() {
+        let probes = [ProbeId::new(1).unwrap(), ProbeId::new(2).unwrap()];
+        let mut bytes = [0xFFu8; 4 * 64];
+        let mut rc = FlashRestartCounter::new(CountingFlash {
+            flash: RamFlash::new(&mut bytes, 64),
+            erases: [0; 4],
+        });
+        for n in 0..1000u16 {
+            for p in probes.iter() {
+                assert_eq!(rc.next_sequence_id(*p), Ok(n));
+            }
+        }
+        let erases = rc.into_inner().erases;
+        let (min, max) = (erases.iter().min().unwrap(), erases.iter().max().unwrap());
+        assert!(*min > 0);
+        assert!(max - min <= 1);
+    }
+
+    #[test]
+    fn interrupted_move_keeps_previous_sector() {
+        let p = ProbeId::new(7).unwrap();
+        let mut bytes = [0xFFu8; 2 * 32];
+        {
+            let mut rc = FlashRestartCounter::new(RamFlash::new(&mut bytes, 32));
+            // The header and three counters fill the first sector
+            for n in 0..3 {
+                assert_eq!(rc.next_sequence_id(p), Ok(n));
+            }
+        }
+        // Carry the counter over as a move would, but without the header
+        let mut flash = RamFlash::new(&mut bytes, 32);
+        flash.erase(1).unwrap();
+        flash.write(32 + RECORD_LEN, &Record::encode(7, 2)).unwrap();
+        let mut rc = FlashRestartCounter::new(flash);
+        assert_eq!(rc.next_sequence_id(p), Ok(3));
+        assert_eq!(rc.next_sequence_id(p), Ok(4));
+    }
+
+    #[test]
+    fn torn_records_are_skipped() {
+        let p = ProbeId::new(3).unwrap();
+        let mut bytes = [0xFFu8; 2 * 64];
+        {
+            let mut rc = FlashRestartCounter::new(RamFlash::new(&mut bytes, 64));
+            assert_eq!(rc.next_sequence_id(p), Ok(0));
+        }
+        // Half a record for the next counter
+        bytes[2 * RECORD_LEN..2 * RECORD_LEN + 4].copy_from_slice(&3u32.to_le_bytes());
+        let mut rc = FlashRestartCounter::new(RamFlash::new(&mut bytes, 64));
+        assert_eq!(rc.next_sequence_id(p), Ok(1));
+        assert_eq!(rc.next_sequence_id(p), Ok(2));
+    }
+
+    #[test]
+    fn too_many_probes() {
+        let mut bytes = [0xFFu8; 2 * 24];
+        let mut rc = FlashRestartCounter::new(RamFlash::new(&mut bytes, 24));
+        assert_eq!(rc.next_sequence_id(ProbeId::new(1).unwrap()), Ok(0));
+        assert_eq!(rc.next_sequence_id(ProbeId::new(2).unwrap()), Ok(0));
+        assert_eq!(
+            rc.next_sequence_id(ProbeId::new(3).unwrap()),
+            Err(RestartSequenceIdUnavailable)
+        );
+    }
+}
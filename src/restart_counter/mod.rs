@@ -1,6 +1,14 @@
 use crate::ProbeId;
 use core::fmt;
 
+#[cfg(feature = "std")]
+mod file;
+pub mod flash;
+
+#[cfg(feature = "std")]
+pub use file::FileRestartCounter;
+pub use flash::{CFlash, Flash, FlashError, FlashRestartCounter, RamFlash};
+
 /// A persistent restart sequence counter
 pub trait RestartCounter {
     /// Get the next persistent sequence number.
@@ -0,0 +1,57 @@
+#[cfg(feature = "probe-stats")]
+use crate::EventId;
+
+/// Counters a probe keeps about its own operation, e.g. to tune a
+/// target's log and report buffer sizes. Each counter saturates
+/// rather than wrapping.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProbeStats {
+    /// The number of events recorded, including internal events and
+    /// time-only events
+    pub events_recorded: u32,
+    /// The number of log entries overwritten before they could be
+    /// reported
+    pub entries_overwritten: u32,
+    /// The number of reports produced, including retransmissions
+    pub reports_produced: u32,
+    /// The total size of the reports produced, in bytes
+    pub bytes_reported: u32,
+    /// The number of causal snapshots produced
+    pub snapshots_produced: u32,
+    /// The number of causal snapshots from other probes merged in
+    pub snapshots_merged: u32,
+    /// The number of times this probe's logical clock overflowed into
+    /// a new epoch
+    pub clock_overflows: u32,
+    /// The number of times a neighbor clock couldn't be tracked
+    /// because the clock table was full
+    pub frontier_clock_overflows: u32,
+}
+
+impl ProbeStats {
+    /// The internal events each counter is reported as, paired with
+    /// the counter's current value
+    #[cfg(feature = "probe-stats")]
+    pub(crate) fn as_events(&self) -> [(EventId, u32); 8] {
+        [
+            (EventId::EVENT_STATS_EVENTS_RECORDED, self.events_recorded),
+            (
+                EventId::EVENT_STATS_ENTRIES_OVERWRITTEN,
+                self.entries_overwritten,
+            ),
+            (EventId::EVENT_STATS_REPORTS_PRODUCED, self.reports_produced),
+            (EventId::EVENT_STATS_BYTES_REPORTED, self.bytes_reported),
+            (
+                EventId::EVENT_STATS_SNAPSHOTS_PRODUCED,
+                self.snapshots_produced,
+            ),
+            (EventId::EVENT_STATS_SNAPSHOTS_MERGED, self.snapshots_merged),
+            (EventId::EVENT_STATS_CLOCK_OVERFLOWS, self.clock_overflows),
+            (
+                EventId::EVENT_STATS_FRONTIER_CLOCK_OVERFLOWS,
+                self.frontier_clock_overflows,
+            ),
+        ]
+    }
+}
@@ -1,10 +1,15 @@
 //! A wire protocol for representing Modality probe causal snapshots
+//!
+//! The format version occupies the top two bits of the probe id
+//! field, which valid probe ids leave clear, so the snapshots written
+//! before versioning was introduced are version 0.
 
 use crate::{
     wire::le_bytes, CausalSnapshot, InvalidProbeId, LogicalClock, ProbeEpoch, ProbeId, ProbeTicks,
 };
 use core::convert::TryFrom;
 use core::mem::size_of;
+use core::num::NonZeroU32;
 use static_assertions::const_assert_eq;
 
 /// Everything that can go wrong when attempting to interpret a causal snapshot
@@ -16,6 +21,8 @@ pub enum CausalSnapshotWireError {
     /// The probe id didn't follow the rules for being
     /// a valid Modality probe-specifying ProbeId
     InvalidProbeId(u32),
+    /// The snapshot's format version is newer than this crate understands
+    UnsupportedVersion(u8),
 }
 
 /// Error that indicates there weren't enough bytes for a full causal snapshot
@@ -51,9 +58,12 @@ mod field {
     type Field = ::core::ops::Range<usize>;
     type Rest = ::core::ops::RangeFrom<usize>;
 
-    /// LogicalClock.id
+    /// LogicalClock.id, with the format version in the top bits
     pub const PROBE_ID: Field = 0..4;
 
+    /// The shift of the format version within the probe id field
+    pub const VERSION_SHIFT: u32 = 30;
+
     /// LogicalClock.clock
     pub const TICKS: Field = 4..6;
 
@@ -71,6 +81,9 @@ mod field {
 }
 
 impl<T: AsRef<[u8]>> WireCausalSnapshot<T> {
+    /// The snapshot format version written by this crate
+    pub const VERSION: u8 = 0;
+
     /// Construct a causal snapshot from a byte buffer
     #[inline]
     pub fn new_unchecked(buffer: T) -> WireCausalSnapshot<T> {
@@ -107,16 +120,38 @@ impl<T: AsRef<[u8]>> WireCausalSnapshot<T> {
         self.buffer
     }
 
+    /// Check that the snapshot's format version is one this crate
+    /// understands.
+    ///
+    /// Returns `Err(CausalSnapshotWireError::UnsupportedVersion)` if
+    /// the version is newer than
+    /// [Self::VERSION](struct.WireCausalSnapshot.html#associatedconstant.VERSION).
+    pub fn check_version(&self) -> Result<(), CausalSnapshotWireError> {
+        let version = self.version();
+        if version > Self::VERSION {
+            Err(CausalSnapshotWireError::UnsupportedVersion(version))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Return the length of a buffer required to hold a causal snapshot
     pub fn min_buffer_len() -> usize {
         field::REST.start
     }
 
+    /// Return the format version
+    #[inline]
+    pub fn version(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        (le_bytes::read_u32(&data[field::PROBE_ID]) >> field::VERSION_SHIFT) as u8
+    }
+
     /// Return the `probe_id` field
     #[inline]
     pub fn probe_id(&self) -> Result<ProbeId, InvalidWireProbeId> {
         let data = self.buffer.as_ref();
-        let raw_probe_id = le_bytes::read_u32(&data[field::PROBE_ID]);
+        let raw_probe_id = le_bytes::read_u32(&data[field::PROBE_ID]) & ProbeId::MAX_ID;
         match ProbeId::new(raw_probe_id) {
             Some(id) => Ok(id),
             None => Err(InvalidWireProbeId(raw_probe_id)),
@@ -152,14 +187,35 @@ impl<T: AsRef<[u8]>> WireCausalSnapshot<T> {
         let field = &data[field::RESERVED_1];
         [field[0], field[1]]
     }
+
+    /// Return the interaction id carried in the reserved fields, if any
+    #[inline]
+    pub fn interaction_id(&self) -> Option<NonZeroU32> {
+        interaction_id_from_reserved(self.reserved_0(), self.reserved_1())
+    }
 }
 
 impl<T: AsRef<[u8]> + AsMut<[u8]>> WireCausalSnapshot<T> {
-    /// Set the `probe_id` field
+    /// Set the `probe_id` field, along with the version, to
+    /// [Self::VERSION](struct.WireCausalSnapshot.html#associatedconstant.VERSION)
     #[inline]
     pub fn set_probe_id(&mut self, value: ProbeId) {
         let data = self.buffer.as_mut();
-        le_bytes::write_u32(&mut data[field::PROBE_ID], value.get_raw());
+        le_bytes::write_u32(
+            &mut data[field::PROBE_ID],
+            value.get_raw() | (u32::from(Self::VERSION) << field::VERSION_SHIFT),
+        );
+    }
+
+    /// Set the format version, keeping the probe id
+    #[inline]
+    pub fn set_version(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        let raw_probe_id = le_bytes::read_u32(&data[field::PROBE_ID]) & ProbeId::MAX_ID;
+        le_bytes::write_u32(
+            &mut data[field::PROBE_ID],
+            raw_probe_id | (u32::from(value) << field::VERSION_SHIFT),
+        );
     }
 
     /// Set the `epoch` field
@@ -191,6 +247,14 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> WireCausalSnapshot<T> {
         data[field::RESERVED_1][0] = value[0];
         data[field::RESERVED_1][1] = value[1];
     }
+
+    /// Set the interaction id carried in the reserved fields
+    #[inline]
+    pub fn set_interaction_id(&mut self, value: Option<NonZeroU32>) {
+        let (reserved_0, reserved_1) = interaction_id_to_reserved(value);
+        self.set_reserved_0(reserved_0);
+        self.set_reserved_1(reserved_1);
+    }
 }
 
 impl<T: AsRef<[u8]>> AsRef<[u8]> for WireCausalSnapshot<T> {
@@ -199,10 +263,46 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for WireCausalSnapshot<T> {
     }
 }
 
+/// The interaction id occupies the reserved fields as a single little
+/// endian u32, `reserved_0` holding the low half; zero means unset.
+#[inline]
+fn interaction_id_from_reserved(reserved_0: [u8; 2], reserved_1: [u8; 2]) -> Option<NonZeroU32> {
+    NonZeroU32::new(u32::from_le_bytes([
+        reserved_0[0],
+        reserved_0[1],
+        reserved_1[0],
+        reserved_1[1],
+    ]))
+}
+
+#[inline]
+fn interaction_id_to_reserved(id: Option<NonZeroU32>) -> ([u8; 2], [u8; 2]) {
+    let b = id.map_or(0, NonZeroU32::get).to_le_bytes();
+    ([b[0], b[1]], [b[2], b[3]])
+}
+
 impl CausalSnapshot {
+    /// The user-supplied id of the interaction (a bus message, a queue,
+    /// a channel, ...) this snapshot was sent over, if one was set.
+    ///
+    /// Merging a snapshot that carries an interaction id records it in
+    /// the receiving probe's log alongside the merged clock.
+    pub fn interaction_id(&self) -> Option<NonZeroU32> {
+        interaction_id_from_reserved(self.reserved_0, self.reserved_1)
+    }
+
+    /// Tag this snapshot with the id of the interaction it is sent over,
+    /// or clear the tag with `None`.
+    pub fn set_interaction_id(&mut self, id: Option<NonZeroU32>) {
+        let (reserved_0, reserved_1) = interaction_id_to_reserved(id);
+        self.reserved_0 = reserved_0;
+        self.reserved_1 = reserved_1;
+    }
+
     /// Construct a causal snapshot from a sequence of little endian bytes
     pub fn from_le_bytes(bytes: [u8; 12]) -> Result<Self, InvalidProbeId> {
         let snapshot = WireCausalSnapshot::new_unchecked(bytes);
+        snapshot.check_version().map_err(|_| InvalidProbeId)?;
         Ok(CausalSnapshot {
             clock: LogicalClock {
                 id: snapshot.probe_id().map_err(|_| InvalidProbeId)?,
@@ -256,6 +356,7 @@ impl TryFrom<&[u8]> for CausalSnapshot {
 
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
         let snapshot = WireCausalSnapshot::new(bytes)?;
+        snapshot.check_version()?;
         Ok(CausalSnapshot {
             clock: LogicalClock {
                 id: snapshot.probe_id()?,
@@ -273,6 +374,7 @@ impl TryFrom<&[u8; 12]> for CausalSnapshot {
 
     fn try_from(bytes: &[u8; 12]) -> Result<Self, Self::Error> {
         let snapshot = WireCausalSnapshot::new_unchecked(bytes);
+        snapshot.check_version().map_err(|_| InvalidProbeId)?;
         Ok(CausalSnapshot {
             clock: LogicalClock {
                 id: snapshot.probe_id().map_err(|_| InvalidProbeId)?,
@@ -327,6 +429,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn interaction_id_in_reserved_fields() {
+        let mut snap = CausalSnapshot {
+            clock: LogicalClock {
+                id: ProbeId::new(1).unwrap(),
+                epoch: ProbeEpoch(0),
+                ticks: ProbeTicks(0),
+            },
+            reserved_0: [0, 0],
+            reserved_1: [0, 0],
+        };
+        assert_eq!(snap.interaction_id(), None);
+        snap.set_interaction_id(NonZeroU32::new(0x9A78_5634));
+        assert_eq!(snap.reserved_0, [0x34, 0x56]);
+        assert_eq!(snap.reserved_1, [0x78, 0x9A]);
+
+        let bytes = snap.to_le_bytes();
+        let mut wire = WireCausalSnapshot::new_unchecked(bytes);
+        assert_eq!(wire.interaction_id(), NonZeroU32::new(0x9A78_5634));
+        wire.set_interaction_id(None);
+        assert_eq!(wire.reserved_0(), [0, 0]);
+        assert_eq!(wire.reserved_1(), [0, 0]);
+    }
+
     proptest! {
         #[test]
         fn round_trip_causal_snapshot(
@@ -408,6 +534,30 @@ mod tests {
         assert_eq!(s.reserved_1(), [0x05, 0x00]);
     }
 
+    #[test]
+    fn version() {
+        let s = WireCausalSnapshot::new(&SNAPSHOT_BYTES[..]).unwrap();
+        assert_eq!(s.version(), 0);
+        assert_eq!(s.check_version(), Ok(()));
+
+        let mut bytes = SNAPSHOT_BYTES;
+        let mut s = WireCausalSnapshot::new_unchecked(&mut bytes[..]);
+        s.set_version(WireCausalSnapshot::<&[u8]>::VERSION + 1);
+        assert_eq!(s.version(), 1);
+        assert_eq!(s.probe_id().unwrap().get_raw(), 1);
+        assert_eq!(
+            CausalSnapshot::try_from(&bytes[..]),
+            Err(CausalSnapshotWireError::UnsupportedVersion(1))
+        );
+        assert_eq!(CausalSnapshot::from_le_bytes(bytes), Err(InvalidProbeId));
+
+        // Setting the probe id resets the version
+        let mut s = WireCausalSnapshot::new_unchecked(&mut bytes[..]);
+        s.set_probe_id(ProbeId::new(ProbeId::MAX_ID).unwrap());
+        assert_eq!(s.version(), 0);
+        assert_eq!(s.probe_id().unwrap().get_raw(), ProbeId::MAX_ID);
+    }
+
     #[test]
     fn missing_bytes() {
         let bytes = [0xFF; 12 - 1];
@@ -0,0 +1,351 @@
+//! A compact wire protocol for causal snapshots, for links (e.g.
+//! classic CAN) whose frames can't spare the 12 bytes of a
+//! `WireCausalSnapshot` alongside application data.
+//!
+//! A compact snapshot is laid out as:
+//! * A header byte, giving the width of the probe id field and
+//!   whether the epoch is truncated.
+//! * The probe id, in the fewest of 1, 2 or 4 bytes that hold it.
+//! * The epoch, either whole in 2 bytes or truncated to its low byte.
+//! * The ticks, in 2 bytes.
+//!
+//! So a probe id below 256 takes 5 bytes with a truncated epoch and 6
+//! with a whole one, while the largest probe ids take 8 or 9.
+//!
+//! A truncated epoch is reconstructed by the receiver as the epoch
+//! nearest the last one it knows for the sending probe, which is right
+//! as long as the sender's epoch has moved by less than 128 since. A
+//! receiver that knows no epoch for the sender can't reconstruct one,
+//! so senders using truncated epochs should send a whole one now and
+//! then, e.g. in their first snapshot.
+
+use crate::{
+    wire::{le_bytes, MissingBytes},
+    LogicalClock, ProbeEpoch, ProbeId, ProbeTicks,
+};
+use core::convert::TryFrom;
+
+/// Everything that can go wrong when attempting to interpret a compact
+/// causal snapshot from the wire representation
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum CompactSnapshotWireError {
+    /// There weren't enough bytes for a full compact snapshot
+    MissingBytes,
+    /// The header byte used a reserved bit or probe id width
+    InvalidHeader(u8),
+    /// The probe id didn't follow the rules for being
+    /// a valid Modality probe-specifying ProbeId
+    InvalidProbeId(u32),
+}
+
+impl From<MissingBytes> for CompactSnapshotWireError {
+    fn from(_: MissingBytes) -> Self {
+        CompactSnapshotWireError::MissingBytes
+    }
+}
+
+/// How to encode the epoch of a compact snapshot
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum EpochEncoding {
+    /// Send the whole epoch
+    Whole,
+    /// Send only the epoch's low byte
+    Truncated,
+}
+
+/// The epoch of a compact snapshot, as sent
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CompactEpoch {
+    /// The whole epoch
+    Whole(ProbeEpoch),
+    /// The low byte of the epoch
+    Truncated(u8),
+}
+
+impl CompactEpoch {
+    /// Encode `epoch` as specified
+    pub fn new(epoch: ProbeEpoch, encoding: EpochEncoding) -> Self {
+        match encoding {
+            EpochEncoding::Whole => CompactEpoch::Whole(epoch),
+            EpochEncoding::Truncated => CompactEpoch::Truncated(epoch.0 as u8),
+        }
+    }
+
+    /// Reconstruct the whole epoch, given the last epoch known for
+    /// the sending probe, if any.
+    ///
+    /// A truncated epoch resolves to the epoch nearest `reference`
+    /// with the same low byte. Returns `None` for a truncated epoch
+    /// when there is no `reference`, as the epoch is then ambiguous.
+    pub fn reconstruct(self, reference: Option<ProbeEpoch>) -> Option<ProbeEpoch> {
+        match (self, reference) {
+            (CompactEpoch::Whole(epoch), _) => Some(epoch),
+            (CompactEpoch::Truncated(low), Some(reference)) => {
+                let delta = low.wrapping_sub(reference.0 as u8) as i8;
+                Some(ProbeEpoch(reference.0.wrapping_add(delta as i16 as u16)))
+            }
+            (CompactEpoch::Truncated(_), None) => None,
+        }
+    }
+}
+
+/// A causal snapshot in its compact form
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CompactCausalSnapshot {
+    /// The probe the snapshot was produced by
+    pub id: ProbeId,
+    /// The epoch portion of the probe's logical clock
+    pub epoch: CompactEpoch,
+    /// The clock portion of the probe's logical clock
+    pub ticks: ProbeTicks,
+}
+
+impl CompactCausalSnapshot {
+    /// A compact snapshot of `clock`, with its epoch encoded as specified
+    pub fn new(clock: LogicalClock, encoding: EpochEncoding) -> Self {
+        CompactCausalSnapshot {
+            id: clock.id,
+            epoch: CompactEpoch::new(clock.epoch, encoding),
+            ticks: clock.ticks,
+        }
+    }
+
+    /// The snapshot's logical clock, reconstructing its epoch with
+    /// `reference` (see `CompactEpoch::reconstruct`).
+    pub fn clock(&self, reference: Option<ProbeEpoch>) -> Option<LogicalClock> {
+        Some(LogicalClock {
+            id: self.id,
+            epoch: self.epoch.reconstruct(reference)?,
+            ticks: self.ticks,
+        })
+    }
+
+    /// The number of bytes the snapshot takes on the wire
+    pub fn wire_len(&self) -> usize {
+        let header = header::new(self.id, self.epoch);
+        header::wire_len(header).expect("Generated an invalid compact snapshot header")
+    }
+
+    /// Writes the snapshot into a slice of little endian bytes.
+    ///
+    /// Returns the number of bytes written.
+    pub fn write_into_le_bytes(&self, bytes: &mut [u8]) -> Result<usize, MissingBytes> {
+        let header = header::new(self.id, self.epoch);
+        let len = self.wire_len();
+        if bytes.len() < len {
+            return Err(MissingBytes);
+        }
+        bytes[0] = header;
+        let id_end = 1 + header::id_width(header);
+        let raw_id = self.id.get_raw();
+        match header::id_width(header) {
+            1 => bytes[1] = raw_id as u8,
+            2 => le_bytes::write_u16(&mut bytes[1..id_end], raw_id as u16),
+            _ => le_bytes::write_u32(&mut bytes[1..id_end], raw_id),
+        }
+        let epoch_end = match self.epoch {
+            CompactEpoch::Whole(epoch) => {
+                le_bytes::write_u16(&mut bytes[id_end..id_end + 2], epoch.0);
+                id_end + 2
+            }
+            CompactEpoch::Truncated(low) => {
+                bytes[id_end] = low;
+                id_end + 1
+            }
+        };
+        le_bytes::write_u16(&mut bytes[epoch_end..len], self.ticks.0);
+        Ok(len)
+    }
+}
+
+impl TryFrom<&[u8]> for CompactCausalSnapshot {
+    type Error = CompactSnapshotWireError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let header = *bytes.first().ok_or(MissingBytes)?;
+        let len =
+            header::wire_len(header).ok_or(CompactSnapshotWireError::InvalidHeader(header))?;
+        if bytes.len() < len {
+            return Err(CompactSnapshotWireError::MissingBytes);
+        }
+        let id_end = 1 + header::id_width(header);
+        let raw_id = match header::id_width(header) {
+            1 => u32::from(bytes[1]),
+            2 => u32::from(le_bytes::read_u16(&bytes[1..id_end])),
+            _ => le_bytes::read_u32(&bytes[1..id_end]),
+        };
+        let id = ProbeId::new(raw_id).ok_or(CompactSnapshotWireError::InvalidProbeId(raw_id))?;
+        let (epoch, epoch_end) = if header & header::TRUNCATED_EPOCH_BIT != 0 {
+            (CompactEpoch::Truncated(bytes[id_end]), id_end + 1)
+        } else {
+            (
+                CompactEpoch::Whole(ProbeEpoch(le_bytes::read_u16(&bytes[id_end..id_end + 2]))),
+                id_end + 2,
+            )
+        };
+        Ok(CompactCausalSnapshot {
+            id,
+            epoch,
+            ticks: ProbeTicks(le_bytes::read_u16(&bytes[epoch_end..len])),
+        })
+    }
+}
+
+mod header {
+    use super::CompactEpoch;
+    use crate::ProbeId;
+
+    /// The low two bits give the width of the probe id field: 1, 2 or
+    /// 4 bytes for 0, 1 or 2.
+    pub const ID_WIDTH_MASK: u8 = 0b011;
+    /// Set when the epoch field holds only the epoch's low byte
+    pub const TRUNCATED_EPOCH_BIT: u8 = 0b100;
+    /// Bits that must be clear
+    pub const RESERVED_MASK: u8 = !(ID_WIDTH_MASK | TRUNCATED_EPOCH_BIT);
+
+    /// The header for a snapshot of `id` with `epoch`
+    pub fn new(id: ProbeId, epoch: CompactEpoch) -> u8 {
+        let raw_id = id.get_raw();
+        let mut header = if raw_id <= u32::from(u8::MAX) {
+            0
+        } else if raw_id <= u32::from(u16::MAX) {
+            1
+        } else {
+            2
+        };
+        if let CompactEpoch::Truncated(_) = epoch {
+            header |= TRUNCATED_EPOCH_BIT;
+        }
+        header
+    }
+
+    /// The width of the probe id field, for a valid header
+    pub fn id_width(header: u8) -> usize {
+        1 << (header & ID_WIDTH_MASK)
+    }
+
+    /// The length of a snapshot with this header, or `None` if the
+    /// header is invalid
+    pub fn wire_len(header: u8) -> Option<usize> {
+        if header & RESERVED_MASK != 0 || header & ID_WIDTH_MASK == ID_WIDTH_MASK {
+            return None;
+        }
+        let epoch_width = if header & TRUNCATED_EPOCH_BIT != 0 {
+            1
+        } else {
+            2
+        };
+        Some(1 + id_width(header) + epoch_width + 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::log_tests::gen_clock;
+    use proptest::prelude::*;
+
+    #[rustfmt::skip]
+    static SMALL_ID_TRUNCATED_BYTES: [u8; 5] = [
+        // header: 1 byte probe id, truncated epoch
+        0x04,
+        // probe_id: 7
+        0x07,
+        // epoch: low byte of 0x0102
+        0x02,
+        // ticks: 3
+        0x03, 0x00,
+    ];
+
+    #[test]
+    fn small_probe_ids_fit_in_five_bytes() {
+        let snap = CompactCausalSnapshot::new(
+            LogicalClock {
+                id: ProbeId::new(7).unwrap(),
+                epoch: ProbeEpoch(0x0102),
+                ticks: ProbeTicks(3),
+            },
+            EpochEncoding::Truncated,
+        );
+        assert_eq!(snap.wire_len(), 5);
+        let mut bytes = [0xFF; 8];
+        assert_eq!(snap.write_into_le_bytes(&mut bytes), Ok(5));
+        assert_eq!(&bytes[..5], &SMALL_ID_TRUNCATED_BYTES[..]);
+        assert_eq!(
+            CompactCausalSnapshot::try_from(&SMALL_ID_TRUNCATED_BYTES[..]),
+            Ok(snap)
+        );
+        assert_eq!(snap.write_into_le_bytes(&mut bytes[..4]), Err(MissingBytes));
+    }
+
+    #[test]
+    fn invalid_wire_snapshots() {
+        assert_eq!(
+            CompactCausalSnapshot::try_from(&[][..]),
+            Err(CompactSnapshotWireError::MissingBytes)
+        );
+        assert_eq!(
+            CompactCausalSnapshot::try_from(&SMALL_ID_TRUNCATED_BYTES[..4]),
+            Err(CompactSnapshotWireError::MissingBytes)
+        );
+        assert_eq!(
+            CompactCausalSnapshot::try_from(&[0x03, 0, 0, 0, 0, 0, 0, 0, 0][..]),
+            Err(CompactSnapshotWireError::InvalidHeader(0x03))
+        );
+        assert_eq!(
+            CompactCausalSnapshot::try_from(&[0x80, 1, 0, 0, 0, 0][..]),
+            Err(CompactSnapshotWireError::InvalidHeader(0x80))
+        );
+        assert_eq!(
+            CompactCausalSnapshot::try_from(&[0x00, 0, 0, 0, 0, 0][..]),
+            Err(CompactSnapshotWireError::InvalidProbeId(0))
+        );
+    }
+
+    #[test]
+    fn truncated_epoch_reconstruction() {
+        let truncated = |e: u16| CompactEpoch::new(ProbeEpoch(e), EpochEncoding::Truncated);
+        assert_eq!(truncated(0x0102).reconstruct(None), None);
+        assert_eq!(
+            truncated(0x0102).reconstruct(Some(ProbeEpoch(0x00F0))),
+            Some(ProbeEpoch(0x0102))
+        );
+        assert_eq!(
+            truncated(0x00F0).reconstruct(Some(ProbeEpoch(0x0102))),
+            Some(ProbeEpoch(0x00F0))
+        );
+        // Across the epoch's wraparound
+        assert_eq!(
+            truncated(0x0001).reconstruct(Some(ProbeEpoch(0xFFFE))),
+            Some(ProbeEpoch(0x0001))
+        );
+        assert_eq!(
+            CompactEpoch::new(ProbeEpoch(0x0102), EpochEncoding::Whole).reconstruct(None),
+            Some(ProbeEpoch(0x0102))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_compact_snapshot(
+            clock in gen_clock(),
+            truncate in proptest::bool::ANY,
+            epoch_drift in -127i16..=127) {
+            let encoding = if truncate {
+                EpochEncoding::Truncated
+            } else {
+                EpochEncoding::Whole
+            };
+            let snap_in = CompactCausalSnapshot::new(clock, encoding);
+            let mut bytes = [0xFF; 9];
+            let len = snap_in.write_into_le_bytes(&mut bytes[..]).unwrap();
+            prop_assert_eq!(len, snap_in.wire_len());
+            prop_assert!(len <= 9);
+            let snap_out = CompactCausalSnapshot::try_from(&bytes[..len]).unwrap();
+            prop_assert_eq!(snap_in, snap_out);
+
+            let reference = ProbeEpoch(clock.epoch.0.wrapping_add(epoch_drift as u16));
+            prop_assert_eq!(snap_out.clock(Some(reference)), Some(clock));
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! A compact encoding for the log entries of a report.
+//!
+//! Reports with the
+//! [COMPRESSED_FLAG](../report/struct.WireReport.html#associatedconstant.COMPRESSED_FLAG)
+//! set carry their log as a sequence of tokens instead of raw 32-bit
+//! words. Every token starts with a LEB128 varint of a value shifted
+//! left over a 3-bit tag:
+//!
+//! * `EVENT`: a single-word entry, verbatim.
+//! * `EVENT_WITH_PAYLOAD`: the event id, followed by a varint of the
+//!   payload.
+//! * `CLOCK`: the clock's probe id, followed by a varint of the packed
+//!   epoch and ticks.
+//! * `TIME` and `PAIRED_TIME`: the zigzag-encoded difference from the
+//!   previous wall clock time in the report, or from zero for the
+//!   first one.
+//! * `REPEAT`: followed by a single count byte, repeating the previous
+//!   entry that many more times.
+//! * `DOUBLE`: any other two-word entry, followed by a varint of its
+//!   second word.
+//!
+//! Decoding yields exactly the words that were encoded.
+
+use crate::{
+    log::{
+        CLOCK_MASK, EVENT_WITH_PAYLOAD_MASK, PAIRED_WALL_CLOCK_TIME_MASK, RESERVED_BITS_MASK,
+        WALL_CLOCK_TIME_MASK,
+    },
+    time::Nanoseconds,
+    wire::{MissingBytes, ReportWireError},
+};
+
+const TAG_BITS: u32 = 3;
+const TAG_MASK: u64 = 0b111;
+
+const EVENT: u64 = 0;
+const EVENT_WITH_PAYLOAD: u64 = 1;
+const CLOCK: u64 = 2;
+const TIME: u64 = 3;
+const PAIRED_TIME: u64 = 4;
+const REPEAT: u64 = 5;
+const DOUBLE: u64 = 6;
+
+/// The bits of a wall clock time entry's first word holding the high
+/// bits of the time
+const TIME_HIGH_BITS_MASK: u32 = !(WALL_CLOCK_TIME_MASK | PAIRED_WALL_CLOCK_TIME_MASK);
+
+/// The longest a varint of a u64 gets
+const MAX_VARINT_LEN: usize = 10;
+
+/// The most bytes an entry's token takes up, per word of the entry
+pub const MAX_ENCODED_WORD_LEN: usize = 5;
+
+/// Writes log entries as tokens, one after the other, keeping track of
+/// what's needed to delta-encode times and collapse repeated entries.
+///
+/// The encoder doesn't own the buffer it writes to, so that it can be
+/// used without allocating; pass it the same buffer on every call.
+#[derive(Debug, Clone, Default)]
+pub struct LogEncoder {
+    len: usize,
+    prev_time: u64,
+    prev_entry: Option<(u32, Option<u32>)>,
+    /// Where the count of the repeat token following `prev_entry` is,
+    /// if there is one
+    repeat_count_index: Option<usize>,
+}
+
+impl LogEncoder {
+    /// Create an encoder for an empty log
+    pub fn new() -> Self {
+        LogEncoder::default()
+    }
+
+    /// The number of bytes written so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether nothing has been written yet
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Append the entry made of `first` and, for two-word entries,
+    /// `second` to the tokens already written to `dest`. Nothing is
+    /// written if the entry doesn't fit.
+    pub fn encode(
+        &mut self,
+        dest: &mut [u8],
+        first: u32,
+        second: Option<u32>,
+    ) -> Result<(), MissingBytes> {
+        if self.prev_entry == Some((first, second)) {
+            if let Some(i) = self.repeat_count_index {
+                if dest[i] < u8::MAX {
+                    dest[i] += 1;
+                    return Ok(());
+                }
+            }
+            let token = dest.get_mut(self.len..self.len + 2).ok_or(MissingBytes)?;
+            token[0] = REPEAT as u8;
+            token[1] = 1;
+            self.repeat_count_index = Some(self.len + 1);
+            self.len += 2;
+            return Ok(());
+        }
+
+        let mut token = [0u8; 2 * MAX_VARINT_LEN];
+        let mut token_len = 0;
+        let mut time = None;
+        match second {
+            None => token_len += write_varint(&mut token, tagged(first.into(), EVENT)),
+            Some(second) => {
+                let (value, tag) = match first & RESERVED_BITS_MASK {
+                    CLOCK_MASK => ((first & !CLOCK_MASK).into(), CLOCK),
+                    EVENT_WITH_PAYLOAD_MASK => (
+                        (first & !EVENT_WITH_PAYLOAD_MASK).into(),
+                        EVENT_WITH_PAYLOAD,
+                    ),
+                    WALL_CLOCK_TIME_MASK => {
+                        let t = u64::from(first & TIME_HIGH_BITS_MASK) << 32 | u64::from(second);
+                        time = Some(t);
+                        let tag = if first & PAIRED_WALL_CLOCK_TIME_MASK != 0 {
+                            PAIRED_TIME
+                        } else {
+                            TIME
+                        };
+                        (zigzag(t.wrapping_sub(self.prev_time)), tag)
+                    }
+                    _ => (first.into(), DOUBLE),
+                };
+                token_len += write_varint(&mut token, tagged(value, tag));
+                if time.is_none() {
+                    token_len += write_varint(&mut token[token_len..], second.into());
+                }
+            }
+        }
+
+        dest.get_mut(self.len..self.len + token_len)
+            .ok_or(MissingBytes)?
+            .copy_from_slice(&token[..token_len]);
+        self.len += token_len;
+        self.prev_entry = Some((first, second));
+        self.repeat_count_index = None;
+        if let Some(t) = time {
+            self.prev_time = t;
+        }
+        Ok(())
+    }
+}
+
+/// Decode the tokens in `bytes`, passing each log word they stand for
+/// to `word`, in order.
+pub fn decode<F: FnMut(u32)>(bytes: &[u8], mut word: F) -> Result<(), ReportWireError> {
+    let mut prev_time = 0u64;
+    let mut prev_entry = None;
+    let mut bytes = bytes;
+    while !bytes.is_empty() {
+        let token = read_varint(&mut bytes)?;
+        let (value, tag) = (token >> TAG_BITS, token & TAG_MASK);
+        let entry = match tag {
+            EVENT => (narrow(value)?, None),
+            EVENT_WITH_PAYLOAD | CLOCK | DOUBLE => {
+                let first = narrow(value)?;
+                let first = match tag {
+                    EVENT_WITH_PAYLOAD => first | EVENT_WITH_PAYLOAD_MASK,
+                    CLOCK => first | CLOCK_MASK,
+                    _ => first,
+                };
+                (first, Some(narrow(read_varint(&mut bytes)?)?))
+            }
+            TIME | PAIRED_TIME => {
+                let t = prev_time.wrapping_add(unzigzag(value)) & Nanoseconds::MAX.get();
+                prev_time = t;
+                let mut first = (t >> 32) as u32 | WALL_CLOCK_TIME_MASK;
+                if tag == PAIRED_TIME {
+                    first |= PAIRED_WALL_CLOCK_TIME_MASK;
+                }
+                (first, Some(t as u32))
+            }
+            REPEAT if value == 0 => {
+                let (first, second) = prev_entry.ok_or(ReportWireError::InvalidCompressedLog)?;
+                let (count, rest) = bytes
+                    .split_first()
+                    .ok_or(ReportWireError::InvalidCompressedLog)?;
+                bytes = rest;
+                for _ in 0..*count {
+                    word(first);
+                    if let Some(second) = second {
+                        word(second);
+                    }
+                }
+                continue;
+            }
+            _ => return Err(ReportWireError::InvalidCompressedLog),
+        };
+        word(entry.0);
+        if let Some(second) = entry.1 {
+            word(second);
+        }
+        prev_entry = Some(entry);
+    }
+    Ok(())
+}
+
+#[inline]
+fn tagged(value: u64, tag: u64) -> u64 {
+    value << TAG_BITS | tag
+}
+
+/// Zigzag-encode a difference between two times, which wraps around
+/// at the 61 bits a time has
+#[inline]
+fn zigzag(delta: u64) -> u64 {
+    let signed = ((delta << TAG_BITS) as i64) >> TAG_BITS;
+    ((signed << 1) ^ (signed >> 63)) as u64 & Nanoseconds::MAX.get()
+}
+
+#[inline]
+fn unzigzag(value: u64) -> u64 {
+    (value >> 1) ^ (value & 1).wrapping_neg()
+}
+
+#[inline]
+fn narrow(value: u64) -> Result<u32, ReportWireError> {
+    if value > u64::from(u32::MAX) {
+        Err(ReportWireError::InvalidCompressedLog)
+    } else {
+        Ok(value as u32)
+    }
+}
+
+fn write_varint(dest: &mut [u8], mut value: u64) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            dest[len] = byte;
+            return len + 1;
+        }
+        dest[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, ReportWireError> {
+    let src = *bytes;
+    let mut value = 0u64;
+    for (i, byte) in src.iter().enumerate().take(MAX_VARINT_LEN) {
+        let bits = u64::from(byte & 0x7F);
+        if i == MAX_VARINT_LEN - 1 && bits > 1 {
+            break;
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &src[i + 1..];
+            return Ok(value);
+        }
+    }
+    Err(ReportWireError::InvalidCompressedLog)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{log::LogEntry, EventId, LogicalClock, ProbeEpoch, ProbeId, ProbeTicks};
+    use proptest::prelude::*;
+
+    fn encode_all(entries: &[(u32, Option<u32>)]) -> ([u8; 1024], usize) {
+        let mut buf = [0u8; 1024];
+        let mut encoder = LogEncoder::new();
+        for (first, second) in entries {
+            encoder.encode(&mut buf, *first, *second).unwrap();
+        }
+        (buf, encoder.len())
+    }
+
+    fn decode_all(bytes: &[u8]) -> Result<Vec<u32>, ReportWireError> {
+        let mut words = Vec::new();
+        decode(bytes, |w| words.push(w))?;
+        Ok(words)
+    }
+
+    fn words(entries: &[(u32, Option<u32>)]) -> Vec<u32> {
+        entries
+            .iter()
+            .flat_map(|(first, second)| core::iter::once(*first).chain(*second))
+            .collect()
+    }
+
+    fn double((first, second): (LogEntry, LogEntry)) -> (u32, Option<u32>) {
+        (first.raw(), Some(second.raw()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let clock = LogicalClock {
+            id: ProbeId::new(3).unwrap(),
+            epoch: ProbeEpoch(1),
+            ticks: ProbeTicks(2),
+        };
+        let event = EventId::new(10).unwrap();
+        let entries = [
+            (LogEntry::event(event).raw(), None),
+            double(LogEntry::clock(clock)),
+            double(LogEntry::event_with_payload(event, 0xFFFF_FFFF)),
+            double(LogEntry::paired_wall_clock_time(Nanoseconds::MAX)),
+            (LogEntry::event(event).raw(), None),
+            double(LogEntry::unpaired_wall_clock_time(
+                Nanoseconds::new(5).unwrap(),
+            )),
+            double(LogEntry::unpaired_wall_clock_time(
+                Nanoseconds::new(5).unwrap(),
+            )),
+            double(LogEntry::paired_wall_clock_time(
+                Nanoseconds::new(1005).unwrap(),
+            )),
+            (0xFFFF_FFFF, None),
+        ];
+        let (buf, len) = encode_all(&entries);
+        assert_eq!(decode_all(&buf[..len]).unwrap(), words(&entries));
+    }
+
+    #[test]
+    fn small_entries_shrink() {
+        let event = EventId::new(10).unwrap();
+        let entries = [
+            (LogEntry::event(event).raw(), None),
+            double(LogEntry::event_with_payload(event, 3)),
+            double(LogEntry::unpaired_wall_clock_time(
+                Nanoseconds::new(1_000_000_000).unwrap(),
+            )),
+            double(LogEntry::unpaired_wall_clock_time(
+                Nanoseconds::new(1_000_001_000).unwrap(),
+            )),
+        ];
+        let (buf, len) = encode_all(&entries);
+        // 1 byte for the event, 2 for the event with payload, 5 for
+        // the first time and 2 for the second one's difference
+        assert_eq!(len, 1 + 2 + 5 + 2);
+        assert_eq!(decode_all(&buf[..len]).unwrap(), words(&entries));
+    }
+
+    #[test]
+    fn repeated_entries() {
+        let event = LogEntry::event(EventId::new(10).unwrap()).raw();
+        let entries = vec![(event, None); 300];
+        let (buf, len) = encode_all(&entries);
+        // The event, a full repeat token, then another for the rest
+        assert_eq!(&buf[..len], &[80, 5, 255, 5, 44]);
+        assert_eq!(decode_all(&buf[..len]).unwrap(), words(&entries));
+    }
+
+    #[test]
+    fn entries_that_dont_fit_are_not_written() {
+        let event = LogEntry::event(EventId::new(10).unwrap()).raw();
+        let mut buf = [0u8; 2];
+        let mut encoder = LogEncoder::new();
+        assert_eq!(encoder.encode(&mut buf, event, None), Ok(()));
+        assert_eq!(
+            encoder.encode(&mut buf, 0x4000_0000, Some(1)),
+            Err(MissingBytes)
+        );
+        assert_eq!(encoder.len(), 1);
+        assert_eq!(encoder.encode(&mut buf, event, None), Err(MissingBytes));
+        assert_eq!(encoder.len(), 1);
+        assert_eq!(decode_all(&buf[..1]).unwrap(), vec![event]);
+    }
+
+    #[test]
+    fn invalid_tokens() {
+        // Truncated varint
+        assert_eq!(
+            decode_all(&[0x80]),
+            Err(ReportWireError::InvalidCompressedLog)
+        );
+        // Missing second word
+        assert_eq!(
+            decode_all(&[EVENT_WITH_PAYLOAD as u8]),
+            Err(ReportWireError::InvalidCompressedLog)
+        );
+        // Repeat with nothing to repeat
+        assert_eq!(
+            decode_all(&[REPEAT as u8, 1]),
+            Err(ReportWireError::InvalidCompressedLog)
+        );
+        // Unknown tag
+        assert_eq!(decode_all(&[7]), Err(ReportWireError::InvalidCompressedLog));
+        // Event wider than a word
+        assert_eq!(
+            decode_all(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x01]),
+            Err(ReportWireError::InvalidCompressedLog)
+        );
+        // Varint overflowing a u64
+        assert_eq!(
+            decode_all(&[0xFF; 11]),
+            Err(ReportWireError::InvalidCompressedLog)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_arbitrary_entries(
+            entries in proptest::collection::vec(
+                (any::<u32>(), proptest::option::of(any::<u32>())),
+                0..64,
+            ),
+        ) {
+            let (buf, len) = encode_all(&entries);
+            let n_words: usize = entries.iter().map(|e| 1 + e.1.is_some() as usize).sum();
+            prop_assert!(len <= n_words * MAX_ENCODED_WORD_LEN);
+            prop_assert_eq!(decode_all(&buf[..len]).unwrap(), words(&entries));
+        }
+    }
+}
@@ -0,0 +1,631 @@
+//! A wire protocol for control messages sent from a collector to a
+//! Modality probe.
+//!
+//! A control message is addressed to a single probe and carries a
+//! single command, followed by the command's arguments (if any).
+//! Messages for a probe with a report key end with a counter and a
+//! SipHash-2-4 MAC of the header, arguments and counter under that
+//! key. The probe rejects any message without a matching MAC, and any
+//! message whose counter isn't greater than that of the last message
+//! it applied, so a recorded message can't be replayed. The probe
+//! forgets the counter when it restarts.
+
+use crate::{
+    event_mask::{EventMask, EVENT_MASK_WORDS},
+    wire::{le_bytes, siphash, MissingBytes},
+    ProbeId,
+};
+use core::mem;
+
+/// Everything that can go wrong when attempting to interpret a
+/// control message from the wire representation
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ControlMessageWireError {
+    /// The fingerprint didn't match expectations
+    InvalidFingerprint,
+    /// There weren't enough bytes for a full header
+    MissingHeader,
+    /// There weren't enough payload bytes for the command's
+    /// arguments
+    IncompletePayload,
+    /// The probe id didn't follow the rules for being
+    /// a valid Modality probe-specifying ProbeId
+    InvalidProbeId(u32),
+    /// The command code isn't one that's understood
+    UnknownCommand(u8),
+    /// The message's MAC was missing or didn't match its contents
+    /// under the key
+    MacMismatch,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ControlMessageWireError {}
+
+impl core::fmt::Display for ControlMessageWireError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ControlMessageWireError::InvalidFingerprint => f.write_str("Invalid Fingerprint"),
+            ControlMessageWireError::MissingHeader => f.write_str("Missing Header"),
+            ControlMessageWireError::IncompletePayload => f.write_str("Incomplete Payload"),
+            ControlMessageWireError::InvalidProbeId(x) => {
+                write!(f, "Invalid Probe Id: 0x{:x}", x)
+            }
+            ControlMessageWireError::UnknownCommand(x) => write!(f, "Unknown Command: {}", x),
+            ControlMessageWireError::MacMismatch => f.write_str("MAC Mismatch"),
+        }
+    }
+}
+
+/// A command for a probe
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ControlCommand {
+    /// Report as soon as possible. The probe can't send reports by
+    /// itself, so this is left to whatever is driving it.
+    RequestReport,
+    /// Set the probe's overwrite priority level
+    SetOverwritePriority(u32),
+    /// Replace the set of events the probe does not record
+    SetDisabledEvents(EventMask),
+    /// Reset the count of log entries the probe has overwritten
+    /// before they could be reported
+    ResetMissedLogEntryCount,
+    /// Acknowledge receipt of the report with the given sequence
+    /// number, for probes using acknowledged reporting
+    AcknowledgeReport(u64),
+}
+
+impl ControlCommand {
+    const REQUEST_REPORT: u8 = 1;
+    const SET_OVERWRITE_PRIORITY: u8 = 2;
+    const SET_DISABLED_EVENTS: u8 = 3;
+    const RESET_MISSED_LOG_ENTRY_COUNT: u8 = 4;
+    const ACKNOWLEDGE_REPORT: u8 = 5;
+
+    /// The command code identifying this command on the wire
+    pub fn code(&self) -> u8 {
+        match self {
+            ControlCommand::RequestReport => Self::REQUEST_REPORT,
+            ControlCommand::SetOverwritePriority(_) => Self::SET_OVERWRITE_PRIORITY,
+            ControlCommand::SetDisabledEvents(_) => Self::SET_DISABLED_EVENTS,
+            ControlCommand::ResetMissedLogEntryCount => Self::RESET_MISSED_LOG_ENTRY_COUNT,
+            ControlCommand::AcknowledgeReport(_) => Self::ACKNOWLEDGE_REPORT,
+        }
+    }
+
+    /// The length of the arguments of the command with the given
+    /// code, or `None` if the code is unknown
+    fn payload_len_for_code(code: u8) -> Option<usize> {
+        match code {
+            Self::REQUEST_REPORT | Self::RESET_MISSED_LOG_ENTRY_COUNT => Some(0),
+            Self::SET_OVERWRITE_PRIORITY => Some(mem::size_of::<u32>()),
+            Self::SET_DISABLED_EVENTS => Some(EVENT_MASK_WORDS * mem::size_of::<u32>()),
+            Self::ACKNOWLEDGE_REPORT => Some(mem::size_of::<u64>()),
+            _ => None,
+        }
+    }
+
+    /// The length of this command's arguments on the wire
+    pub fn payload_len(&self) -> usize {
+        // Every constructible command has a known code
+        Self::payload_len_for_code(self.code()).unwrap_or(0)
+    }
+
+    /// Write a control message addressed to `probe_id` carrying this
+    /// command into `destination`, returning the number of bytes
+    /// written.
+    pub fn write_message(
+        &self,
+        probe_id: ProbeId,
+        destination: &mut [u8],
+    ) -> Result<usize, MissingBytes> {
+        let len = WireControlMessage::<&[u8]>::buffer_len(self);
+        if destination.len() < len {
+            return Err(MissingBytes);
+        }
+        let mut msg = WireControlMessage::new_unchecked(&mut destination[..len]);
+        msg.set_fingerprint();
+        msg.set_probe_id(probe_id);
+        msg.set_command(self);
+        Ok(len)
+    }
+
+    /// Write a control message addressed to `probe_id` carrying this
+    /// command into `destination`, followed by `counter` and the MAC
+    /// under `key`, returning the number of bytes written.
+    ///
+    /// `counter` must be greater than that of any message sent to the
+    /// probe before, or the probe will reject the message.
+    pub fn write_authenticated_message(
+        &self,
+        probe_id: ProbeId,
+        key: &siphash::Key,
+        counter: u64,
+        destination: &mut [u8],
+    ) -> Result<usize, MissingBytes> {
+        let len = WireControlMessage::<&[u8]>::authenticated_buffer_len(self);
+        if destination.len() < len {
+            return Err(MissingBytes);
+        }
+        self.write_message(probe_id, destination)?;
+        let mut msg = WireControlMessage::new_unchecked(&mut destination[..len]);
+        msg.set_counter(counter);
+        msg.set_mac(key);
+        Ok(len)
+    }
+}
+
+/// A read/write wrapper around a control message buffer
+#[derive(Debug, Clone)]
+pub struct WireControlMessage<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    type Field = ::core::ops::Range<usize>;
+    type Rest = ::core::ops::RangeFrom<usize>;
+
+    /// A magical (constant) value used as a hint about the data
+    /// encoded in this pile of bytes.
+    pub const FINGERPRINT: Field = 0..4;
+    /// A u32 representing the probe_id of the Modality probe instance
+    /// this message is addressed to.
+    pub const PROBE_ID: Field = 4..8;
+    /// The command code.
+    pub const COMMAND: usize = 8;
+    /// The command's arguments, if any.
+    pub const PAYLOAD: Rest = 9..;
+}
+
+/// The length of the counter following an authenticated message's
+/// arguments
+const COUNTER_LEN: usize = mem::size_of::<u64>();
+
+/// The length of the MAC following an authenticated message's counter
+const MAC_LEN: usize = mem::size_of::<u64>();
+
+impl<T: AsRef<[u8]>> WireControlMessage<T> {
+    /// Control message fingerprint (MCTL)
+    pub const FINGERPRINT: u32 = 0x4D_43_54_4C;
+
+    /// Construct a control message from a byte buffer
+    pub fn new_unchecked(buffer: T) -> WireControlMessage<T> {
+        WireControlMessage { buffer }
+    }
+
+    /// Construct a control message from a byte buffer, with checks.
+    ///
+    /// A combination of:
+    /// * [new_unchecked](struct.WireControlMessage.html#method.new_unchecked)
+    /// * [check_len](struct.WireControlMessage.html#method.check_len)
+    /// * [check_fingerprint](struct.WireControlMessage.html#method.check_fingerprint)
+    /// * [check_payload_len](struct.WireControlMessage.html#method.check_payload_len)
+    pub fn new(buffer: T) -> Result<Self, ControlMessageWireError> {
+        let m = Self::new_unchecked(buffer);
+        m.check_len()?;
+        m.check_fingerprint()?;
+        m.check_payload_len()?;
+        Ok(m)
+    }
+
+    /// Ensure that no header accessor method will panic if called.
+    ///
+    /// Returns `Err(ControlMessageWireError::MissingHeader)` if the
+    /// buffer is too short.
+    pub fn check_len(&self) -> Result<(), ControlMessageWireError> {
+        let len = self.buffer.as_ref().len();
+        if len < field::PAYLOAD.start {
+            Err(ControlMessageWireError::MissingHeader)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check for the expected fingerprint value.
+    ///
+    /// Returns `Err(ControlMessageWireError::InvalidFingerprint)` if
+    /// the fingerprint does not match.
+    pub fn check_fingerprint(&self) -> Result<(), ControlMessageWireError> {
+        if self.fingerprint() != Self::FINGERPRINT {
+            Err(ControlMessageWireError::InvalidFingerprint)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ensure the payload holds the arguments of the command.
+    ///
+    /// Returns `Err(ControlMessageWireError::UnknownCommand)` if the
+    /// command code is unknown, or
+    /// `Err(ControlMessageWireError::IncompletePayload)` if the
+    /// buffer is too short.
+    pub fn check_payload_len(&self) -> Result<(), ControlMessageWireError> {
+        let code = self.command_code();
+        let payload_len = ControlCommand::payload_len_for_code(code)
+            .ok_or(ControlMessageWireError::UnknownCommand(code))?;
+        if self.buffer.as_ref().len() < field::PAYLOAD.start + payload_len {
+            Err(ControlMessageWireError::IncompletePayload)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consumes the message, returning the underlying buffer
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the length of a control message header
+    pub fn header_len() -> usize {
+        field::PAYLOAD.start
+    }
+
+    /// Return the length of a buffer required to hold a control
+    /// message carrying `command`
+    pub fn buffer_len(command: &ControlCommand) -> usize {
+        field::PAYLOAD.start + command.payload_len()
+    }
+
+    /// Return the length of a buffer required to hold a control
+    /// message carrying `command`, followed by its counter and MAC
+    pub fn authenticated_buffer_len(command: &ControlCommand) -> usize {
+        Self::buffer_len(command) + COUNTER_LEN + MAC_LEN
+    }
+
+    /// Ensure the message's MAC matches its header, arguments and
+    /// counter under `key`.
+    ///
+    /// Returns `Err(ControlMessageWireError::MacMismatch)` if the
+    /// message has no MAC or its MAC does not match.
+    pub fn check_mac(&self, key: &siphash::Key) -> Result<(), ControlMessageWireError> {
+        match self.mac() {
+            Some(mac) if mac == self.compute_mac(key) => Ok(()),
+            _ => Err(ControlMessageWireError::MacMismatch),
+        }
+    }
+
+    /// Return the `fingerprint` field
+    #[inline]
+    pub fn fingerprint(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        le_bytes::read_u32(&data[field::FINGERPRINT])
+    }
+
+    /// Return the `probe_id` field
+    #[inline]
+    pub fn probe_id(&self) -> Result<ProbeId, ControlMessageWireError> {
+        let data = self.buffer.as_ref();
+        let raw_probe_id = le_bytes::read_u32(&data[field::PROBE_ID]);
+        match ProbeId::new(raw_probe_id) {
+            Some(id) => Ok(id),
+            None => Err(ControlMessageWireError::InvalidProbeId(raw_probe_id)),
+        }
+    }
+
+    /// Return the raw `command` field
+    #[inline]
+    pub fn command_code(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::COMMAND]
+    }
+
+    /// Return the command, along with its arguments
+    ///
+    /// Assumes [check_payload_len](struct.WireControlMessage.html#method.check_payload_len)
+    /// has passed, as it does for messages constructed with `new`.
+    pub fn command(&self) -> Result<ControlCommand, ControlMessageWireError> {
+        let data = self.buffer.as_ref();
+        let payload = &data[field::PAYLOAD];
+        match self.command_code() {
+            ControlCommand::REQUEST_REPORT => Ok(ControlCommand::RequestReport),
+            ControlCommand::SET_OVERWRITE_PRIORITY => Ok(ControlCommand::SetOverwritePriority(
+                le_bytes::read_u32(payload),
+            )),
+            ControlCommand::SET_DISABLED_EVENTS => {
+                let mut words = [0; EVENT_MASK_WORDS];
+                for (word, bytes) in words
+                    .iter_mut()
+                    .zip(payload.chunks_exact(mem::size_of::<u32>()))
+                {
+                    *word = le_bytes::read_u32(bytes);
+                }
+                Ok(ControlCommand::SetDisabledEvents(EventMask::from_words(
+                    words,
+                )))
+            }
+            ControlCommand::RESET_MISSED_LOG_ENTRY_COUNT => {
+                Ok(ControlCommand::ResetMissedLogEntryCount)
+            }
+            ControlCommand::ACKNOWLEDGE_REPORT => Ok(ControlCommand::AcknowledgeReport(
+                le_bytes::read_u64(payload),
+            )),
+            code => Err(ControlMessageWireError::UnknownCommand(code)),
+        }
+    }
+
+    /// The end of the command's arguments. Assumes the command code
+    /// is known.
+    #[inline]
+    fn payload_end(&self) -> usize {
+        let payload_len = ControlCommand::payload_len_for_code(self.command_code()).unwrap_or(0);
+        field::PAYLOAD.start + payload_len
+    }
+
+    /// Whether the message is long enough to be followed by a counter
+    /// and MAC
+    #[inline]
+    fn is_authenticated(&self) -> bool {
+        self.buffer.as_ref().len() >= self.payload_end() + COUNTER_LEN + MAC_LEN
+    }
+
+    /// Return the counter following the command's arguments, if the
+    /// message is authenticated
+    ///
+    /// Assumes [check_payload_len](struct.WireControlMessage.html#method.check_payload_len)
+    /// has passed, as it does for messages constructed with `new`.
+    #[inline]
+    pub fn counter(&self) -> Option<u64> {
+        if self.is_authenticated() {
+            let data = self.buffer.as_ref();
+            let start = self.payload_end();
+            Some(le_bytes::read_u64(&data[start..start + COUNTER_LEN]))
+        } else {
+            None
+        }
+    }
+
+    /// Return the MAC following the counter, if the message is
+    /// authenticated
+    ///
+    /// Assumes [check_payload_len](struct.WireControlMessage.html#method.check_payload_len)
+    /// has passed, as it does for messages constructed with `new`.
+    #[inline]
+    pub fn mac(&self) -> Option<u64> {
+        if self.is_authenticated() {
+            let data = self.buffer.as_ref();
+            let start = self.payload_end() + COUNTER_LEN;
+            Some(le_bytes::read_u64(&data[start..start + MAC_LEN]))
+        } else {
+            None
+        }
+    }
+
+    /// Compute the SipHash-2-4 MAC of the message's header, arguments
+    /// and counter under `key`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is shorter than
+    /// [authenticated_buffer_len](struct.WireControlMessage.html#method.authenticated_buffer_len).
+    #[inline]
+    pub fn compute_mac(&self, key: &siphash::Key) -> u64 {
+        let data = self.buffer.as_ref();
+        siphash::mac(key, &data[..self.payload_end() + COUNTER_LEN])
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> WireControlMessage<T> {
+    /// Set the `fingerprint` field to
+    /// [Self::FINGERPRINT](struct.WireControlMessage.html#associatedconstant.FINGERPRINT)
+    #[inline]
+    pub fn set_fingerprint(&mut self) {
+        let data = self.buffer.as_mut();
+        le_bytes::write_u32(&mut data[field::FINGERPRINT], Self::FINGERPRINT);
+    }
+
+    /// Set the `probe_id` field
+    #[inline]
+    pub fn set_probe_id(&mut self, value: ProbeId) {
+        let data = self.buffer.as_mut();
+        le_bytes::write_u32(&mut data[field::PROBE_ID], value.get_raw());
+    }
+
+    /// Set the `command` field and the command's arguments
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is shorter than
+    /// [buffer_len](struct.WireControlMessage.html#method.buffer_len).
+    pub fn set_command(&mut self, command: &ControlCommand) {
+        let data = self.buffer.as_mut();
+        data[field::COMMAND] = command.code();
+        let payload = &mut data[field::PAYLOAD];
+        match command {
+            ControlCommand::RequestReport | ControlCommand::ResetMissedLogEntryCount => (),
+            ControlCommand::SetOverwritePriority(level) => le_bytes::write_u32(payload, *level),
+            ControlCommand::AcknowledgeReport(seq_num) => le_bytes::write_u64(payload, *seq_num),
+            ControlCommand::SetDisabledEvents(mask) => {
+                for (word, bytes) in mask
+                    .words()
+                    .iter()
+                    .zip(payload.chunks_exact_mut(mem::size_of::<u32>()))
+                {
+                    le_bytes::write_u32(bytes, *word);
+                }
+            }
+        }
+    }
+
+    /// Write `counter` after the arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is shorter than
+    /// [authenticated_buffer_len](struct.WireControlMessage.html#method.authenticated_buffer_len).
+    pub fn set_counter(&mut self, counter: u64) {
+        let start = self.payload_end();
+        let data = self.buffer.as_mut();
+        le_bytes::write_u64(&mut data[start..start + COUNTER_LEN], counter);
+    }
+
+    /// Write the MAC of the header, arguments and counter under `key`
+    /// after the counter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is shorter than
+    /// [authenticated_buffer_len](struct.WireControlMessage.html#method.authenticated_buffer_len).
+    pub fn set_mac(&mut self, key: &siphash::Key) {
+        let mac = self.compute_mac(key);
+        let start = self.payload_end() + COUNTER_LEN;
+        let data = self.buffer.as_mut();
+        le_bytes::write_u64(&mut data[start..start + MAC_LEN], mac);
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for WireControlMessage<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventId;
+
+    #[rustfmt::skip]
+    static MSG_BYTES: [u8; 13] = [
+        // fingerprint
+        0x4C, 0x54, 0x43, 0x4D,
+        // probe_id: 1
+        0x01, 0x00, 0x00, 0x00,
+        // command: set overwrite priority
+        0x02,
+        // priority: 3
+        0x03, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn header_len() {
+        assert_eq!(WireControlMessage::<&[u8]>::header_len(), 9);
+        assert_eq!(
+            WireControlMessage::<&[u8]>::buffer_len(&ControlCommand::RequestReport),
+            9
+        );
+        assert_eq!(
+            WireControlMessage::<&[u8]>::buffer_len(&ControlCommand::SetDisabledEvents(
+                EventMask::EMPTY
+            )),
+            9 + 32
+        );
+    }
+
+    #[test]
+    fn construct() {
+        let mut bytes = [0xFF; 13];
+        let len = ControlCommand::SetOverwritePriority(3)
+            .write_message(ProbeId::new(1).unwrap(), &mut bytes)
+            .unwrap();
+        assert_eq!(len, 13);
+        assert_eq!(&bytes[..], &MSG_BYTES[..]);
+    }
+
+    #[test]
+    fn deconstruct() {
+        let m = WireControlMessage::new(&MSG_BYTES[..]).unwrap();
+        assert_eq!(m.fingerprint(), WireControlMessage::<&[u8]>::FINGERPRINT);
+        assert_eq!(m.probe_id(), Ok(ProbeId::new(1).unwrap()));
+        assert_eq!(m.command(), Ok(ControlCommand::SetOverwritePriority(3)));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mask = EventMask::EMPTY
+            .with_event(EventId::new(2).unwrap())
+            .with_event(EventId::new(200).unwrap());
+        for command in [
+            ControlCommand::RequestReport,
+            ControlCommand::SetOverwritePriority(u32::MAX),
+            ControlCommand::SetDisabledEvents(mask),
+            ControlCommand::ResetMissedLogEntryCount,
+            ControlCommand::AcknowledgeReport(0x0102_0304_0506_0708),
+        ]
+        .iter()
+        {
+            let mut bytes = [0u8; 64];
+            let len = command
+                .write_message(ProbeId::new(7).unwrap(), &mut bytes)
+                .unwrap();
+            let m = WireControlMessage::new(&bytes[..len]).unwrap();
+            assert_eq!(m.probe_id(), Ok(ProbeId::new(7).unwrap()));
+            assert_eq!(m.command(), Ok(*command));
+        }
+    }
+
+    #[test]
+    fn invalid_messages() {
+        assert_eq!(
+            WireControlMessage::new(&MSG_BYTES[..8]).unwrap_err(),
+            ControlMessageWireError::MissingHeader
+        );
+        assert_eq!(
+            WireControlMessage::new(&MSG_BYTES[..12]).unwrap_err(),
+            ControlMessageWireError::IncompletePayload
+        );
+
+        let mut bytes = MSG_BYTES;
+        bytes[8] = 0xEE;
+        assert_eq!(
+            WireControlMessage::new(&bytes[..]).unwrap_err(),
+            ControlMessageWireError::UnknownCommand(0xEE)
+        );
+
+        bytes[0] = 0;
+        assert_eq!(
+            WireControlMessage::new(&bytes[..]).unwrap_err(),
+            ControlMessageWireError::InvalidFingerprint
+        );
+
+        let mut too_small = [0u8; 12];
+        assert_eq!(
+            ControlCommand::SetOverwritePriority(3)
+                .write_message(ProbeId::new(1).unwrap(), &mut too_small),
+            Err(MissingBytes)
+        );
+    }
+
+    #[test]
+    fn authenticated_round_trip() {
+        let key = [3; siphash::KEY_LEN];
+        let mut bytes = [0u8; 64];
+        let len = ControlCommand::AcknowledgeReport(9)
+            .write_authenticated_message(ProbeId::new(7).unwrap(), &key, 42, &mut bytes)
+            .unwrap();
+        assert_eq!(len, 9 + 8 + 8 + 8);
+        let m = WireControlMessage::new(&bytes[..len]).unwrap();
+        assert_eq!(m.command(), Ok(ControlCommand::AcknowledgeReport(9)));
+        assert_eq!(m.counter(), Some(42));
+        assert_eq!(m.mac(), Some(siphash::mac(&key, &bytes[..25])));
+        assert_eq!(m.check_mac(&key), Ok(()));
+        assert_eq!(
+            m.check_mac(&[4; siphash::KEY_LEN]),
+            Err(ControlMessageWireError::MacMismatch)
+        );
+
+        // The MAC covers the counter and the arguments
+        bytes[17] ^= 1;
+        let m = WireControlMessage::new(&bytes[..len]).unwrap();
+        assert_eq!(m.check_mac(&key), Err(ControlMessageWireError::MacMismatch));
+        bytes[17] ^= 1;
+        bytes[9] ^= 1;
+        let m = WireControlMessage::new(&bytes[..len]).unwrap();
+        assert_eq!(m.check_mac(&key), Err(ControlMessageWireError::MacMismatch));
+
+        // And is required
+        let m = WireControlMessage::new(&MSG_BYTES[..]).unwrap();
+        assert_eq!(m.counter(), None);
+        assert_eq!(m.mac(), None);
+        assert_eq!(m.check_mac(&key), Err(ControlMessageWireError::MacMismatch));
+
+        let mut too_small = [0u8; 32];
+        assert_eq!(
+            ControlCommand::AcknowledgeReport(9).write_authenticated_message(
+                ProbeId::new(7).unwrap(),
+                &key,
+                42,
+                &mut too_small
+            ),
+            Err(MissingBytes)
+        );
+    }
+}
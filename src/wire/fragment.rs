@@ -0,0 +1,297 @@
+//! A wire protocol for splitting a report across several small
+//! frames, for links (CAN, BLE, 802.15.4) whose frames are too
+//! small to carry a whole report.
+//!
+//! Each fragment carries a slice of the report's wire bytes, prefixed
+//! by a 3 byte header:
+//! * The low byte of the report's sequence number, which tells the
+//!   fragments of consecutive reports apart.
+//! * The fragment's index within the report, with the top bit set on
+//!   the last fragment.
+//!
+//! The report's own header, with its fingerprint and probe id, starts
+//! off the first fragment. A receiver that misses any fragment of a
+//! report drops only that report.
+
+use crate::wire::{le_bytes, report::WireReport, MissingBytes};
+
+/// The largest number of fragments a report can be split into
+pub const MAX_FRAGMENTS: usize = 0x8000;
+
+/// The bit of the `index` field that marks the last fragment of a report
+const LAST_FRAGMENT_BIT: u16 = 0x8000;
+
+/// A read/write wrapper around a report fragment buffer
+#[derive(Debug, Clone)]
+pub struct WireReportFragment<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    type Rest = ::core::ops::RangeFrom<usize>;
+    type Field = ::core::ops::Range<usize>;
+
+    /// The low byte of the sequence number of the report this is a
+    /// fragment of.
+    pub const REPORT_TAG: usize = 0;
+    /// A u16 holding the fragment's index within the report in its
+    /// low 15 bits. The top bit is set on the report's last fragment.
+    pub const INDEX: Field = 1..3;
+    /// A slice of the report's wire bytes.
+    pub const PAYLOAD: Rest = 3..;
+}
+
+impl<T: AsRef<[u8]>> WireReportFragment<T> {
+    /// Construct a fragment from a byte buffer
+    pub fn new_unchecked(buffer: T) -> WireReportFragment<T> {
+        WireReportFragment { buffer }
+    }
+
+    /// Construct a fragment from a byte buffer, with checks.
+    ///
+    /// A combination of:
+    /// * [new_unchecked](struct.WireReportFragment.html#method.new_unchecked)
+    /// * [check_len](struct.WireReportFragment.html#method.check_len)
+    pub fn new(buffer: T) -> Result<WireReportFragment<T>, MissingBytes> {
+        let f = Self::new_unchecked(buffer);
+        f.check_len()?;
+        Ok(f)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    ///
+    /// Returns `Err(MissingBytes)` if the buffer is too short to hold
+    /// a header.
+    pub fn check_len(&self) -> Result<(), MissingBytes> {
+        if self.buffer.as_ref().len() < field::PAYLOAD.start {
+            Err(MissingBytes)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Consumes the fragment, returning the underlying buffer
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the length of a fragment header
+    pub fn header_len() -> usize {
+        field::PAYLOAD.start
+    }
+
+    /// Return the `report_tag` field
+    #[inline]
+    pub fn report_tag(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        data[field::REPORT_TAG]
+    }
+
+    /// Return the fragment's index within its report
+    #[inline]
+    pub fn index(&self) -> u16 {
+        let data = self.buffer.as_ref();
+        le_bytes::read_u16(&data[field::INDEX]) & !LAST_FRAGMENT_BIT
+    }
+
+    /// Is this the last fragment of its report?
+    #[inline]
+    pub fn is_last(&self) -> bool {
+        let data = self.buffer.as_ref();
+        le_bytes::read_u16(&data[field::INDEX]) & LAST_FRAGMENT_BIT != 0
+    }
+
+    /// Return the fragment's slice of the report
+    #[inline]
+    pub fn payload(&self) -> &[u8] {
+        let data = self.buffer.as_ref();
+        &data[field::PAYLOAD]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> WireReportFragment<T> {
+    /// Set the `report_tag` field
+    #[inline]
+    pub fn set_report_tag(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::REPORT_TAG] = value;
+    }
+
+    /// Set the `index` field
+    ///
+    /// Only the low 15 bits of `index` are kept.
+    #[inline]
+    pub fn set_index(&mut self, index: u16, is_last: bool) {
+        let data = self.buffer.as_mut();
+        let mut value = index & !LAST_FRAGMENT_BIT;
+        if is_last {
+            value |= LAST_FRAGMENT_BIT;
+        }
+        le_bytes::write_u16(&mut data[field::INDEX], value);
+    }
+
+    /// Return a mutable pointer to the payload
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let data = self.buffer.as_mut();
+        &mut data[field::PAYLOAD]
+    }
+}
+
+impl<T: AsRef<[u8]>> AsRef<[u8]> for WireReportFragment<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+}
+
+/// Splits a wire report into fragments of a bounded size
+#[derive(Debug, Clone)]
+pub struct ReportFragments<'a> {
+    report: &'a [u8],
+    report_tag: u8,
+    chunk_len: usize,
+}
+
+impl<'a> ReportFragments<'a> {
+    /// Prepare to split `report`, the wire bytes of a report, into
+    /// fragments no longer than `max_fragment_len`.
+    ///
+    /// Returns `None` if `report` is too short to hold a report
+    /// header, if `max_fragment_len` leaves no room for any of the
+    /// report after the fragment header, or if the report would need
+    /// more than `MAX_FRAGMENTS` fragments.
+    pub fn new(report: &'a [u8], max_fragment_len: usize) -> Option<Self> {
+        let wire_report = WireReport::new_unchecked(report);
+        wire_report.check_len().ok()?;
+        let chunk_len = max_fragment_len.checked_sub(field::PAYLOAD.start)?;
+        if chunk_len == 0 {
+            return None;
+        }
+        let fragments = ReportFragments {
+            report,
+            report_tag: wire_report.seq_num() as u8,
+            chunk_len,
+        };
+        if fragments.fragment_count() > MAX_FRAGMENTS {
+            None
+        } else {
+            Some(fragments)
+        }
+    }
+
+    /// The number of fragments the report is split into
+    pub fn fragment_count(&self) -> usize {
+        self.report.chunks(self.chunk_len).count()
+    }
+
+    /// Write the fragment at `index` into `destination`, returning the
+    /// number of bytes written.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than `fragment_count()`.
+    pub fn write_fragment(
+        &self,
+        index: usize,
+        destination: &mut [u8],
+    ) -> Result<usize, MissingBytes> {
+        let count = self.fragment_count();
+        let chunk = self
+            .report
+            .chunks(self.chunk_len)
+            .nth(index)
+            .expect("Fragment index out of range");
+        let len = field::PAYLOAD.start + chunk.len();
+        if destination.len() < len {
+            return Err(MissingBytes);
+        }
+        let mut fragment = WireReportFragment::new_unchecked(&mut destination[..len]);
+        fragment.set_report_tag(self.report_tag);
+        fragment.set_index(index as u16, index + 1 == count);
+        fragment.payload_mut().copy_from_slice(chunk);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    static FRAGMENT_BYTES: [u8; 6] = [
+        // report_tag
+        0x07,
+        // index: 2, last
+        0x02, 0x80,
+        // payload
+        0x01, 0x02, 0x03,
+    ];
+
+    #[test]
+    fn construct() {
+        let mut bytes = [0xFF; 6];
+        let mut f = WireReportFragment::new_unchecked(&mut bytes[..]);
+        f.set_report_tag(7);
+        f.set_index(2, true);
+        f.payload_mut().copy_from_slice(&[1, 2, 3]);
+        assert_eq!(&f.into_inner()[..], &FRAGMENT_BYTES[..]);
+    }
+
+    #[test]
+    fn deconstruct() {
+        let f = WireReportFragment::new(&FRAGMENT_BYTES[..]).unwrap();
+        assert_eq!(f.report_tag(), 7);
+        assert_eq!(f.index(), 2);
+        assert!(f.is_last());
+        assert_eq!(f.payload(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn missing_bytes() {
+        let bytes = [0xFF; 2];
+        let f = WireReportFragment::new(&bytes[..]);
+        assert_eq!(f.unwrap_err(), MissingBytes);
+    }
+
+    #[test]
+    fn split_report() {
+        let mut report = [0u8; 40];
+        for (i, b) in report.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        {
+            let mut r = WireReport::new_unchecked(&mut report[..]);
+            r.set_seq_num(0x1_02);
+        }
+        let fragments = ReportFragments::new(&report, 8).unwrap();
+        assert_eq!(fragments.fragment_count(), 8);
+
+        let mut reassembled = [0u8; 40];
+        let mut reassembled_len = 0;
+        let mut buf = [0u8; 8];
+        for index in 0..fragments.fragment_count() {
+            let len = fragments.write_fragment(index, &mut buf).unwrap();
+            let f = WireReportFragment::new(&buf[..len]).unwrap();
+            assert_eq!(f.report_tag(), 0x02);
+            assert_eq!(usize::from(f.index()), index);
+            assert_eq!(f.is_last(), index == 7);
+            let payload = f.payload();
+            reassembled[reassembled_len..reassembled_len + payload.len()].copy_from_slice(payload);
+            reassembled_len += payload.len();
+        }
+        assert_eq!(reassembled_len, report.len());
+        assert_eq!(&reassembled[..], &report[..]);
+
+        assert_eq!(
+            fragments.write_fragment(0, &mut buf[..7]),
+            Err(MissingBytes)
+        );
+    }
+
+    #[test]
+    fn unsplittable_reports() {
+        let report = [0u8; 40];
+        assert!(ReportFragments::new(&report, 3).is_none());
+        assert!(ReportFragments::new(&report[..10], 8).is_none());
+    }
+}
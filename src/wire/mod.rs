@@ -1,11 +1,20 @@
 //! Wire protocols
 
-use crate::{MergeError, ProduceError};
+use crate::{ControlError, MergeError, ProduceError, ReportError, RestoreStateError};
 
 pub mod causal_snapshot;
+pub mod compact_snapshot;
+pub mod compression;
+pub mod control;
+pub mod fragment;
+pub mod probe_state;
 pub mod report;
 
 pub use causal_snapshot::*;
+pub use compact_snapshot::*;
+pub use control::*;
+pub use fragment::*;
+pub use probe_state::*;
 pub use report::*;
 
 impl From<MissingBytes> for ProduceError {
@@ -15,6 +24,13 @@ impl From<MissingBytes> for ProduceError {
     }
 }
 
+impl From<MissingBytes> for ReportError {
+    #[inline]
+    fn from(_: MissingBytes) -> Self {
+        ReportError::InsufficientDestinationSize
+    }
+}
+
 impl From<MissingBytes> for MergeError {
     #[inline]
     fn from(_: MissingBytes) -> Self {
@@ -34,7 +50,39 @@ impl From<CausalSnapshotWireError> for MergeError {
     fn from(e: CausalSnapshotWireError) -> Self {
         match e {
             CausalSnapshotWireError::MissingBytes => MergeError::InsufficientSourceSize,
-            CausalSnapshotWireError::InvalidProbeId(_) => MergeError::ExternalHistorySemantics,
+            CausalSnapshotWireError::InvalidProbeId(_)
+            | CausalSnapshotWireError::UnsupportedVersion(_) => {
+                MergeError::ExternalHistorySemantics
+            }
+        }
+    }
+}
+
+impl From<CompactSnapshotWireError> for MergeError {
+    #[inline]
+    fn from(e: CompactSnapshotWireError) -> Self {
+        match e {
+            CompactSnapshotWireError::MissingBytes => MergeError::InsufficientSourceSize,
+            CompactSnapshotWireError::InvalidHeader(_)
+            | CompactSnapshotWireError::InvalidProbeId(_) => MergeError::ExternalHistorySemantics,
+        }
+    }
+}
+
+impl From<ProbeStateWireError> for RestoreStateError {
+    #[inline]
+    fn from(_: ProbeStateWireError) -> Self {
+        RestoreStateError::InvalidState
+    }
+}
+
+impl From<ControlMessageWireError> for ControlError {
+    #[inline]
+    fn from(e: ControlMessageWireError) -> Self {
+        match e {
+            ControlMessageWireError::UnknownCommand(_) => ControlError::UnknownCommand,
+            ControlMessageWireError::MacMismatch => ControlError::MacMismatch,
+            _ => ControlError::InvalidMessage,
         }
     }
 }
@@ -132,11 +180,146 @@ pub mod le_bytes {
     }
 }
 
+/// CRC-32 (IEEE 802.3, as used by zlib and Ethernet) checksums.
+pub mod crc32 {
+    // A nibble at a time keeps the table small enough for
+    // flash-constrained targets
+    const TABLE: [u32; 16] = [
+        0x0000_0000,
+        0x1DB7_1064,
+        0x3B6E_20C8,
+        0x26D9_30AC,
+        0x76DC_4190,
+        0x6B6B_51F4,
+        0x4DB2_6158,
+        0x5005_713C,
+        0xEDB8_8320,
+        0xF00F_9344,
+        0xD6D6_A3E8,
+        0xCB61_B38C,
+        0x9B64_C2B0,
+        0x86D3_D2D4,
+        0xA00A_E278,
+        0xBDBD_F21C,
+    ];
+
+    /// Compute the CRC-32 of `bytes`.
+    #[inline]
+    pub fn checksum(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for b in bytes {
+            crc ^= u32::from(*b);
+            crc = (crc >> 4) ^ TABLE[(crc & 0xF) as usize];
+            crc = (crc >> 4) ^ TABLE[(crc & 0xF) as usize];
+        }
+        !crc
+    }
+}
+
+/// SipHash-2-4 message authentication codes.
+///
+/// SipHash is a keyed hash meant for short inputs, which makes it
+/// cheap enough to authenticate every report on small targets, with
+/// no allocation or tables.
+pub mod siphash {
+    use super::le_bytes;
+
+    /// The length of a key, in bytes
+    pub const KEY_LEN: usize = 16;
+
+    /// A 128-bit SipHash key
+    pub type Key = [u8; KEY_LEN];
+
+    struct State {
+        v0: u64,
+        v1: u64,
+        v2: u64,
+        v3: u64,
+    }
+
+    impl State {
+        #[inline]
+        fn round(&mut self) {
+            self.v0 = self.v0.wrapping_add(self.v1);
+            self.v1 = self.v1.rotate_left(13) ^ self.v0;
+            self.v0 = self.v0.rotate_left(32);
+            self.v2 = self.v2.wrapping_add(self.v3);
+            self.v3 = self.v3.rotate_left(16) ^ self.v2;
+            self.v0 = self.v0.wrapping_add(self.v3);
+            self.v3 = self.v3.rotate_left(21) ^ self.v0;
+            self.v2 = self.v2.wrapping_add(self.v1);
+            self.v1 = self.v1.rotate_left(17) ^ self.v2;
+            self.v2 = self.v2.rotate_left(32);
+        }
+
+        #[inline]
+        fn compress(&mut self, m: u64) {
+            self.v3 ^= m;
+            self.round();
+            self.round();
+            self.v0 ^= m;
+        }
+    }
+
+    /// Compute the SipHash-2-4 of `bytes` under `key`.
+    #[inline]
+    pub fn mac(key: &Key, bytes: &[u8]) -> u64 {
+        let k0 = le_bytes::read_u64(&key[..8]);
+        let k1 = le_bytes::read_u64(&key[8..]);
+        let mut s = State {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+        };
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            s.compress(le_bytes::read_u64(word));
+        }
+        // The last word holds the remaining bytes and the message
+        // length in its top byte
+        let rest = words.remainder();
+        let mut last = [0u8; 8];
+        last[..rest.len()].copy_from_slice(rest);
+        last[7] = bytes.len() as u8;
+        s.compress(u64::from_le_bytes(last));
+        s.v2 ^= 0xFF;
+        s.round();
+        s.round();
+        s.round();
+        s.round();
+        s.v0 ^ s.v1 ^ s.v2 ^ s.v3
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32::checksum(b""), 0);
+        assert_eq!(crc32::checksum(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn siphash_test_vectors() {
+        // From the SipHash paper's reference implementation
+        let mut key = [0u8; siphash::KEY_LEN];
+        for (i, b) in key.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        let mut msg = [0u8; 64];
+        for (i, b) in msg.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        assert_eq!(siphash::mac(&key, &msg[..0]), 0x726F_DB47_DD0E_0E31);
+        assert_eq!(siphash::mac(&key, &msg[..8]), 0x93F5_F579_9A93_2462);
+        assert_eq!(siphash::mac(&key, &msg[..15]), 0xA129_CA61_49BE_45E5);
+        assert_eq!(siphash::mac(&key, &msg[..63]), 0x958A_324C_EB06_4572);
+    }
+
     proptest! {
         #[test]
         fn le_u16(
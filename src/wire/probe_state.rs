@@ -0,0 +1,454 @@
+//! A wire format for a probe's state, saved so the probe can pick up
+//! where it left off after its memory is powered down, e.g. across a
+//! deep sleep.
+//!
+//! A saved state holds the probe's own clock, its frontier clocks,
+//! its report sequence number and missed log entry count, and
+//! optionally the log entries it hasn't reported yet. It ends with a
+//! CRC-32 of everything before it, since it's likely to sit in
+//! retention memory or flash for a while before being restored.
+//!
+//! The format is versioned, with version 0 being the only one so far.
+
+use crate::{
+    log::LogEntry,
+    wire::{crc32, le_bytes},
+    LogicalClock, ProbeId,
+};
+use core::mem;
+
+/// Everything that can go wrong when attempting to interpret a
+/// saved probe state from the wire representation
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ProbeStateWireError {
+    /// The fingerprint didn't match expectations
+    InvalidFingerprint,
+    /// There weren't enough bytes for a full header
+    MissingHeader,
+    /// There weren't enough payload bytes (based on
+    /// expectations from inspecting the header).
+    IncompletePayload,
+    /// The probe id didn't follow the rules for being
+    /// a valid Modality probe-specifying ProbeId
+    InvalidProbeId(u32),
+    /// The state's checksum didn't match its contents
+    ChecksumMismatch,
+    /// The state's format version is newer than this crate understands
+    UnsupportedVersion(u8),
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProbeStateWireError {}
+
+impl core::fmt::Display for ProbeStateWireError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProbeStateWireError::InvalidFingerprint => f.write_str("Invalid Fingerprint"),
+            ProbeStateWireError::MissingHeader => f.write_str("Missing Header"),
+            ProbeStateWireError::IncompletePayload => f.write_str("Incomplete Payload"),
+            ProbeStateWireError::InvalidProbeId(x) => write!(f, "Invalid Probe Id: 0x{:x}", x),
+            ProbeStateWireError::ChecksumMismatch => f.write_str("Checksum Mismatch"),
+            ProbeStateWireError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported Version: {}", v)
+            }
+        }
+    }
+}
+
+/// A read/write wrapper around a saved probe state buffer
+#[derive(Debug, Clone)]
+pub struct WireProbeState<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+mod field {
+    type Field = ::core::ops::Range<usize>;
+    type Rest = ::core::ops::RangeFrom<usize>;
+
+    /// A magical (constant) value used as a hint about the data
+    /// encoded in this pile of bytes.
+    pub const FINGERPRINT: Field = 0..4;
+    /// The format version.
+    pub const VERSION: usize = 4;
+    /// Reserved for flags, currently always zero.
+    pub const FLAGS: usize = 5;
+    /// The number of frontier clocks present in the payload.
+    pub const N_CLOCKS: Field = 6..8;
+    /// A u32 representing the probe_id of the probe whose state this is.
+    pub const PROBE_ID: Field = 8..12;
+    /// The packed logical clock word of the probe's own clock.
+    pub const CLOCK: Field = 12..16;
+    /// The number of events seen since the probe's clock last ticked.
+    pub const EVENT_COUNT: Field = 16..20;
+    /// The sequence number of the probe's next report.
+    pub const SEQ_NUM: Field = 20..28;
+    /// The number of log entries the probe overwrote before they
+    /// could be reported.
+    pub const MISSED_LOG_ENTRY_COUNT: Field = 28..32;
+    /// The number of log entries present in the payload.
+    pub const N_LOG_ENTRIES: Field = 32..36;
+    /// The payload, consists of (in order):
+    /// * Frontier clocks, as a probe id and a packed clock word each
+    /// * Log entries
+    /// * The checksum
+    pub const PAYLOAD: Rest = 36..;
+}
+
+/// The length of the checksum following the payload
+const CHECKSUM_LEN: usize = mem::size_of::<u32>();
+
+impl<T: AsRef<[u8]>> WireProbeState<T> {
+    /// Saved probe state fingerprint (MPST)
+    pub const FINGERPRINT: u32 = 0x4D_50_53_54;
+
+    /// The saved state format version written by this crate
+    pub const VERSION: u8 = 0;
+
+    /// Construct a saved state from a byte buffer
+    pub fn new_unchecked(buffer: T) -> WireProbeState<T> {
+        WireProbeState { buffer }
+    }
+
+    /// Construct a saved state from a byte buffer, with checks.
+    ///
+    /// A combination of:
+    /// * [new_unchecked](struct.WireProbeState.html#method.new_unchecked)
+    /// * [check_len](struct.WireProbeState.html#method.check_len)
+    /// * [check_fingerprint](struct.WireProbeState.html#method.check_fingerprint)
+    /// * [check_version](struct.WireProbeState.html#method.check_version)
+    /// * [check_payload_len](struct.WireProbeState.html#method.check_payload_len)
+    /// * [check_checksum](struct.WireProbeState.html#method.check_checksum)
+    /// * [check_probe_id](struct.WireProbeState.html#method.check_probe_id)
+    pub fn new(buffer: T) -> Result<Self, ProbeStateWireError> {
+        let s = Self::new_unchecked(buffer);
+        s.check_len()?;
+        s.check_fingerprint()?;
+        s.check_version()?;
+        s.check_payload_len()?;
+        s.check_checksum()?;
+        s.check_probe_id()?;
+        Ok(s)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    ///
+    /// Returns `Err(ProbeStateWireError::MissingHeader)` if the buffer
+    /// is too short.
+    pub fn check_len(&self) -> Result<(), ProbeStateWireError> {
+        if self.buffer.as_ref().len() < field::PAYLOAD.start {
+            Err(ProbeStateWireError::MissingHeader)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check for the expected fingerprint value.
+    ///
+    /// Returns `Err(ProbeStateWireError::InvalidFingerprint)` if the
+    /// fingerprint does not match.
+    pub fn check_fingerprint(&self) -> Result<(), ProbeStateWireError> {
+        if self.fingerprint() == Self::FINGERPRINT {
+            Ok(())
+        } else {
+            Err(ProbeStateWireError::InvalidFingerprint)
+        }
+    }
+
+    /// Check that the format version is one this crate understands.
+    ///
+    /// Returns `Err(ProbeStateWireError::UnsupportedVersion)` if the
+    /// version is newer than
+    /// [Self::VERSION](struct.WireProbeState.html#associatedconstant.VERSION).
+    pub fn check_version(&self) -> Result<(), ProbeStateWireError> {
+        let version = self.version();
+        if version > Self::VERSION {
+            Err(ProbeStateWireError::UnsupportedVersion(version))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ensure the buffer is big enough to hold the payload according
+    /// to the header fields `n_clocks` and `n_log_entries`, and the
+    /// checksum.
+    ///
+    /// Returns `Err(ProbeStateWireError::IncompletePayload)` if the
+    /// buffer is too short.
+    pub fn check_payload_len(&self) -> Result<(), ProbeStateWireError> {
+        if self.buffer.as_ref().len() < self.state_len() {
+            Err(ProbeStateWireError::IncompletePayload)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ensure the checksum matches the header and payload.
+    ///
+    /// Returns `Err(ProbeStateWireError::ChecksumMismatch)` if the
+    /// checksum does not match.
+    pub fn check_checksum(&self) -> Result<(), ProbeStateWireError> {
+        if self.checksum() == self.compute_checksum() {
+            Ok(())
+        } else {
+            Err(ProbeStateWireError::ChecksumMismatch)
+        }
+    }
+
+    /// Ensure the probe id is a valid one.
+    ///
+    /// Returns `Err(ProbeStateWireError::InvalidProbeId)` if it isn't.
+    pub fn check_probe_id(&self) -> Result<(), ProbeStateWireError> {
+        self.probe_id().map(|_| ())
+    }
+
+    /// Consumes the saved state, returning the underlying buffer
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+
+    /// Return the length of the header of a saved state
+    pub fn header_len() -> usize {
+        field::PAYLOAD.start
+    }
+
+    /// Return the length of a buffer required to hold a saved state
+    /// with a payload of `n_clocks` + `n_log_entries`
+    pub fn buffer_len(n_clocks: usize, n_log_entries: usize) -> usize {
+        Self::header_len()
+            + (n_clocks * mem::size_of::<LogicalClock>())
+            + (n_log_entries * mem::size_of::<LogEntry>())
+            + CHECKSUM_LEN
+    }
+
+    /// Return the length of the payload, not including the checksum
+    pub fn payload_len(&self) -> usize {
+        self.n_clocks() as usize * mem::size_of::<LogicalClock>()
+            + self.n_log_entries() as usize * mem::size_of::<LogEntry>()
+    }
+
+    /// Return the length of the whole saved state: header, payload
+    /// and checksum
+    pub fn state_len(&self) -> usize {
+        field::PAYLOAD.start + self.payload_len() + CHECKSUM_LEN
+    }
+
+    /// Return the `fingerprint` field
+    #[inline]
+    pub fn fingerprint(&self) -> u32 {
+        le_bytes::read_u32(&self.buffer.as_ref()[field::FINGERPRINT])
+    }
+
+    /// Return the format version
+    #[inline]
+    pub fn version(&self) -> u8 {
+        self.buffer.as_ref()[field::VERSION]
+    }
+
+    /// Return the `n_clocks` field
+    #[inline]
+    pub fn n_clocks(&self) -> u16 {
+        le_bytes::read_u16(&self.buffer.as_ref()[field::N_CLOCKS])
+    }
+
+    /// Return the `probe_id` field
+    #[inline]
+    pub fn probe_id(&self) -> Result<ProbeId, ProbeStateWireError> {
+        let raw = le_bytes::read_u32(&self.buffer.as_ref()[field::PROBE_ID]);
+        ProbeId::new(raw).ok_or(ProbeStateWireError::InvalidProbeId(raw))
+    }
+
+    /// Return the `clock` field
+    #[inline]
+    pub fn clock(&self) -> u32 {
+        le_bytes::read_u32(&self.buffer.as_ref()[field::CLOCK])
+    }
+
+    /// Return the `event_count` field
+    #[inline]
+    pub fn event_count(&self) -> u32 {
+        le_bytes::read_u32(&self.buffer.as_ref()[field::EVENT_COUNT])
+    }
+
+    /// Return the `seq_num` field
+    #[inline]
+    pub fn seq_num(&self) -> u64 {
+        le_bytes::read_u64(&self.buffer.as_ref()[field::SEQ_NUM])
+    }
+
+    /// Return the `missed_log_entry_count` field
+    #[inline]
+    pub fn missed_log_entry_count(&self) -> u32 {
+        le_bytes::read_u32(&self.buffer.as_ref()[field::MISSED_LOG_ENTRY_COUNT])
+    }
+
+    /// Return the `n_log_entries` field
+    #[inline]
+    pub fn n_log_entries(&self) -> u32 {
+        le_bytes::read_u32(&self.buffer.as_ref()[field::N_LOG_ENTRIES])
+    }
+
+    /// Return the frontier clocks, as pairs of a raw probe id and a
+    /// packed clock word
+    #[inline]
+    pub fn clocks(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let start = field::PAYLOAD.start;
+        let end = start + self.n_clocks() as usize * mem::size_of::<LogicalClock>();
+        self.buffer.as_ref()[start..end]
+            .chunks_exact(mem::size_of::<LogicalClock>())
+            .map(|c| (le_bytes::read_u32(&c[0..4]), le_bytes::read_u32(&c[4..8])))
+    }
+
+    /// Return the saved log entries
+    #[inline]
+    pub fn log_entries(&self) -> impl Iterator<Item = LogEntry> + '_ {
+        let start =
+            field::PAYLOAD.start + self.n_clocks() as usize * mem::size_of::<LogicalClock>();
+        let end = field::PAYLOAD.start + self.payload_len();
+        self.buffer.as_ref()[start..end]
+            .chunks_exact(mem::size_of::<LogEntry>())
+            .map(|w| unsafe { LogEntry::new_unchecked(le_bytes::read_u32(w)) })
+    }
+
+    /// Return the checksum following the payload
+    #[inline]
+    pub fn checksum(&self) -> u32 {
+        let start = field::PAYLOAD.start + self.payload_len();
+        le_bytes::read_u32(&self.buffer.as_ref()[start..start + CHECKSUM_LEN])
+    }
+
+    /// Compute the CRC-32 of the header and payload
+    #[inline]
+    pub fn compute_checksum(&self) -> u32 {
+        crc32::checksum(&self.buffer.as_ref()[..field::PAYLOAD.start + self.payload_len()])
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> WireProbeState<T> {
+    /// Set the `fingerprint`, `version` and `flags` fields for the
+    /// current format version
+    #[inline]
+    pub fn set_fingerprint_and_version(&mut self) {
+        let data = self.buffer.as_mut();
+        le_bytes::write_u32(&mut data[field::FINGERPRINT], Self::FINGERPRINT);
+        data[field::VERSION] = Self::VERSION;
+        data[field::FLAGS] = 0;
+    }
+
+    /// Set the `n_clocks` field
+    #[inline]
+    pub fn set_n_clocks(&mut self, value: u16) {
+        le_bytes::write_u16(&mut self.buffer.as_mut()[field::N_CLOCKS], value);
+    }
+
+    /// Set the `probe_id` field
+    #[inline]
+    pub fn set_probe_id(&mut self, value: ProbeId) {
+        le_bytes::write_u32(&mut self.buffer.as_mut()[field::PROBE_ID], value.get_raw());
+    }
+
+    /// Set the `clock` field
+    #[inline]
+    pub fn set_clock(&mut self, value: u32) {
+        le_bytes::write_u32(&mut self.buffer.as_mut()[field::CLOCK], value);
+    }
+
+    /// Set the `event_count` field
+    #[inline]
+    pub fn set_event_count(&mut self, value: u32) {
+        le_bytes::write_u32(&mut self.buffer.as_mut()[field::EVENT_COUNT], value);
+    }
+
+    /// Set the `seq_num` field
+    #[inline]
+    pub fn set_seq_num(&mut self, value: u64) {
+        le_bytes::write_u64(&mut self.buffer.as_mut()[field::SEQ_NUM], value);
+    }
+
+    /// Set the `missed_log_entry_count` field
+    #[inline]
+    pub fn set_missed_log_entry_count(&mut self, value: u32) {
+        le_bytes::write_u32(
+            &mut self.buffer.as_mut()[field::MISSED_LOG_ENTRY_COUNT],
+            value,
+        );
+    }
+
+    /// Set the `n_log_entries` field
+    #[inline]
+    pub fn set_n_log_entries(&mut self, value: u32) {
+        le_bytes::write_u32(&mut self.buffer.as_mut()[field::N_LOG_ENTRIES], value);
+    }
+
+    /// Return a mutable pointer to the payload
+    #[inline]
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer.as_mut()[field::PAYLOAD]
+    }
+
+    /// Compute the checksum of the header and payload and write it
+    /// after the payload. The `n_clocks` and `n_log_entries` fields
+    /// must be set first.
+    #[inline]
+    pub fn set_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        let start = field::PAYLOAD.start + self.payload_len();
+        le_bytes::write_u32(
+            &mut self.buffer.as_mut()[start..start + CHECKSUM_LEN],
+            checksum,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut buffer = [0u8; 64];
+        let len = WireProbeState::<&[u8]>::buffer_len(1, 3);
+        let mut s = WireProbeState::new_unchecked(&mut buffer[..len]);
+        s.set_fingerprint_and_version();
+        s.set_n_clocks(1);
+        s.set_probe_id(ProbeId::new(3).unwrap());
+        s.set_clock(0x0001_0002);
+        s.set_event_count(4);
+        s.set_seq_num(5);
+        s.set_missed_log_entry_count(6);
+        s.set_n_log_entries(3);
+        let payload = s.payload_mut();
+        le_bytes::write_u32(&mut payload[0..4], 3);
+        le_bytes::write_u32(&mut payload[4..8], 0x0001_0001);
+        for (i, w) in payload[8..20].chunks_exact_mut(4).enumerate() {
+            le_bytes::write_u32(w, 10 + i as u32);
+        }
+        s.set_checksum();
+
+        let s = WireProbeState::new(&buffer[..len]).unwrap();
+        assert_eq!(s.probe_id(), Ok(ProbeId::new(3).unwrap()));
+        assert_eq!(s.clock(), 0x0001_0002);
+        assert_eq!(s.event_count(), 4);
+        assert_eq!(s.seq_num(), 5);
+        assert_eq!(s.missed_log_entry_count(), 6);
+        assert_eq!(s.clocks().collect::<Vec<_>>(), vec![(3, 0x0001_0001)]);
+        assert_eq!(
+            s.log_entries().map(LogEntry::raw).collect::<Vec<_>>(),
+            vec![10, 11, 12]
+        );
+
+        buffer[len - CHECKSUM_LEN - 1] ^= 1;
+        assert_eq!(
+            WireProbeState::new(&buffer[..len]).unwrap_err(),
+            ProbeStateWireError::ChecksumMismatch
+        );
+        assert_eq!(
+            WireProbeState::new(&buffer[..len - 1]).unwrap_err(),
+            ProbeStateWireError::IncompletePayload
+        );
+        buffer[field::VERSION] = 1;
+        assert_eq!(
+            WireProbeState::new(&buffer[..len]).unwrap_err(),
+            ProbeStateWireError::UnsupportedVersion(1)
+        );
+    }
+}
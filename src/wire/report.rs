@@ -1,11 +1,26 @@
 //! A wire protocol for representing Modality probe log reports.
 //! A report is a section of the probe's event log prepended by
 //! the most up-to-date neighbor clocks UP TO that report.
+//!
+//! The report format is versioned:
+//! * Version 0 reports are fingerprinted with
+//!   [FINGERPRINT](struct.WireReport.html#associatedconstant.FINGERPRINT).
+//! * Version 1 reports are fingerprinted with
+//!   [VERSIONED_FINGERPRINT](struct.WireReport.html#associatedconstant.VERSIONED_FINGERPRINT),
+//!   which is followed by a version byte, a flags byte and then the
+//!   fields of a version 0 report. They end with a CRC-32 of
+//!   everything before it. Reports with the
+//!   [AUTHENTICATED_FLAG](struct.WireReport.html#associatedconstant.AUTHENTICATED_FLAG)
+//!   set carry a SipHash-2-4 MAC of their header and payload between
+//!   the payload and the checksum. Reports with the
+//!   [COMPRESSED_FLAG](struct.WireReport.html#associatedconstant.COMPRESSED_FLAG)
+//!   set carry their log entries in the [compressed](../compression/index.html)
+//!   encoding, and their `n_log_entries` field holds its length in bytes.
 
 use crate::{
     log::LogEntry,
     time::{NanosecondResolution, WallClockId},
-    wire::le_bytes,
+    wire::{crc32, le_bytes, siphash},
     LogicalClock, ProbeId,
 };
 use core::mem;
@@ -27,6 +42,15 @@ pub enum ReportWireError {
     /// The probe id didn't follow the rules for being
     /// a valid Modality probe-specifying ProbeId
     InvalidProbeId(u32),
+    /// The report's checksum didn't match its contents
+    ChecksumMismatch,
+    /// The report's format version is newer than this crate understands
+    UnsupportedVersion(u8),
+    /// The report's MAC didn't match its contents under the key
+    /// it was checked with
+    MacMismatch,
+    /// The report's compressed log entries couldn't be decoded
+    InvalidCompressedLog,
 }
 
 #[cfg(feature = "std")]
@@ -39,6 +63,10 @@ impl core::fmt::Display for ReportWireError {
             ReportWireError::MissingHeader => f.write_str("Missing Header"),
             ReportWireError::IncompletePayload => f.write_str("Incomplete Payload"),
             ReportWireError::InvalidProbeId(x) => write!(f, "Invalid Probe Id: 0x{:x}", x),
+            ReportWireError::ChecksumMismatch => f.write_str("Checksum Mismatch"),
+            ReportWireError::UnsupportedVersion(v) => write!(f, "Unsupported Version: {}", v),
+            ReportWireError::MacMismatch => f.write_str("MAC Mismatch"),
+            ReportWireError::InvalidCompressedLog => f.write_str("Invalid Compressed Log"),
         }
     }
 }
@@ -49,6 +77,9 @@ pub struct WireReport<T: AsRef<[u8]>> {
     buffer: T,
 }
 
+/// Field offsets of a version 0 report. In version 1 reports, every
+/// field after the fingerprint sits two bytes further along, behind
+/// the version and flags bytes.
 mod field {
     type Field = ::core::ops::Range<usize>;
     type Rest = ::core::ops::RangeFrom<usize>;
@@ -56,6 +87,10 @@ mod field {
     /// A magical (constant) value used as a hint about the data
     /// encoded in this pile of bytes.
     pub const FINGERPRINT: Field = 0..4;
+    /// The format version, in reports with a versioned fingerprint.
+    pub const VERSION: usize = 4;
+    /// Report flags, in reports with a versioned fingerprint.
+    pub const FLAGS: usize = 5;
     /// A u32 representing the probe_id of the Modality probe instance
     /// producing this report.
     pub const PROBE_ID: Field = 4..8;
@@ -80,13 +115,33 @@ mod field {
     /// The payload, consists of (in order):
     /// * Frontier clocks
     /// * Log entries
+    /// * The MAC, if the report is authenticated
+    /// * The checksum, if the report has one
     pub const PAYLOAD: Rest = 33..;
 }
 
+/// The length of the checksum following a checksummed report's payload
+const CHECKSUM_LEN: usize = mem::size_of::<u32>();
+
+/// The length of the MAC following an authenticated report's payload
+const MAC_LEN: usize = mem::size_of::<u64>();
+
 impl<T: AsRef<[u8]>> WireReport<T> {
     /// Report fingerprint (MRPT)
     pub const FINGERPRINT: u32 = 0x4D_52_50_54;
 
+    /// Versioned report fingerprint (MRPV)
+    pub const VERSIONED_FINGERPRINT: u32 = 0x4D_52_50_56;
+
+    /// The report format version written by this crate
+    pub const VERSION: u8 = 1;
+
+    /// The flag marking a report as authenticated, followed by a MAC
+    pub const AUTHENTICATED_FLAG: u8 = 0b0000_0001;
+
+    /// The flag marking a report's log entries as compressed
+    pub const COMPRESSED_FLAG: u8 = 0b0000_0010;
+
     /// Construct a report from a byte buffer
     pub fn new_unchecked(buffer: T) -> WireReport<T> {
         WireReport { buffer }
@@ -98,12 +153,16 @@ impl<T: AsRef<[u8]>> WireReport<T> {
     /// * [new_unchecked](struct.WireReport.html#method.new_unchecked)
     /// * [check_len](struct.WireReport.html#method.check_len)
     /// * [check_fingerprint](struct.WireReport.html#method.check_fingerprint)
+    /// * [check_version](struct.WireReport.html#method.check_version)
     /// * [check_payload_len](struct.WireReport.html#method.check_payload_len)
+    /// * [check_checksum](struct.WireReport.html#method.check_checksum)
     pub fn new(buffer: T) -> Result<Self, ReportWireError> {
         let r = Self::new_unchecked(buffer);
         r.check_len()?;
         r.check_fingerprint()?;
+        r.check_version()?;
         r.check_payload_len()?;
+        r.check_checksum()?;
         Ok(r)
     }
 
@@ -113,65 +172,248 @@ impl<T: AsRef<[u8]>> WireReport<T> {
     /// is too short.
     pub fn check_len(&self) -> Result<(), ReportWireError> {
         let len = self.buffer.as_ref().len();
-        if len < field::PAYLOAD.start {
+        if len < field::FINGERPRINT.end || len < self.payload_start() {
             Err(ReportWireError::MissingHeader)
         } else {
             Ok(())
         }
     }
 
-    /// Check for the expected fingerprint value.
+    /// Check for one of the expected fingerprint values.
     ///
     /// Returns `Err(ReportWireError::InvalidFingerprint)` if the fingerprint
     /// does not match.
     pub fn check_fingerprint(&self) -> Result<(), ReportWireError> {
-        if self.fingerprint() != Self::FINGERPRINT {
-            Err(ReportWireError::InvalidFingerprint)
+        match self.fingerprint() {
+            Self::FINGERPRINT | Self::VERSIONED_FINGERPRINT => Ok(()),
+            _ => Err(ReportWireError::InvalidFingerprint),
+        }
+    }
+
+    /// Check that the report's format version is one this crate
+    /// understands.
+    ///
+    /// Returns `Err(ReportWireError::UnsupportedVersion)` if the
+    /// version is newer than
+    /// [Self::VERSION](struct.WireReport.html#associatedconstant.VERSION),
+    /// or if a report with the versioned fingerprint claims version 0,
+    /// which only exists under the original fingerprint.
+    pub fn check_version(&self) -> Result<(), ReportWireError> {
+        let version = self.version();
+        let versioned = self.fingerprint() == Self::VERSIONED_FINGERPRINT;
+        if version > Self::VERSION || (versioned && version == 0) {
+            Err(ReportWireError::UnsupportedVersion(version))
         } else {
             Ok(())
         }
     }
 
     /// Ensure the payload size is sufficient to hold bytes according to the header
-    /// fields `n_clocks` and `n_log_entries`.
+    /// fields `n_clocks` and `n_log_entries`, and the MAC and checksum
+    /// if the report has them.
     ///
     /// Returns `Err(ReportWireError::IncompletePayload)` if the buffer
     /// is too short.
     pub fn check_payload_len(&self) -> Result<(), ReportWireError> {
-        let payload_len = self.payload_len();
         let len = self.buffer.as_ref().len();
-        if len < (field::PAYLOAD.start + payload_len) {
+        if len < self.report_len() {
             Err(ReportWireError::IncompletePayload)
         } else {
             Ok(())
         }
     }
 
+    /// Ensure a checksummed report's checksum matches its header and
+    /// payload. Reports without a checksum always pass.
+    ///
+    /// Returns `Err(ReportWireError::ChecksumMismatch)` if the checksum
+    /// does not match.
+    pub fn check_checksum(&self) -> Result<(), ReportWireError> {
+        match self.checksum() {
+            Some(checksum) if checksum != self.compute_checksum() => {
+                Err(ReportWireError::ChecksumMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Ensure an authenticated report's MAC matches its header and
+    /// payload under `key`. Unlike the other checks, this one is up to
+    /// the receiver, since only it knows the key.
+    ///
+    /// Returns `Err(ReportWireError::MacMismatch)` if the report isn't
+    /// authenticated or its MAC does not match.
+    pub fn check_mac(&self, key: &siphash::Key) -> Result<(), ReportWireError> {
+        match self.mac() {
+            Some(mac) if mac == self.compute_mac(key) => Ok(()),
+            _ => Err(ReportWireError::MacMismatch),
+        }
+    }
+
     /// Consumes the report, returning the underlying buffer
     pub fn into_inner(self) -> T {
         self.buffer
     }
 
-    /// Return the length of a report header
+    /// Return the length of the header of a report in the current
+    /// version
     pub fn header_len() -> usize {
-        field::PAYLOAD.start
+        2 + field::PAYLOAD.start
     }
 
-    /// Return the length of a buffer required to hold a report
-    /// with a payload of `n_clocks` + `n_log_entries`
+    /// Return the length of a buffer required to hold a report in the
+    /// current version with a payload of `n_clocks` + `n_log_entries`
     pub fn buffer_len(n_clocks: usize, n_log_entries: usize) -> usize {
-        field::PAYLOAD.start
+        Self::header_len()
             + (n_clocks * mem::size_of::<LogicalClock>())
             + (n_log_entries * mem::size_of::<LogEntry>())
+            + CHECKSUM_LEN
     }
 
-    /// Return the length of the report payload
+    /// Return the length of a buffer required to hold an authenticated
+    /// report in the current version with a payload of `n_clocks` +
+    /// `n_log_entries`
+    pub fn authenticated_buffer_len(n_clocks: usize, n_log_entries: usize) -> usize {
+        Self::buffer_len(n_clocks, n_log_entries) + MAC_LEN
+    }
+
+    /// Return the length of the report payload, not including the
+    /// checksum
     pub fn payload_len(&self) -> usize {
         let n_clock_bytes = self.n_clocks() as usize * mem::size_of::<LogicalClock>();
-        let n_log_bytes = self.n_log_entries() as usize * mem::size_of::<LogEntry>();
+        let n_log_bytes = if self.is_compressed() {
+            self.n_log_entries() as usize
+        } else {
+            self.n_log_entries() as usize * mem::size_of::<LogEntry>()
+        };
         n_clock_bytes + n_log_bytes
     }
 
+    /// Return the length of the whole report: header, payload, and
+    /// MAC and checksum, if it has them
+    pub fn report_len(&self) -> usize {
+        let checksum_len = if self.is_checksummed() {
+            CHECKSUM_LEN
+        } else {
+            0
+        };
+        self.mac_end() + checksum_len
+    }
+
+    /// Return the report's format version
+    #[inline]
+    pub fn version(&self) -> u8 {
+        match self.fingerprint() {
+            Self::FINGERPRINT => 0,
+            _ => self.buffer.as_ref()[field::VERSION],
+        }
+    }
+
+    /// Return the report's flags, which are all clear in version 0
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        if self.version() >= 1 {
+            self.buffer.as_ref()[field::FLAGS]
+        } else {
+            0
+        }
+    }
+
+    /// Whether the report is followed by a checksum
+    #[inline]
+    pub fn is_checksummed(&self) -> bool {
+        self.version() >= 1
+    }
+
+    /// Whether the report's payload is followed by a MAC
+    #[inline]
+    pub fn is_authenticated(&self) -> bool {
+        self.flags() & Self::AUTHENTICATED_FLAG != 0
+    }
+
+    /// Whether the report's log entries are compressed
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        self.flags() & Self::COMPRESSED_FLAG != 0
+    }
+
+    /// How far the fields after the fingerprint sit from their
+    /// version 0 offsets
+    #[inline]
+    fn field_offset(&self) -> usize {
+        if self.fingerprint() == Self::VERSIONED_FINGERPRINT {
+            2
+        } else {
+            0
+        }
+    }
+
+    #[inline]
+    fn payload_start(&self) -> usize {
+        self.field_offset() + field::PAYLOAD.start
+    }
+
+    /// The buffer, from the offset the field ranges are relative to
+    #[inline]
+    fn fields(&self) -> &[u8] {
+        &self.buffer.as_ref()[self.field_offset()..]
+    }
+
+    #[inline]
+    fn payload_end(&self) -> usize {
+        self.payload_start() + self.payload_len()
+    }
+
+    #[inline]
+    fn mac_end(&self) -> usize {
+        if self.is_authenticated() {
+            self.payload_end() + MAC_LEN
+        } else {
+            self.payload_end()
+        }
+    }
+
+    /// Return the MAC following the payload, if the report is
+    /// authenticated
+    #[inline]
+    pub fn mac(&self) -> Option<u64> {
+        if self.is_authenticated() {
+            let data = self.buffer.as_ref();
+            let start = self.payload_end();
+            Some(le_bytes::read_u64(&data[start..start + MAC_LEN]))
+        } else {
+            None
+        }
+    }
+
+    /// Compute the SipHash-2-4 MAC of the report's header and payload
+    /// under `key`
+    #[inline]
+    pub fn compute_mac(&self, key: &siphash::Key) -> u64 {
+        let data = self.buffer.as_ref();
+        siphash::mac(key, &data[..self.payload_end()])
+    }
+
+    /// Return the checksum following the payload and MAC, if the report
+    /// has one
+    #[inline]
+    pub fn checksum(&self) -> Option<u32> {
+        if self.is_checksummed() {
+            let data = self.buffer.as_ref();
+            let start = self.mac_end();
+            Some(le_bytes::read_u32(&data[start..start + CHECKSUM_LEN]))
+        } else {
+            None
+        }
+    }
+
+    /// Compute the CRC-32 of the report's header, payload and MAC
+    #[inline]
+    pub fn compute_checksum(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        crc32::checksum(&data[..self.mac_end()])
+    }
+
     /// Return the `fingerprint` field
     #[inline]
     pub fn fingerprint(&self) -> u32 {
@@ -182,7 +424,7 @@ impl<T: AsRef<[u8]>> WireReport<T> {
     /// Return the `probe_id` field
     #[inline]
     pub fn probe_id(&self) -> Result<ProbeId, ReportWireError> {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         let raw_probe_id = le_bytes::read_u32(&data[field::PROBE_ID]);
         match ProbeId::new(raw_probe_id) {
             Some(id) => Ok(id),
@@ -193,56 +435,57 @@ impl<T: AsRef<[u8]>> WireReport<T> {
     /// Return the `clock` field
     #[inline]
     pub fn clock(&self) -> u32 {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         le_bytes::read_u32(&data[field::CLOCK])
     }
 
     /// Return the `seq_num` field
     #[inline]
     pub fn seq_num(&self) -> u64 {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         le_bytes::read_u64(&data[field::SEQ_NUM])
     }
 
     /// Return the `persistent_epoch_counting` field
     #[inline]
     pub fn persistent_epoch_counting(&self) -> bool {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         data[field::PERSISTENT_EPOCH_COUNTING] != 0
     }
 
     /// Return the raw value `persistent_epoch_counting` field
     #[inline]
     pub fn raw_persistent_epoch_counting(&self) -> u8 {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         data[field::PERSISTENT_EPOCH_COUNTING]
     }
 
     /// Return the `time_resolution` field
     #[inline]
     pub fn time_resolution(&self) -> NanosecondResolution {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         NanosecondResolution(le_bytes::read_u32(&data[field::TIME_RESOLUTION]))
     }
 
     /// Return the `wall_clock_id` field
     #[inline]
     pub fn wall_clock_id(&self) -> WallClockId {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         le_bytes::read_u16(&data[field::WALL_CLOCK_ID]).into()
     }
 
     /// Return the `n_clocks` field
     #[inline]
     pub fn n_clocks(&self) -> u16 {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         le_bytes::read_u16(&data[field::N_CLOCKS])
     }
 
-    /// Return the `n_log_entries` field
+    /// Return the `n_log_entries` field. In compressed reports, this
+    /// is the length of the log in bytes.
     #[inline]
     pub fn n_log_entries(&self) -> u32 {
-        let data = self.buffer.as_ref();
+        let data = self.fields();
         le_bytes::read_u32(&data[field::N_LOG_ENTRIES])
     }
 }
@@ -252,7 +495,7 @@ impl<'a, T: AsRef<[u8]> + ?Sized> WireReport<&'a T> {
     #[inline]
     pub fn payload(&self) -> &'a [u8] {
         let data = self.buffer.as_ref();
-        &data[field::PAYLOAD]
+        &data[self.payload_start()..]
     }
 }
 
@@ -265,73 +508,134 @@ impl<T: AsRef<[u8]> + AsMut<[u8]>> WireReport<T> {
         le_bytes::write_u32(&mut data[field::FINGERPRINT], Self::FINGERPRINT);
     }
 
+    /// The buffer, from the offset the field ranges are relative to
+    #[inline]
+    fn fields_mut(&mut self) -> &mut [u8] {
+        let offset = self.field_offset();
+        &mut self.buffer.as_mut()[offset..]
+    }
+
+    /// Set the `fingerprint` field to
+    /// [Self::VERSIONED_FINGERPRINT](struct.WireReport.html#associatedconstant.VERSIONED_FINGERPRINT)
+    #[inline]
+    pub fn set_versioned_fingerprint(&mut self) {
+        let data = self.buffer.as_mut();
+        le_bytes::write_u32(&mut data[field::FINGERPRINT], Self::VERSIONED_FINGERPRINT);
+    }
+
+    /// Set the `version` field, present in reports with a versioned
+    /// fingerprint.
+    ///
+    /// Set the fingerprint first: it decides where all the other
+    /// fields are.
+    #[inline]
+    pub fn set_version(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::VERSION] = value;
+    }
+
+    /// Set the `flags` field, present in reports with a versioned
+    /// fingerprint.
+    #[inline]
+    pub fn set_flags(&mut self, value: u8) {
+        let data = self.buffer.as_mut();
+        data[field::FLAGS] = value;
+    }
+
+    /// Write the MAC of the header and payload under `key` after the
+    /// payload of an authenticated report.
+    ///
+    /// Call this once the flags, the `n_clocks` and `n_log_entries`
+    /// fields and the payload have been written, and before
+    /// [set_checksum](struct.WireReport.html#method.set_checksum).
+    #[inline]
+    pub fn set_mac(&mut self, key: &siphash::Key) {
+        let mac = self.compute_mac(key);
+        let start = self.payload_end();
+        let data = self.buffer.as_mut();
+        le_bytes::write_u64(&mut data[start..start + MAC_LEN], mac);
+    }
+
+    /// Write the checksum of the header, payload and MAC after them.
+    ///
+    /// Call this last, once the `n_clocks` and `n_log_entries` fields,
+    /// the payload and the MAC have been written.
+    #[inline]
+    pub fn set_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        let start = self.mac_end();
+        let data = self.buffer.as_mut();
+        le_bytes::write_u32(&mut data[start..start + CHECKSUM_LEN], checksum);
+    }
+
     /// Set the `probe_id` field
     #[inline]
     pub fn set_probe_id(&mut self, value: ProbeId) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         le_bytes::write_u32(&mut data[field::PROBE_ID], value.get_raw());
     }
 
     /// Set the `clock` field
     #[inline]
     pub fn set_clock(&mut self, value: u32) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         le_bytes::write_u32(&mut data[field::CLOCK], value);
     }
 
     /// Set the `seq_num` field
     #[inline]
     pub fn set_seq_num(&mut self, value: u64) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         le_bytes::write_u64(&mut data[field::SEQ_NUM], value);
     }
 
     /// Set the `persistent_epoch_counting` field
     #[inline]
     pub fn set_persistent_epoch_counting(&mut self, value: bool) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         data[field::PERSISTENT_EPOCH_COUNTING] = u8::from(value);
     }
 
     /// Set the raw value of the `persistent_epoch_counting` field
     #[inline]
     pub fn set_raw_persistent_epoch_counting(&mut self, value: u8) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         data[field::PERSISTENT_EPOCH_COUNTING] = value;
     }
 
     /// Set the `time_resolution` field
     #[inline]
     pub fn set_time_resolution(&mut self, value: NanosecondResolution) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         le_bytes::write_u32(&mut data[field::TIME_RESOLUTION], value.0);
     }
 
     /// Set the `wall_clock_id` field
     #[inline]
     pub fn set_wall_clock_id(&mut self, value: WallClockId) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         le_bytes::write_u16(&mut data[field::WALL_CLOCK_ID], u16::from(value));
     }
 
     /// Set the `n_clocks` field
     #[inline]
     pub fn set_n_clocks(&mut self, value: u16) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         le_bytes::write_u16(&mut data[field::N_CLOCKS], value);
     }
 
     /// Set the `n_log_entries` field
     #[inline]
     pub fn set_n_log_entries(&mut self, value: u32) {
-        let data = self.buffer.as_mut();
+        let data = self.fields_mut();
         le_bytes::write_u32(&mut data[field::N_LOG_ENTRIES], value);
     }
 
     /// Return a mutable pointer to the payload
     #[inline]
     pub fn payload_mut(&mut self) -> &mut [u8] {
-        &mut self.buffer.as_mut()[field::PAYLOAD.start..]
+        let start = self.payload_start();
+        &mut self.buffer.as_mut()[start..]
     }
 }
 
@@ -394,12 +698,16 @@ mod tests {
 
     #[test]
     fn header_len() {
-        assert_eq!(WireReport::<&[u8]>::header_len(), 33);
+        assert_eq!(WireReport::<&[u8]>::header_len(), 35);
         let n_clocks = 12;
         let n_log_items = 14;
         assert_eq!(
             WireReport::<&[u8]>::buffer_len(n_clocks, n_log_items),
-            33 + (12 * mem::size_of::<LogicalClock>()) + (14 * mem::size_of::<LogEntry>())
+            35 + (12 * mem::size_of::<LogicalClock>()) + (14 * mem::size_of::<LogEntry>()) + 4
+        );
+        assert_eq!(
+            WireReport::<&[u8]>::authenticated_buffer_len(n_clocks, n_log_items),
+            WireReport::<&[u8]>::buffer_len(n_clocks, n_log_items) + 8
         );
     }
 
@@ -475,7 +783,7 @@ mod tests {
             (&mut r.payload_mut()[..payload_len]).copy_from_slice(&PAYLOAD_BYTES[..]);
             assert_eq!(r.check_fingerprint(), Ok(()));
             assert_eq!(r.check_payload_len(), Ok(()));
-            WireReport::<&[u8]>::header_len() + payload_len
+            r.report_len()
         };
 
         let r = WireReport::new(&bytes[..wire_size]).unwrap();
@@ -495,6 +803,196 @@ mod tests {
         assert_eq!(r.payload(), &PAYLOAD_BYTES[..]);
     }
 
+    #[test]
+    fn checksum_mismatch() {
+        let mut bytes = [0xFF; 67];
+        let mut r = WireReport::new_unchecked(&mut bytes[..]);
+        r.set_versioned_fingerprint();
+        r.set_version(1);
+        r.set_flags(0);
+        r.set_probe_id(ProbeId::new(1).unwrap());
+        r.set_n_clocks(2);
+        r.set_n_log_entries(3);
+        r.payload_mut()[..28].copy_from_slice(&PAYLOAD_BYTES[..]);
+        r.set_checksum();
+        assert!(WireReport::new(&bytes[..]).is_ok());
+
+        // Flip a bit in the payload, then in the header
+        bytes[40] ^= 0x10;
+        let r = WireReport::new(&bytes[..]);
+        assert_eq!(r.unwrap_err(), ReportWireError::ChecksumMismatch);
+        bytes[40] ^= 0x10;
+        bytes[13] ^= 0x01;
+        let r = WireReport::new(&bytes[..]);
+        assert_eq!(r.unwrap_err(), ReportWireError::ChecksumMismatch);
+    }
+
+    fn authenticated_report(bytes: &mut [u8], key: &siphash::Key) {
+        let mut r = WireReport::new_unchecked(bytes);
+        r.set_versioned_fingerprint();
+        r.set_version(1);
+        r.set_flags(WireReport::<&[u8]>::AUTHENTICATED_FLAG);
+        r.set_probe_id(ProbeId::new(1).unwrap());
+        r.set_clock(2);
+        r.set_seq_num(8);
+        r.set_persistent_epoch_counting(false);
+        r.set_time_resolution(255.into());
+        r.set_wall_clock_id(1.into());
+        r.set_n_clocks(2);
+        r.set_n_log_entries(3);
+        r.payload_mut()[..28].copy_from_slice(&PAYLOAD_BYTES[..]);
+        r.set_mac(key);
+        r.set_checksum();
+    }
+
+    #[test]
+    fn versioned() {
+        let mut bytes = [0xFF; 67];
+        let mut r = WireReport::new_unchecked(&mut bytes[..]);
+        r.set_versioned_fingerprint();
+        r.set_version(1);
+        r.set_flags(0);
+        r.set_probe_id(ProbeId::new(1).unwrap());
+        r.set_clock(2);
+        r.set_seq_num(8);
+        r.set_persistent_epoch_counting(false);
+        r.set_time_resolution(255.into());
+        r.set_wall_clock_id(1.into());
+        r.set_n_clocks(2);
+        r.set_n_log_entries(3);
+        r.payload_mut()[..28].copy_from_slice(&PAYLOAD_BYTES[..]);
+        r.set_checksum();
+        assert_eq!(r.report_len(), 67);
+        // The version and flags bytes, then the fields of a version 0 report
+        assert_eq!(bytes[4], 1);
+        assert_eq!(bytes[5], 0);
+        assert_eq!(&bytes[6..63], &MSG_BYTES[4..]);
+
+        let r = WireReport::new(&bytes[..]).unwrap();
+        assert_eq!(r.version(), 1);
+        assert_eq!(r.flags(), 0);
+        assert!(r.is_checksummed());
+        assert_eq!(r.checksum(), Some(r.compute_checksum()));
+        assert!(!r.is_authenticated());
+        assert_eq!(r.mac(), None);
+        assert_eq!(r.probe_id().unwrap().get_raw(), 1);
+        assert_eq!(r.clock(), 2);
+        assert_eq!(r.seq_num(), 8);
+        assert_eq!(r.time_resolution(), 255.into());
+        assert_eq!(r.wall_clock_id(), 1.into());
+        assert_eq!(r.n_clocks(), 2);
+        assert_eq!(r.n_log_entries(), 3);
+        assert_eq!(&r.payload()[..28], &PAYLOAD_BYTES[..]);
+        assert_eq!(
+            r.check_mac(&[0; siphash::KEY_LEN]),
+            Err(ReportWireError::MacMismatch)
+        );
+
+        // The checksum itself is required
+        let r = WireReport::new(&bytes[..66]);
+        assert_eq!(r.unwrap_err(), ReportWireError::IncompletePayload);
+    }
+
+    #[test]
+    fn compressed() {
+        let mut bytes = [0xFF; 67];
+        let mut r = WireReport::new_unchecked(&mut bytes[..]);
+        r.set_versioned_fingerprint();
+        r.set_version(1);
+        r.set_flags(WireReport::<&[u8]>::COMPRESSED_FLAG);
+        r.set_probe_id(ProbeId::new(1).unwrap());
+        r.set_clock(2);
+        r.set_seq_num(8);
+        r.set_persistent_epoch_counting(false);
+        r.set_time_resolution(255.into());
+        r.set_wall_clock_id(1.into());
+        r.set_n_clocks(2);
+        // The compressed log's length in bytes
+        r.set_n_log_entries(12);
+        r.payload_mut()[..28].copy_from_slice(&PAYLOAD_BYTES[..]);
+        r.set_checksum();
+
+        let r = WireReport::new(&bytes[..]).unwrap();
+        assert!(r.is_compressed());
+        assert!(!r.is_authenticated());
+        assert_eq!(r.payload_len(), 28);
+        assert_eq!(r.report_len(), 67);
+        assert_eq!(r.check_checksum(), Ok(()));
+    }
+
+    #[test]
+    fn authenticated() {
+        let key = [7; siphash::KEY_LEN];
+        let mut bytes = [0xFF; 75];
+        authenticated_report(&mut bytes[..], &key);
+        assert_eq!(
+            bytes.len(),
+            WireReport::<&[u8]>::authenticated_buffer_len(2, 3)
+        );
+
+        let r = WireReport::new(&bytes[..]).unwrap();
+        assert!(r.is_authenticated());
+        assert_eq!(r.report_len(), 75);
+        assert_eq!(r.mac(), Some(siphash::mac(&key, &bytes[..63])));
+        assert_eq!(r.check_mac(&key), Ok(()));
+        assert_eq!(&r.payload()[..28], &PAYLOAD_BYTES[..]);
+
+        // The MAC is required
+        let r = WireReport::new(&bytes[..74]);
+        assert_eq!(r.unwrap_err(), ReportWireError::IncompletePayload);
+    }
+
+    #[test]
+    fn mac_mismatch() {
+        let key = [7; siphash::KEY_LEN];
+        let mut bytes = [0xFF; 75];
+        authenticated_report(&mut bytes[..], &key);
+
+        let r = WireReport::new(&bytes[..]).unwrap();
+        assert_eq!(
+            r.check_mac(&[8; siphash::KEY_LEN]),
+            Err(ReportWireError::MacMismatch)
+        );
+
+        // A forged payload with a recomputed checksum still fails
+        bytes[40] ^= 0x10;
+        WireReport::new_unchecked(&mut bytes[..]).set_checksum();
+        let r = WireReport::new(&bytes[..]).unwrap();
+        assert_eq!(r.check_mac(&key), Err(ReportWireError::MacMismatch));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut bytes = [0xFF; 66];
+        let mut r = WireReport::new_unchecked(&mut bytes[..]);
+        r.set_versioned_fingerprint();
+        r.set_version(WireReport::<&[u8]>::VERSION + 1);
+        let r = WireReport::new(&bytes[..]);
+        assert_eq!(
+            r.unwrap_err(),
+            ReportWireError::UnsupportedVersion(WireReport::<&[u8]>::VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn versioned_fingerprint_with_version_zero() {
+        let mut bytes = [0xFF; 66];
+        let mut r = WireReport::new_unchecked(&mut bytes[..]);
+        r.set_versioned_fingerprint();
+        r.set_version(0);
+        let r = WireReport::new(&bytes[..]);
+        assert_eq!(r.unwrap_err(), ReportWireError::UnsupportedVersion(0));
+    }
+
+    #[test]
+    fn unversioned_reports_have_no_checksum() {
+        let r = WireReport::new(&MSG_BYTES[..]).unwrap();
+        assert_eq!(r.version(), 0);
+        assert!(!r.is_checksummed());
+        assert_eq!(r.checksum(), None);
+        assert_eq!(r.report_len(), MSG_BYTES.len());
+    }
+
     #[test]
     fn invalid_fingerprint() {
         let bytes = [0xFF; 33];
@@ -505,7 +1003,22 @@ mod tests {
     #[test]
     fn missing_header() {
         let bytes = [0xFF; 33 - 1];
+        assert_eq!(bytes.len(), field::PAYLOAD.start - 1);
+        let r = WireReport::new(&bytes[..]);
+        assert_eq!(r.unwrap_err(), ReportWireError::MissingHeader);
+
+        // A version 1 report's header is two bytes longer
+        let mut bytes = [0xFF; 35 - 1];
         assert_eq!(bytes.len(), WireReport::<&[u8]>::header_len() - 1);
+        let mut r = WireReport::new_unchecked(&mut bytes[..]);
+        r.set_versioned_fingerprint();
+        r.set_version(1);
+        let r = WireReport::new(&bytes[..]);
+        assert_eq!(r.unwrap_err(), ReportWireError::MissingHeader);
+
+        // Even the version and flags bytes may be missing
+        let mut bytes = [0xFF; 4];
+        WireReport::new_unchecked(&mut bytes[..]).set_versioned_fingerprint();
         let r = WireReport::new(&bytes[..]);
         assert_eq!(r.unwrap_err(), ReportWireError::MissingHeader);
     }
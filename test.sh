@@ -3,12 +3,14 @@
 set -ex
 
 cargo build --all
-cargo test --workspace --features "std, debug-collector-access"
+cargo build -p modality-probe
+cargo test --workspace --features "std, debug-collector-access, log"
 cargo test --workspace
 
 (
     cd modality-probe-capi
     cargo test --workspace
+    cargo build --no-default-features
 )
 
 (
@@ -258,16 +258,14 @@ fn report_buffer_too_small_error() -> Result<(), ModalityProbeError> {
     assert!(probe.try_record_event(EventId::MAX_USER_ID).is_ok());
 
     // Only room for a header, hard error since we can't log a single event
-    let mut report_dest =
-        vec![0u8; wire::WireReport::<&[u8]>::header_len() + mem::size_of::<log::LogEntry>() - 1];
+    let mut report_dest = vec![0u8; wire::WireReport::<&[u8]>::buffer_len(0, 1) - 1];
     assert_eq!(
         probe.report(&mut report_dest),
         Err(ReportError::InsufficientDestinationSize)
     );
 
     // Not enough room for the frontier clocks, only a single event
-    let mut report_dest =
-        vec![0u8; wire::WireReport::<&[u8]>::header_len() + mem::size_of::<log::LogEntry>()];
+    let mut report_dest = vec![0u8; wire::WireReport::<&[u8]>::buffer_len(0, 1)];
     let bytes_written = probe.report(&mut report_dest)?.unwrap();
     let log_report = wire::WireReport::new(&report_dest[..bytes_written.get()]).unwrap();
 
@@ -284,6 +282,40 @@ fn report_buffer_too_small_error() -> Result<(), ModalityProbeError> {
     Ok(())
 }
 
+#[test]
+fn report_fragmented_checks_fragment_size_before_reporting() -> Result<(), ModalityProbeError> {
+    let mut storage = [MaybeUninit::new(0u8); 512];
+    let probe = ModalityProbe::try_initialize_at(
+        &mut storage,
+        1,
+        NanosecondResolution::UNSPECIFIED,
+        WallClockId::local_only(),
+        RestartCounterProvider::NoRestartTracking,
+    )?;
+    probe.try_record_event(1)?;
+    probe.try_record_event(2)?;
+
+    // No room after the fragment header, so nothing leaves the log
+    let mut scratch = vec![0u8; 256];
+    let mut fragment = vec![0u8; wire::WireReportFragment::<&[u8]>::header_len()];
+    assert_eq!(
+        probe.report_fragmented(&mut scratch, &mut fragment, |_| panic!("Sent a fragment")),
+        Err(ReportError::InsufficientDestinationSize)
+    );
+
+    let mut report_dest = vec![0u8; 256];
+    let bytes_written = probe.report(&mut report_dest)?.unwrap();
+    let log_report = wire::WireReport::new(&report_dest[..bytes_written.get()]).unwrap();
+    let clocks_len = log_report.n_clocks() as usize * mem::size_of::<LogicalClock>();
+    let events: Vec<u32> = log_report.payload()[clocks_len..]
+        .chunks(mem::size_of::<log::LogEntry>())
+        .map(|e| u32::from_le_bytes([e[0], e[1], e[2], e[3]]))
+        .collect();
+    assert!(events.contains(&1));
+    assert!(events.contains(&2));
+    Ok(())
+}
+
 #[test]
 fn report_can_end_in_local_clock_from_snapshot_produce() -> Result<(), ModalityProbeError> {
     let mut storage = [MaybeUninit::new(0u8); 512];
@@ -385,9 +417,9 @@ fn report_missed_log_items() -> Result<(), ModalityProbeError> {
 
         assert_eq!(log_report.n_clocks(), 1);
         #[cfg(target_pointer_width = "64")]
-        assert_eq!(log_report.n_log_entries(), 80);
+        assert_eq!(log_report.n_log_entries(), 42);
         #[cfg(target_pointer_width = "32")]
-        assert_eq!(log_report.n_log_entries(), 88);
+        assert_eq!(log_report.n_log_entries(), 54);
 
         let offset = log_report.n_clocks() as usize * mem::size_of::<LogicalClock>();
         let log_bytes = &log_report.payload()[offset..];
@@ -405,14 +437,14 @@ fn report_missed_log_items() -> Result<(), ModalityProbeError> {
 
         if i == 0 {
             #[cfg(target_pointer_width = "64")]
-            assert_eq!(raw_payload, 949);
+            assert_eq!(raw_payload, 987);
             #[cfg(target_pointer_width = "32")]
-            assert_eq!(raw_payload, 941);
+            assert_eq!(raw_payload, 975);
         } else {
             #[cfg(target_pointer_width = "64")]
-            assert_eq!(raw_payload, 947);
+            assert_eq!(raw_payload, 985);
             #[cfg(target_pointer_width = "32")]
-            assert_eq!(raw_payload, 939);
+            assert_eq!(raw_payload, 973);
         }
     }
 
@@ -459,8 +491,7 @@ proptest! {
             }
         }
 
-        let min_report_size =
-            wire::WireReport::<&[u8]>::header_len() + (3 * mem::size_of::<LogicalClock>());
+        let min_report_size = wire::WireReport::<&[u8]>::buffer_len(3, 0);
         let mut report_dest = vec![0u8; min_report_size + report_buffer_space];
 
         let bytes_written = probe.report(&mut report_dest).unwrap().unwrap();
@@ -662,6 +693,94 @@ fn persistent_restart_sequence_id() -> Result<(), ModalityProbeError> {
     Ok(())
 }
 
+#[test]
+fn retained_log_survives_reinitialization() -> Result<(), ModalityProbeError> {
+    /// The reported log words and the epoch of the first frontier
+    /// clock, which is the probe's own
+    fn reported_log(report: &[u8]) -> (Vec<u32>, ProbeEpoch) {
+        let r = wire::WireReport::new(report).unwrap();
+        let payload = r.payload();
+        let (epoch, _ticks) =
+            unpack_clock_word(u32::from_le_bytes(payload[4..8].try_into().unwrap()));
+        let log = payload[r.n_clocks() as usize * mem::size_of::<LogicalClock>()..]
+            .chunks_exact(4)
+            .take(r.n_log_entries() as usize)
+            .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        (log, epoch)
+    }
+    fn init<'a, S: LogStorage>(
+        storage: &'a mut [MaybeUninit<u8>],
+        probe_id: u32,
+        log_storage: &mut S,
+    ) -> &'a mut ModalityProbe<'a> {
+        ModalityProbe::try_initialize_at_with_log_storage(
+            storage,
+            probe_id,
+            NanosecondResolution::UNSPECIFIED,
+            WallClockId::local_only(),
+            RestartCounterProvider::NoRestartTracking,
+            log_storage,
+        )
+        .unwrap()
+    }
+
+    let mut storage = [MaybeUninit::new(0u8); 1024];
+    let mut report = [0u8; 512];
+    let prior_epoch = {
+        let probe = init(&mut storage, 1, &mut RetainedRam);
+        probe.try_record_event(1)?;
+        probe.report(&mut report)?.unwrap();
+        probe.try_record_event(2)?;
+        probe.try_record_event(3)?;
+        probe.now().clock.epoch
+    };
+
+    // Simulate a reset by initializing over the same memory. The
+    // unreported entries come first, under the epoch they were
+    // recorded in.
+    {
+        let probe = init(&mut storage, 1, &mut RetainedRam);
+        assert_eq!(probe.now().clock.epoch.0, prior_epoch.0 + 1);
+        probe.try_record_event(4)?;
+        let len = probe.report(&mut report)?.unwrap().get();
+        assert_eq!(wire::WireReport::new(&report[..len]).unwrap().seq_num(), 1);
+        let (log, frontier_epoch) = reported_log(&report[..len]);
+        assert_eq!(frontier_epoch, prior_epoch);
+        assert!(!log.contains(&1));
+        // The kept entries are the first report's event, 2 and 3
+        let (retained, _) =
+            modality_probe::log::LogEntry::event_with_payload(EventId::EVENT_PRIOR_LOG_RETAINED, 3);
+        let pos = |word: u32| log.iter().position(|e| *e == word).unwrap();
+        assert!(pos(2) < pos(3));
+        assert!(pos(3) < pos(retained.raw()));
+        assert!(pos(retained.raw()) < pos(4));
+    }
+
+    // Volatile memory, or a different probe, starts fresh
+    {
+        let probe = init(&mut storage, 1, &mut RetainedRam);
+        probe.try_record_event(5)?;
+    }
+    {
+        let probe = init(&mut storage, 1, &mut VolatileRam);
+        let len = probe.report(&mut report)?.unwrap().get();
+        assert_eq!(wire::WireReport::new(&report[..len]).unwrap().seq_num(), 0);
+        let (log, _) = reported_log(&report[..len]);
+        assert!(!log.contains(&5));
+        probe.try_record_event(6)?;
+    }
+    {
+        let probe = init(&mut storage, 2, &mut RetainedRam);
+        let len = probe.report(&mut report)?.unwrap().get();
+        let (log, frontier_epoch) = reported_log(&report[..len]);
+        assert!(!log.contains(&6));
+        assert_eq!(frontier_epoch, ProbeEpoch::MIN);
+    }
+
+    Ok(())
+}
+
 struct PersistentRestartProvider {
     next_seq_id: u16,
     count: usize,